@@ -11,8 +11,8 @@ interface QueueItemProps {
   onCancel?: (id: string) => void;
   onRetry?: (id: string) => void;
   onRemove?: (id: string) => void;
-  onOpenFile?: (path: string) => void;
-  onOpenFolder?: (path: string) => void;
+  onOpenFile?: (path: string, outputDir?: string) => void;
+  onOpenFolder?: (path: string, outputDir?: string) => void;
 }
 
 export function QueueItemComponent({
@@ -31,8 +31,8 @@ export function QueueItemComponent({
   const handleRetry = useCallback(() => onRetry?.(item.id), [item.id, onRetry]);
   const handleRemove = useCallback(() => onRemove?.(item.id), [item.id, onRemove]);
   const handleOpenFile = useCallback(
-    () => item.final_path && onOpenFile?.(item.final_path),
-    [item.final_path, onOpenFile]
+    () => item.final_path && onOpenFile?.(item.final_path, item.output_dir),
+    [item.final_path, item.output_dir, onOpenFile]
   );
   const isDone = item.status === "done";
   const isActive =
@@ -46,7 +46,7 @@ export function QueueItemComponent({
     // For incomplete downloads, just open the output directory
     // Only use final_path if the download is complete
     const path = isDone && item.final_path ? item.final_path : item.output_dir;
-    if (path) onOpenFolder?.(path);
+    if (path) onOpenFolder?.(path, item.output_dir);
   }, [item.final_path, item.output_dir, onOpenFolder, isDone]);
 
   const displayTitle = item.title || item.source_url;
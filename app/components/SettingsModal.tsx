@@ -1,7 +1,8 @@
 "use client";
 
 import { useCallback, useEffect, useState } from "react";
-import type { UserSettings, AppUpdateInfo } from "../types";
+import { invoke } from "@tauri-apps/api/core";
+import type { UserSettings, AppUpdateInfo, SponsorBlockCategory } from "../types";
 import { AppUpdater } from "./AppUpdater";
 
 interface SettingsModalProps {
@@ -26,13 +27,14 @@ const TABS: { id: TabId; label: string }[] = [
   { id: "network", label: "Network" },
 ];
 
-const SPONSORBLOCK_CATEGORIES = [
-  { id: "sponsor", label: "Sponsor" },
-  { id: "intro", label: "Intro" },
-  { id: "outro", label: "Outro" },
-  { id: "selfpromo", label: "Self-promo" },
-  { id: "interaction", label: "Interaction" },
-  { id: "music_offtopic", label: "Non-music" },
+// Shown until get_sponsorblock_categories answers
+const SPONSORBLOCK_CATEGORIES: SponsorBlockCategory[] = [
+  { id: "sponsor", label: "Sponsor", description: "Paid promotion" },
+  { id: "intro", label: "Intro", description: "Intro animation/sequence" },
+  { id: "outro", label: "Outro", description: "Outro/end cards" },
+  { id: "selfpromo", label: "Self-promo", description: "Self-promotion" },
+  { id: "interaction", label: "Interaction", description: "Subscribe reminders" },
+  { id: "music_offtopic", label: "Non-music", description: "Non-music in music videos" },
 ];
 
 export function SettingsModal({
@@ -49,6 +51,14 @@ export function SettingsModal({
   const [localSettings, setLocalSettings] = useState<UserSettings | null>(null);
   const [isSaving, setIsSaving] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [sponsorblockCategories, setSponsorblockCategories] =
+    useState<SponsorBlockCategory[]>(SPONSORBLOCK_CATEGORIES);
+
+  useEffect(() => {
+    invoke<SponsorBlockCategory[]>("get_sponsorblock_categories")
+      .then(setSponsorblockCategories)
+      .catch(() => {});
+  }, []);
 
   // Initialize local settings when modal opens
   useEffect(() => {
@@ -349,7 +359,7 @@ export function SettingsModal({
                     <div>
                       <label className="mb-2 block text-sm font-medium">Categories</label>
                       <div className="space-y-2">
-                        {SPONSORBLOCK_CATEGORIES.map((cat) => (
+                        {sponsorblockCategories.map((cat) => (
                           <label key={cat.id} className="flex items-center gap-3">
                             <input
                               type="checkbox"
@@ -14,8 +14,10 @@ import type {
   ExpandPlaylistResult,
   FetchMetadataOptions,
   FetchMetadataResult,
+  LogLevel,
   PresetInfo,
   QueueItem,
+  Thumbnail,
   ToolchainStatus,
   UserSettings,
   WindowState,
@@ -70,6 +72,7 @@ export interface UseDownlinkReturn {
   // Settings
   getSettings: () => Promise<UserSettings>;
   saveSettings: (settings: UserSettings) => Promise<void>;
+  setLogLevel: (level: LogLevel) => Promise<void>;
   getWindowState: () => Promise<WindowState>;
   saveWindowState: (state: WindowState) => Promise<void>;
 
@@ -77,6 +80,7 @@ export interface UseDownlinkReturn {
   getToolchainStatus: () => Promise<ToolchainStatus>;
   checkForUpdates: () => Promise<string[]>;
   updateTool: (toolName: string) => Promise<string>;
+  cancelToolUpdate: (toolName: string) => Promise<boolean>;
 
   // App updates
   checkAppUpdate: () => Promise<AppUpdateInfo>;
@@ -88,9 +92,12 @@ export interface UseDownlinkReturn {
 
   // Utilities
   getAppDataDir: () => Promise<string>;
+  getDatabasePath: () => Promise<string>;
+  backupDatabase: (destPath: string, includeCredentials?: boolean) => Promise<void>;
+  collectDiagnostics: () => Promise<string>;
   getDefaultDownloadDir: () => Promise<string>;
-  openFile: (path: string) => Promise<void>;
-  openFolder: (path: string) => Promise<void>;
+  openFile: (path: string, outputDir?: string) => Promise<void>;
+  openFolder: (path: string, outputDir?: string) => Promise<void>;
 
   // Error state
   lastError: string | null;
@@ -196,6 +203,14 @@ export function useDownlink(): UseDownlinkReturn {
         break;
       }
 
+      case "NotifyCompleted": {
+        const data = event.data as { title: string; body: string };
+        if (typeof Notification !== "undefined" && Notification.permission === "granted") {
+          new Notification(data.title, { body: data.body });
+        }
+        break;
+      }
+
       case "DownloadFailed": {
         const data = event.data as {
           id: string;
@@ -270,6 +285,7 @@ export function useDownlink(): UseDownlinkReturn {
             uploader: string | null;
             duration_seconds: number | null;
             thumbnail_url: string | null;
+            thumbnails: Thumbnail[];
             webpage_url: string | null;
           };
         };
@@ -398,6 +414,25 @@ export function useDownlink(): UseDownlinkReturn {
     loadInitialData();
   }, [isTauri, refreshQueue, refreshHistory]);
 
+  // Let the backend hold downloads while offline and resume them when back online
+  useEffect(() => {
+    if (!isTauri) return;
+
+    const report = (online: boolean) => {
+      invoke("set_online_status", { online }).catch((e) =>
+        console.warn("Failed to report online status:", e)
+      );
+    };
+    const onOnline = () => report(true);
+    const onOffline = () => report(false);
+    window.addEventListener("online", onOnline);
+    window.addEventListener("offline", onOffline);
+    return () => {
+      window.removeEventListener("online", onOnline);
+      window.removeEventListener("offline", onOffline);
+    };
+  }, [isTauri]);
+
   // Dismiss update notification
   const dismissUpdateNotification = useCallback(() => {
     setUpdateAvailable(prev => ({ ...prev, dismissed: true }));
@@ -512,6 +547,10 @@ export function useDownlink(): UseDownlinkReturn {
     []
   );
 
+  const setLogLevel = useCallback(async (level: LogLevel): Promise<void> => {
+    await invoke("set_log_level", { level });
+  }, []);
+
   const getWindowState = useCallback(async (): Promise<WindowState> => {
     return invoke<WindowState>("get_window_state");
   }, []);
@@ -536,6 +575,10 @@ export function useDownlink(): UseDownlinkReturn {
     return invoke<string>("update_tool", { toolName: toolName });
   }, []);
 
+  const cancelToolUpdate = useCallback(async (toolName: string): Promise<boolean> => {
+    return invoke<boolean>("cancel_tool_update", { toolName: toolName });
+  }, []);
+
   // App updates
   const checkAppUpdate = useCallback(async (): Promise<AppUpdateInfo> => {
     return invoke<AppUpdateInfo>("check_app_update");
@@ -577,16 +620,33 @@ export function useDownlink(): UseDownlinkReturn {
     return invoke<string>("get_app_data_dir");
   }, []);
 
+  const getDatabasePath = useCallback(async (): Promise<string> => {
+    return invoke<string>("get_database_path");
+  }, []);
+
+  // Saved site passwords are plain text; they're left out unless includeCredentials is set
+  const backupDatabase = useCallback(
+    async (destPath: string, includeCredentials?: boolean): Promise<void> => {
+      await invoke("backup_database", { destPath, includeCredentials });
+    },
+    [],
+  );
+
+  // Writes a redacted support bundle to the logs folder and returns its path
+  const collectDiagnostics = useCallback(async (): Promise<string> => {
+    return invoke<string>("collect_diagnostics");
+  }, []);
+
   const getDefaultDownloadDir = useCallback(async (): Promise<string> => {
     return invoke<string>("get_default_download_dir");
   }, []);
 
-  const openFile = useCallback(async (path: string): Promise<void> => {
-    await invoke("open_file", { path });
+  const openFile = useCallback(async (path: string, outputDir?: string): Promise<void> => {
+    await invoke("open_file", { path, outputDir: outputDir ?? null });
   }, []);
 
-  const openFolder = useCallback(async (path: string): Promise<void> => {
-    await invoke("open_folder", { path });
+  const openFolder = useCallback(async (path: string, outputDir?: string): Promise<void> => {
+    await invoke("open_folder", { path, outputDir: outputDir ?? null });
   }, []);
 
   const clearError = useCallback(() => {
@@ -631,6 +691,7 @@ export function useDownlink(): UseDownlinkReturn {
     // Settings
     getSettings,
     saveSettings,
+    setLogLevel,
     getWindowState,
     saveWindowState,
 
@@ -638,6 +699,7 @@ export function useDownlink(): UseDownlinkReturn {
     getToolchainStatus,
     checkForUpdates,
     updateTool,
+    cancelToolUpdate,
 
     // App updates
     checkAppUpdate,
@@ -649,6 +711,9 @@ export function useDownlink(): UseDownlinkReturn {
 
     // Utilities
     getAppDataDir,
+    getDatabasePath,
+    backupDatabase,
+    collectDiagnostics,
     getDefaultDownloadDir,
     openFile,
     openFolder,
@@ -26,13 +26,45 @@ export type DownloadStatus =
 // Source kind for downloads
 export type SourceKind = "single" | "playlist_parent" | "playlist_item";
 
+// Options for preview_playlist
+export interface PreviewPlaylistOptions {
+  // Optional 1-based item range, or an explicit spec like "1-10,15"
+  start?: number | null;
+  end?: number | null;
+  items?: string | null;
+  // Pass the same id to cancel_metadata_fetch to abort
+  request_id?: string | null;
+}
+
+// One entry of a playlist preview
+export interface PlaylistPreviewEntry {
+  url: string;
+  title: string | null;
+  uploader: string | null;
+  duration_seconds: number | null;
+  thumbnail_url: string | null;
+}
+
+// Result of preview_playlist (nothing is queued)
+export interface PlaylistPreview {
+  url: string;
+  entries: PlaylistPreviewEntry[];
+  count: number;
+}
+
 // Queue item from backend
 export interface QueueItem {
   id: string;
+  created_at: string; // RFC 3339
+  updated_at: string; // RFC 3339
   source_url: string;
+  source_kind: SourceKind;
+  parent_id: string | null; // playlist row this item was expanded from
   title: string | null;
   uploader: string | null;
   thumbnail_url: string | null;
+  // Local cached copy (load with convertFileSrc); null until cache_thumbnail runs
+  thumbnail_path: string | null;
   duration_seconds: number | null;
   status: DownloadStatus;
   phase: string | null;
@@ -44,7 +76,73 @@ export interface QueueItem {
   preset_id: string;
   output_dir: string;
   final_path: string | null;
+  // Every output file (chapter splits, subtitles), final_path first
+  final_paths: string[];
+  error_code: string | null;
   error_message: string | null;
+  // Running, waiting for a download slot, or neither (e.g. a Queued row nothing will start)
+  queue_state: QueueState;
+}
+
+export type QueueState = "active" | "pending" | "not_managed";
+
+// Result of get_queue_tree: playlist items nested under their parent
+export interface QueueItemNode {
+  item: QueueItem;
+  children: QueueItemNode[];
+}
+
+// Completed download whose file was moved or deleted (from verify_files)
+export interface MissingFile {
+  id: string;
+  title: string | null;
+  final_path: string;
+}
+
+// Point on an active download's speed graph (from get_speed_samples)
+export interface SpeedSample {
+  at: string;
+  speed_bps: number | null;
+  percent: number | null;
+}
+
+// Download slot usage (from get_manager_status), e.g. "2/4 active, 3 queued"
+export interface ManagerStatus {
+  active: number;
+  max_concurrent: number;
+  pending: number; // waiting for a free slot
+  paused: boolean; // pause_all_downloads: nothing starts until resume_all_downloads
+  online: boolean; // false while downloads wait for the network to come back
+}
+
+// From get_sponsorblock_categories
+export interface SponsorBlockCategory {
+  id: string; // e.g. "music_offtopic"
+  label: string;
+  description: string;
+}
+
+// Result of clear_temp_files
+export interface CleanupReport {
+  removed: number;
+  freed_bytes: number;
+}
+
+export type ChecksumAlgorithm = "sha256" | "sha1" | "md5";
+
+// verify_download_checksum
+export interface ChecksumResult {
+  algorithm: ChecksumAlgorithm;
+  digest: string; // lowercase hex
+  matches: boolean | null; // null when no expected digest was given
+}
+
+// Optional get_queue filters
+export interface QueueFilter {
+  status?: DownloadStatus | null;
+  source_kind?: SourceKind | null;
+  // Hide playlist items while their parent is in the queue
+  collapse_playlists?: boolean;
 }
 
 // Preset info
@@ -60,6 +158,7 @@ export interface PresetWithHint extends PresetInfo {
 
 // Add URLs options
 export interface AddUrlsOptions {
+  // Empty string: use the site's preset or the default per URL
   preset_id: string;
   output_dir: string;
   parent_id: string | null;
@@ -69,6 +168,12 @@ export interface AddUrlsOptions {
   uploader?: string | null;
   thumbnail_url?: string | null;
   duration_seconds?: number | null;
+  // Optional time range to download, e.g. "*00:10:00-00:15:00"
+  sections?: string | null;
+  // Subtitle languages for these downloads, overriding the default language
+  subtitle_langs?: string[] | null;
+  // Speed limit in bytes/sec for these downloads, overriding the global limit
+  rate_limit_bps?: number | null;
 }
 
 // Add URLs result
@@ -81,9 +186,17 @@ export interface AddUrlsResult {
 export interface FetchMetadataOptions {
   preset_id: string;
   output_dir: string;
+  // Pass the same id to cancel_metadata_fetch to abort
+  request_id?: string | null;
 }
 
 // Fetch metadata result
+export interface Thumbnail {
+  url: string;
+  width: number | null;
+  height: number | null;
+}
+
 export interface FetchMetadataResult {
   id: string;
   url: string;
@@ -91,16 +204,129 @@ export interface FetchMetadataResult {
   title: string | null;
   uploader: string | null;
   duration_seconds: number | null;
-  thumbnail_url: string | null;
+  thumbnail_url: string | null; // default: largest up to 1280px wide
+  thumbnails: Thumbnail[]; // every size on offer, smallest first
   filesize_bytes: number | null;
   playlist_title: string | null;
   playlist_count_hint: number | null;
+  // Preset to preselect: the requested one, else the site's preset or the default
+  preset_id: string;
+}
+
+// Per-URL result of fetch_metadata_batch (exactly one of metadata/error is set)
+export interface FetchMetadataBatchItem {
+  url: string;
+  metadata: FetchMetadataResult | null;
+  error: string | null;
+}
+
+// Exact yt-dlp invocation returned by preview_command
+export interface CommandPreview {
+  program: string;
+  args: string[];
+}
+
+// Result of get_disk_space for the filesystem holding a folder
+export interface DiskSpace {
+  available_bytes: number;
+  total_bytes: number;
+}
+
+// Result of a successful test_proxy round trip
+export interface ProxyTestResult {
+  latency_ms: number;
+  status: number;
+}
+
+// A site with stored login credentials (list_site_credentials). Passwords are never returned.
+export interface SiteCredentialsInfo {
+  site: string;
+  username: string;
+}
+
+// Error returned by normalize_url
+export interface InvalidUrl {
+  reason: "empty" | "malformed" | "unsupported_scheme";
+  message: string;
+}
+
+// Dashboard aggregates from get_statistics
+export interface DailyCount {
+  date: string; // YYYY-MM-DD (UTC)
+  count: number;
+}
+
+export interface DownloadStatistics {
+  total_downloads: number;
+  total_bytes_downloaded: number;
+  by_status: Record<DownloadStatus, number>;
+  per_day: DailyCount[];
+}
+
+// Bytes of completed downloads (from get_bandwidth_usage)
+export type UsageRange = "today" | "week" | "month" | "all";
+
+export interface DailyUsage {
+  date: string; // YYYY-MM-DD (UTC)
+  bytes: number;
+}
+
+export interface BandwidthUsage {
+  session_bytes: number;
+  total_bytes: number;
+  per_day: DailyUsage[];
+}
+
+// Subtitle language offered for a video (from list_subtitles)
+export interface SubtitleTrack {
+  lang: string; // code for subtitle_langs, e.g. "en", "pt-BR"
+  name: string | null;
+  auto_generated: boolean;
+  formats: string[];
+}
+
+// Result of is_url_supported. "unsupported" may still work via yt-dlp's generic extractor
+export type UrlSupport =
+  | { verdict: "supported"; extractor: string }
+  | { verdict: "unsupported" }
+  | { verdict: "unknown" };
+
+// Rejection value of commands returning structured errors (download control so far;
+// the rest still reject with a plain string)
+export interface CommandError {
+  code: string; // ErrorCode, e.g. "NOT_FOUND", "INVALID_REQUEST", "DATABASE", "TOOL_MISSING"
+  message: string;
+  details?: string;
+}
+
+// Result of diagnose_url: yt-dlp --simulate --verbose output and classified error
+export interface UrlDiagnosis {
+  url: string;
+  exit_code: number | null;
+  stdout_tail: string[];
+  stderr_tail: string[];
+  error_code: string | null;
+  user_message: string | null;
+  actions: Array<{
+    kind: string;
+    label: string;
+  }>;
 }
 
 // Expand playlist options
 export interface ExpandPlaylistOptions {
   preset_id: string;
   output_dir: string;
+  // Optional 1-based item range, or an explicit spec like "1-10,15"
+  start?: number | null;
+  end?: number | null;
+  items?: string | null;
+  // Skip entries outside these lengths in seconds, e.g. min_duration: 60 skips shorts
+  min_duration?: number | null;
+  max_duration?: number | null;
+  keep_unknown_duration?: boolean | null; // default true
+  // Pass the same id to cancel_metadata_fetch to abort
+  request_id?: string | null;
 }
 
 // Expand playlist result
@@ -108,6 +334,7 @@ export interface ExpandPlaylistResult {
   parent_id: string;
   item_ids: string[];
   count: number;
+  skipped: number; // left out by the duration filters
 }
 
 // Tool status
@@ -131,6 +358,36 @@ export interface ToolchainStatus {
   overall_status: ToolStatus;
 }
 
+// Where a resolved tool binary comes from
+export type ToolSource = "updated" | "bundled" | "system";
+
+// Resolved binary for one tool (all null when not found)
+export interface ToolPath {
+  tool: string;
+  path: string | null;
+  version: string | null;
+  source: ToolSource | null;
+}
+
+// Resolved binaries for every managed tool
+export interface ToolPaths {
+  yt_dlp: ToolPath;
+  ffmpeg: ToolPath;
+  ffprobe: ToolPath;
+}
+
+// How verify_and_repair_tools fixed a tool
+export type RepairAction = "none" | "reset_to_bundled" | "redownload" | "unrepairable";
+
+// Result of verifying (and possibly repairing) one tool
+export interface ToolRepairResult {
+  tool: string;
+  status: ToolStatus; // before the repair
+  action: RepairAction;
+  error: string | null;
+  info: ToolInfo; // after the repair
+}
+
 // User settings
 export interface UserSettings {
   general: GeneralSettings;
@@ -142,18 +399,45 @@ export interface UserSettings {
   network: NetworkSettings;
 }
 
+// Settings with defaults filled in. at_default is keyed by "section.field"
+// (e.g. "network.retries") and is true when the value equals the default.
+export interface EffectiveSettings {
+  settings: UserSettings;
+  at_default: Record<string, boolean>;
+  // Valid but costly choices, e.g. write_comments being slow
+  warnings: SettingsFieldError[];
+}
+
+export interface SettingsFieldError {
+  field: string; // "section.field"
+  message: string;
+}
+
 export interface GeneralSettings {
   download_folder: string;
   default_preset: string;
   concurrency: number;
   auto_start: boolean;
+  auto_resume_on_launch: boolean; // restart downloads interrupted by exit/crash, up to concurrency
   notify_on_complete: boolean;
   minimize_to_tray: boolean;
   start_minimized: boolean;
   remember_window_state: boolean;
   show_advanced_by_default: boolean;
+  // Host (subdomains included) -> preset id, used when no preset is chosen
+  site_preset_overrides: Record<string, string>;
+  use_download_archive: boolean; // skip items yt-dlp has already downloaded
+  download_archive_scope: "global" | "per_folder";
+  download_to_temp: boolean; // stage in the app tmp dir, move on completion
+  // Passed verbatim to every yt-dlp call; quote values with spaces
+  extra_ytdlp_args: string;
+  // Extractor name -> "key=value;key2=value2", passed as --extractor-args
+  extractor_args: Record<string, string>;
+  log_level: LogLevel; // applied immediately on save
 }
 
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
+
 export interface FormatSettings {
   prefer_mp4: boolean;
   max_video_height: number;
@@ -162,7 +446,17 @@ export interface FormatSettings {
   embed_metadata: boolean;
   embed_thumbnail: boolean;
   write_info_json: boolean;
+  write_comments: boolean; // into the .info.json, so implies write_info_json; slow
+  write_description: boolean;
+  write_thumbnail: boolean;
+  split_chapters: boolean;
+  audio_normalize: boolean; // loudnorm, audio-only presets
+  remux_to: string; // "" | "mp4" | "mkv" | "webm" | "mov"; "" keeps the preset's container
   filename_template: string;
+  restrict_filenames: boolean; // --restrict-filenames, for FAT drives and network shares
+  trim_filenames: number; // 0 = off, else 20-255 characters
+  // Subfolder per download: "none" | "uploader" | "playlist" | "date" (upload month)
+  organize_by: string;
 }
 
 export interface SponsorBlockSettings {
@@ -187,9 +481,26 @@ export interface UpdateSettings {
   last_checked: string | null;
 }
 
+// Browser profile found by list_browser_profiles
+export interface BrowserProfile {
+  browser: string;
+  profile: string | null; // null = the browser's default profile
+  name: string;
+  path: string;
+}
+
+// Argument to set_cookies_from_browser
+export interface BrowserSelection {
+  browser: string;
+  profile?: string | null;
+  keyring?: string | null; // Linux only
+  container?: string | null; // Firefox only
+}
+
 export interface PrivacySettings {
   cookie_mode: string;
   cookies_path: string | null;
+  cookies_from_browser: string | null; // e.g. "chrome:Profile 1"; overrides cookies_path
   clear_cookies_on_exit: boolean;
   keep_history: boolean;
   max_history_entries: number;
@@ -199,9 +510,23 @@ export interface NetworkSettings {
   use_proxy: boolean;
   proxy_url: string;
   rate_limit_bps: number;
+  global_rate_limit: boolean;
   retries: number;
   concurrent_fragments: number;
   socket_timeout: number;
+  max_filesize_bytes: number; // 0 = no limit
+  external_downloader: "none" | "aria2c";
+  ip_version: "any" | "ipv4" | "ipv6";
+  source_address: string; // empty = system default
+  keep_fragments: boolean;
+  no_part: boolean; // stopped downloads restart from scratch when on
+  geo_bypass: boolean;
+  geo_bypass_country: string; // two-letter ISO code, empty = none
+  xff: string; // "default" | "never" | country code | CIDR block, empty = yt-dlp default
+  polite_mode: boolean; // pace downloads/requests to avoid site bans
+  sleep_interval_secs: number; // 0-600
+  max_sleep_interval_secs: number; // >= sleep_interval_secs; random wait between the two
+  sleep_requests_secs: number; // between extraction requests, 0 = none
 }
 
 // Window state
@@ -222,12 +547,15 @@ export type DownlinkEventType =
   | "PlaylistExpanded"
   | "DownloadQueued"
   | "DownloadStarted"
+  | "DownloadWarning"
   | "DownloadProgress"
   | "DownloadPostProcessing"
   | "DownloadStopped"
   | "DownloadCanceled"
   | "DownloadCompleted"
+  | "NotifyCompleted"
   | "DownloadFailed"
+  | "ConnectivityChanged"
   | "ToolUpdateAvailable"
   | "ToolUpdateProgress"
   | "ToolUpdateCompleted"
@@ -272,6 +600,34 @@ export interface DownloadCompletedEvent {
   };
 }
 
+export interface NotifyCompletedEvent {
+  event: "NotifyCompleted";
+  data: {
+    id: string;
+    title: string;
+    body: string;
+    final_path: string | null;
+  };
+}
+
+export interface DownloadStartedEvent {
+  event: "DownloadStarted";
+  data: {
+    id: string;
+    preset_name: string | null; // resolved preset, e.g. "1080p MP4"
+    output_template: string | null; // yt-dlp's -o value (staging folder with download_to_temp)
+  };
+}
+
+// Settings likely to make the download fail (e.g. H.264 remuxed into WebM); it still runs
+export interface DownloadWarningEvent {
+  event: "DownloadWarning";
+  data: {
+    id: string;
+    message: string;
+  };
+}
+
 export interface DownloadFailedEvent {
   event: "DownloadFailed";
   data: {
@@ -282,6 +638,15 @@ export interface DownloadFailedEvent {
       kind: string;
       label: string;
     }>;
+    raw_error: string | null;
+  };
+}
+
+// Offline: downloads wait instead of failing; back online: they resume
+export interface ConnectivityChangedEvent {
+  event: "ConnectivityChanged";
+  data: {
+    online: boolean;
   };
 }
 
@@ -289,7 +654,11 @@ export type DownlinkEvent =
   | AppReadyEvent
   | DownloadProgressEvent
   | DownloadCompletedEvent
+  | NotifyCompletedEvent
   | DownloadFailedEvent
+  | DownloadStartedEvent
+  | DownloadWarningEvent
+  | ConnectivityChangedEvent
   | { event: DownlinkEventType; data: unknown };
 
 // UI state types
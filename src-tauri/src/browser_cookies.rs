@@ -0,0 +1,432 @@
+//! Browser Cookies
+//!
+//! Builds yt-dlp's `--cookies-from-browser BROWSER[+KEYRING][:PROFILE][::CONTAINER]`
+//! value and finds the browser profiles installed on this machine, so users with
+//! several Chrome (or Firefox) profiles can pick the one they're signed in with.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+
+/// Browsers `--cookies-from-browser` can read.
+pub const SUPPORTED_BROWSERS: &[&str] = &[
+    "brave", "chrome", "chromium", "edge", "firefox", "opera", "safari", "vivaldi", "whale",
+];
+
+/// Linux keyrings yt-dlp can decrypt Chromium cookies with.
+pub const SUPPORTED_KEYRINGS: &[&str] = &[
+    "basictext",
+    "gnomekeyring",
+    "kwallet",
+    "kwallet5",
+    "kwallet6",
+];
+
+/// Which browser, and optionally which profile of it, to read cookies from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BrowserSelection {
+    pub browser: String,
+    /// Profile name (e.g. "Profile 1") or the full path to the profile folder.
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub keyring: Option<String>,
+    /// Firefox container name.
+    #[serde(default)]
+    pub container: Option<String>,
+}
+
+/// A profile found on disk, for the cookie import picker.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BrowserProfile {
+    pub browser: String,
+    /// Value for [`BrowserSelection::profile`]; `None` means the browser's default.
+    pub profile: Option<String>,
+    /// Name the browser shows for the profile.
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Build the `--cookies-from-browser` value for `selection`. Errors are user-facing.
+pub fn cookies_from_browser_value(selection: &BrowserSelection) -> Result<String, String> {
+    let browser = selection.browser.trim().to_lowercase();
+    if !SUPPORTED_BROWSERS.contains(&browser.as_str()) {
+        return Err(format!(
+            "Can't read cookies from \"{}\". Supported browsers: {}",
+            selection.browser,
+            SUPPORTED_BROWSERS.join(", ")
+        ));
+    }
+
+    let mut value = browser.clone();
+    if let Some(keyring) = non_empty(&selection.keyring) {
+        let keyring = keyring.to_lowercase();
+        if !SUPPORTED_KEYRINGS.contains(&keyring.as_str()) {
+            return Err(format!(
+                "Unknown keyring \"{keyring}\". Supported keyrings: {}",
+                SUPPORTED_KEYRINGS.join(", ")
+            ));
+        }
+        value.push('+');
+        value.push_str(&keyring);
+    }
+    if let Some(profile) = non_empty(&selection.profile) {
+        if profile.contains("::") {
+            return Err(format!("Browser profile can't contain \"::\": {profile}"));
+        }
+        value.push(':');
+        value.push_str(profile);
+    }
+    if let Some(container) = non_empty(&selection.container) {
+        if browser != "firefox" {
+            return Err("Containers are only supported for Firefox".to_string());
+        }
+        value.push_str("::");
+        value.push_str(container);
+    }
+    Ok(value)
+}
+
+/// Split a stored `--cookies-from-browser` value back into its parts, checking it
+/// the same way [`cookies_from_browser_value`] does.
+pub fn parse_cookies_from_browser(value: &str) -> Result<BrowserSelection, String> {
+    let (rest, container) = match value.split_once("::") {
+        Some((rest, container)) => (rest, Some(container.to_string())),
+        None => (value, None),
+    };
+    let (head, profile) = match rest.split_once(':') {
+        Some((head, profile)) => (head, Some(profile.to_string())),
+        None => (rest, None),
+    };
+    let (browser, keyring) = match head.split_once('+') {
+        Some((browser, keyring)) => (browser, Some(keyring.to_string())),
+        None => (head, None),
+    };
+
+    let selection = BrowserSelection {
+        browser: browser.to_string(),
+        profile,
+        keyring,
+        container,
+    };
+    cookies_from_browser_value(&selection)?;
+    Ok(selection)
+}
+
+/// Profiles of every supported browser found in the usual places for this OS.
+/// Best-effort: unreadable or unusual installs are skipped.
+pub fn detect_browser_profiles() -> Vec<BrowserProfile> {
+    let Some(base) = BaseDirs::new() else {
+        return Vec::new();
+    };
+
+    let mut profiles = Vec::new();
+    for (browser, dir) in chromium_user_data_dirs(&base) {
+        if browser == "opera" {
+            // Opera keeps a single profile in the user data dir itself
+            if dir.join("Preferences").is_file() {
+                profiles.push(BrowserProfile {
+                    browser: browser.to_string(),
+                    profile: None,
+                    name: "Default".to_string(),
+                    path: dir,
+                });
+            }
+            continue;
+        }
+        profiles.extend(chromium_profiles(browser, &dir));
+    }
+    if let Some(dir) = firefox_dir(&base) {
+        if let Ok(ini) = fs::read_to_string(dir.join("profiles.ini")) {
+            profiles.extend(parse_firefox_profiles(&ini, &dir));
+        }
+    }
+    if cfg!(target_os = "macos") {
+        let safari = base.home_dir().join("Library/Safari");
+        if safari.is_dir() {
+            profiles.push(BrowserProfile {
+                browser: "safari".to_string(),
+                profile: None,
+                name: "Safari".to_string(),
+                path: safari,
+            });
+        }
+    }
+    profiles
+}
+
+/// Profiles in a Chromium-style user data dir: `Default` and `Profile N` folders
+/// that have a `Preferences` file. The display name comes from that file.
+pub fn chromium_profiles(browser: &str, user_data_dir: &Path) -> Vec<BrowserProfile> {
+    let Ok(entries) = fs::read_dir(user_data_dir) else {
+        return Vec::new();
+    };
+
+    let mut profiles: Vec<BrowserProfile> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir_name = entry.file_name().to_string_lossy().to_string();
+            if dir_name != "Default" && !dir_name.starts_with("Profile ") {
+                return None;
+            }
+            let path = entry.path();
+            let preferences = fs::read_to_string(path.join("Preferences")).ok()?;
+            let name = serde_json::from_str::<serde_json::Value>(&preferences)
+                .ok()
+                .and_then(|v| v["profile"]["name"].as_str().map(str::to_string))
+                .filter(|n| !n.is_empty())
+                .unwrap_or_else(|| dir_name.clone());
+            Some(BrowserProfile {
+                browser: browser.to_string(),
+                profile: Some(dir_name),
+                name,
+                path,
+            })
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.profile.cmp(&b.profile));
+    profiles
+}
+
+/// Profiles listed in Firefox's `profiles.ini`. They're selected by full path, as
+/// the folder layout under the Firefox dir differs between OSes.
+pub fn parse_firefox_profiles(ini: &str, firefox_dir: &Path) -> Vec<BrowserProfile> {
+    let mut profiles = Vec::new();
+    let mut section: Option<(Option<String>, Option<String>, bool)> = None;
+
+    let mut finish = |section: Option<(Option<String>, Option<String>, bool)>| {
+        if let Some((name, Some(path), relative)) = section {
+            let path = if relative {
+                firefox_dir.join(path)
+            } else {
+                PathBuf::from(path)
+            };
+            profiles.push(BrowserProfile {
+                browser: "firefox".to_string(),
+                profile: Some(path.to_string_lossy().to_string()),
+                name: name.unwrap_or_else(|| "Firefox".to_string()),
+                path,
+            });
+        }
+    };
+
+    for line in ini.lines().map(str::trim) {
+        if line.starts_with('[') {
+            finish(section.take());
+            if line.starts_with("[Profile") {
+                section = Some((None, None, true));
+            }
+            continue;
+        }
+        let (Some(current), Some((key, value))) = (section.as_mut(), line.split_once('=')) else {
+            continue;
+        };
+        match key.trim() {
+            "Name" => current.0 = Some(value.trim().to_string()),
+            "Path" => current.1 = Some(value.trim().to_string()),
+            "IsRelative" => current.2 = value.trim() == "1",
+            _ => {}
+        }
+    }
+    finish(section);
+    profiles
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Chromium-based browsers' user data dirs for this OS.
+fn chromium_user_data_dirs(base: &BaseDirs) -> Vec<(&'static str, PathBuf)> {
+    if cfg!(target_os = "windows") {
+        let local = base.data_local_dir();
+        vec![
+            ("chrome", local.join(r"Google\Chrome\User Data")),
+            ("chromium", local.join(r"Chromium\User Data")),
+            (
+                "brave",
+                local.join(r"BraveSoftware\Brave-Browser\User Data"),
+            ),
+            ("edge", local.join(r"Microsoft\Edge\User Data")),
+            ("vivaldi", local.join(r"Vivaldi\User Data")),
+            ("whale", local.join(r"Naver\Naver Whale\User Data")),
+            (
+                "opera",
+                base.data_dir().join(r"Opera Software\Opera Stable"),
+            ),
+        ]
+    } else if cfg!(target_os = "macos") {
+        let support = base.data_dir();
+        vec![
+            ("chrome", support.join("Google/Chrome")),
+            ("chromium", support.join("Chromium")),
+            ("brave", support.join("BraveSoftware/Brave-Browser")),
+            ("edge", support.join("Microsoft Edge")),
+            ("vivaldi", support.join("Vivaldi")),
+            ("whale", support.join("Naver/Whale")),
+            ("opera", support.join("com.operasoftware.Opera")),
+        ]
+    } else {
+        let config = base.config_dir();
+        vec![
+            ("chrome", config.join("google-chrome")),
+            ("chromium", config.join("chromium")),
+            ("brave", config.join("BraveSoftware/Brave-Browser")),
+            ("edge", config.join("microsoft-edge")),
+            ("vivaldi", config.join("vivaldi")),
+            ("whale", config.join("naver-whale")),
+            ("opera", config.join("opera")),
+        ]
+    }
+}
+
+/// Folder holding Firefox's `profiles.ini` for this OS.
+fn firefox_dir(base: &BaseDirs) -> Option<PathBuf> {
+    let dir = if cfg!(target_os = "windows") {
+        base.data_dir().join(r"Mozilla\Firefox")
+    } else if cfg!(target_os = "macos") {
+        base.data_dir().join("Firefox")
+    } else {
+        base.home_dir().join(".mozilla/firefox")
+    };
+    dir.is_dir().then_some(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(
+        browser: &str,
+        profile: Option<&str>,
+        container: Option<&str>,
+    ) -> BrowserSelection {
+        BrowserSelection {
+            browser: browser.to_string(),
+            profile: profile.map(str::to_string),
+            keyring: None,
+            container: container.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_cookies_from_browser_value() {
+        assert_eq!(
+            cookies_from_browser_value(&selection("chrome", None, None)).unwrap(),
+            "chrome"
+        );
+        assert_eq!(
+            cookies_from_browser_value(&selection("Chrome", Some("Profile 1"), None)).unwrap(),
+            "chrome:Profile 1"
+        );
+        assert_eq!(
+            cookies_from_browser_value(&selection(
+                "firefox",
+                Some("/home/me/.mozilla/firefox/abc.default"),
+                Some("Work")
+            ))
+            .unwrap(),
+            "firefox:/home/me/.mozilla/firefox/abc.default::Work"
+        );
+        assert_eq!(
+            cookies_from_browser_value(&selection(
+                "edge",
+                Some(r"C:\Users\me\AppData\Local\Microsoft\Edge\User Data\Default"),
+                None
+            ))
+            .unwrap(),
+            r"edge:C:\Users\me\AppData\Local\Microsoft\Edge\User Data\Default"
+        );
+        assert_eq!(
+            cookies_from_browser_value(&BrowserSelection {
+                keyring: Some("gnomekeyring".to_string()),
+                ..selection("chromium", Some("Default"), None)
+            })
+            .unwrap(),
+            "chromium+gnomekeyring:Default"
+        );
+        // Blank parts are left out rather than producing "chrome:"
+        assert_eq!(
+            cookies_from_browser_value(&selection("chrome", Some("  "), Some(""))).unwrap(),
+            "chrome"
+        );
+    }
+
+    #[test]
+    fn test_cookies_from_browser_value_rejects_invalid_selections() {
+        assert!(cookies_from_browser_value(&selection("netscape", None, None)).is_err());
+        assert!(cookies_from_browser_value(&selection("chrome", None, Some("Work"))).is_err());
+        assert!(cookies_from_browser_value(&selection("chrome", Some("a::b"), None)).is_err());
+        assert!(cookies_from_browser_value(&BrowserSelection {
+            keyring: Some("vault".to_string()),
+            ..selection("chrome", None, None)
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_cookies_from_browser_round_trips() {
+        for value in [
+            "chrome",
+            "chrome:Profile 1",
+            "chromium+kwallet6:Default",
+            "firefox:/home/me/.mozilla/firefox/abc.default::Work",
+            r"edge:C:\Users\me\Edge\User Data\Default",
+        ] {
+            let parsed = parse_cookies_from_browser(value).unwrap();
+            assert_eq!(cookies_from_browser_value(&parsed).unwrap(), value);
+        }
+        assert!(parse_cookies_from_browser("lynx").is_err());
+    }
+
+    #[test]
+    fn test_chromium_profiles() {
+        let dir = std::env::temp_dir().join(format!("downlink-chrome-{}", uuid::Uuid::new_v4()));
+        for (profile, prefs) in [
+            ("Default", r#"{"profile":{"name":"Personal"}}"#),
+            ("Profile 2", r#"{"profile":{}}"#),
+        ] {
+            fs::create_dir_all(dir.join(profile)).unwrap();
+            fs::write(dir.join(profile).join("Preferences"), prefs).unwrap();
+        }
+        fs::create_dir_all(dir.join("Profile 3")).unwrap();
+        fs::create_dir_all(dir.join("ShaderCache")).unwrap();
+
+        let profiles = chromium_profiles("chrome", &dir);
+        let summary: Vec<(Option<&str>, &str)> = profiles
+            .iter()
+            .map(|p| (p.profile.as_deref(), p.name.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some("Default"), "Personal"),
+                (Some("Profile 2"), "Profile 2")
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_firefox_profiles() {
+        let ini = "[Install4F96D1932A9F858E]\nDefault=Profiles/abc.default-release\n\n\
+                   [Profile1]\nName=work\nIsRelative=0\nPath=/mnt/work-profile\n\n\
+                   [Profile0]\nName=default-release\nIsRelative=1\nPath=Profiles/abc.default-release\nDefault=1\n\n\
+                   [General]\nStartWithLastProfile=1\n";
+        let root = Path::new("/home/me/.mozilla/firefox");
+
+        let profiles = parse_firefox_profiles(ini, root);
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].name, "work");
+        assert_eq!(profiles[0].path, PathBuf::from("/mnt/work-profile"));
+        assert_eq!(profiles[1].name, "default-release");
+        assert_eq!(profiles[1].path, root.join("Profiles/abc.default-release"));
+        assert_eq!(
+            profiles[1].profile.as_deref(),
+            Some(root.join("Profiles/abc.default-release").to_str().unwrap())
+        );
+    }
+}
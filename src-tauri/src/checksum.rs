@@ -0,0 +1,179 @@
+//! File Checksums
+//!
+//! Streamed digests of downloaded files, for users checking their copies against a
+//! published hash. sha1 and md5 are only offered to match published checksums,
+//! never for security.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::Result;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Read size when hashing, so large files are never held in memory.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+}
+
+/// Digest of a file, and whether it matched the expected value if one was given.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecksumResult {
+    pub algorithm: ChecksumAlgorithm,
+    /// Lowercase hex.
+    pub digest: String,
+    pub matches: Option<bool>,
+}
+
+impl ChecksumResult {
+    pub fn new(algorithm: ChecksumAlgorithm, digest: String, expected: Option<&str>) -> Self {
+        let matches = expected
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(|e| e.eq_ignore_ascii_case(&digest));
+        Self {
+            algorithm,
+            digest,
+            matches,
+        }
+    }
+}
+
+/// Hex digest of everything `reader` yields, read in fixed-size chunks.
+pub fn hash_reader<R: Read>(mut reader: R, algorithm: ChecksumAlgorithm) -> io::Result<String> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Hex digest of the file at `path`.
+pub fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> Result<String> {
+    let file = File::open(path)?;
+    Ok(hash_reader(file, algorithm)?)
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(Md5),
+}
+
+impl Hasher {
+    fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Sha1 => Self::Sha1(Sha1::new()),
+            ChecksumAlgorithm::Md5 => Self::Md5(Md5::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(data),
+            Self::Sha1(h) => h.update(data),
+            Self::Md5(h) => h.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Sha256(h) => h.finalize().to_vec(),
+            Self::Sha1(h) => h.finalize().to_vec(),
+            Self::Md5(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(data: &[u8], algorithm: ChecksumAlgorithm) -> String {
+        hash_reader(data, algorithm).unwrap()
+    }
+
+    #[test]
+    fn test_known_digests() {
+        let cases: [(&[u8], &str, &str, &str); 3] = [
+            (
+                b"",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+                "d41d8cd98f00b204e9800998ecf8427e",
+            ),
+            (
+                b"abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+                "900150983cd24fb0d6963f7d28e17f72",
+            ),
+            (
+                // 56 bytes, so padding spills into a second block
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+                "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+                "8215ef0796a20bcaaae116d3876c664a",
+            ),
+        ];
+        for (data, sha256, sha1, md5) in cases {
+            assert_eq!(digest(data, ChecksumAlgorithm::Sha256), sha256);
+            assert_eq!(digest(data, ChecksumAlgorithm::Sha1), sha1);
+            assert_eq!(digest(data, ChecksumAlgorithm::Md5), md5);
+        }
+    }
+
+    #[test]
+    fn test_hash_file_across_chunks() {
+        // One million 'a's spans many read chunks
+        let path = std::env::temp_dir().join(format!("downlink-sum-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, vec![b'a'; 1_000_000]).unwrap();
+
+        let sha256 = hash_file(&path, ChecksumAlgorithm::Sha256).unwrap();
+        let sha1 = hash_file(&path, ChecksumAlgorithm::Sha1).unwrap();
+        let md5 = hash_file(&path, ChecksumAlgorithm::Md5).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            sha256,
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+        assert_eq!(sha1, "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+        assert_eq!(md5, "7707d6ae4e027c70eea2a935c2296f21");
+    }
+
+    #[test]
+    fn test_checksum_result_compares_expected() {
+        let digest = "900150983cd24fb0d6963f7d28e17f72".to_string();
+        let result =
+            |expected| ChecksumResult::new(ChecksumAlgorithm::Md5, digest.clone(), expected);
+
+        assert_eq!(result(None).matches, None);
+        assert_eq!(result(Some("  ")).matches, None);
+        assert_eq!(
+            result(Some(" 900150983CD24FB0D6963F7D28E17F72\n")).matches,
+            Some(true)
+        );
+        assert_eq!(
+            result(Some("d41d8cd98f00b204e9800998ecf8427e")).matches,
+            Some(false)
+        );
+    }
+}
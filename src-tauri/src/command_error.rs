@@ -0,0 +1,171 @@
+//! Structured errors for Tauri commands
+//!
+//! Commands have returned `Result<T, String>`, which leaves the UI parsing text to
+//! decide what to offer. Commands are moving to [`CommandError`], whose `code`
+//! reuses the download [`ErrorCode`]s; the download-control commands come first.
+
+use serde::Serialize;
+
+use crate::download_manager::{classify_error, RequestError};
+use crate::events::ErrorCode;
+use crate::url_utils::InvalidUrl;
+use crate::ytdlp::{YtDlpError, YtDlpErrorKind};
+
+/// Error returned to the frontend by converted commands.
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct CommandError {
+    pub code: ErrorCode,
+    /// Shown to the user, e.g. "Failed to start download: Download not found".
+    pub message: String,
+    /// Underlying causes, for logs and a "details" disclosure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// Wrap an internal error as "`context`: error". The code comes from the first
+    /// error in the chain with a known type; the rest of the chain goes in `details`.
+    pub fn wrap(context: &str, err: anyhow::Error) -> Self {
+        let causes: Vec<String> = err.chain().skip(1).map(ToString::to_string).collect();
+        Self {
+            code: code_for(&err),
+            message: format!("{context}: {err}"),
+            details: (!causes.is_empty()).then(|| causes.join(": ")),
+        }
+    }
+}
+
+/// The code for an internal error, by the type of the errors in its chain.
+fn code_for(err: &anyhow::Error) -> ErrorCode {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<RequestError>() {
+            return match e {
+                RequestError::NotFound => ErrorCode::NotFound,
+                RequestError::Invalid(_) => ErrorCode::InvalidRequest,
+            };
+        }
+        if let Some(e) = cause.downcast_ref::<YtDlpError>() {
+            return match e.kind {
+                YtDlpErrorKind::NotFound => ErrorCode::ToolMissing,
+                YtDlpErrorKind::Timeout => ErrorCode::Network,
+                YtDlpErrorKind::InvalidJson => ErrorCode::ExtractorOutdated,
+                YtDlpErrorKind::Canceled => ErrorCode::Canceled,
+                YtDlpErrorKind::NonZeroExit => {
+                    let stderr = e
+                        .output
+                        .as_ref()
+                        .map(|o| o.stderr_lines.join("\n"))
+                        .unwrap_or_default();
+                    classify_error(&stderr).0
+                }
+            };
+        }
+        if cause.is::<InvalidUrl>() {
+            return ErrorCode::InvalidUrl;
+        }
+        if cause.is::<rusqlite::Error>() {
+            return ErrorCode::Database;
+        }
+        if cause.is::<std::io::Error>() {
+            return ErrorCode::OutputWriteFailed;
+        }
+        if cause.is::<reqwest::Error>() {
+            return ErrorCode::Network;
+        }
+    }
+    ErrorCode::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn test_wrap_maps_request_errors() {
+        let err = CommandError::wrap("Failed to start download", RequestError::NotFound.into());
+        assert_eq!(err.code, ErrorCode::NotFound);
+        assert_eq!(err.message, "Failed to start download: Download not found");
+        assert_eq!(err.details, None);
+
+        let err = CommandError::wrap(
+            "Failed to retry post-processing",
+            RequestError::Invalid("Download is not in a failed state".to_string()).into(),
+        );
+        assert_eq!(err.code, ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn test_wrap_maps_ytdlp_errors() {
+        let ytdlp = |kind, stderr: &str| -> anyhow::Error {
+            YtDlpError {
+                kind,
+                message: "yt-dlp failed".to_string(),
+                output: Some(crate::ytdlp::YtDlpOutput {
+                    stdout_lines: Vec::new(),
+                    stderr_lines: vec![stderr.to_string()],
+                    exit_code: Some(1),
+                }),
+            }
+            .into()
+        };
+
+        let code = |err| CommandError::wrap("Failed", err).code;
+        assert_eq!(
+            code(ytdlp(YtDlpErrorKind::NotFound, "")),
+            ErrorCode::ToolMissing
+        );
+        assert_eq!(
+            code(ytdlp(YtDlpErrorKind::Canceled, "")),
+            ErrorCode::Canceled
+        );
+        assert_eq!(
+            code(ytdlp(
+                YtDlpErrorKind::NonZeroExit,
+                "ERROR: HTTP Error 429: Too Many Requests"
+            )),
+            ErrorCode::RateLimited
+        );
+    }
+
+    #[test]
+    fn test_wrap_finds_code_in_chain_and_keeps_causes() {
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "access denied");
+        let err = CommandError::wrap(
+            "Failed to move file",
+            anyhow::Error::new(io).context("rename /a to /b"),
+        );
+        assert_eq!(err.code, ErrorCode::OutputWriteFailed);
+        assert_eq!(err.message, "Failed to move file: rename /a to /b");
+        assert_eq!(err.details.as_deref(), Some("access denied"));
+
+        let err = CommandError::wrap("Failed", anyhow!("something odd"));
+        assert_eq!(err.code, ErrorCode::Unknown);
+
+        let db: anyhow::Result<()> = Err(rusqlite::Error::InvalidQuery).context("load queue");
+        assert_eq!(
+            CommandError::wrap("Failed", db.unwrap_err()).code,
+            ErrorCode::Database
+        );
+    }
+
+    #[test]
+    fn test_command_error_serializes_code() {
+        let json =
+            serde_json::to_value(CommandError::new(ErrorCode::NotFound, "Download not found"))
+                .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"code": "NOT_FOUND", "message": "Download not found"})
+        );
+    }
+}
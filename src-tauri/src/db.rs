@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -5,23 +6,57 @@ use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Utc};
 use directories::ProjectDirs;
 use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
 use uuid::Uuid;
 
-/// Database schema version.
+/// A single schema migration step.
 ///
-/// Bump this when introducing a new migration.
-const SCHEMA_VERSION: i64 = 1;
+/// Each step runs inside its own transaction together with the `schema_version` bump.
+type Migration = fn(&Connection) -> Result<()>;
+
+/// Ordered schema migrations. Entry `i` upgrades the schema from version `i` to `i + 1`.
+///
+/// Append new migrations to the end; never reorder or remove existing entries.
+const MIGRATIONS: &[Migration] = &[
+    migration_v1,
+    migration_v2,
+    migration_v3,
+    migration_v4,
+    migration_v5,
+    migration_v6,
+    migration_v7,
+    migration_v8,
+    migration_v9,
+    migration_v10,
+    migration_v11,
+    migration_v12,
+];
 
 /// Database handle wrapper.
 ///
 /// Notes:
-/// - This uses `rusqlite::Connection`, which is not `Send`/`Sync`.
-/// - In practice, you should keep DB access on a single thread (or wrap behind a Tokio task).
+/// - This uses `rusqlite::Connection`, which is not `Sync`.
+/// - The app keeps it on the thread behind [`crate::db_actor::DbHandle`].
 pub struct Db {
     conn: Connection,
     path: PathBuf,
 }
 
+/// Phase of a download stopped because the app exited or crashed, as opposed to
+/// one the user stopped.
+pub const INTERRUPTED_PHASE: &str = "Interrupted";
+
+/// Progress fields of one download, as written by [`Db::update_progress_batch`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressUpdate {
+    pub id: Uuid,
+    pub percent: Option<f64>,
+    pub bytes_downloaded: Option<i64>,
+    pub bytes_total: Option<i64>,
+    pub speed_bps: Option<i64>,
+    pub eta_seconds: Option<i64>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DownloadStatus {
     Queued,
@@ -36,6 +71,18 @@ pub enum DownloadStatus {
 }
 
 impl DownloadStatus {
+    pub const ALL: [DownloadStatus; 9] = [
+        DownloadStatus::Queued,
+        DownloadStatus::Fetching,
+        DownloadStatus::Ready,
+        DownloadStatus::Downloading,
+        DownloadStatus::PostProcessing,
+        DownloadStatus::Stopped,
+        DownloadStatus::Done,
+        DownloadStatus::Failed,
+        DownloadStatus::Canceled,
+    ];
+
     pub fn as_str(self) -> &'static str {
         match self {
             DownloadStatus::Queued => "queued",
@@ -64,6 +111,14 @@ impl DownloadStatus {
             _ => return None,
         })
     }
+
+    /// Whether yt-dlp is (or is about to be) running for a download in this state.
+    pub fn is_active(self) -> bool {
+        matches!(
+            self,
+            DownloadStatus::Fetching | DownloadStatus::Downloading | DownloadStatus::PostProcessing
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -92,6 +147,17 @@ impl SourceKind {
     }
 }
 
+/// Optional narrowing of the queue view (see [`Db::get_queue`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueFilter {
+    pub status: Option<DownloadStatus>,
+    pub source_kind: Option<SourceKind>,
+    /// Hide playlist items whose parent is itself in the queue, so the UI can show
+    /// them only when the parent is expanded. Items whose parent has finished or was
+    /// canceled (orphaned in the queue view) are still listed.
+    pub collapse_playlists: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct DownloadRow {
     pub id: Uuid,
@@ -122,8 +188,107 @@ pub struct DownloadRow {
 
     pub error_code: Option<String>,
     pub error_message: Option<String>,
+
+    /// yt-dlp `--download-sections` spec for partial downloads (e.g. `*00:10:00-00:15:00`).
+    pub sections: Option<String>,
+
+    /// Tail of yt-dlp's stderr from the last failure, for diagnostics.
+    pub raw_error: Option<String>,
+
+    /// Local copy of the thumbnail in the app's thumbnail cache.
+    pub thumbnail_path: Option<String>,
+
+    /// Files yt-dlp wrote during the last failed run (separate video/audio streams
+    /// before merging), so a post-processing failure can be retried without
+    /// downloading again. Stored as a JSON array.
+    pub downloaded_files: Vec<String>,
+
+    /// Every output file of a completed download (per-chapter files, subtitles),
+    /// with `final_path` first. Stored as a JSON array.
+    pub final_paths: Vec<String>,
+
+    /// The URL exactly as the user entered it, before normalization dropped the
+    /// fragment (e.g. a `#t=90` timestamp) and default port.
+    pub original_url: Option<String>,
+
+    /// Comma-separated `--sub-langs` chosen for this download, overriding the default language.
+    pub subtitle_langs: Option<String>,
+
+    /// Title of the playlist a `playlist_item` was expanded from.
+    pub playlist_title: Option<String>,
+
+    /// 1-based position of a `playlist_item` in its playlist.
+    pub playlist_index: Option<i64>,
+
+    /// Per-download speed limit in bytes/sec, overriding the global `rate_limit_bps`.
+    pub rate_limit_bps: Option<i64>,
+}
+
+/// Login passed to yt-dlp as `--username`/`--password`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SiteCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+// Keep the password out of `{:?}` output (and therefore out of logs)
+impl std::fmt::Debug for SiteCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SiteCredentials")
+            .field("username", &self.username)
+            .field("password", &"****")
+            .finish()
+    }
+}
+
+/// Key credentials are stored under: the lowercase host without a leading `www.`.
+///
+/// Accepts a full URL or a bare host (`example.com`).
+pub fn site_key(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    let parsed = if trimmed.contains("://") {
+        url::Url::parse(trimmed).ok()?
+    } else {
+        url::Url::parse(&format!("https://{trimmed}")).ok()?
+    };
+    let host = parsed.host_str()?.to_ascii_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    if host.is_empty() {
+        return None;
+    }
+    Some(host.to_string())
+}
+
+/// Aggregates for the statistics dashboard.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadStatistics {
+    pub total_downloads: u64,
+    /// Sum of file sizes of completed downloads.
+    pub total_bytes_downloaded: u64,
+    /// Count per status, keyed by the status string (e.g. "done").
+    pub by_status: BTreeMap<String, u64>,
+    /// Downloads added per UTC day over the last `STATS_DAYS` days, oldest first.
+    pub per_day: Vec<DailyCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DailyCount {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub count: u64,
+}
+
+/// Bytes of completed downloads on one UTC day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DailyUsage {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub bytes: u64,
 }
 
+/// How many days of history `get_statistics` reports in `per_day`.
+pub const STATS_DAYS: i64 = 30;
+
 /// Determines the per-user app data directory and returns its path.
 ///
 /// macOS:  ~/Library/Application Support/Downlink
@@ -144,6 +309,31 @@ pub fn db_path() -> Result<PathBuf> {
     Ok(app_data_dir()?.join("downlink.sqlite3"))
 }
 
+/// `path` with symlinks and `..` resolved, for comparing paths. A file that doesn't
+/// exist yet is resolved through its parent.
+fn resolve_path(path: &Path) -> PathBuf {
+    if let Ok(resolved) = path.canonicalize() {
+        return resolved;
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Delete saved site logins from the database copy at `path`, then vacuum it so the
+/// passwords don't linger in free pages.
+fn strip_credentials(path: &Path) -> Result<()> {
+    let conn = Connection::open(path)?;
+    conn.execute("DELETE FROM site_credentials", [])?;
+    conn.execute_batch("VACUUM")?;
+    Ok(())
+}
+
 /// Create required directories for state storage: data dir, logs dir, tools dir, tmp dir.
 pub fn ensure_app_dirs() -> Result<AppDirs> {
     let data = app_data_dir()?;
@@ -164,6 +354,14 @@ pub fn ensure_app_dirs() -> Result<AppDirs> {
     })
 }
 
+/// Base name of the app log file; tauri_plugin_log appends `.log`.
+pub const LOG_FILE_STEM: &str = "downlink";
+
+/// Path of the current log file inside the logs dir (created if missing).
+pub fn log_file_path() -> Result<PathBuf> {
+    Ok(ensure_app_dirs()?.logs.join(format!("{LOG_FILE_STEM}.log")))
+}
+
 #[derive(Debug, Clone)]
 pub struct AppDirs {
     pub data: PathBuf,
@@ -175,9 +373,12 @@ pub struct AppDirs {
 impl Db {
     /// Open database connection at the per-user location and apply migrations.
     pub fn open() -> Result<Self> {
-        let dirs = ensure_app_dirs()?;
-        let path = dirs.data.join("downlink.sqlite3");
+        ensure_app_dirs()?;
+        Self::open_at(db_path()?)
+    }
 
+    /// Open (creating if needed) the database file at `path` and apply migrations.
+    fn open_at(path: PathBuf) -> Result<Self> {
         let mut conn = Connection::open(&path)
             .with_context(|| format!("open sqlite db: {}", path.display()))?;
 
@@ -193,10 +394,64 @@ impl Db {
         Ok(Self { conn, path })
     }
 
+    /// Open a throwaway in-memory database with all migrations applied.
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        let mut conn = Connection::open_in_memory()?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        migrate(&mut conn)?;
+
+        Ok(Self {
+            conn,
+            path: PathBuf::from(":memory:"),
+        })
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    /// Copy the whole database to `dest`, replacing any file there. Safe while the
+    /// app is running: the WAL is checkpointed first and the copy is a consistent
+    /// snapshot written with `VACUUM INTO`.
+    ///
+    /// Saved site passwords are stored in plain text, so they're left out of the copy
+    /// unless `include_credentials` is set.
+    pub fn backup_to(&self, dest: &Path, include_credentials: bool) -> Result<()> {
+        if resolve_path(dest) == resolve_path(&self.path) {
+            return Err(anyhow!(
+                "can't back up the database over itself: {}",
+                dest.display()
+            ));
+        }
+
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .context("checkpoint WAL")?;
+
+        // VACUUM INTO refuses to overwrite, so write next to `dest` and swap it in
+        let mut partial = dest.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let _ = fs::remove_file(&partial);
+        let partial_str = partial
+            .to_str()
+            .ok_or_else(|| anyhow!("backup path is not valid UTF-8: {}", partial.display()))?;
+
+        if let Err(e) = self.conn.execute("VACUUM INTO ?1", [partial_str]) {
+            let _ = fs::remove_file(&partial);
+            return Err(e).with_context(|| format!("write backup: {}", dest.display()));
+        }
+        if !include_credentials {
+            if let Err(e) = strip_credentials(&partial) {
+                let _ = fs::remove_file(&partial);
+                return Err(e).with_context(|| format!("write backup: {}", dest.display()));
+            }
+        }
+        fs::rename(&partial, dest).with_context(|| format!("move backup: {}", dest.display()))?;
+        Ok(())
+    }
+
     pub fn conn(&self) -> &Connection {
         &self.conn
     }
@@ -269,86 +524,233 @@ impl Db {
                   preset_id, output_dir,
                   final_path,
                   progress_percent, bytes_downloaded, bytes_total, speed_bps, eta_seconds,
-                  error_code, error_message
+                  error_code, error_message,
+                  sections, raw_error, thumbnail_path, downloaded_files, final_paths, original_url, subtitle_langs, playlist_title, playlist_index, rate_limit_bps
                 FROM downloads
                 WHERE id = ?1
                 "#,
                 params![id.to_string()],
-                |r| {
-                    let id: String = r.get(0)?;
-                    let created_at: String = r.get(1)?;
-                    let updated_at: String = r.get(2)?;
-                    let source_url: String = r.get(3)?;
-                    let source_kind: String = r.get(4)?;
-                    let parent_id: Option<String> = r.get(5)?;
-                    let title: Option<String> = r.get(6)?;
-                    let uploader: Option<String> = r.get(7)?;
-                    let duration_seconds: Option<i64> = r.get(8)?;
-                    let thumbnail_url: Option<String> = r.get(9)?;
-                    let status: String = r.get(10)?;
-                    let phase: Option<String> = r.get(11)?;
-                    let preset_id: String = r.get(12)?;
-                    let output_dir: String = r.get(13)?;
-                    let final_path: Option<String> = r.get(14)?;
-                    let progress_percent: Option<f64> = r.get(15)?;
-                    let bytes_downloaded: Option<i64> = r.get(16)?;
-                    let bytes_total: Option<i64> = r.get(17)?;
-                    let speed_bps: Option<i64> = r.get(18)?;
-                    let eta_seconds: Option<i64> = r.get(19)?;
-                    let error_code: Option<String> = r.get(20)?;
-                    let error_message: Option<String> = r.get(21)?;
-
-                    let id = Uuid::parse_str(&id).map_err(|_| rusqlite::Error::InvalidQuery)?;
-                    let created_at = DateTime::parse_from_rfc3339(&created_at)
-                        .map_err(|_| rusqlite::Error::InvalidQuery)?
-                        .with_timezone(&Utc);
-                    let updated_at = DateTime::parse_from_rfc3339(&updated_at)
-                        .map_err(|_| rusqlite::Error::InvalidQuery)?
-                        .with_timezone(&Utc);
-
-                    let source_kind =
-                        SourceKind::from_str(&source_kind).ok_or(rusqlite::Error::InvalidQuery)?;
-                    let parent_id = match parent_id {
-                        Some(s) => {
-                            Some(Uuid::parse_str(&s).map_err(|_| rusqlite::Error::InvalidQuery)?)
-                        }
-                        None => None,
-                    };
-
-                    let status =
-                        DownloadStatus::from_str(&status).ok_or(rusqlite::Error::InvalidQuery)?;
-
-                    Ok(DownloadRow {
-                        id,
-                        created_at,
-                        updated_at,
-                        source_url,
-                        source_kind,
-                        parent_id,
-                        title,
-                        uploader,
-                        duration_seconds,
-                        thumbnail_url,
-                        status,
-                        phase,
-                        preset_id,
-                        output_dir,
-                        final_path,
-                        progress_percent,
-                        bytes_downloaded,
-                        bytes_total,
-                        speed_bps,
-                        eta_seconds,
-                        error_code,
-                        error_message,
-                    })
-                },
+                Self::row_to_download,
             )
             .optional()?;
 
         Ok(row)
     }
 
+    /// Switch a download to another preset. Refused while the download is active,
+    /// since the running yt-dlp process was started with the old preset's arguments.
+    pub fn set_preset(&mut self, id: Uuid, preset_id: &str) -> Result<()> {
+        let row = self
+            .get_download(id)?
+            .ok_or_else(|| anyhow!("download {id} not found"))?;
+        if row.status.is_active() {
+            return Err(anyhow!(
+                "can't change the preset while the download is {}",
+                row.status.as_str()
+            ));
+        }
+
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET preset_id = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), preset_id, now],
+        )?;
+        Ok(())
+    }
+
+    /// Record where a download's thumbnail was cached locally.
+    pub fn set_thumbnail_path(&mut self, id: Uuid, path: Option<&str>) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET thumbnail_path = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), path, now],
+        )?;
+        Ok(())
+    }
+
+    /// Forget cached thumbnails that were deleted, for every download pointing at them.
+    pub fn clear_thumbnail_paths(&mut self, paths: &[String]) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                r#"
+                UPDATE downloads
+                SET thumbnail_path = NULL, updated_at = ?2
+                WHERE thumbnail_path = ?1
+                "#,
+            )?;
+            for path in paths {
+                stmt.execute(params![path, now])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record the files yt-dlp wrote for a download; an empty slice clears them.
+    pub fn set_downloaded_files(&mut self, id: Uuid, files: &[String]) -> Result<()> {
+        let json = if files.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(files)?)
+        };
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET downloaded_files = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), json, now],
+        )?;
+        Ok(())
+    }
+
+    /// Record every output file of a completed download; an empty slice clears them.
+    /// Point a download at `new_path` after the user moved its file.
+    ///
+    /// The old `final_path` is replaced in `final_paths` too, so the primary file
+    /// stays first; other outputs (chapters, subtitles) are left as they were.
+    pub fn relink_final_path(&mut self, id: Uuid, new_path: &str) -> Result<()> {
+        let row = self
+            .get_download(id)?
+            .ok_or_else(|| anyhow::anyhow!("Download {id} not found"))?;
+
+        let mut paths = row.final_paths;
+        match row
+            .final_path
+            .as_deref()
+            .and_then(|old| paths.iter().position(|p| p == old))
+        {
+            Some(i) => paths[i] = new_path.to_string(),
+            None if !paths.is_empty() => paths.insert(0, new_path.to_string()),
+            None => {}
+        }
+        paths.dedup();
+
+        let json = if paths.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&paths)?)
+        };
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET final_path = ?2, final_paths = ?3, updated_at = ?4
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), new_path, json, now],
+        )?;
+        Ok(())
+    }
+
+    /// Remember the URL as entered, alongside the normalized `source_url`.
+    pub fn set_original_url(&mut self, id: Uuid, original_url: &str) -> Result<()> {
+        self.conn.execute(
+            "UPDATE downloads SET original_url = ?2 WHERE id = ?1",
+            params![id.to_string(), original_url],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_final_paths(&mut self, id: Uuid, paths: &[String]) -> Result<()> {
+        let json = if paths.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(paths)?)
+        };
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET final_paths = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), json, now],
+        )?;
+        Ok(())
+    }
+
+    /// Set (or clear) the partial-download sections spec for a download.
+    /// Set (or clear) a download's own speed limit, in bytes/sec.
+    pub fn set_rate_limit(&mut self, id: Uuid, rate_limit_bps: Option<i64>) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET rate_limit_bps = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), rate_limit_bps, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_sections(&mut self, id: Uuid, sections: Option<&str>) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET sections = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), sections, now],
+        )?;
+        Ok(())
+    }
+
+    /// Set (or clear) the subtitle languages chosen for one download.
+    pub fn set_subtitle_langs(&mut self, id: Uuid, langs: Option<&str>) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET subtitle_langs = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), langs, now],
+        )?;
+        Ok(())
+    }
+
+    /// Record the size of the finished file, replacing yt-dlp's estimate.
+    pub fn set_actual_filesize(&mut self, id: Uuid, size_bytes: i64) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET bytes_total = ?2, bytes_downloaded = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), size_bytes, now],
+        )?;
+        Ok(())
+    }
+
+    /// Record the duration of the finished file, leaving the rest of the metadata alone.
+    pub fn set_duration(&mut self, id: Uuid, duration_seconds: i64) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET duration_seconds = ?2, updated_at = ?3
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), duration_seconds, now],
+        )?;
+        Ok(())
+    }
+
     /// Updates a download status+phase+updated_at.
     pub fn set_status(
         &mut self,
@@ -389,6 +791,25 @@ impl Db {
         Ok(())
     }
 
+    /// Record which playlist an expanded item came from, and where in it.
+    pub fn set_playlist_position(
+        &mut self,
+        id: Uuid,
+        playlist_title: Option<&str>,
+        playlist_index: Option<i64>,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET playlist_title = ?2, playlist_index = ?3, updated_at = ?4
+            WHERE id = ?1
+            "#,
+            params![id.to_string(), playlist_title, playlist_index, now],
+        )?;
+        Ok(())
+    }
+
     /// Update progress fields for a download.
     pub fn update_progress(
         &mut self,
@@ -420,6 +841,35 @@ impl Db {
         Ok(())
     }
 
+    /// Apply several progress updates in one transaction.
+    pub fn update_progress_batch(&mut self, updates: &[ProgressUpdate]) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                r#"
+                UPDATE downloads
+                SET progress_percent = ?2, bytes_downloaded = ?3, bytes_total = ?4,
+                    speed_bps = ?5, eta_seconds = ?6, updated_at = ?7
+                WHERE id = ?1
+                "#,
+            )?;
+            for u in updates {
+                stmt.execute(params![
+                    u.id.to_string(),
+                    u.percent,
+                    u.bytes_downloaded,
+                    u.bytes_total,
+                    u.speed_bps,
+                    u.eta_seconds,
+                    now
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     /// Set the final path for a completed download.
     pub fn set_final_path(&mut self, id: Uuid, final_path: &str) -> Result<()> {
         let now = Utc::now().to_rfc3339();
@@ -440,18 +890,21 @@ impl Db {
         id: Uuid,
         error_code: Option<&str>,
         error_message: Option<&str>,
+        raw_error: Option<&str>,
     ) -> Result<()> {
         let now = Utc::now().to_rfc3339();
         self.conn.execute(
             r#"
             UPDATE downloads
-            SET error_code = ?2, error_message = ?3, status = ?4, phase = ?5, updated_at = ?6
+            SET error_code = ?2, error_message = ?3, raw_error = ?4,
+                status = ?5, phase = ?6, updated_at = ?7
             WHERE id = ?1
             "#,
             params![
                 id.to_string(),
                 error_code,
                 error_message,
+                raw_error,
                 DownloadStatus::Failed.as_str(),
                 "Failed",
                 now
@@ -469,8 +922,8 @@ impl Db {
         Ok(())
     }
 
-    /// Get all active downloads (not completed, canceled, or failed).
-    pub fn get_active_downloads(&mut self) -> Result<Vec<DownloadRow>> {
+    /// Downloads still in the queue (not done or canceled) matching `filter`, newest first.
+    pub fn get_queue(&mut self, filter: &QueueFilter) -> Result<Vec<DownloadRow>> {
         let mut stmt = self.conn.prepare(
             r#"
             SELECT
@@ -481,14 +934,32 @@ impl Db {
                 preset_id, output_dir,
                 final_path,
                 progress_percent, bytes_downloaded, bytes_total, speed_bps, eta_seconds,
-                error_code, error_message
+                error_code, error_message,
+                sections, raw_error, thumbnail_path, downloaded_files, final_paths, original_url, subtitle_langs, playlist_title, playlist_index, rate_limit_bps
             FROM downloads
             WHERE status NOT IN ('done', 'canceled')
+              AND (?1 IS NULL OR status = ?1)
+              AND (?2 IS NULL OR source_kind = ?2)
+              AND (
+                ?3 = 0
+                OR source_kind != 'playlist_item'
+                OR parent_id IS NULL
+                OR parent_id NOT IN (
+                  SELECT id FROM downloads WHERE status NOT IN ('done', 'canceled')
+                )
+              )
             ORDER BY created_at DESC
             "#,
         )?;
 
-        let rows = stmt.query_map([], |row| Self::row_to_download(row))?;
+        let rows = stmt.query_map(
+            params![
+                filter.status.map(DownloadStatus::as_str),
+                filter.source_kind.map(SourceKind::as_str),
+                filter.collapse_playlists,
+            ],
+            |row| Self::row_to_download(row),
+        )?;
         let mut result = Vec::new();
         for row in rows {
             result.push(row?);
@@ -508,7 +979,8 @@ impl Db {
                 preset_id, output_dir,
                 final_path,
                 progress_percent, bytes_downloaded, bytes_total, speed_bps, eta_seconds,
-                error_code, error_message
+                error_code, error_message,
+                sections, raw_error, thumbnail_path, downloaded_files, final_paths, original_url, subtitle_langs, playlist_title, playlist_index, rate_limit_bps
             FROM downloads
             WHERE status = 'done'
             ORDER BY updated_at DESC
@@ -551,35 +1023,70 @@ impl Db {
 
     /// Clear all completed downloads from history.
     pub fn clear_completed_downloads(&mut self) -> Result<()> {
-        self.conn.execute(
-            "DELETE FROM downloads WHERE status IN ('done', 'canceled', 'failed')",
-            [],
-        )?;
+        self.conn
+            .execute("DELETE FROM downloads WHERE status = 'done'", [])?;
         Ok(())
     }
 
-    /// Get downloads by parent ID (for playlist items).
-    pub fn get_playlist_items(&mut self, parent_id: Uuid) -> Result<Vec<DownloadRow>> {
+    /// Clear all failed downloads.
+    pub fn clear_failed_downloads(&mut self) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM downloads WHERE status = 'failed'", [])?;
+        Ok(())
+    }
+
+    /// Clear all canceled downloads.
+    pub fn clear_canceled_downloads(&mut self) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM downloads WHERE status = 'canceled'", [])?;
+        Ok(())
+    }
+
+    /// Reset downloads left mid-flight by a previous run to `stopped` so they can be resumed.
+    ///
+    /// Call once at startup, before any download is started. Returns the number of rows reset.
+    pub fn reconcile_interrupted_downloads(&mut self) -> Result<usize> {
+        let now = Utc::now().to_rfc3339();
+        let changed = self.conn.execute(
+            r#"
+            UPDATE downloads
+            SET status = ?1, phase = ?6, updated_at = ?2
+            WHERE status IN (?3, ?4, ?5)
+            "#,
+            params![
+                DownloadStatus::Stopped.as_str(),
+                now,
+                DownloadStatus::Fetching.as_str(),
+                DownloadStatus::Downloading.as_str(),
+                DownloadStatus::PostProcessing.as_str(),
+                INTERRUPTED_PHASE,
+            ],
+        )?;
+        Ok(changed)
+    }
+
+    /// Downloads stopped by a crash or app exit rather than by the user, oldest
+    /// first. Playlist parents are left out; their items are rows of their own.
+    pub fn get_interrupted_download_ids(&mut self) -> Result<Vec<Uuid>> {
         let mut stmt = self.conn.prepare(
             r#"
-            SELECT
-                id, created_at, updated_at,
-                source_url, source_kind, parent_id,
-                title, uploader, duration_seconds, thumbnail_url,
-                status, phase,
-                preset_id, output_dir,
-                final_path,
-                progress_percent, bytes_downloaded, bytes_total, speed_bps, eta_seconds,
-                error_code, error_message
-            FROM downloads
-            WHERE parent_id = ?1
+            SELECT id FROM downloads
+            WHERE status = ?1 AND phase = ?2 AND source_kind != ?3
             ORDER BY created_at ASC
             "#,
         )?;
 
-        let rows = stmt.query_map(params![parent_id.to_string()], |row| {
-            Self::row_to_download(row)
-        })?;
+        let rows = stmt.query_map(
+            params![
+                DownloadStatus::Stopped.as_str(),
+                INTERRUPTED_PHASE,
+                SourceKind::PlaylistParent.as_str(),
+            ],
+            |row| {
+                let id_str: String = row.get(0)?;
+                Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidQuery)
+            },
+        )?;
 
         let mut result = Vec::new();
         for row in rows {
@@ -588,16 +1095,141 @@ impl Db {
         Ok(result)
     }
 
-    /// Count downloads by status.
-    pub fn count_by_status(&mut self, status: DownloadStatus) -> Result<u64> {
-        let count: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM downloads WHERE status = ?1",
+    /// Get IDs of all failed downloads.
+    pub fn get_failed_download_ids(&mut self) -> Result<Vec<Uuid>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM downloads WHERE status = 'failed' ORDER BY created_at ASC")?;
+
+        let rows = stmt.query_map([], |row| {
+            let id_str: String = row.get(0)?;
+            Uuid::parse_str(&id_str).map_err(|_| rusqlite::Error::InvalidQuery)
+        })?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    /// Get downloads by parent ID (for playlist items).
+    pub fn get_playlist_items(&mut self, parent_id: Uuid) -> Result<Vec<DownloadRow>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT
+                id, created_at, updated_at,
+                source_url, source_kind, parent_id,
+                title, uploader, duration_seconds, thumbnail_url,
+                status, phase,
+                preset_id, output_dir,
+                final_path,
+                progress_percent, bytes_downloaded, bytes_total, speed_bps, eta_seconds,
+                error_code, error_message,
+                sections, raw_error, thumbnail_path, downloaded_files, final_paths, original_url, subtitle_langs, playlist_title, playlist_index, rate_limit_bps
+            FROM downloads
+            WHERE parent_id = ?1
+            ORDER BY created_at ASC
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![parent_id.to_string()], |row| {
+            Self::row_to_download(row)
+        })?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    /// Count downloads by status.
+    pub fn count_by_status(&mut self, status: DownloadStatus) -> Result<u64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM downloads WHERE status = ?1",
             params![status.as_str()],
             |row| row.get(0),
         )?;
         Ok(count as u64)
     }
 
+    /// Totals, per-status counts and per-day counts for the dashboard.
+    pub fn get_statistics(&mut self) -> Result<DownloadStatistics> {
+        let mut by_status = BTreeMap::new();
+        for status in DownloadStatus::ALL {
+            by_status.insert(status.as_str().to_string(), self.count_by_status(status)?);
+        }
+        let total_downloads = by_status.values().sum();
+
+        let total_bytes: i64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(bytes_total), 0) FROM downloads WHERE status = ?1",
+            params![DownloadStatus::Done.as_str()],
+            |row| row.get(0),
+        )?;
+
+        // created_at is RFC 3339 UTC, so the first 10 chars are the UTC date
+        let cutoff = (Utc::now() - chrono::Duration::days(STATS_DAYS - 1))
+            .format("%Y-%m-%d")
+            .to_string();
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT substr(created_at, 1, 10) AS day, COUNT(*)
+            FROM downloads
+            WHERE created_at >= ?1
+            GROUP BY day
+            ORDER BY day ASC
+            "#,
+        )?;
+        let per_day = stmt
+            .query_map(params![cutoff], |row| {
+                Ok(DailyCount {
+                    date: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(DownloadStatistics {
+            total_downloads,
+            total_bytes_downloaded: total_bytes.max(0) as u64,
+            by_status,
+            per_day,
+        })
+    }
+
+    /// Add `bytes` to the usage total of the UTC day containing `at`.
+    pub fn add_usage(&mut self, at: DateTime<Utc>, bytes: u64) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO usage (date, bytes) VALUES (?1, ?2)
+            ON CONFLICT(date) DO UPDATE SET bytes = bytes + excluded.bytes
+            "#,
+            params![at.format("%Y-%m-%d").to_string(), bytes as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Daily usage totals from the UTC day containing `since` (or all of them), oldest first.
+    /// Days without downloads are omitted.
+    pub fn get_usage(&mut self, since: Option<DateTime<Utc>>) -> Result<Vec<DailyUsage>> {
+        let cutoff = since
+            .map(|at| at.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT date, bytes FROM usage WHERE date >= ?1 ORDER BY date ASC")?;
+        let days = stmt
+            .query_map(params![cutoff], |row| {
+                Ok(DailyUsage {
+                    date: row.get(0)?,
+                    bytes: row.get::<_, i64>(1)?.max(0) as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(days)
+    }
+
     /// Helper function to convert a database row to DownloadRow.
     fn row_to_download(row: &Row) -> rusqlite::Result<DownloadRow> {
         let id: String = row.get(0)?;
@@ -622,6 +1254,22 @@ impl Db {
         let eta_seconds: Option<i64> = row.get(19)?;
         let error_code: Option<String> = row.get(20)?;
         let error_message: Option<String> = row.get(21)?;
+        let sections: Option<String> = row.get(22)?;
+        let raw_error: Option<String> = row.get(23)?;
+        let thumbnail_path: Option<String> = row.get(24)?;
+        let downloaded_files: Vec<String> = row
+            .get::<_, Option<String>>(25)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let final_paths: Vec<String> = row
+            .get::<_, Option<String>>(26)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let original_url: Option<String> = row.get(27)?;
+        let subtitle_langs: Option<String> = row.get(28)?;
+        let playlist_title: Option<String> = row.get(29)?;
+        let playlist_index: Option<i64> = row.get(30)?;
+        let rate_limit_bps: Option<i64> = row.get(31)?;
 
         let id = Uuid::parse_str(&id).map_err(|_| rusqlite::Error::InvalidQuery)?;
         let created_at = DateTime::parse_from_rfc3339(&created_at)
@@ -663,9 +1311,91 @@ impl Db {
             eta_seconds,
             error_code,
             error_message,
+            sections,
+            raw_error,
+            thumbnail_path,
+            downloaded_files,
+            final_paths,
+            original_url,
+            subtitle_langs,
+            playlist_title,
+            playlist_index,
+            rate_limit_bps,
         })
     }
 
+    /// Store `--username`/`--password` credentials for a site (see [`site_key`]).
+    pub fn set_site_credentials(
+        &mut self,
+        site: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            INSERT INTO site_credentials (site, username, password_plaintext, updated_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(site) DO UPDATE SET
+              username = excluded.username,
+              password_plaintext = excluded.password_plaintext,
+              updated_at = excluded.updated_at
+            "#,
+            params![site, username, password, now],
+        )?;
+        Ok(())
+    }
+
+    /// Remove stored credentials for a site. Returns whether any were stored.
+    pub fn remove_site_credentials(&mut self, site: &str) -> Result<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM site_credentials WHERE site = ?1",
+            params![site],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Sites with stored credentials and their usernames. Passwords are never listed.
+    pub fn list_site_credentials(&mut self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT site, username FROM site_credentials ORDER BY site ASC")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Credentials to use for a download URL.
+    ///
+    /// Tries the URL's host first, then each parent domain, so credentials saved for
+    /// `example.com` also apply to `m.example.com`.
+    pub fn credentials_for_url(&mut self, url: &str) -> Result<Option<SiteCredentials>> {
+        let Some(host) = site_key(url) else {
+            return Ok(None);
+        };
+
+        let labels: Vec<&str> = host.split('.').collect();
+        for start in 0..labels.len().saturating_sub(1).max(1) {
+            let site = labels[start..].join(".");
+            let found = self
+                .conn
+                .query_row(
+                    "SELECT username, password_plaintext FROM site_credentials WHERE site = ?1",
+                    params![site],
+                    |row| {
+                        Ok(SiteCredentials {
+                            username: row.get(0)?,
+                            password: row.get(1)?,
+                        })
+                    },
+                )
+                .optional()?;
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    }
+
     /// Add a log entry for a download.
     pub fn add_log_entry(&mut self, download_id: Uuid, stream: &str, line: &str) -> Result<()> {
         let now = Utc::now().to_rfc3339();
@@ -728,6 +1458,11 @@ impl Db {
 
 /// Apply migrations to bring database to current schema.
 fn migrate(conn: &mut Connection) -> Result<()> {
+    apply_migrations(conn, MIGRATIONS)
+}
+
+/// Apply every migration in `migrations` that is newer than the stored `schema_version`.
+fn apply_migrations(conn: &mut Connection, migrations: &[Migration]) -> Result<()> {
     conn.execute_batch(
         r#"
         CREATE TABLE IF NOT EXISTS meta (
@@ -737,40 +1472,50 @@ fn migrate(conn: &mut Connection) -> Result<()> {
         "#,
     )?;
 
-    // Ensure meta row exists for schema_version.
-    let existing: Option<String> = conn
-        .query_row(
-            r#"SELECT value FROM meta WHERE key = 'schema_version'"#,
-            [],
-            |r| r.get(0),
-        )
-        .optional()?;
+    let current_version = schema_version(conn)?;
+    let target_version = migrations.len() as i64;
 
-    let current_version: i64 = existing
-        .as_deref()
-        .and_then(|s| s.parse::<i64>().ok())
-        .unwrap_or(0);
-
-    if current_version > SCHEMA_VERSION {
+    if current_version > target_version {
         return Err(anyhow!(
             "db schema version {} is newer than app supports {}",
             current_version,
-            SCHEMA_VERSION
+            target_version
         ));
     }
 
-    if current_version == 0 {
-        migration_v1(conn)?;
-        set_schema_version(conn, 1)?;
+    for (idx, migration) in migrations
+        .iter()
+        .enumerate()
+        .skip(current_version.max(0) as usize)
+    {
+        let version = idx as i64 + 1;
+        let tx = conn.transaction()?;
+        migration(&tx).with_context(|| format!("apply db migration v{}", version))?;
+        set_schema_version(&tx, version)?;
+        tx.commit()?;
+        log::info!("Applied db migration v{}", version);
     }
 
-    // Future:
-    // if current_version < 2 { migration_v2(conn)?; set_schema_version(conn, 2)?; }
-
     Ok(())
 }
 
-fn set_schema_version(conn: &mut Connection, v: i64) -> Result<()> {
+/// Read the stored schema version (0 if the database has never been migrated).
+fn schema_version(conn: &Connection) -> Result<i64> {
+    let existing: Option<String> = conn
+        .query_row(
+            r#"SELECT value FROM meta WHERE key = 'schema_version'"#,
+            [],
+            |r| r.get(0),
+        )
+        .optional()?;
+
+    Ok(existing
+        .as_deref()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(0))
+}
+
+fn set_schema_version(conn: &Connection, v: i64) -> Result<()> {
     conn.execute(
         r#"
         INSERT INTO meta(key, value) VALUES('schema_version', ?1)
@@ -781,7 +1526,7 @@ fn set_schema_version(conn: &mut Connection, v: i64) -> Result<()> {
     Ok(())
 }
 
-fn migration_v1(conn: &mut Connection) -> Result<()> {
+fn migration_v1(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         r#"
         CREATE TABLE IF NOT EXISTS downloads (
@@ -850,3 +1595,833 @@ fn migration_v1(conn: &mut Connection) -> Result<()> {
 
     Ok(())
 }
+
+/// v2: optional `--download-sections` spec for partial downloads.
+fn migration_v2(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN sections TEXT NULL;")?;
+    Ok(())
+}
+
+/// v3: keep the stderr tail of the last failure alongside the user-facing message.
+fn migration_v3(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN raw_error TEXT NULL;")?;
+    Ok(())
+}
+
+/// v4: where a download's thumbnail is cached locally.
+fn migration_v4(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN thumbnail_path TEXT NULL;")?;
+    Ok(())
+}
+
+/// v5: per-site `--username`/`--password` credentials.
+///
+/// The password column is named `password_plaintext` on purpose: it is NOT encrypted,
+/// and the name keeps that visible to anyone touching the schema.
+fn migration_v5(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS site_credentials (
+          site TEXT PRIMARY KEY,
+          username TEXT NOT NULL,
+          password_plaintext TEXT NOT NULL,
+          updated_at TEXT NOT NULL
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+/// v6: files written by the last failed run, for post-processing-only retries.
+fn migration_v6(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN downloaded_files TEXT NULL;")?;
+    Ok(())
+}
+
+/// v7: all output files of a completed download, for multi-file outputs.
+fn migration_v7(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN final_paths TEXT NULL;")?;
+    Ok(())
+}
+
+/// v8: the URL as the user entered it, before normalization.
+fn migration_v8(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN original_url TEXT NULL;")?;
+    Ok(())
+}
+
+/// v9: bytes downloaded per day, for usage totals.
+fn migration_v9(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS usage (
+          date TEXT PRIMARY KEY NOT NULL,
+          bytes INTEGER NOT NULL DEFAULT 0
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+/// v10: per-download subtitle languages, overriding the global setting.
+fn migration_v10(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN subtitle_langs TEXT NULL;")?;
+    Ok(())
+}
+
+/// v11: the playlist a download came from, for organizing output by playlist.
+fn migration_v11(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        r#"
+        ALTER TABLE downloads ADD COLUMN playlist_title TEXT NULL;
+        ALTER TABLE downloads ADD COLUMN playlist_index INTEGER NULL;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// v12: per-download speed limit, overriding the global one.
+fn migration_v12(conn: &Connection) -> Result<()> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN rate_limit_bps INTEGER NULL;")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Insert one row per status and return the ids in the same order.
+    fn seed_statuses(db: &mut Db, statuses: &[DownloadStatus]) -> Vec<Uuid> {
+        statuses
+            .iter()
+            .map(|status| {
+                let id = db
+                    .insert_download(
+                        "https://example.com/watch",
+                        SourceKind::Single,
+                        None,
+                        "recommended_best",
+                        "/tmp",
+                    )
+                    .unwrap();
+                db.set_status(id, *status, None).unwrap();
+                id
+            })
+            .collect()
+    }
+
+    /// A playlist parent with two items, plus a single download. Returns (parent, items, single).
+    fn seed_playlist(db: &mut Db) -> (Uuid, Vec<Uuid>, Uuid) {
+        let parent = db
+            .insert_download(
+                "https://example.com/playlist?list=x",
+                SourceKind::PlaylistParent,
+                None,
+                "recommended_best",
+                "/tmp",
+            )
+            .unwrap();
+        db.set_status(parent, DownloadStatus::Ready, None).unwrap();
+        let items: Vec<Uuid> = (0..2)
+            .map(|i| {
+                db.insert_download(
+                    &format!("https://example.com/watch?v={i}"),
+                    SourceKind::PlaylistItem,
+                    Some(parent),
+                    "recommended_best",
+                    "/tmp",
+                )
+                .unwrap()
+            })
+            .collect();
+        db.set_status(items[1], DownloadStatus::Failed, None)
+            .unwrap();
+        let single = seed_statuses(db, &[DownloadStatus::Failed])[0];
+        (parent, items, single)
+    }
+
+    fn queue_ids(db: &mut Db, filter: QueueFilter) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = db
+            .get_queue(&filter)
+            .unwrap()
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn sorted(mut ids: Vec<Uuid>) -> Vec<Uuid> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_get_queue_filters() {
+        let mut db = Db::open_in_memory().unwrap();
+        let (parent, items, single) = seed_playlist(&mut db);
+        let done = seed_statuses(&mut db, &[DownloadStatus::Done])[0];
+
+        let all = queue_ids(&mut db, QueueFilter::default());
+        assert_eq!(all, sorted(vec![parent, items[0], items[1], single]));
+        assert!(!all.contains(&done));
+
+        let failed = QueueFilter {
+            status: Some(DownloadStatus::Failed),
+            ..Default::default()
+        };
+        assert_eq!(queue_ids(&mut db, failed), sorted(vec![items[1], single]));
+
+        let playlist_items = QueueFilter {
+            source_kind: Some(SourceKind::PlaylistItem),
+            ..Default::default()
+        };
+        assert_eq!(queue_ids(&mut db, playlist_items), sorted(items.clone()));
+
+        let failed_items = QueueFilter {
+            status: Some(DownloadStatus::Failed),
+            source_kind: Some(SourceKind::PlaylistItem),
+            ..Default::default()
+        };
+        assert_eq!(queue_ids(&mut db, failed_items), vec![items[1]]);
+
+        // Terminal statuses never show up in the queue view
+        let done_filter = QueueFilter {
+            status: Some(DownloadStatus::Done),
+            ..Default::default()
+        };
+        assert!(queue_ids(&mut db, done_filter).is_empty());
+    }
+
+    #[test]
+    fn test_get_queue_collapse_playlists() {
+        let mut db = Db::open_in_memory().unwrap();
+        let (parent, items, single) = seed_playlist(&mut db);
+
+        let collapsed = QueueFilter {
+            collapse_playlists: true,
+            ..Default::default()
+        };
+        assert_eq!(queue_ids(&mut db, collapsed), sorted(vec![parent, single]));
+
+        let failed_collapsed = QueueFilter {
+            status: Some(DownloadStatus::Failed),
+            collapse_playlists: true,
+            ..Default::default()
+        };
+        assert_eq!(queue_ids(&mut db, failed_collapsed), vec![single]);
+
+        // Once the parent leaves the queue its remaining items are listed on their own
+        db.set_status(parent, DownloadStatus::Canceled, None)
+            .unwrap();
+        assert_eq!(
+            queue_ids(&mut db, collapsed),
+            sorted(vec![items[0], items[1], single])
+        );
+    }
+
+    #[test]
+    fn test_get_queue_playlist_parent_filter_excludes_items() {
+        let mut db = Db::open_in_memory().unwrap();
+        let (parent, _items, _single) = seed_playlist(&mut db);
+
+        let parents = QueueFilter {
+            source_kind: Some(SourceKind::PlaylistParent),
+            ..Default::default()
+        };
+        assert_eq!(queue_ids(&mut db, parents), vec![parent]);
+
+        // Orphaned items (parent no longer queued) are still items, not parents
+        db.set_status(parent, DownloadStatus::Done, None).unwrap();
+        assert!(queue_ids(&mut db, parents).is_empty());
+        let parents_collapsed = QueueFilter {
+            collapse_playlists: true,
+            ..parents
+        };
+        assert!(queue_ids(&mut db, parents_collapsed).is_empty());
+    }
+
+    const MIXED: [DownloadStatus; 4] = [
+        DownloadStatus::Queued,
+        DownloadStatus::Done,
+        DownloadStatus::Failed,
+        DownloadStatus::Canceled,
+    ];
+
+    #[test]
+    fn test_clear_completed_only_removes_done() {
+        let mut db = Db::open_in_memory().unwrap();
+        seed_statuses(&mut db, &MIXED);
+
+        db.clear_completed_downloads().unwrap();
+
+        assert_eq!(db.count_by_status(DownloadStatus::Done).unwrap(), 0);
+        assert_eq!(db.count_by_status(DownloadStatus::Failed).unwrap(), 1);
+        assert_eq!(db.count_by_status(DownloadStatus::Canceled).unwrap(), 1);
+        assert_eq!(db.count_by_status(DownloadStatus::Queued).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_clear_failed_only_removes_failed() {
+        let mut db = Db::open_in_memory().unwrap();
+        seed_statuses(&mut db, &MIXED);
+
+        db.clear_failed_downloads().unwrap();
+
+        assert_eq!(db.count_by_status(DownloadStatus::Failed).unwrap(), 0);
+        assert_eq!(db.count_by_status(DownloadStatus::Done).unwrap(), 1);
+        assert_eq!(db.count_by_status(DownloadStatus::Canceled).unwrap(), 1);
+        assert_eq!(db.count_by_status(DownloadStatus::Queued).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_clear_canceled_only_removes_canceled() {
+        let mut db = Db::open_in_memory().unwrap();
+        seed_statuses(&mut db, &MIXED);
+
+        db.clear_canceled_downloads().unwrap();
+
+        assert_eq!(db.count_by_status(DownloadStatus::Canceled).unwrap(), 0);
+        assert_eq!(db.count_by_status(DownloadStatus::Done).unwrap(), 1);
+        assert_eq!(db.count_by_status(DownloadStatus::Failed).unwrap(), 1);
+        assert_eq!(db.count_by_status(DownloadStatus::Queued).unwrap(), 1);
+    }
+
+    fn record_step(conn: &Connection, step: i64) -> Result<()> {
+        conn.execute_batch("CREATE TABLE IF NOT EXISTS migration_log (step INTEGER NOT NULL)")?;
+        conn.execute(
+            "INSERT INTO migration_log (step) VALUES (?1)",
+            params![step],
+        )?;
+        Ok(())
+    }
+
+    fn step_one(conn: &Connection) -> Result<()> {
+        record_step(conn, 1)
+    }
+
+    fn step_two(conn: &Connection) -> Result<()> {
+        record_step(conn, 2)
+    }
+
+    fn step_three(conn: &Connection) -> Result<()> {
+        record_step(conn, 3)
+    }
+
+    fn logged_steps(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare("SELECT step FROM migration_log ORDER BY rowid ASC")
+            .unwrap();
+        let rows = stmt.query_map([], |r| r.get(0)).unwrap();
+        rows.map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_log_file_path_is_under_app_data_dir() {
+        let path = log_file_path().unwrap();
+        let data = app_data_dir().unwrap();
+
+        assert!(path.starts_with(data.join("logs")));
+        assert_eq!(path.file_name().unwrap(), "downlink.log");
+        assert!(path.parent().unwrap().is_dir());
+    }
+
+    #[test]
+    fn test_backup_to_is_a_complete_openable_copy() {
+        let dir = std::env::temp_dir().join(format!("downlink-backup-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut db = Db::open_at(dir.join("downlink.sqlite3")).unwrap();
+        seed_statuses(
+            &mut db,
+            &[
+                DownloadStatus::Done,
+                DownloadStatus::Failed,
+                DownloadStatus::Queued,
+            ],
+        );
+        let dest = dir.join("backup.sqlite3");
+
+        // A second backup replaces the first
+        db.backup_to(&dest, false).unwrap();
+        seed_statuses(&mut db, &[DownloadStatus::Done]);
+        db.backup_to(&dest, false).unwrap();
+
+        let copy = Connection::open(&dest).unwrap();
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM downloads", [], |r| r.get(0))
+                .unwrap()
+        };
+        let (copied, original) = (count(&copy), count(db.conn()));
+        let version = schema_version(&copy).unwrap();
+        let partial_left = dir.join("backup.sqlite3.partial").exists();
+        drop(copy);
+        drop(db);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(copied, 4);
+        assert_eq!(copied, original);
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        assert!(!partial_left);
+    }
+
+    #[test]
+    fn test_backup_to_leaves_out_credentials_and_refuses_live_db() {
+        let dir = std::env::temp_dir().join(format!("downlink-backup-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut db = Db::open_at(dir.join("downlink.sqlite3")).unwrap();
+        db.set_site_credentials("example.com", "alice", "hunter2")
+            .unwrap();
+
+        let stripped = dir.join("stripped.sqlite3");
+        let full = dir.join("full.sqlite3");
+        db.backup_to(&stripped, false).unwrap();
+        db.backup_to(&full, true).unwrap();
+        let logins = |path: &Path| -> i64 {
+            Connection::open(path)
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM site_credentials", [], |r| r.get(0))
+                .unwrap()
+        };
+        let (stripped_logins, full_logins) = (logins(&stripped), logins(&full));
+        let stripped_bytes = fs::read(&stripped).unwrap();
+
+        // Same file through a different spelling
+        let over_itself = db.backup_to(&dir.join(".").join("downlink.sqlite3"), false);
+        let live_ok = db.list_site_credentials().unwrap().len() == 1;
+        drop(db);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(stripped_logins, 0);
+        assert_eq!(full_logins, 1);
+        assert!(!stripped_bytes.windows(7).any(|w| w == b"hunter2"));
+        assert!(over_itself.is_err());
+        assert!(live_ok);
+    }
+
+    #[test]
+    fn test_fresh_db_is_at_latest_schema_version() {
+        let db = Db::open_in_memory().unwrap();
+        assert_eq!(schema_version(db.conn()).unwrap(), MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn test_migrations_run_in_order_from_stored_version() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let steps: &[Migration] = &[step_one, step_two, step_three];
+
+        apply_migrations(&mut conn, steps).unwrap();
+        assert_eq!(logged_steps(&conn), vec![1, 2, 3]);
+        assert_eq!(schema_version(&conn).unwrap(), 3);
+
+        // Simulate a database that was only migrated to v1.
+        conn.execute("DELETE FROM migration_log", []).unwrap();
+        set_schema_version(&conn, 1).unwrap();
+
+        apply_migrations(&mut conn, steps).unwrap();
+        assert_eq!(logged_steps(&conn), vec![2, 3]);
+        assert_eq!(schema_version(&conn).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        apply_migrations(&mut conn, &[step_one]).unwrap();
+        set_schema_version(&conn, 5).unwrap();
+
+        assert!(apply_migrations(&mut conn, &[step_one]).is_err());
+    }
+
+    #[test]
+    fn test_set_error_persists_raw_error() {
+        let mut db = Db::open_in_memory().unwrap();
+        let id = seed_statuses(&mut db, &[DownloadStatus::Downloading])[0];
+
+        db.set_error(
+            id,
+            Some("RATE_LIMITED"),
+            Some("The site is rate limiting downloads."),
+            Some("ERROR: HTTP Error 429: Too Many Requests"),
+        )
+        .unwrap();
+
+        let row = db.get_download(id).unwrap().unwrap();
+        assert_eq!(row.status, DownloadStatus::Failed);
+        assert_eq!(row.error_code.as_deref(), Some("RATE_LIMITED"));
+        assert_eq!(
+            row.raw_error.as_deref(),
+            Some("ERROR: HTTP Error 429: Too Many Requests")
+        );
+    }
+
+    #[test]
+    fn test_reconcile_interrupted_downloads() {
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = seed_statuses(
+            &mut db,
+            &[
+                DownloadStatus::Fetching,
+                DownloadStatus::Downloading,
+                DownloadStatus::PostProcessing,
+                DownloadStatus::Queued,
+                DownloadStatus::Done,
+                DownloadStatus::Failed,
+            ],
+        );
+
+        assert_eq!(db.reconcile_interrupted_downloads().unwrap(), 3);
+
+        let status = |db: &mut Db, id| db.get_download(id).unwrap().unwrap().status;
+        for id in &ids[..3] {
+            assert_eq!(status(&mut db, *id), DownloadStatus::Stopped);
+        }
+        assert_eq!(status(&mut db, ids[3]), DownloadStatus::Queued);
+        assert_eq!(status(&mut db, ids[4]), DownloadStatus::Done);
+        assert_eq!(status(&mut db, ids[5]), DownloadStatus::Failed);
+
+        // Nothing left to reconcile on a second pass
+        assert_eq!(db.reconcile_interrupted_downloads().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_get_interrupted_download_ids() {
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = seed_statuses(
+            &mut db,
+            &[
+                DownloadStatus::Downloading,
+                DownloadStatus::Stopped,
+                DownloadStatus::Fetching,
+                DownloadStatus::Queued,
+                DownloadStatus::Done,
+                DownloadStatus::PostProcessing,
+            ],
+        );
+        // Stopped by the user before the crash: not resumed
+        db.set_status(ids[1], DownloadStatus::Stopped, Some("Stopped"))
+            .unwrap();
+        let parent = db
+            .insert_download(
+                "https://example.com/list",
+                SourceKind::PlaylistParent,
+                None,
+                "recommended_best",
+                "/tmp",
+            )
+            .unwrap();
+        db.set_status(parent, DownloadStatus::Fetching, None)
+            .unwrap();
+
+        db.reconcile_interrupted_downloads().unwrap();
+
+        assert_eq!(
+            db.get_interrupted_download_ids().unwrap(),
+            vec![ids[0], ids[2], ids[5]]
+        );
+    }
+
+    #[test]
+    fn test_set_downloaded_files_roundtrip() {
+        let mut db = Db::open_in_memory().unwrap();
+        let id = seed_statuses(&mut db, &[DownloadStatus::Failed])[0];
+        assert!(db
+            .get_download(id)
+            .unwrap()
+            .unwrap()
+            .downloaded_files
+            .is_empty());
+
+        let files = vec![
+            "/downloads/clip [abc].f137.mp4".to_string(),
+            "/downloads/clip [abc].f140.m4a".to_string(),
+        ];
+        db.set_downloaded_files(id, &files).unwrap();
+        assert_eq!(
+            db.get_download(id).unwrap().unwrap().downloaded_files,
+            files
+        );
+
+        db.set_downloaded_files(id, &[]).unwrap();
+        assert!(db
+            .get_download(id)
+            .unwrap()
+            .unwrap()
+            .downloaded_files
+            .is_empty());
+    }
+
+    #[test]
+    fn test_clear_thumbnail_paths_forgets_evicted_files() {
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = seed_statuses(&mut db, &[DownloadStatus::Done; 3]);
+        db.set_thumbnail_path(ids[0], Some("/thumbs/a.jpg"))
+            .unwrap();
+        db.set_thumbnail_path(ids[1], Some("/thumbs/a.jpg"))
+            .unwrap();
+        db.set_thumbnail_path(ids[2], Some("/thumbs/b.jpg"))
+            .unwrap();
+
+        db.clear_thumbnail_paths(&["/thumbs/a.jpg".to_string()])
+            .unwrap();
+
+        let paths: Vec<Option<String>> = ids
+            .iter()
+            .map(|id| db.get_download(*id).unwrap().unwrap().thumbnail_path)
+            .collect();
+        assert_eq!(paths, vec![None, None, Some("/thumbs/b.jpg".to_string())]);
+    }
+
+    #[test]
+    fn test_set_duration_keeps_other_metadata() {
+        let mut db = Db::open_in_memory().unwrap();
+        let id = seed_statuses(&mut db, &[DownloadStatus::Done])[0];
+        db.update_metadata(
+            id,
+            Some("Talk"),
+            Some("Channel"),
+            Some(600),
+            Some("https://i/t.jpg"),
+        )
+        .unwrap();
+
+        db.set_duration(id, 612).unwrap();
+
+        let row = db.get_download(id).unwrap().unwrap();
+        assert_eq!(row.duration_seconds, Some(612));
+        assert_eq!(row.title.as_deref(), Some("Talk"));
+        assert_eq!(row.uploader.as_deref(), Some("Channel"));
+        assert_eq!(row.thumbnail_url.as_deref(), Some("https://i/t.jpg"));
+    }
+
+    #[test]
+    fn test_set_final_paths_roundtrip() {
+        let mut db = Db::open_in_memory().unwrap();
+        let id = seed_statuses(&mut db, &[DownloadStatus::Done])[0];
+        assert!(db.get_download(id).unwrap().unwrap().final_paths.is_empty());
+
+        let paths = vec![
+            "/downloads/talk [abc].mp4".to_string(),
+            "/downloads/talk [abc] - 001 Intro.mp4".to_string(),
+            "/downloads/talk [abc] - 002 Q&A.mp4".to_string(),
+        ];
+        db.set_final_paths(id, &paths).unwrap();
+        assert_eq!(db.get_download(id).unwrap().unwrap().final_paths, paths);
+    }
+
+    #[test]
+    fn test_set_preset_allowed_when_not_active() {
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = seed_statuses(
+            &mut db,
+            &[
+                DownloadStatus::Queued,
+                DownloadStatus::Ready,
+                DownloadStatus::Stopped,
+                DownloadStatus::Failed,
+                DownloadStatus::Canceled,
+                DownloadStatus::Done,
+            ],
+        );
+
+        for id in ids {
+            db.set_preset(id, "audio_mp3_320").unwrap();
+            assert_eq!(
+                db.get_download(id).unwrap().unwrap().preset_id,
+                "audio_mp3_320"
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_preset_rejected_while_active() {
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = seed_statuses(
+            &mut db,
+            &[
+                DownloadStatus::Fetching,
+                DownloadStatus::Downloading,
+                DownloadStatus::PostProcessing,
+            ],
+        );
+
+        for id in ids {
+            let before = db.get_download(id).unwrap().unwrap().preset_id;
+            let err = db.set_preset(id, "audio_mp3_320").unwrap_err();
+            assert!(err.to_string().contains("can't change the preset"));
+            assert_eq!(db.get_download(id).unwrap().unwrap().preset_id, before);
+        }
+    }
+
+    #[test]
+    fn test_set_preset_missing_download() {
+        let mut db = Db::open_in_memory().unwrap();
+        assert!(db.set_preset(Uuid::new_v4(), "audio_mp3_320").is_err());
+    }
+
+    #[test]
+    fn test_site_key() {
+        assert_eq!(
+            site_key("https://WWW.Example.com/watch?v=1").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(site_key("vimeo.com").as_deref(), Some("vimeo.com"));
+        assert_eq!(
+            site_key(" m.example.com ").as_deref(),
+            Some("m.example.com")
+        );
+        assert_eq!(site_key(""), None);
+    }
+
+    #[test]
+    fn test_site_credentials_lookup() {
+        let mut db = Db::open_in_memory().unwrap();
+        db.set_site_credentials("example.com", "alice", "old")
+            .unwrap();
+        db.set_site_credentials("example.com", "alice", "hunter2")
+            .unwrap();
+
+        let expected = SiteCredentials {
+            username: "alice".to_string(),
+            password: "hunter2".to_string(),
+        };
+        assert_eq!(
+            db.credentials_for_url("https://www.example.com/v/1")
+                .unwrap(),
+            Some(expected.clone())
+        );
+        // Parent domain applies to subdomains
+        assert_eq!(
+            db.credentials_for_url("https://m.example.com/v/1").unwrap(),
+            Some(expected)
+        );
+        assert_eq!(
+            db.credentials_for_url("https://other.org/v/1").unwrap(),
+            None
+        );
+        assert_eq!(
+            db.list_site_credentials().unwrap(),
+            vec![("example.com".to_string(), "alice".to_string())]
+        );
+
+        assert!(db.remove_site_credentials("example.com").unwrap());
+        assert!(!db.remove_site_credentials("example.com").unwrap());
+        assert_eq!(
+            db.credentials_for_url("https://example.com/v/1").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_site_credentials_debug_hides_password() {
+        let creds = SiteCredentials {
+            username: "alice".to_string(),
+            password: "hunter2".to_string(),
+        };
+        assert!(!format!("{creds:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn test_get_statistics() {
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = seed_statuses(
+            &mut db,
+            &[
+                DownloadStatus::Done,
+                DownloadStatus::Done,
+                DownloadStatus::Failed,
+                DownloadStatus::Queued,
+            ],
+        );
+        db.set_actual_filesize(ids[0], 1_000).unwrap();
+        db.set_actual_filesize(ids[1], 2_500).unwrap();
+        // Size of an unfinished download doesn't count as downloaded
+        db.set_actual_filesize(ids[3], 9_999).unwrap();
+
+        // Backdate two rows: one inside the window, one outside it
+        let today = Utc::now();
+        let three_days_ago = today - chrono::Duration::days(3);
+        let long_ago = today - chrono::Duration::days(STATS_DAYS + 5);
+        for (id, when) in [(ids[1], three_days_ago), (ids[2], long_ago)] {
+            db.conn()
+                .execute(
+                    "UPDATE downloads SET created_at = ?2 WHERE id = ?1",
+                    params![id.to_string(), when.to_rfc3339()],
+                )
+                .unwrap();
+        }
+
+        let stats = db.get_statistics().unwrap();
+
+        assert_eq!(stats.total_downloads, 4);
+        assert_eq!(stats.total_bytes_downloaded, 3_500);
+        assert_eq!(stats.by_status["done"], 2);
+        assert_eq!(stats.by_status["failed"], 1);
+        assert_eq!(stats.by_status["queued"], 1);
+        assert_eq!(stats.by_status["downloading"], 0);
+        assert_eq!(stats.by_status.len(), DownloadStatus::ALL.len());
+        assert_eq!(
+            stats.per_day,
+            vec![
+                DailyCount {
+                    date: three_days_ago.format("%Y-%m-%d").to_string(),
+                    count: 1,
+                },
+                DailyCount {
+                    date: today.format("%Y-%m-%d").to_string(),
+                    count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_statistics_empty() {
+        let mut db = Db::open_in_memory().unwrap();
+        let stats = db.get_statistics().unwrap();
+
+        assert_eq!(stats.total_downloads, 0);
+        assert_eq!(stats.total_bytes_downloaded, 0);
+        assert!(stats.per_day.is_empty());
+    }
+
+    #[test]
+    fn test_usage_buckets_by_utc_day() {
+        let mut db = Db::open_in_memory().unwrap();
+        let day = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+
+        db.add_usage(day("2026-03-01T00:00:00Z"), 100).unwrap();
+        db.add_usage(day("2026-03-01T23:59:59Z"), 50).unwrap();
+        // 01:00 in UTC+2 is still the previous UTC day
+        db.add_usage(day("2026-03-03T01:00:00+02:00"), 7).unwrap();
+        db.add_usage(day("2026-03-03T12:00:00Z"), 3).unwrap();
+
+        assert_eq!(
+            db.get_usage(None).unwrap(),
+            vec![
+                DailyUsage {
+                    date: "2026-03-01".to_string(),
+                    bytes: 150,
+                },
+                DailyUsage {
+                    date: "2026-03-02".to_string(),
+                    bytes: 7,
+                },
+                DailyUsage {
+                    date: "2026-03-03".to_string(),
+                    bytes: 3,
+                },
+            ]
+        );
+
+        let since = db.get_usage(Some(day("2026-03-02T18:00:00Z"))).unwrap();
+        assert_eq!(since.len(), 2);
+        assert_eq!(since[0].date, "2026-03-02");
+    }
+
+    #[test]
+    fn test_get_failed_download_ids() {
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = seed_statuses(&mut db, &MIXED);
+
+        assert_eq!(db.get_failed_download_ids().unwrap(), vec![ids[2]]);
+    }
+}
@@ -0,0 +1,245 @@
+//! Database actor
+//!
+//! `rusqlite::Connection` can't be shared between threads, so a dedicated thread
+//! owns the [`Db`] and everything else sends it work through a [`DbHandle`].
+//!
+//! Progress updates don't wait for a reply: they're coalesced per download and
+//! written together at most once per [`PROGRESS_FLUSH_INTERVAL`], so a busy
+//! download never holds up the commands behind it.
+
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::db::{Db, ProgressUpdate};
+
+/// How long progress updates are held before being written.
+pub const PROGRESS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+type Job = Box<dyn FnOnce(&mut Db) + Send>;
+
+enum Message {
+    Call(Job),
+    Progress(ProgressUpdate),
+}
+
+/// Cheaply cloneable handle to the database thread.
+#[derive(Clone)]
+pub struct DbHandle {
+    tx: mpsc::Sender<Message>,
+}
+
+impl DbHandle {
+    /// Move `db` onto its own thread. The thread exits once every handle is dropped,
+    /// writing any progress still pending.
+    pub fn spawn(db: Db) -> Result<Self> {
+        Self::spawn_with_interval(db, PROGRESS_FLUSH_INTERVAL)
+    }
+
+    fn spawn_with_interval(db: Db, flush_interval: Duration) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("db".to_string())
+            .spawn(move || run(db, rx, flush_interval))?;
+        Ok(Self { tx })
+    }
+
+    /// Run `f` on the database thread and wait for its result. Pending progress is
+    /// written first, so `f` sees it and can't be overwritten by it.
+    pub async fn call<R, F>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Db) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let rx = self.send_call(f)?;
+        rx.await.map_err(|_| stopped())?
+    }
+
+    /// [`DbHandle::call`] for synchronous code. Must not be called from an async task.
+    pub fn blocking_call<R, F>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Db) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let rx = self.send_call(f)?;
+        rx.blocking_recv().map_err(|_| stopped())?
+    }
+
+    /// Queue a progress update without waiting. Only the latest update per download
+    /// is written.
+    pub fn record_progress(&self, update: ProgressUpdate) {
+        let _ = self.tx.send(Message::Progress(update));
+    }
+
+    fn send_call<R, F>(&self, f: F) -> Result<oneshot::Receiver<Result<R>>>
+    where
+        F: FnOnce(&mut Db) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Message::Call(Box::new(move |db| {
+                let _ = tx.send(f(db));
+            })))
+            .map_err(|_| stopped())?;
+        Ok(rx)
+    }
+}
+
+fn stopped() -> anyhow::Error {
+    anyhow!("database thread stopped")
+}
+
+fn run(mut db: Db, rx: mpsc::Receiver<Message>, flush_interval: Duration) {
+    let mut pending: HashMap<Uuid, ProgressUpdate> = HashMap::new();
+    let mut flush_at: Option<Instant> = None;
+
+    loop {
+        let message = match flush_at {
+            Some(at) => match rx.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                Ok(m) => Some(m),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            },
+            None => match rx.recv() {
+                Ok(m) => Some(m),
+                Err(_) => break,
+            },
+        };
+
+        match message {
+            Some(Message::Progress(update)) => {
+                pending.insert(update.id, update);
+                flush_at.get_or_insert_with(|| Instant::now() + flush_interval);
+            }
+            Some(Message::Call(job)) => {
+                flush(&mut db, &mut pending);
+                flush_at = None;
+                // A panicking job only loses its own reply; the thread keeps serving
+                if catch_unwind(AssertUnwindSafe(|| job(&mut db))).is_err() {
+                    log::error!("Database job panicked");
+                }
+            }
+            None => {
+                flush(&mut db, &mut pending);
+                flush_at = None;
+            }
+        }
+    }
+
+    flush(&mut db, &mut pending);
+}
+
+fn flush(db: &mut Db, pending: &mut HashMap<Uuid, ProgressUpdate>) {
+    if pending.is_empty() {
+        return;
+    }
+    let updates: Vec<ProgressUpdate> = pending.drain().map(|(_, u)| u).collect();
+    if let Err(e) = db.update_progress_batch(&updates) {
+        log::warn!("Failed to write download progress: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{DownloadStatus, SourceKind};
+
+    fn insert(handle: &DbHandle) -> Uuid {
+        handle
+            .blocking_call(|db| {
+                db.insert_download(
+                    "https://example.com/v",
+                    SourceKind::Single,
+                    None,
+                    "best",
+                    "/tmp",
+                )
+            })
+            .unwrap()
+    }
+
+    fn progress(id: Uuid, percent: f64) -> ProgressUpdate {
+        ProgressUpdate {
+            id,
+            percent: Some(percent),
+            bytes_downloaded: Some((percent * 10.0) as i64),
+            bytes_total: Some(1000),
+            speed_bps: Some(100),
+            eta_seconds: Some(5),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_progress_does_not_block_calls() {
+        let handle =
+            DbHandle::spawn_with_interval(Db::open_in_memory().unwrap(), Duration::from_secs(60))
+                .unwrap();
+        let ids: Vec<Uuid> = {
+            let handle = handle.clone();
+            tokio::task::spawn_blocking(move || (0..4).map(|_| insert(&handle)).collect())
+                .await
+                .unwrap()
+        };
+
+        let writers: Vec<_> = ids
+            .iter()
+            .map(|&id| {
+                let handle = handle.clone();
+                tokio::spawn(async move {
+                    for i in 1..=500 {
+                        handle.record_progress(progress(id, i as f64 / 5.0));
+                        if i % 50 == 0 {
+                            tokio::task::yield_now().await;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // Commands keep being answered while progress streams in
+        let started = Instant::now();
+        for _ in 0..20 {
+            let count = handle
+                .call(|db| db.count_by_status(DownloadStatus::Queued))
+                .await
+                .unwrap();
+            assert_eq!(count, 4);
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        for w in writers {
+            w.await.unwrap();
+        }
+
+        // A call flushes what's pending, so only the latest update per download shows
+        for id in ids {
+            let row = handle
+                .call(move |db| db.get_download(id))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(row.progress_percent, Some(100.0));
+            assert_eq!(row.bytes_downloaded, Some(1000));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_survives_panicking_job() {
+        let handle = DbHandle::spawn(Db::open_in_memory().unwrap()).unwrap();
+        let result: Result<()> = handle.call(|_| panic!("boom")).await;
+        assert!(result.is_err());
+        assert_eq!(
+            handle
+                .call(|db| db.count_by_status(DownloadStatus::Queued))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+/// Extra room required on top of the expected file size. Merging separate video/audio
+/// streams and writing `.part` files needs some slack beyond the final size.
+const SPACE_HEADROOM_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Free/total space of the filesystem containing a path.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskSpace {
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Query the filesystem that contains `path`.
+///
+/// The output directory may not exist yet (it's created on first download), so this
+/// walks up to the nearest existing ancestor before asking the OS.
+pub fn disk_space(path: &Path) -> Result<DiskSpace> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| anyhow!("No existing parent directory for {}", path.display()))?;
+
+    platform::disk_space(existing)
+        .with_context(|| format!("Failed to query disk space for {}", existing.display()))
+}
+
+/// Whether `available` bytes is enough for a download still needing `required` bytes.
+pub fn has_enough_space(required: u64, available: u64) -> bool {
+    available >= required.saturating_add(SPACE_HEADROOM_BYTES)
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use anyhow::Result;
+
+    use super::DiskSpace;
+
+    // Field widths differ between platforms (u32 on macOS, u64 on Linux).
+    #[allow(clippy::unnecessary_cast)]
+    pub fn disk_space(path: &Path) -> Result<DiskSpace> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+        // SAFETY: `c_path` is a valid NUL-terminated string and `stat` is a valid out pointer.
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let fragment_size = stat.f_frsize as u64;
+        Ok(DiskSpace {
+            available_bytes: stat.f_bavail as u64 * fragment_size,
+            total_bytes: stat.f_blocks as u64 * fragment_size,
+        })
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    use anyhow::Result;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    use super::DiskSpace;
+
+    pub fn disk_space(path: &Path) -> Result<DiskSpace> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut available: u64 = 0;
+        let mut total: u64 = 0;
+
+        // SAFETY: `wide` is NUL-terminated and the out pointers are valid for the call.
+        let ok = unsafe {
+            GetDiskFreeSpaceExW(
+                wide.as_ptr(),
+                &mut available,
+                &mut total,
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(DiskSpace {
+            available_bytes: available,
+            total_bytes: total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_has_enough_space_with_plenty_free() {
+        assert!(has_enough_space(500 * MIB, 10 * 1024 * MIB));
+    }
+
+    #[test]
+    fn test_has_enough_space_requires_headroom() {
+        // Fits exactly, but leaves nothing for merging/temp files
+        assert!(!has_enough_space(500 * MIB, 500 * MIB));
+        assert!(!has_enough_space(
+            500 * MIB,
+            500 * MIB + SPACE_HEADROOM_BYTES - 1
+        ));
+        assert!(has_enough_space(
+            500 * MIB,
+            500 * MIB + SPACE_HEADROOM_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_enough_space_low_disk() {
+        assert!(!has_enough_space(4 * 1024 * MIB, 50 * MIB));
+        assert!(!has_enough_space(0, 0));
+    }
+
+    #[test]
+    fn test_has_enough_space_does_not_overflow() {
+        assert!(!has_enough_space(u64::MAX, u64::MAX - 1));
+    }
+
+    #[test]
+    fn test_disk_space_uses_existing_ancestor() {
+        let missing = std::env::temp_dir().join("downlink-missing-dir/nested/deeper");
+        let space = disk_space(&missing).unwrap();
+        assert!(space.total_bytes > 0);
+        assert!(space.available_bytes <= space.total_bytes);
+    }
+}
@@ -3,9 +3,10 @@
 //! Handles the execution of downloads using yt-dlp, progress tracking, concurrency control,
 //! and lifecycle management (start, stop, cancel, retry).
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -17,17 +18,30 @@ use std::os::windows::process::CommandExt;
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
-use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use uuid::Uuid;
 
-use crate::db::{Db, DownloadStatus};
+use crate::db::{
+    Db, DownloadRow, DownloadStatus, ProgressUpdate, SiteCredentials, INTERRUPTED_PHASE,
+};
+use crate::db_actor::DbHandle;
+use crate::disk_space;
 use crate::events::{
     self, Action, ActionKind, DownlinkEvent, ErrorCode, MediaInfo, Phase, Progress,
 };
+use crate::settings::{
+    container_codec_conflict, validate_cookies_file, validate_extractor_args,
+    validate_remux_container, validate_subtitle_langs, FormatSettings, GeneralSettings,
+    NetworkSettings, PrivacySettings, SettingsManager, SponsorBlockSettings, SubtitleSettings,
+    UserSettings, SPONSORBLOCK_CATEGORIES,
+};
+use crate::tool_manager::Tool;
+use crate::ytdlp::{default_thumbnail, parse_extra_args, parse_thumbnails, redact_args, Thumbnail};
 
 /// Configuration for download execution.
 #[derive(Debug, Clone)]
@@ -36,33 +50,166 @@ pub struct DownloadConfig {
     pub ffmpeg_path: Option<PathBuf>,
     pub max_concurrent: usize,
     pub default_output_template: String,
+    /// ffprobe, used to read the real duration/size of finished files. Optional.
+    pub ffprobe_path: Option<PathBuf>,
+    /// Kill a download as stalled if yt-dlp prints nothing for this long while transferring.
+    pub stall_timeout: Duration,
+    /// Automatic retries for transient failures (network, rate limiting). 0 disables.
+    pub max_auto_retries: u32,
+    /// First backoff delay for network retries; doubles on each attempt.
+    pub retry_base_delay: Duration,
+    /// aria2c, used when the user picks it as the external downloader. Optional.
+    pub aria2c_path: Option<PathBuf>,
+    /// How often progress events are sent to the frontend.
+    pub progress_throttle: ProgressThrottle,
+    /// App data directory, where download archives are kept. Empty disables them.
+    pub data_dir: PathBuf,
+    /// App tmp directory, where `download_to_temp` downloads are staged. Empty disables it.
+    pub tmp_dir: PathBuf,
 }
 
-/// Find yt-dlp binary by checking bundled sidecar first, then common installation paths.
-/// This is needed because bundled macOS apps don't have access to the user's PATH.
-pub fn find_ytdlp_binary() -> PathBuf {
-    // First, check for bundled sidecar binary next to the executable
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            // In macOS .app bundle, binaries are in Contents/MacOS/
-            let sidecar_path = exe_dir.join("yt-dlp");
-            if sidecar_path.exists() {
-                log::info!("Found bundled yt-dlp sidecar at: {:?}", sidecar_path);
-                return sidecar_path;
-            }
+/// Limits on how often `DownloadProgress` events are emitted for one download.
+///
+/// An update is sent once `min_interval` has passed since the last one and the
+/// percentage moved by at least `min_percent_delta`. The first update and anything
+/// at 99.9% or above are always sent so the UI never sticks short of done.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressThrottle {
+    pub min_interval: Duration,
+    pub min_percent_delta: f64,
+}
 
-            // Also check parent directories for different bundle structures
-            if let Some(parent) = exe_dir.parent() {
-                let resources_path = parent.join("Resources").join("yt-dlp");
-                if resources_path.exists() {
-                    log::info!("Found bundled yt-dlp in Resources at: {:?}", resources_path);
-                    return resources_path;
-                }
+impl Default for ProgressThrottle {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_millis(250),
+            min_percent_delta: 0.1,
+        }
+    }
+}
+
+impl ProgressThrottle {
+    /// Whether to emit `percent` at `now`, given the last emitted (time, percent).
+    pub fn should_emit(
+        &self,
+        last: Option<(std::time::Instant, f64)>,
+        now: std::time::Instant,
+        percent: f64,
+    ) -> bool {
+        let Some((last_at, last_percent)) = last else {
+            return true;
+        };
+        if percent >= 99.9 {
+            return true;
+        }
+        now.saturating_duration_since(last_at) >= self.min_interval
+            && (percent - last_percent).abs() >= self.min_percent_delta
+    }
+}
+
+/// Smooths yt-dlp's jumpy ETA with an exponential moving average of the speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EtaSmoother {
+    /// Weight of the newest speed sample, in (0, 1]. Higher reacts faster.
+    alpha: f64,
+    speed: Option<f64>,
+}
+
+impl Default for EtaSmoother {
+    fn default() -> Self {
+        Self::new(0.2)
+    }
+}
+
+impl EtaSmoother {
+    pub fn new(alpha: f64) -> Self {
+        Self { alpha, speed: None }
+    }
+
+    /// Fold a speed sample into the average and return the smoothed speed.
+    fn add_sample(&mut self, speed_bps: Option<u64>) -> Option<f64> {
+        if let Some(sample) = speed_bps {
+            let sample = sample as f64;
+            self.speed = Some(match self.speed {
+                Some(avg) => self.alpha * sample + (1.0 - self.alpha) * avg,
+                None => sample,
+            });
+        }
+        self.speed
+    }
+
+    /// ETA in seconds for `progress`, from the remaining bytes and the smoothed speed.
+    ///
+    /// Falls back to yt-dlp's own ETA when the total size or a speed isn't known yet.
+    pub fn eta(&mut self, progress: &ParsedProgress) -> Option<u64> {
+        let speed = self.add_sample(progress.speed_bps);
+        let remaining = progress.bytes_total.and_then(|total| {
+            let done = progress
+                .bytes_downloaded
+                .or_else(|| progress.percent.map(|p| (total as f64 * p / 100.0) as u64))?;
+            Some(total.saturating_sub(done))
+        });
+        match (remaining, speed) {
+            (Some(remaining), Some(speed)) if speed > 0.0 => {
+                Some((remaining as f64 / speed).round() as u64)
             }
+            _ => progress.eta_seconds,
+        }
+    }
+}
+
+/// Speed samples kept per active download for the live speed graph.
+pub const SPEED_SAMPLE_CAPACITY: usize = 120;
+
+/// One point on a download's speed graph.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpeedSample {
+    pub at: DateTime<Utc>,
+    pub speed_bps: Option<u64>,
+    pub percent: Option<f64>,
+}
+
+/// Ring buffer of the most recent speed samples for one download.
+#[derive(Debug, Clone)]
+pub struct SpeedHistory {
+    capacity: usize,
+    samples: VecDeque<SpeedSample>,
+}
+
+impl Default for SpeedHistory {
+    fn default() -> Self {
+        Self::new(SPEED_SAMPLE_CAPACITY)
+    }
+}
+
+impl SpeedHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Append a sample, evicting the oldest once the buffer is full.
+    pub fn push(&mut self, sample: SpeedSample) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.samples.len() >= self.capacity {
+            self.samples.pop_front();
         }
+        self.samples.push_back(sample);
+    }
+
+    /// Samples from oldest to newest.
+    pub fn samples(&self) -> Vec<SpeedSample> {
+        self.samples.iter().cloned().collect()
     }
+}
 
-    // Common paths where yt-dlp might be installed
+/// Find yt-dlp binary by checking bundled sidecar first, then common installation paths.
+/// This is needed because bundled macOS apps don't have access to the user's PATH.
+pub fn find_ytdlp_binary() -> PathBuf {
     let common_paths = [
         // Homebrew on Apple Silicon
         "/opt/homebrew/bin/yt-dlp",
@@ -77,14 +224,47 @@ pub fn find_ytdlp_binary() -> PathBuf {
         // MacPorts
         "/opt/local/bin/yt-dlp",
     ];
+    if let Some(path) = find_binary("yt-dlp", &common_paths) {
+        return path;
+    }
+
+    // Last resort - hope it's in PATH
+    log::warn!("Could not find yt-dlp in common paths, falling back to PATH lookup");
+    PathBuf::from("yt-dlp")
+}
+
+/// Look for a binary next to the executable (sidecar), in a macOS bundle's
+/// Resources, at `common_paths` (`$HOME` expanded), then on PATH.
+fn find_binary(binary: &str, common_paths: &[&str]) -> Option<PathBuf> {
+    // In macOS .app bundle, binaries are in Contents/MacOS/
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(exe_dir) = exe_path.parent() {
+            let sidecar_path = exe_dir.join(binary);
+            if sidecar_path.exists() {
+                log::info!("Found bundled {} sidecar at: {:?}", binary, sidecar_path);
+                return Some(sidecar_path);
+            }
+
+            // Also check parent directories for different bundle structures
+            if let Some(parent) = exe_dir.parent() {
+                let resources_path = parent.join("Resources").join(binary);
+                if resources_path.exists() {
+                    log::info!(
+                        "Found bundled {} in Resources at: {:?}",
+                        binary,
+                        resources_path
+                    );
+                    return Some(resources_path);
+                }
+            }
+        }
+    }
 
-    for path_template in &common_paths {
-        // Expand $HOME
+    for path_template in common_paths {
         let expanded = if path_template.starts_with("$HOME") {
-            if let Some(home) = std::env::var_os("HOME") {
-                path_template.replace("$HOME", &home.to_string_lossy())
-            } else {
-                continue;
+            match std::env::var_os("HOME") {
+                Some(home) => path_template.replace("$HOME", &home.to_string_lossy()),
+                None => continue,
             }
         } else {
             path_template.to_string()
@@ -92,29 +272,19 @@ pub fn find_ytdlp_binary() -> PathBuf {
 
         let path = PathBuf::from(&expanded);
         if path.exists() {
-            log::info!("Found yt-dlp at: {:?}", path);
-            return path;
+            log::info!("Found {} at: {:?}", binary, path);
+            return Some(path);
         }
     }
 
-    // Try which command as fallback (works in dev mode)
-    #[cfg(not(windows))]
-    if let Ok(output) = std::process::Command::new("which").arg("yt-dlp").output() {
-        if output.status.success() {
-            let path_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !path_str.is_empty() {
-                let path = PathBuf::from(&path_str);
-                if path.exists() {
-                    log::info!("Found yt-dlp via which: {:?}", path);
-                    return path;
-                }
-            }
+    // Fall back to PATH (covers Windows installs and dev setups)
+    match which::which(binary) {
+        Ok(path) => {
+            log::info!("Found {} via PATH: {:?}", binary, path);
+            Some(path)
         }
+        Err(_) => None,
     }
-
-    // Last resort - hope it's in PATH
-    log::warn!("Could not find yt-dlp in common paths, falling back to PATH lookup");
-    PathBuf::from("yt-dlp")
 }
 
 /// Metadata fetched for a URL
@@ -124,6 +294,7 @@ pub struct FetchedMetadata {
     pub uploader: Option<String>,
     pub duration_seconds: Option<u64>,
     pub thumbnail_url: Option<String>,
+    pub thumbnails: Vec<Thumbnail>,
 }
 
 /// Fetch metadata for a single URL using yt-dlp --dump-json
@@ -151,6 +322,7 @@ async fn fetch_metadata_for_url(yt_dlp_path: &PathBuf, url: &str) -> Option<Fetc
             // Parse first line as JSON
             if let Some(line) = stdout.lines().next() {
                 if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
+                    let thumbnails = parse_thumbnails(&json);
                     return Some(FetchedMetadata {
                         title: json
                             .get("title")
@@ -167,10 +339,8 @@ async fn fetch_metadata_for_url(yt_dlp_path: &PathBuf, url: &str) -> Option<Fetc
                                     .map(|f| f as u64)
                             },
                         ),
-                        thumbnail_url: json
-                            .get("thumbnail")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string()),
+                        thumbnail_url: default_thumbnail(&json, &thumbnails),
+                        thumbnails,
                     });
                 }
             }
@@ -193,67 +363,59 @@ async fn fetch_metadata_for_url(yt_dlp_path: &PathBuf, url: &str) -> Option<Fetc
 
 /// Find ffmpeg binary by checking bundled sidecar first, then common installation paths.
 pub fn find_ffmpeg_binary() -> Option<PathBuf> {
-    // First, check for bundled sidecar binary next to the executable
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            let sidecar_path = exe_dir.join("ffmpeg");
-            if sidecar_path.exists() {
-                log::info!("Found bundled ffmpeg sidecar at: {:?}", sidecar_path);
-                return Some(sidecar_path);
-            }
-
-            if let Some(parent) = exe_dir.parent() {
-                let resources_path = parent.join("Resources").join("ffmpeg");
-                if resources_path.exists() {
-                    log::info!("Found bundled ffmpeg in Resources at: {:?}", resources_path);
-                    return Some(resources_path);
-                }
-            }
-        }
-    }
-
-    // Common paths where ffmpeg might be installed
     let common_paths = [
         "/opt/homebrew/bin/ffmpeg",
         "/usr/local/bin/ffmpeg",
         "/usr/bin/ffmpeg",
         "/opt/local/bin/ffmpeg",
     ];
-
-    for path_str in &common_paths {
-        let path = PathBuf::from(path_str);
-        if path.exists() {
-            log::info!("Found ffmpeg at: {:?}", path);
-            return Some(path);
-        }
+    let found = find_binary("ffmpeg", &common_paths);
+    if found.is_none() {
+        log::warn!("Could not find ffmpeg");
     }
+    found
+}
 
-    // Try which command as fallback (Unix only)
-    #[cfg(not(windows))]
-    if let Ok(output) = std::process::Command::new("which").arg("ffmpeg").output() {
-        if output.status.success() {
-            let path_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !path_str.is_empty() {
-                let path = PathBuf::from(&path_str);
-                if path.exists() {
-                    log::info!("Found ffmpeg via which: {:?}", path);
-                    return Some(path);
-                }
-            }
-        }
+/// Find aria2c by checking bundled sidecar first, then common installation paths.
+pub fn find_aria2c_binary() -> Option<PathBuf> {
+    let binary = if cfg!(windows) {
+        "aria2c.exe"
+    } else {
+        "aria2c"
+    };
+    let common_paths = [
+        "/opt/homebrew/bin/aria2c",
+        "/usr/local/bin/aria2c",
+        "/usr/bin/aria2c",
+        "/opt/local/bin/aria2c",
+    ];
+    let found = find_binary(binary, &common_paths);
+    if found.is_none() {
+        log::info!("aria2c not found; the native downloader will be used");
     }
-
-    log::warn!("Could not find ffmpeg");
-    None
+    found
 }
 
 impl Default for DownloadConfig {
     fn default() -> Self {
+        let ffmpeg_path = find_ffmpeg_binary();
         Self {
             yt_dlp_path: find_ytdlp_binary(),
-            ffmpeg_path: find_ffmpeg_binary(),
+            ffmpeg_path: ffmpeg_path.clone(),
+            ffprobe_path: ffmpeg_path
+                .map(|p| p.with_file_name(Tool::Ffprobe.binary_name()))
+                .filter(|p| p.exists()),
             max_concurrent: 2,
             default_output_template: "%(title)s [%(id)s].%(ext)s".to_string(),
+            stall_timeout: Duration::from_secs(120),
+            max_auto_retries: 3,
+            retry_base_delay: Duration::from_secs(5),
+            aria2c_path: find_aria2c_binary(),
+            progress_throttle: ProgressThrottle::default(),
+            data_dir: crate::db::app_data_dir().unwrap_or_default(),
+            tmp_dir: crate::db::ensure_app_dirs()
+                .map(|dirs| dirs.tmp)
+                .unwrap_or_default(),
         }
     }
 }
@@ -331,6 +493,13 @@ impl Preset {
     }
 }
 
+/// A yt-dlp invocation shown to the user instead of being run.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandPreview {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
 /// Progress parsed from yt-dlp output.
 #[derive(Debug, Clone, Default)]
 pub struct ParsedProgress {
@@ -342,13 +511,57 @@ pub struct ParsedProgress {
     pub phase: Option<String>,
 }
 
+/// How many download slots are in use, for "2/4 active, 3 queued".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ManagerStatus {
+    pub active: usize,
+    pub max_concurrent: usize,
+    /// Downloads waiting for a slot.
+    pub pending: usize,
+    /// Set by `pause_all`: queued downloads wait until `resume_all`.
+    pub paused: bool,
+    /// False while downloads wait for the network to come back.
+    pub online: bool,
+}
+
+/// Whether the manager is running a download or holding it for a free slot. Tells
+/// a `Queued` row that's waiting apart from one nothing will start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueState {
+    Active,
+    Pending,
+    NotManaged,
+}
+
 /// Download Manager handles scheduling and execution of downloads.
 /// Uses lazy initialization to avoid spawning tasks before runtime is ready.
 pub struct DownloadManager {
     config: DownloadConfig,
-    db: Arc<Mutex<Db>>,
+    db: DbHandle,
     event_tx: mpsc::Sender<DownlinkEvent>,
     active_downloads: Arc<RwLock<HashMap<Uuid, broadcast::Sender<()>>>>,
+    /// Running downloads that got a share of a global `rate_limit_bps`, see
+    /// `rate_limit_share`. Downloads with their own limit aren't in here.
+    rate_limit_sharers: Arc<RwLock<HashSet<Uuid>>>,
+    /// Recent progress samples per active download, for the speed graph. Never persisted.
+    speed_samples: Arc<RwLock<HashMap<Uuid, SpeedHistory>>>,
+    /// Bytes of downloads completed since the app started.
+    session_bytes: Arc<AtomicU64>,
+    /// Country to geo-bypass from per download, set by `retry_with_geo_bypass`.
+    /// Kept for later automatic retries until the app restarts.
+    geo_bypass_countries: RwLock<HashMap<Uuid, String>>,
+    /// Running downloads the user asked to stop, which may still be winding down.
+    /// Shutdown leaves these stopped instead of marking them interrupted.
+    stop_requested: RwLock<HashSet<Uuid>>,
+    /// Automatic retry attempts made so far per download; cleared once it stops failing.
+    retry_attempts: Arc<RwLock<HashMap<Uuid, u32>>>,
+    /// Downloads waiting for a free slot, in the order they were started.
+    pending: Arc<PendingQueue>,
+    network_failures: Arc<NetworkFailures>,
+    /// Download tasks send ids here to have them started (again), see `start_retry_loop`.
+    retry_tx: mpsc::UnboundedSender<Uuid>,
+    retry_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<Uuid>>>,
 }
 
 impl DownloadManager {
@@ -356,45 +569,140 @@ impl DownloadManager {
     /// This does NOT spawn any background tasks - all operations are on-demand.
     pub fn new(
         config: DownloadConfig,
-        db: Arc<Mutex<Db>>,
+        db: DbHandle,
         event_tx: mpsc::Sender<DownlinkEvent>,
     ) -> Self {
+        let (retry_tx, retry_rx) = mpsc::unbounded_channel();
         Self {
             config,
             db,
             event_tx,
             active_downloads: Arc::new(RwLock::new(HashMap::new())),
+            rate_limit_sharers: Arc::new(RwLock::new(HashSet::new())),
+            speed_samples: Arc::new(RwLock::new(HashMap::new())),
+            session_bytes: Arc::new(AtomicU64::new(0)),
+            geo_bypass_countries: RwLock::new(HashMap::new()),
+            stop_requested: RwLock::new(HashSet::new()),
+            retry_attempts: Arc::new(RwLock::new(HashMap::new())),
+            pending: Arc::new(PendingQueue::default()),
+            network_failures: Arc::new(NetworkFailures::default()),
+            retry_tx,
+            retry_rx: std::sync::Mutex::new(Some(retry_rx)),
         }
     }
 
-    /// Start a download by ID.
+    /// Spawn the task that starts downloads handed over by download tasks: automatic
+    /// retries, and queued downloads once a slot frees up.
+    ///
+    /// Call once after wrapping the manager in an `Arc`; later calls are no-ops.
+    pub fn start_retry_loop(self: &Arc<Self>) {
+        let Some(mut retry_rx) = self.retry_rx.lock().ok().and_then(|mut rx| rx.take()) else {
+            return;
+        };
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            while let Some(id) = retry_rx.recv().await {
+                if let Err(e) = manager.start(id).await {
+                    log::error!("Download {} failed to start: {}", id, e);
+                }
+            }
+        });
+
+        // While offline, probe now and then and resume the held downloads once it's back
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(CONNECTIVITY_PROBE_INTERVAL).await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if !manager.pending.is_offline() {
+                    continue;
+                }
+                let network = manager.load_settings().await.network;
+                let proxy = (network.use_proxy && !network.proxy_url.trim().is_empty())
+                    .then(|| network.proxy_url.trim().to_string());
+                if crate::network::is_reachable(proxy.as_deref()).await {
+                    log::info!("Network is reachable again");
+                    manager.set_online(true).await;
+                }
+            }
+        });
+    }
+
+    /// Start a download by ID. When every slot is taken it waits in the pending
+    /// queue and starts once a running download finishes.
     pub async fn start(&self, id: Uuid) -> Result<()> {
-        // Check concurrency limit
-        let active_count = self.active_downloads.read().await.len();
-        if active_count >= self.config.max_concurrent {
+        let Some(cancel_rx) = self.reserve_slot(id).await else {
+            return Ok(());
+        };
+        let result = self.start_reserved(id, cancel_rx).await;
+        if !matches!(result, Ok(true)) {
+            self.release_slot(id).await;
+        }
+        result.map(|_| ())
+    }
+
+    /// Take a slot for `id`, or queue it when every slot is taken or downloads are
+    /// paused. The check and the reservation happen under one lock, so concurrent
+    /// starts can't run more than `max_concurrent`. None if `id` is already running or
+    /// was queued.
+    async fn reserve_slot(&self, id: Uuid) -> Option<broadcast::Receiver<()>> {
+        let mut active = self.active_downloads.write().await;
+        if active.contains_key(&id) {
+            log::warn!("Download {} is already active", id);
+            return None;
+        }
+        if active.len() >= self.config.max_concurrent {
             log::info!(
                 "Concurrency limit reached ({}/{}), download {} will wait",
-                active_count,
+                active.len(),
                 self.config.max_concurrent,
                 id
             );
-            return Ok(());
+            drop(active);
+            self.pending.push_back(id).await;
+            return None;
         }
-
-        // Check if already active
-        if self.active_downloads.read().await.contains_key(&id) {
-            log::warn!("Download {} is already active", id);
-            return Ok(());
+        if self.pending.is_paused() {
+            log::info!("Downloads are paused, download {} will wait", id);
+            drop(active);
+            self.pending.push_back(id).await;
+            return None;
         }
+        if self.pending.is_held() {
+            log::info!("yt-dlp is being updated, download {} will wait", id);
+            drop(active);
+            self.pending.push_back(id).await;
+            return None;
+        }
+
+        // Subscribed now so a stop sent before the task runs isn't lost
+        let (cancel_tx, cancel_rx) = broadcast::channel::<()>(1);
+        active.insert(id, cancel_tx);
+        drop(active);
+        self.stop_requested.write().await.remove(&id);
+        Some(cancel_rx)
+    }
+
+    /// Give back a slot taken by `reserve_slot` for a download that didn't start.
+    async fn release_slot(&self, id: Uuid) {
+        self.active_downloads.write().await.remove(&id);
+    }
 
+    /// The rest of `start`, once `id` holds a slot. Returns whether the download task
+    /// was spawned; otherwise `start` gives the slot back.
+    async fn start_reserved(&self, id: Uuid, cancel_rx: broadcast::Receiver<()>) -> Result<bool> {
         // Get download info from DB
         let mut download_info = {
-            let mut db = self.db.lock().await;
-            match db.get_download(id) {
+            match self.db.call(move |db| db.get_download(id)).await {
                 Ok(Some(row)) => row,
                 Ok(None) => {
                     log::error!("Download {} not found in database", id);
-                    return Err(anyhow!("Download not found"));
+                    self.release_slot(id).await;
+                    start_next_pending(&self.pending, &self.retry_tx).await;
+                    return Err(RequestError::NotFound.into());
                 }
                 Err(e) => {
                     log::error!("Failed to get download {}: {}", id, e);
@@ -412,19 +720,44 @@ impl DownloadManager {
                     id,
                     download_info.status
                 );
-                return Ok(());
+                self.release_slot(id).await;
+                start_next_pending(&self.pending, &self.retry_tx).await;
+                return Ok(false);
             }
         }
 
+        // Don't burn retries while the network is down; `set_online` starts it again
+        if self.pending.is_offline() {
+            log::info!("Network is down, download {} will wait", id);
+            self.pending.push_back(id).await;
+            let _ = self
+                .db
+                .call(move |db| {
+                    db.set_status(id, DownloadStatus::Queued, Some(WAITING_FOR_NETWORK))
+                })
+                .await;
+            let _ = self.event_tx.send(waiting_for_network(id, None)).await;
+            return Ok(false);
+        }
+
         // If the download doesn't have a title, fetch metadata first
         if download_info.title.is_none() {
             log::info!("Download {} has no title, fetching metadata first", id);
+            let _ = self
+                .event_tx
+                .send(DownlinkEvent::MetadataStarted {
+                    id,
+                    url: download_info.source_url.clone(),
+                })
+                .await;
 
             // Update status to Fetching
-            {
-                let mut db = self.db.lock().await;
-                let _ = db.set_status(id, DownloadStatus::Fetching, Some("Fetching metadata…"));
-            }
+            let _ = self
+                .db
+                .call(move |db| {
+                    db.set_status(id, DownloadStatus::Fetching, Some("Fetching metadata…"))
+                })
+                .await;
 
             // Emit progress event for fetching phase
             let _ = self
@@ -454,14 +787,19 @@ impl DownloadManager {
 
                 // Update the database with fetched metadata
                 {
-                    let mut db = self.db.lock().await;
-                    let _ = db.update_metadata(
-                        id,
-                        metadata.title.as_deref(),
-                        metadata.uploader.as_deref(),
-                        metadata.duration_seconds.map(|d| d as i64),
-                        metadata.thumbnail_url.as_deref(),
-                    );
+                    let metadata = metadata.clone();
+                    let _ = self
+                        .db
+                        .call(move |db| {
+                            db.update_metadata(
+                                id,
+                                metadata.title.as_deref(),
+                                metadata.uploader.as_deref(),
+                                metadata.duration_seconds.map(|d| d as i64),
+                                metadata.thumbnail_url.as_deref(),
+                            )
+                        })
+                        .await;
                 }
 
                 // Update local download_info for the progress event
@@ -480,6 +818,7 @@ impl DownloadManager {
                             uploader: metadata.uploader,
                             duration_seconds: metadata.duration_seconds,
                             thumbnail_url: metadata.thumbnail_url,
+                            thumbnails: metadata.thumbnails,
                             webpage_url: Some(download_info.source_url.clone()),
                         },
                     })
@@ -489,94 +828,579 @@ impl DownloadManager {
             }
         }
 
-        // Create cancel channel
-        let (cancel_tx, _) = broadcast::channel::<()>(1);
-        self.active_downloads
+        let credentials = self.credentials_for(&download_info.source_url).await;
+
+        // Snapshot settings for this run; later changes apply to the next download.
+        let mut settings = self.load_settings().await;
+        if let Some(country) = self.geo_bypass_countries.read().await.get(&id) {
+            settings.network.geo_bypass_country = country.clone();
+        }
+
+        // Fail fast instead of letting yt-dlp run out of space halfway through
+        if let Some(message) = insufficient_space_message(
+            &download_info,
+            Path::new(&write_dir(&download_info, &self.config, &settings.general)),
+        ) {
+            self.fail_before_start(
+                id,
+                ErrorCode::OutputWriteFailed,
+                message,
+                vec![Action {
+                    kind: ActionKind::Retry,
+                    label: "Retry".to_string(),
+                }],
+            )
+            .await;
+            return Ok(false);
+        }
+
+        // A missing or malformed cookies file makes yt-dlp fail with a cryptic error
+        if let Some(path) = settings.privacy.active_cookies_path() {
+            let valid = validate_cookies_file(path);
+            let imported = valid.is_ok();
+            let _ = self
+                .db
+                .call(move |db| SettingsManager::new(db.conn()).set_cookies_imported(imported))
+                .await;
+            if let Err(message) = valid {
+                self.fail_before_start(
+                    id,
+                    ErrorCode::LoginRequired,
+                    message,
+                    vec![Action {
+                        kind: ActionKind::ImportCookies,
+                        label: "Import cookies from browser".to_string(),
+                    }],
+                )
+                .await;
+                return Ok(false);
+            }
+        }
+        // A download's own limit replaces the global one, so it takes no share of it
+        if download_rate_limit(&download_info).is_none()
+            && settings.network.global_rate_limit
+            && settings.network.rate_limit_bps > 0
+        {
+            let sharing = {
+                let mut sharers = self.rate_limit_sharers.write().await;
+                sharers.insert(id);
+                sharers.len()
+            };
+            settings.network.rate_limit_bps =
+                rate_limit_share(settings.network.rate_limit_bps, sharing);
+            log::info!(
+                "Shared rate limit: download {} gets {} B/s ({} sharing)",
+                id,
+                settings.network.rate_limit_bps,
+                sharing
+            );
+        }
+
+        self.pending.remove(id).await;
+        self.speed_samples
             .write()
             .await
-            .insert(id, cancel_tx.clone());
+            .insert(id, SpeedHistory::default());
 
         // Update status to Downloading
-        {
-            let mut db = self.db.lock().await;
-            let _ = db.set_status(id, DownloadStatus::Downloading, Some("Starting…"));
-        }
+        let _ = self
+            .db
+            .call(move |db| db.set_status(id, DownloadStatus::Downloading, Some("Starting…")))
+            .await;
 
         let _ = self
             .event_tx
-            .send(DownlinkEvent::DownloadStarted { id })
+            .send(started_event(&download_info, &self.config, &settings))
             .await;
 
+        // Merging into a container that can't hold the codecs fails late and cryptically
+        let preset_args = effective_preset_args(
+            &download_info.preset_id,
+            &settings.formats,
+            self.config.ffmpeg_path.is_some(),
+        );
+        // and normalizing audio that's only copied silently does nothing
+        let warnings = [
+            container_compatibility_warning(&preset_args, &settings.formats),
+            audio_normalize_warning(&preset_args, &settings.formats),
+        ];
+        for message in warnings.into_iter().flatten() {
+            log::warn!("Download {}: {}", id, message);
+            let _ = self
+                .event_tx
+                .send(DownlinkEvent::DownloadWarning { id, message })
+                .await;
+        }
+
         // Spawn the download task
         let config = self.config.clone();
         let db = self.db.clone();
         let event_tx = self.event_tx.clone();
         let active_downloads = self.active_downloads.clone();
-        let source_url = download_info.source_url.clone();
-        let preset_id = download_info.preset_id.clone();
-        let output_dir = download_info.output_dir.clone();
+        let rate_limit_sharers = self.rate_limit_sharers.clone();
+        let speed_samples = self.speed_samples.clone();
+        let session_bytes = self.session_bytes.clone();
+        let retry_attempts = self.retry_attempts.clone();
+        let pending = self.pending.clone();
+        let network_failures = self.network_failures.clone();
+        let retry_tx = self.retry_tx.clone();
 
-        tokio::spawn(async move {
+        self.spawn_supervised(id, async move {
+            // With download_to_temp, yt-dlp writes into a staging dir instead
+            let staging = staging_dir(&config, &settings.general, id);
+            if let Some(ref dir) = staging {
+                if let Err(e) = std::fs::create_dir_all(dir) {
+                    log::warn!("Failed to create staging dir {:?}: {}", dir, e);
+                }
+            }
+            let written_to = write_dir(&download_info, &config, &settings.general);
+
+            let mut outputs = OutputTracker::default();
             let result = execute_download(
-                id,
-                &source_url,
-                &preset_id,
-                &output_dir,
+                &download_info,
                 &config,
-                cancel_tx.subscribe(),
+                &settings,
+                credentials.as_ref(),
+                &mut outputs,
+                cancel_rx,
                 event_tx.clone(),
+                &speed_samples,
+                &db,
             )
             .await;
 
-            // Remove from active downloads
+            // Remove from active downloads and hand the slot to the next in line
             active_downloads.write().await.remove(&id);
+            rate_limit_sharers.write().await.remove(&id);
+            speed_samples.write().await.remove(&id);
+            start_next_pending(&pending, &retry_tx).await;
+
+            // Merge inputs and the like are deleted by yt-dlp; keep what's left on disk
+            let mut final_paths = if result.is_ok() {
+                existing_paths(outputs.final_paths(), &written_to)
+            } else {
+                Vec::new()
+            };
+
+            let result = match (result, staging) {
+                (Ok(final_path), Some(staging)) => {
+                    let dest = Path::new(&download_info.output_dir);
+                    match move_staged_outputs(&staging, dest) {
+                        Ok(()) => {
+                            for path in &mut final_paths {
+                                *path = relocate_staged_path(path, &staging, dest);
+                            }
+                            Ok(final_path.map(|p| relocate_staged_path(&p, &staging, dest)))
+                        }
+                        Err(e) => {
+                            final_paths.clear();
+                            Err(DownloadError::Failed {
+                                code: ErrorCode::OutputWriteFailed,
+                                message: format!(
+                                    "Downloaded, but couldn't move the files into {}: {}",
+                                    dest.display(),
+                                    e
+                                ),
+                                actions: vec![],
+                                raw_error: None,
+                            })
+                        }
+                    }
+                }
+                (Err(DownloadError::Canceled), Some(staging)) => {
+                    let _ = std::fs::remove_dir_all(&staging);
+                    Err(DownloadError::Canceled)
+                }
+                (result, _) => result,
+            };
+
+            // yt-dlp's duration/size are pre-download estimates; read the real ones from the file.
+            let probed = match (&result, &config.ffprobe_path) {
+                (Ok(Some(path)), Some(ffprobe)) => {
+                    let file = resolve_final_path(path, Some(&download_info.output_dir));
+                    probe_media(ffprobe, &file).await
+                }
+                _ => None,
+            };
+
+            if !matches!(result, Err(DownloadError::Failed { .. })) {
+                retry_attempts.write().await.remove(&id);
+            }
 
             // Update DB based on result
-            let mut db_guard = db.lock().await;
+            let notify_on_complete = settings.general.notify_on_complete;
             match result {
                 Ok(final_path) => {
-                    let _ = db_guard.set_status(id, DownloadStatus::Done, Some("Completed"));
+                    network_failures.clear();
+                    // Nothing was written, so there's no file to point at
+                    let archived = outputs.in_archive && final_path.is_none();
+                    let phase = if archived { ALREADY_IN_ARCHIVE } else { "Completed" };
+                    let notification = {
+                        let final_path = final_path.clone();
+                        let download_info = download_info.clone();
+                        db.call(move |db| {
+                            if let Some(ref path) = final_path {
+                                let _ = db.set_final_path(id, path);
+                            }
+                            let _ = db.set_final_paths(id, &final_paths);
+                            let _ = db.set_downloaded_files(id, &[]);
+                            if let Some(probed) = probed {
+                                if let Some(duration) = probed.duration_seconds {
+                                    let _ = db.set_duration(id, duration.round() as i64);
+                                }
+                                if let Some(size) = probed.size_bytes {
+                                    let _ = db.set_actual_filesize(id, size as i64);
+                                }
+                            }
+                            let _ = db.set_status(id, DownloadStatus::Done, Some(phase));
+                            record_usage(db, &session_bytes, id);
+
+                            Ok(completion_notification(
+                                db,
+                                &download_info,
+                                true,
+                                final_path.as_deref(),
+                                notify_on_complete,
+                            ))
+                        })
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::warn!("Failed to record completion of {}: {}", id, e);
+                            None
+                        })
+                    };
+
+                    if archived {
+                        let _ = event_tx
+                            .send(DownlinkEvent::DownloadWarning {
+                                id,
+                                message: "Skipped: already in the download archive. Clear the archive to download it again.".to_string(),
+                            })
+                            .await;
+                    }
                     let _ = event_tx
                         .send(DownlinkEvent::DownloadCompleted {
                             id,
                             final_path: final_path.unwrap_or_default(),
                         })
                         .await;
+                    if let Some(notification) = notification {
+                        let _ = event_tx.send(notification).await;
+                    }
                 }
                 Err(DownloadError::Canceled) => {
-                    let _ = db_guard.set_status(id, DownloadStatus::Canceled, Some("Canceled"));
+                    let notification = db
+                        .call(move |db| {
+                            db.set_status(id, DownloadStatus::Canceled, Some("Canceled"))?;
+                            Ok(completion_notification(
+                                db,
+                                &download_info,
+                                false,
+                                None,
+                                notify_on_complete,
+                            ))
+                        })
+                        .await
+                        .unwrap_or(None);
                     let _ = event_tx.send(DownlinkEvent::DownloadCanceled { id }).await;
+                    if let Some(notification) = notification {
+                        let _ = event_tx.send(notification).await;
+                    }
                 }
                 Err(DownloadError::Stopped) => {
-                    let _ = db_guard.set_status(id, DownloadStatus::Stopped, Some("Stopped"));
+                    let _ = db
+                        .call(move |db| db.set_status(id, DownloadStatus::Stopped, Some("Stopped")))
+                        .await;
                     let _ = event_tx.send(DownlinkEvent::DownloadStopped { id }).await;
                 }
                 Err(DownloadError::Failed {
                     code,
                     message,
                     actions,
+                    raw_error,
                 }) => {
-                    let _ = db_guard.set_status(id, DownloadStatus::Failed, Some("Failed"));
-                    let _ = event_tx
-                        .send(DownlinkEvent::DownloadFailed {
-                            id,
-                            error_code: code,
-                            user_message: message,
-                            actions,
-                        })
-                        .await;
-                }
-            }
-        });
+                    {
+                        let message = message.clone();
+                        let raw_error = raw_error.clone();
+                        let destinations = outputs.destinations.clone();
+                        let code = code.as_str();
+                        let _ = db
+                            .call(move |db| {
+                                db.set_error(id, Some(code), Some(&message), raw_error.as_deref())?;
+                                db.set_downloaded_files(id, &destinations)
+                            })
+                            .await;
+                    }
 
-        Ok(())
+                    // Several downloads failing on the network close together means the
+                    // connection is gone: hold them until it's back instead of retrying
+                    if code == ErrorCode::Network
+                        && (network_failures.record(id, std::time::Instant::now())
+                            || pending.is_offline())
+                    {
+                        if !pending.set_offline(true) {
+                            log::warn!("Repeated network failures, holding downloads until the network is back");
+                            let _ = event_tx
+                                .send(DownlinkEvent::ConnectivityChanged { online: false })
+                                .await;
+                            let earlier = network_failures
+                                .recent()
+                                .into_iter()
+                                .filter(|earlier| *earlier != id)
+                                .collect();
+                            hold_earlier_network_failures(&db, &pending, &event_tx, earlier)
+                                .await;
+                        }
+                        log::info!("Download {} failed offline, will resume when the network is back", id);
+                        retry_attempts.write().await.remove(&id);
+                        pending.push_back(id).await;
+                        let _ = db
+                            .call(move |db| {
+                                db.set_status(id, DownloadStatus::Queued, Some(WAITING_FOR_NETWORK))
+                            })
+                            .await;
+                        let _ = event_tx.send(waiting_for_network(id, Some(message))).await;
+                        return;
+                    }
+
+                    let attempt = {
+                        let mut attempts = retry_attempts.write().await;
+                        let attempt = attempts.entry(id).or_insert(0);
+                        *attempt += 1;
+                        *attempt
+                    };
+
+                    let delay = retry_delay(
+                        &code,
+                        attempt,
+                        config.max_auto_retries,
+                        config.retry_base_delay,
+                    );
+                    if let Some(delay) = delay {
+                        let phase = format!(
+                            "Retrying in {}s (attempt {}/{})",
+                            delay.as_secs(),
+                            attempt,
+                            config.max_auto_retries
+                        );
+                        log::info!("Download {} failed ({:?}): {}", id, code, phase);
+                        {
+                            let phase = phase.clone();
+                            let _ = db
+                                .call(move |db| {
+                                    db.set_status(id, DownloadStatus::Queued, Some(&phase))
+                                })
+                                .await;
+                        }
+
+                        let _ = event_tx
+                            .send(DownlinkEvent::DownloadProgress {
+                                id,
+                                status: events::DownloadStatus::Queued,
+                                progress: Progress {
+                                    percent: None,
+                                    bytes_downloaded: None,
+                                    bytes_total: None,
+                                    speed_bps: None,
+                                    eta_seconds: None,
+                                    phase: Some(Phase {
+                                        name: phase,
+                                        detail: Some(message),
+                                    }),
+                                },
+                            })
+                            .await;
+
+                        // `start` skips it if the user canceled or removed it meanwhile
+                        tokio::time::sleep(delay).await;
+                        log::info!("Auto-retrying download {}", id);
+                        let _ = retry_tx.send(id);
+                    } else {
+                        retry_attempts.write().await.remove(&id);
+                        let notification = db
+                            .call(move |db| {
+                                Ok(completion_notification(
+                                    db,
+                                    &download_info,
+                                    false,
+                                    None,
+                                    notify_on_complete,
+                                ))
+                            })
+                            .await
+                            .unwrap_or(None);
+                        let _ = event_tx
+                            .send(DownlinkEvent::DownloadFailed {
+                                id,
+                                error_code: code,
+                                user_message: message,
+                                actions,
+                                raw_error,
+                            })
+                            .await;
+                        if let Some(notification) = notification {
+                            let _ = event_tx.send(notification).await;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(true)
+    }
+
+    /// The exact yt-dlp invocation `start` would run for a download, without running it.
+    pub async fn preview_command(&self, id: Uuid) -> Result<CommandPreview> {
+        let download = self
+            .db
+            .call(move |db| db.get_download(id))
+            .await?
+            .ok_or(RequestError::NotFound)?;
+        let settings = self.load_settings().await;
+        let credentials_config = self
+            .credentials_for(&download.source_url)
+            .await
+            .map(|_| credentials_config_path(&self.config.tmp_dir, id));
+        let args = build_download_args(
+            &download,
+            &self.config,
+            &settings,
+            credentials_config.as_deref(),
+        );
+
+        Ok(CommandPreview {
+            program: self.config.yt_dlp_path.to_string_lossy().to_string(),
+            args: redact_args(&args),
+        })
+    }
+
+    /// Mark a download failed without running yt-dlp and tell the UI why.
+    async fn fail_before_start(
+        &self,
+        id: Uuid,
+        code: ErrorCode,
+        message: String,
+        actions: Vec<Action>,
+    ) {
+        log::warn!("Not starting download {}: {}", id, message);
+        self.release_slot(id).await;
+        {
+            let message = message.clone();
+            let code = code.as_str();
+            let _ = self
+                .db
+                .call(move |db| db.set_error(id, Some(code), Some(&message), None))
+                .await;
+        }
+        let _ = self
+            .event_tx
+            .send(DownlinkEvent::DownloadFailed {
+                id,
+                error_code: code,
+                user_message: message,
+                actions,
+                raw_error: None,
+            })
+            .await;
+        start_next_pending(&self.pending, &self.retry_tx).await;
+    }
+
+    /// Run a download's task. If it panics, the download is failed with
+    /// [`ErrorCode::Unknown`] and its slot freed, instead of staying stuck as running.
+    fn spawn_supervised<F>(&self, id: Uuid, task: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task);
+        let db = self.db.clone();
+        let event_tx = self.event_tx.clone();
+        let active_downloads = self.active_downloads.clone();
+        let rate_limit_sharers = self.rate_limit_sharers.clone();
+        let speed_samples = self.speed_samples.clone();
+        let pending = self.pending.clone();
+        let retry_tx = self.retry_tx.clone();
+
+        tokio::spawn(async move {
+            let panic = match handle.await {
+                Err(e) if e.is_panic() => e.into_panic(),
+                _ => return,
+            };
+            let detail = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            log::error!("Download task for {} panicked: {}", id, detail);
+
+            active_downloads.write().await.remove(&id);
+            rate_limit_sharers.write().await.remove(&id);
+            speed_samples.write().await.remove(&id);
+
+            let message = "Downlink ran into an internal error. The logs have the details.";
+            {
+                let detail = detail.clone();
+                let code = ErrorCode::Unknown.as_str();
+                let _ = db
+                    .call(move |db| db.set_error(id, Some(code), Some(message), Some(&detail)))
+                    .await;
+            }
+            let _ = event_tx
+                .send(DownlinkEvent::DownloadFailed {
+                    id,
+                    error_code: ErrorCode::Unknown,
+                    user_message: message.to_string(),
+                    actions: vec![Action {
+                        kind: ActionKind::OpenLogs,
+                        label: "View logs".to_string(),
+                    }],
+                    raw_error: Some(detail),
+                })
+                .await;
+            start_next_pending(&pending, &retry_tx).await;
+        });
+    }
+
+    /// Stored site credentials for a URL, if any.
+    async fn credentials_for(&self, url: &str) -> Option<SiteCredentials> {
+        let url = url.to_string();
+        self.db
+            .call(move |db| db.credentials_for_url(&url))
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load site credentials: {}", e);
+                None
+            })
+    }
+
+    /// Load the current user settings, falling back to defaults if they can't be read.
+    async fn load_settings(&self) -> UserSettings {
+        self.db
+            .call(|db| SettingsManager::new(db.conn()).get_user_settings())
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to load user settings, using defaults: {}", e);
+                UserSettings::default()
+            })
     }
 
-    /// Stop a download (resumable).
+    /// Stop a download (resumable). A download still waiting for a slot leaves the
+    /// pending queue and is marked stopped.
     pub async fn stop(&self, id: Uuid) -> Result<()> {
         if let Some(cancel_tx) = self.active_downloads.read().await.get(&id) {
             let _ = cancel_tx.send(());
+            self.stop_requested.write().await.insert(id);
             log::info!("Sent stop signal to download {}", id);
+            return Ok(());
+        }
+
+        if self.pending.remove(id).await {
+            self.db
+                .call(move |db| db.set_status(id, DownloadStatus::Stopped, Some("Stopped")))
+                .await?;
+            let _ = self
+                .event_tx
+                .send(DownlinkEvent::DownloadStopped { id })
+                .await;
         }
         Ok(())
     }
@@ -587,39 +1411,560 @@ impl DownloadManager {
         self.stop(id).await?;
 
         // Update status to canceled
-        let mut db = self.db.lock().await;
-        let _ = db.set_status(id, DownloadStatus::Canceled, Some("Canceled"));
+        let _ = self
+            .db
+            .call(move |db| db.set_status(id, DownloadStatus::Canceled, Some("Canceled")))
+            .await;
         Ok(())
     }
 
+    /// Retry only the post-processing of a download whose merge/convert step failed.
+    ///
+    /// yt-dlp skips formats whose files are already on disk, so re-running with the
+    /// same arguments goes straight to post-processing. Fails if the files from the
+    /// failed run are gone; use `retry` then.
+    pub async fn retry_postprocessing(&self, id: Uuid) -> Result<()> {
+        self.db
+            .call(move |db| {
+                let download = db.get_download(id)?.ok_or(RequestError::NotFound)?;
+                if download.status != DownloadStatus::Failed {
+                    return Err(RequestError::Invalid(
+                        "Download is not in a failed state".to_string(),
+                    )
+                    .into());
+                }
+
+                let files: Vec<PathBuf> = download
+                    .downloaded_files
+                    .iter()
+                    .map(|f| resolve_final_path(f, Some(&download.output_dir)))
+                    .collect();
+                postprocessing_retry_possible(download.error_code.as_deref(), &files)
+                    .map_err(RequestError::Invalid)?;
+
+                db.set_status(id, DownloadStatus::Queued, Some("Retrying post-processing"))
+            })
+            .await?;
+        self.start(id).await
+    }
+
+    /// Fetch a download's metadata again (e.g. a changed title or an expired
+    /// thumbnail link), store it and emit `MetadataReady`. Not while it's running.
+    pub async fn refresh_metadata(&self, id: Uuid) -> Result<MediaInfo> {
+        let running = self.is_active(id).await || self.pending.contains(id).await;
+        let row = self
+            .db
+            .call(move |db| db.get_download(id))
+            .await?
+            .ok_or(RequestError::NotFound)?;
+        if running
+            || matches!(
+                row.status,
+                DownloadStatus::Fetching
+                    | DownloadStatus::Downloading
+                    | DownloadStatus::PostProcessing
+            )
+        {
+            return Err(RequestError::Invalid(
+                "Can't refresh metadata while the download is running".to_string(),
+            )
+            .into());
+        }
+
+        let metadata = fetch_metadata_for_url(&self.config.yt_dlp_path, &row.source_url)
+            .await
+            .ok_or_else(|| anyhow!("yt-dlp couldn't fetch metadata for {}", row.source_url))?;
+        {
+            let metadata = metadata.clone();
+            self.db
+                .call(move |db| {
+                    db.update_metadata(
+                        id,
+                        metadata.title.as_deref(),
+                        metadata.uploader.as_deref(),
+                        metadata.duration_seconds.map(|d| d as i64),
+                        metadata.thumbnail_url.as_deref(),
+                    )
+                })
+                .await?;
+        }
+
+        let info = MediaInfo {
+            title: metadata.title,
+            uploader: metadata.uploader,
+            duration_seconds: metadata.duration_seconds,
+            thumbnail_url: metadata.thumbnail_url,
+            thumbnails: metadata.thumbnails,
+            webpage_url: Some(row.source_url),
+        };
+        let _ = self
+            .event_tx
+            .send(DownlinkEvent::MetadataReady {
+                id,
+                info: info.clone(),
+            })
+            .await;
+        Ok(info)
+    }
+
     /// Retry a failed download.
     pub async fn retry(&self, id: Uuid) -> Result<()> {
+        // A manual retry starts a fresh round of automatic retries
+        self.retry_attempts.write().await.remove(&id);
+
         // Reset status to Queued and start
-        {
-            let mut db = self.db.lock().await;
-            let _ = db.set_status(id, DownloadStatus::Queued, Some("Queued"));
-        }
+        let _ = self
+            .db
+            .call(move |db| db.set_status(id, DownloadStatus::Queued, Some("Queued")))
+            .await;
         self.start(id).await
     }
 
+    /// Retry a download as if from `country` (a two-letter ISO code), for
+    /// geo-restricted content.
+    pub async fn retry_with_geo_bypass(&self, id: Uuid, country: &str) -> Result<()> {
+        let country = crate::network::validate_country_code(country)
+            .map_err(RequestError::Invalid)?
+            .ok_or_else(|| RequestError::Invalid("No country given for geo-bypass".to_string()))?;
+        log::info!("Retrying download {} with geo-bypass from {}", id, country);
+        self.geo_bypass_countries.write().await.insert(id, country);
+        self.retry(id).await
+    }
+
     /// Check if a download is currently active.
     pub async fn is_active(&self, id: Uuid) -> bool {
         self.active_downloads.read().await.contains_key(&id)
     }
 
+    /// Recent speed samples for an active download, oldest first. Empty once it finishes.
+    pub async fn speed_samples(&self, id: Uuid) -> Vec<SpeedSample> {
+        self.speed_samples
+            .read()
+            .await
+            .get(&id)
+            .map(SpeedHistory::samples)
+            .unwrap_or_default()
+    }
+
+    /// Bytes of downloads completed since the app started.
+    pub fn session_bytes(&self) -> u64 {
+        self.session_bytes.load(Ordering::Relaxed)
+    }
+
     /// Get count of active downloads.
     pub async fn active_count(&self) -> usize {
         self.active_downloads.read().await.len()
     }
 
-    /// Shutdown the download manager - stops all active downloads.
-    pub async fn shutdown(&self) -> Result<()> {
+    /// Whether `id` is running, waiting for a slot, or neither.
+    pub async fn queue_state(&self, id: Uuid) -> QueueState {
+        if self.active_downloads.read().await.contains_key(&id) {
+            QueueState::Active
+        } else if self.pending.contains(id).await {
+            QueueState::Pending
+        } else {
+            QueueState::NotManaged
+        }
+    }
+
+    /// Downloads that are running or queued to start.
+    pub async fn managed_ids(&self) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = self.active_downloads.read().await.keys().cloned().collect();
+        ids.extend(self.pending.ids.read().await.iter().cloned());
+        ids
+    }
+
+    /// Slot usage from the manager's own bookkeeping, without touching the database.
+    pub async fn status(&self) -> ManagerStatus {
+        ManagerStatus {
+            active: self.active_downloads.read().await.len(),
+            max_concurrent: self.config.max_concurrent,
+            pending: self.pending.len().await,
+            paused: self.pending.is_paused(),
+            online: !self.pending.is_offline(),
+        }
+    }
+
+    /// Stop the active downloads, resumably, and hold the queue: nothing starts
+    /// until [`resume_all`](Self::resume_all). The stopped downloads go back to the
+    /// front of the queue so they resume first.
+    pub async fn pause_all(&self) -> Result<()> {
+        self.pending.set_paused(true);
         let ids: Vec<Uuid> = self.active_downloads.read().await.keys().cloned().collect();
+        {
+            let mut pending = self.pending.ids.write().await;
+            for id in &ids {
+                if !pending.contains(id) {
+                    pending.push_front(*id);
+                }
+            }
+        }
         for id in ids {
             self.stop(id).await?;
         }
         Ok(())
     }
+
+    /// Let the queue run again after [`pause_all`](Self::pause_all), filling the
+    /// free slots from the front of the queue.
+    pub async fn resume_all(&self) -> Result<()> {
+        self.pending.set_paused(false);
+        self.start_waiting().await;
+        Ok(())
+    }
+
+    /// Keep downloads from starting while yt-dlp is replaced, if none is running.
+    /// Returns false, holding nothing, when downloads are active. Checked under the
+    /// lock `reserve_slot` takes, so no download slips in between.
+    pub async fn hold_starts_if_idle(&self) -> bool {
+        let active = self.active_downloads.read().await;
+        if !active.is_empty() {
+            return false;
+        }
+        self.pending.set_held(true);
+        true
+    }
+
+    /// Undo `hold_starts_if_idle` and start the downloads that queued meanwhile.
+    pub async fn release_starts(&self) {
+        self.pending.set_held(false);
+        self.start_waiting().await;
+    }
+
+    /// Start queued downloads from the front, up to the number of free slots.
+    async fn start_waiting(&self) {
+        let free = self
+            .config
+            .max_concurrent
+            .saturating_sub(self.active_count().await);
+        for _ in 0..free {
+            let Some(id) = self.pending.next().await else {
+                break;
+            };
+            if let Err(e) = self.start(id).await {
+                log::warn!("Failed to resume download {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Tell the manager whether the network is up, from the connectivity probe or the
+    /// UI's online/offline events. While offline nothing starts and network failures
+    /// wait instead of retrying; coming back online starts the waiting downloads.
+    pub async fn set_online(&self, online: bool) {
+        let was_offline = self.pending.set_offline(!online);
+        if online {
+            self.network_failures.clear();
+        }
+        if was_offline != online {
+            return;
+        }
+
+        log::info!("Network is {}", if online { "back" } else { "down" });
+        let _ = self
+            .event_tx
+            .send(DownlinkEvent::ConnectivityChanged { online })
+            .await;
+        if online {
+            self.start_waiting().await;
+        }
+    }
+
+    /// Stop everything: the active downloads and every one waiting for a slot.
+    /// Unlike [`pause_all`](Self::pause_all), nothing is left queued to start later.
+    pub async fn stop_all(&self) -> Result<()> {
+        // Empty the queue first so finishing downloads don't hand their slot on
+        let pending: Vec<Uuid> = self.pending.ids.read().await.iter().copied().collect();
+        for id in pending {
+            self.stop(id).await?;
+        }
+        let active: Vec<Uuid> = self.active_downloads.read().await.keys().cloned().collect();
+        for id in active {
+            self.stop(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Shutdown the download manager - stops all active downloads.
+    ///
+    /// Waits briefly for each yt-dlp process to exit so `.part` files are left
+    /// resumable, then makes sure every download it stopped is persisted as `Stopped`
+    /// with the [`INTERRUPTED_PHASE`].
+    pub async fn shutdown(&self) -> Result<()> {
+        self.shutdown_within(SHUTDOWN_GRACE).await
+    }
+
+    async fn shutdown_within(&self, grace: Duration) -> Result<()> {
+        let ids: Vec<Uuid> = self.active_downloads.read().await.keys().cloned().collect();
+        // Only what was running is interrupted; the user's own stops stay stopped
+        let user_stopped = self.stop_requested.read().await.clone();
+        let candidates: Vec<Uuid> = ids
+            .iter()
+            .copied()
+            .filter(|id| !user_stopped.contains(id))
+            .collect();
+        let running = self
+            .db
+            .call(move |db| {
+                let mut running = Vec::new();
+                for id in candidates {
+                    if db
+                        .get_download(id)?
+                        .is_some_and(|row| row.status.is_active())
+                    {
+                        running.push(id);
+                    }
+                }
+                Ok(running)
+            })
+            .await?;
+        for id in &ids {
+            self.stop(*id).await?;
+        }
+
+        let deadline = tokio::time::Instant::now() + grace;
+        while self.active_count().await > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // A task that hasn't finished yet (or hasn't written its result) would leave
+        // the row looking active after the process exits. Either way the row is
+        // marked interrupted, so `auto_resume_on_launch` picks it up next time.
+        self.db
+            .call(move |db| {
+                for id in running {
+                    if db.get_download(id)?.is_some_and(|row| {
+                        row.status.is_active() || row.status == DownloadStatus::Stopped
+                    }) {
+                        db.set_status(id, DownloadStatus::Stopped, Some(INTERRUPTED_PHASE))?;
+                    }
+                }
+                Ok(())
+            })
+            .await
+    }
+}
+
+/// A request the manager refused because of the download it names, as opposed to
+/// a failure while carrying it out.
+#[derive(Debug, thiserror::Error)]
+pub enum RequestError {
+    #[error("Download not found")]
+    NotFound,
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// Whether a failed download can re-run just its post-processing.
+///
+/// Only for post-processing failures, and only while every file the failed run
+/// downloaded is still on disk and complete; otherwise yt-dlp would download again
+/// anyway. The error is a user-facing reason.
+fn postprocessing_retry_possible(
+    error_code: Option<&str>,
+    files: &[PathBuf],
+) -> Result<(), String> {
+    if error_code != Some(ErrorCode::PostProcessingFailed.as_str()) {
+        return Err(
+            "Only downloads that failed during post-processing can retry it alone".to_string(),
+        );
+    }
+    if files.is_empty() {
+        return Err(
+            "No downloaded files were recorded for this download; retry it instead".to_string(),
+        );
+    }
+    if let Some(missing) = files.iter().find(|f| !f.is_file()) {
+        return Err(format!(
+            "{} is no longer on disk; retry the download instead",
+            missing.display()
+        ));
+    }
+    if files
+        .iter()
+        .any(|f| f.extension().is_some_and(|e| e == "part"))
+    {
+        return Err("The download didn't finish; retry it instead".to_string());
+    }
+    Ok(())
+}
+
+/// How long `shutdown` waits for stopped downloads to wind down on their own.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Which completion notification, if any, a finished download should produce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CompletionNotice {
+    Skip,
+    Single,
+    /// Last child of a playlist finished; summarize the whole playlist once.
+    PlaylistSummary {
+        completed: usize,
+        failed: usize,
+    },
+}
+
+/// Progress of a playlist's children at the moment one of them completes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct PlaylistTally {
+    unfinished: usize,
+    completed: usize,
+    failed: usize,
+}
+
+/// Decide whether to notify. Playlist children stay silent until the last one finishes.
+fn completion_notice(
+    notify_on_complete: bool,
+    playlist: Option<PlaylistTally>,
+) -> CompletionNotice {
+    if !notify_on_complete {
+        return CompletionNotice::Skip;
+    }
+
+    match playlist {
+        None => CompletionNotice::Single,
+        Some(tally) if tally.unfinished > 0 => CompletionNotice::Skip,
+        Some(tally) => CompletionNotice::PlaylistSummary {
+            completed: tally.completed,
+            failed: tally.failed,
+        },
+    }
+}
+
+/// Count a playlist's children by outcome.
+fn playlist_tally(children: &[DownloadRow]) -> PlaylistTally {
+    children
+        .iter()
+        .fold(PlaylistTally::default(), |mut tally, child| {
+            match child.status {
+                DownloadStatus::Done => tally.completed += 1,
+                DownloadStatus::Failed => tally.failed += 1,
+                DownloadStatus::Canceled => {}
+                _ => tally.unfinished += 1,
+            }
+            tally
+        })
+}
+
+/// Build the `NotifyCompleted` event for a download that just reached a final state,
+/// if one is due. A failed or canceled download (`completed` false) only ever closes
+/// its playlist's summary.
+fn completion_notification(
+    db: &mut Db,
+    download: &DownloadRow,
+    completed: bool,
+    final_path: Option<&str>,
+    notify_on_complete: bool,
+) -> Option<DownlinkEvent> {
+    let tally = match download.parent_id {
+        Some(parent_id) if notify_on_complete => match db.get_playlist_items(parent_id) {
+            Ok(children) => Some(playlist_tally(&children)),
+            Err(e) => {
+                log::warn!(
+                    "Failed to load playlist {} for notification: {}",
+                    parent_id,
+                    e
+                );
+                return None;
+            }
+        },
+        _ => None,
+    };
+
+    match completion_notice(notify_on_complete, tally) {
+        CompletionNotice::Skip => None,
+        CompletionNotice::Single if !completed => None,
+        CompletionNotice::Single => Some(DownlinkEvent::NotifyCompleted {
+            id: download.id,
+            title: "Download complete".to_string(),
+            body: download
+                .title
+                .clone()
+                .unwrap_or_else(|| download.source_url.clone()),
+            final_path: final_path.map(str::to_string),
+        }),
+        CompletionNotice::PlaylistSummary { completed, failed } => {
+            let parent_id = download.parent_id?;
+            let name = db
+                .get_download(parent_id)
+                .ok()
+                .flatten()
+                .and_then(|p| p.title)
+                .unwrap_or_else(|| "Playlist".to_string());
+            let mut body = format!(
+                "{}: {} item{} downloaded",
+                name,
+                completed,
+                if completed == 1 { "" } else { "s" }
+            );
+            if failed > 0 {
+                body.push_str(&format!(", {} failed", failed));
+            }
+
+            Some(DownlinkEvent::NotifyCompleted {
+                id: parent_id,
+                title: "Playlist complete".to_string(),
+                body,
+                final_path: None,
+            })
+        }
+    }
+}
+
+/// User-facing message if `dir`, where yt-dlp writes this download (the staging dir
+/// with `download_to_temp`), can't fit what's left of it.
+///
+/// Only checks when the size is known (e.g. resuming a stopped download); a failed
+/// disk query never blocks the download.
+fn insufficient_space_message(download: &DownloadRow, dir: &Path) -> Option<String> {
+    let total = u64::try_from(download.bytes_total?).ok()?;
+    let downloaded = download
+        .bytes_downloaded
+        .and_then(|b| u64::try_from(b).ok())
+        .unwrap_or(0);
+    let remaining = total.saturating_sub(downloaded);
+
+    let space = disk_space::disk_space(dir).ok()?;
+    if disk_space::has_enough_space(remaining, space.available_bytes) {
+        return None;
+    }
+
+    Some(format!(
+        "Not enough disk space in {}: about {} MB needed, {} MB free. Free up space or choose another folder, then retry.",
+        dir.display(),
+        remaining.div_ceil(1024 * 1024),
+        space.available_bytes / (1024 * 1024)
+    ))
+}
+
+/// Fixed wait before retrying a rate-limited (HTTP 429) download.
+pub const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Upper bound for the exponential network backoff.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Delay before automatic retry number `attempt` (1-based), or None if the failure
+/// shouldn't be retried automatically.
+///
+/// Network errors back off exponentially from `base`. Rate limits wait a fixed
+/// cooldown instead, since retrying sooner tends to extend the block.
+fn retry_delay(
+    code: &ErrorCode,
+    attempt: u32,
+    max_attempts: u32,
+    base: Duration,
+) -> Option<Duration> {
+    if attempt == 0 || attempt > max_attempts {
+        return None;
+    }
+
+    match code {
+        ErrorCode::RateLimited => Some(RATE_LIMIT_COOLDOWN),
+        ErrorCode::Network => {
+            let factor = 2u32.saturating_pow(attempt - 1);
+            Some(base.saturating_mul(factor).min(MAX_RETRY_DELAY))
+        }
+        _ => None,
+    }
 }
 
 /// Error types for download execution.
@@ -631,525 +1976,4958 @@ enum DownloadError {
         code: ErrorCode,
         message: String,
         actions: Vec<Action>,
+        /// Tail of yt-dlp's stderr, kept for support/diagnostics.
+        raw_error: Option<String>,
     },
 }
 
-/// Execute a single download.
-async fn execute_download(
-    id: Uuid,
-    url: &str,
-    preset_id: &str,
-    output_dir: &str,
-    config: &DownloadConfig,
-    mut cancel_rx: broadcast::Receiver<()>,
-    event_tx: mpsc::Sender<DownlinkEvent>,
-) -> Result<Option<String>, DownloadError> {
-    let preset =
-        Preset::get_by_id(preset_id).unwrap_or_else(|| Preset::builtin_presets()[0].clone());
+/// Tags of yt-dlp's post-processor output lines (`[ExtractAudio] Destination: …`).
+/// Any of them means ffmpeg or another step may now run silently for a long time.
+const POSTPROCESSOR_LINE: &str = r"^\[(?:Merger|ExtractAudio|VideoRemuxer|VideoConvertor|SplitChapters|ModifyChapters|SponsorBlock|Metadata|EmbedSubtitle|EmbedThumbnail|ThumbnailsConvertor|SubtitlesConvertor|Fixup\w*|MoveFiles|CopyStream|ffmpeg|XAttrMetadata|ConcatPlaylist|Exec)\]|Merging formats";
 
-    // Build yt-dlp command
-    let mut args = vec![
-        "--newline".to_string(),
-        "--no-warnings".to_string(),
-        "--no-call-home".to_string(),
-        "--progress".to_string(),
-        "--progress-template".to_string(),
-        "download:[downlink] %(progress._percent_str)s %(progress._speed_str)s %(progress._eta_str)s %(progress._total_bytes_str)s".to_string(),
-        "-o".to_string(),
-        format!("{}/%(title)s [%(id)s].%(ext)s", output_dir),
-    ];
+/// What yt-dlp prints before a `--sleep-*` pause (`[download] Sleeping 5.00 seconds ...`).
+const SLEEP_LINE: &str = r"^\[[^\]]+\] Sleeping [\d.]+ seconds";
 
-    // Add preset args
-    args.extend(preset.yt_dlp_args.clone());
+/// Watchdog that fires when yt-dlp stops producing output mid-transfer.
+///
+/// Starts disarmed and is armed by the first download progress line, so extraction
+/// isn't watched. Reset on every stdout line; disarmed while yt-dlp sleeps between
+/// requests, and once a post-processor starts, since ffmpeg steps (merging, audio
+/// extraction, remuxing, loudnorm) can legitimately run for a long time without
+/// printing anything. Re-armed when download progress resumes.
+struct StallWatchdog {
+    timeout: Duration,
+    deadline: tokio::time::Instant,
+    armed: bool,
+}
 
-    // Add ffmpeg location if configured
-    if let Some(ref ffmpeg_path) = config.ffmpeg_path {
-        args.push("--ffmpeg-location".to_string());
-        args.push(ffmpeg_path.to_string_lossy().to_string());
+impl StallWatchdog {
+    /// A disarmed watchdog; `rearm` starts watching.
+    fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            deadline: tokio::time::Instant::now() + timeout,
+            armed: false,
+        }
     }
 
-    // Add URL last
-    args.push(url.to_string());
-
-    log::info!("Starting download {} with args: {:?}", id, args);
+    /// Push the deadline out by a full timeout from now.
+    fn reset(&mut self) {
+        self.deadline = tokio::time::Instant::now() + self.timeout;
+    }
 
-    let mut cmd = Command::new(&config.yt_dlp_path);
-    cmd.args(&args)
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+    /// Stop watching; `stalled` won't resolve until `rearm`.
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
 
-    // Hide console window on Windows
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
+    /// Watch again, with a full timeout from now.
+    fn rearm(&mut self) {
+        self.armed = true;
+        self.reset();
+    }
 
-    let mut child = cmd.spawn().map_err(|e| DownloadError::Failed {
-        code: ErrorCode::ToolMissing,
-        message: format!("Failed to start yt-dlp: {}", e),
-        actions: vec![],
-    })?;
+    /// Resolves once the deadline passes while armed.
+    async fn stalled(&self) {
+        if self.armed {
+            tokio::time::sleep_until(self.deadline).await;
+        } else {
+            std::future::pending::<()>().await;
+        }
+    }
+}
 
-    let stdout = child.stdout.take().ok_or_else(|| DownloadError::Failed {
-        code: ErrorCode::Unknown,
-        message: "Failed to capture stdout".to_string(),
-        actions: vec![],
+/// Validate a `--download-sections` time-range spec such as `*00:10:00-00:15:00`.
+///
+/// Accepts `*START-END` where each side is `SS`, `MM:SS` or `HH:MM:SS` (fractional
+/// seconds allowed) and END may also be `inf`. START must come before END.
+pub fn validate_sections(spec: &str) -> Result<(), String> {
+    let range = spec.trim().strip_prefix('*').ok_or_else(|| {
+        format!("Section \"{spec}\" must start with '*', e.g. *00:10:00-00:15:00")
     })?;
 
-    let stderr = child.stderr.take().ok_or_else(|| DownloadError::Failed {
-        code: ErrorCode::Unknown,
-        message: "Failed to capture stderr".to_string(),
-        actions: vec![],
-    })?;
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("Section \"{spec}\" must be a START-END range"))?;
 
-    let mut stdout_reader = BufReader::new(stdout).lines();
-    let mut stderr_reader = BufReader::new(stderr).lines();
+    let start = parse_section_timestamp(start)
+        .ok_or_else(|| format!("Invalid section start time \"{start}\""))?;
+    let end = if end.trim() == "inf" {
+        f64::INFINITY
+    } else {
+        parse_section_timestamp(end).ok_or_else(|| format!("Invalid section end time \"{end}\""))?
+    };
 
-    let mut stderr_lines: Vec<String> = Vec::new();
-    let mut final_path: Option<String> = None;
-    let mut last_percent: f64 = 0.0;
+    if start >= end {
+        return Err(format!("Section \"{spec}\" ends before it starts"));
+    }
 
-    // Progress regex for our custom template: [downlink] 50.5% 1.5MiB/s 00:30 100MiB
-    let progress_re = Regex::new(r"\[downlink\]\s+(\S+)\s+(\S+)\s+(\S+)\s+(\S+)").ok();
-    // Fallback: standard yt-dlp progress line: [download]  50.5% of 100.00MiB at 1.50MiB/s ETA 00:30
-    let fallback_progress_re =
-        Regex::new(r"\[download\]\s+(\d+\.?\d*)%\s+of\s+(\S+)\s+at\s+(\S+)\s+ETA\s+(\S+)").ok();
-    // Also match: [download]  50.5% of ~100.00MiB at 1.50MiB/s ETA 00:30
-    let fallback_progress_re2 = Regex::new(r"\[download\]\s+(\d+\.?\d*)%").ok();
-    let merge_re = Regex::new(r"\[Merger\]|Merging formats|\[ffmpeg\]").ok();
-    let dest_re = Regex::new(r#"\[download\] Destination: (.+)"#).ok();
-    let already_re = Regex::new(r#"\[download\] (.+) has already been downloaded"#).ok();
-    let finished_re = Regex::new(r#"\[download\] 100%"#).ok();
+    Ok(())
+}
 
-    loop {
-        tokio::select! {
-            _ = cancel_rx.recv() => {
-                log::info!("Download {} received cancel signal", id);
-                let _ = child.kill().await;
-                return Err(DownloadError::Stopped);
-            }
-            line = stdout_reader.next_line() => {
-                match line {
-                    Ok(Some(l)) => {
-                        log::info!("yt-dlp stdout: {}", l);
+/// Parse `SS`, `MM:SS` or `HH:MM:SS` (with optional fractional seconds) into seconds.
+fn parse_section_timestamp(s: &str) -> Option<f64> {
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.is_empty() || parts.len() > 3 {
+        return None;
+    }
 
-                        // Try to parse progress from various formats
-                        let mut parsed: Option<ParsedProgress> = None;
+    let (seconds, rest) = parts.split_last()?;
+    let mut total: f64 = seconds
+        .parse()
+        .ok()
+        .filter(|v: &f64| v.is_finite() && *v >= 0.0)?;
+    if !rest.is_empty() && total >= 60.0 {
+        return None;
+    }
 
-                        // Try our custom template first
-                        if let Some(ref re) = progress_re {
-                            if let Some(caps) = re.captures(&l) {
-                                parsed = Some(parse_progress_line(&caps));
-                            }
-                        }
+    for (i, unit) in rest.iter().rev().enumerate() {
+        let value: u64 = unit.parse().ok()?;
+        if i == 0 && rest.len() > 1 && value >= 60 {
+            return None;
+        }
+        total += value as f64 * 60f64.powi(i as i32 + 1);
+    }
 
-                        // Fallback to standard yt-dlp progress format
-                        if parsed.is_none() {
-                            if let Some(ref re) = fallback_progress_re {
-                                if let Some(caps) = re.captures(&l) {
-                                    let percent = caps.get(1).and_then(|m| m.as_str().parse::<f64>().ok());
-                                    let total = caps.get(2).map(|m| m.as_str().to_string());
-                                    let speed = caps.get(3).and_then(|m| parse_speed(m.as_str()));
-                                    let eta = caps.get(4).and_then(|m| parse_eta(m.as_str()));
-                                    parsed = Some(ParsedProgress {
-                                        percent,
-                                        bytes_total: total.as_deref().and_then(parse_bytes),
-                                        bytes_downloaded: None,
-                                        speed_bps: speed,
-                                        eta_seconds: eta,
-                                        phase: Some("Downloading".to_string()),
-                                    });
-                                }
-                            }
-                        }
+    Some(total)
+}
 
-                        // Simple fallback - just get percent
-                        if parsed.is_none() {
-                            if let Some(ref re) = fallback_progress_re2 {
-                                if let Some(caps) = re.captures(&l) {
-                                    let percent = caps.get(1).and_then(|m| m.as_str().parse::<f64>().ok());
-                                    if percent.is_some() {
-                                        parsed = Some(ParsedProgress {
-                                            percent,
-                                            bytes_total: None,
-                                            bytes_downloaded: None,
-                                            speed_bps: None,
-                                            eta_seconds: None,
-                                            phase: Some("Downloading".to_string()),
-                                        });
-                                    }
-                                }
-                            }
-                        }
+/// yt-dlp arguments for downloading only the given section(s).
+fn sections_args(sections: &str) -> Vec<String> {
+    vec![
+        "--download-sections".to_string(),
+        sections.trim().to_string(),
+        "--force-keyframes-at-cuts".to_string(),
+    ]
+}
 
-                        // Send progress event if we parsed something
-                        if let Some(p) = parsed {
-                            // Only send if percent changed significantly (avoid flooding)
-                            let current_percent = p.percent.unwrap_or(0.0);
-                            if (current_percent - last_percent).abs() >= 0.5 || current_percent >= 99.9 {
-                                last_percent = current_percent;
-                                log::info!("Progress: {}%", current_percent);
-                                let _ = event_tx.send(DownlinkEvent::DownloadProgress {
-                                    id,
-                                    status: events::DownloadStatus::Downloading,
-                                    progress: Progress {
-                                        percent: p.percent,
-                                        bytes_downloaded: p.bytes_downloaded,
-                                        bytes_total: p.bytes_total,
-                                        speed_bps: p.speed_bps,
-                                        eta_seconds: p.eta_seconds,
-                                        phase: Some(Phase {
-                                            name: p.phase.clone().unwrap_or_else(|| "Downloading".to_string()),
-                                            detail: None,
-                                        }),
-                                    },
-                                }).await;
-                            }
-                        }
+/// yt-dlp arguments for sidecar files written next to the download.
+///
+/// Independent of the embed options: with both `--embed-thumbnail` and
+/// `--write-thumbnail`, yt-dlp embeds the thumbnail and keeps the image file.
+/// Comments are stored in the `.info.json`, so they turn that on too.
+fn sidecar_args(formats: &FormatSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if formats.write_info_json || formats.write_comments {
+        args.push("--write-info-json".to_string());
+    }
+    if formats.write_comments {
+        args.push("--write-comments".to_string());
+    }
+    if formats.write_description {
+        args.push("--write-description".to_string());
+    }
+    if formats.write_thumbnail {
+        args.push("--write-thumbnail".to_string());
+    }
+    args
+}
 
-                        // Check for merge phase
-                        if let Some(ref re) = merge_re {
-                            if re.is_match(&l) {
-                                log::info!("Post-processing: merging streams");
-                                let _ = event_tx.send(DownlinkEvent::DownloadPostProcessing {
-                                    id,
-                                    step: "Merging streams".to_string(),
-                                    detail: None,
-                                }).await;
-                            }
-                        }
+/// yt-dlp arguments that keep filenames writable on restrictive filesystems.
+fn filename_args(formats: &FormatSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if formats.restrict_filenames {
+        args.push("--restrict-filenames".to_string());
+    }
+    if formats.trim_filenames > 0 {
+        args.push("--trim-filenames".to_string());
+        args.push(formats.trim_filenames.to_string());
+    }
+    args
+}
 
-                        // Check for 100% complete
-                        if let Some(ref re) = finished_re {
-                            if re.is_match(&l) {
-                                log::info!("Download complete, post-processing...");
-                                let _ = event_tx.send(DownlinkEvent::DownloadProgress {
-                                    id,
-                                    status: events::DownloadStatus::Downloading,
-                                    progress: Progress {
-                                        percent: Some(100.0),
-                                        bytes_downloaded: None,
-                                        bytes_total: None,
-                                        speed_bps: None,
-                                        eta_seconds: None,
-                                        phase: Some(Phase {
-                                            name: "Finishing...".to_string(),
-                                            detail: None,
-                                        }),
-                                    },
-                                }).await;
-                            }
-                        }
+/// yt-dlp arguments for writing subtitles.
+///
+/// Languages chosen for the download itself win over the default language, and
+/// fetch subtitles even when they're off by default. Embedding needs ffmpeg.
+fn subtitle_args(
+    subtitles: &SubtitleSettings,
+    download_langs: Option<&str>,
+    ffmpeg_available: bool,
+) -> Vec<String> {
+    let langs = match download_langs.map(validate_subtitle_langs) {
+        Some(Ok(Some(langs))) => langs,
+        Some(Err(e)) => {
+            log::warn!("Ignoring subtitle languages: {}", e);
+            return Vec::new();
+        }
+        Some(Ok(None)) | None if !subtitles.enabled_by_default => return Vec::new(),
+        Some(Ok(None)) | None => match validate_subtitle_langs(&subtitles.default_language) {
+            Ok(Some(langs)) => langs,
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                log::warn!("Ignoring default subtitle language: {}", e);
+                return Vec::new();
+            }
+        },
+    };
 
-                        // Capture destination path
-                        if let Some(ref re) = dest_re {
-                            if let Some(caps) = re.captures(&l) {
-                                final_path = caps.get(1).map(|m| m.as_str().to_string());
-                            }
-                        }
+    let mut args = vec!["--write-subs".to_string(), "--sub-langs".to_string(), langs];
+    if subtitles.include_auto_captions {
+        args.push("--write-auto-subs".to_string());
+    }
+    let format = subtitles.preferred_format.trim();
+    if !format.is_empty() {
+        args.push("--sub-format".to_string());
+        args.push(format!("{format}/best"));
+    }
+    if subtitles.embed_subtitles {
+        if ffmpeg_available {
+            args.push("--embed-subs".to_string());
+        } else {
+            log::warn!("Embedding subtitles needs ffmpeg, which was not found, skipping it");
+        }
+    }
+    args
+}
 
-                        // Check for already downloaded
-                        if let Some(ref re) = already_re {
-                            if let Some(caps) = re.captures(&l) {
-                                final_path = caps.get(1).map(|m| m.as_str().to_string());
-                            }
-                        }
-                    }
-                    Ok(None) => break,
-                    Err(e) => {
-                        log::error!("Error reading stdout: {}", e);
-                        break;
+/// Output template for `--split-chapters` files, written next to the full video.
+const CHAPTER_OUTPUT_TEMPLATE: &str =
+    "%(title)s - %(section_number)03d %(section_title)s [%(id)s].%(ext)s";
+
+/// yt-dlp arguments for splitting the download into one file per chapter.
+///
+/// The split is an ffmpeg postprocessor, so nothing is added without ffmpeg. The
+/// full video is kept alongside the chapter files.
+fn split_chapters_args(
+    formats: &FormatSettings,
+    output_dir: &str,
+    ffmpeg_available: bool,
+) -> Vec<String> {
+    if !formats.split_chapters {
+        return Vec::new();
+    }
+    if !ffmpeg_available {
+        log::warn!("Splitting by chapters needs ffmpeg, which was not found, skipping it");
+        return Vec::new();
+    }
+    vec![
+        "--split-chapters".to_string(),
+        "-o".to_string(),
+        format!("chapter:{}/{}", output_dir, CHAPTER_OUTPUT_TEMPLATE),
+    ]
+}
+
+/// `DownloadStarted` for `download`, describing the preset and output path it runs with.
+fn started_event(
+    download: &DownloadRow,
+    config: &DownloadConfig,
+    settings: &UserSettings,
+) -> DownlinkEvent {
+    let preset = Preset::get_by_id(&download.preset_id)
+        .unwrap_or_else(|| Preset::builtin_presets()[0].clone());
+    let output_dir = write_dir(download, config, &settings.general);
+
+    DownlinkEvent::DownloadStarted {
+        id: download.id,
+        preset_name: Some(preset.name),
+        output_template: Some(format!(
+            "{}/{}",
+            output_dir,
+            output_template(download, config, &settings.formats)
+        )),
+    }
+}
+
+/// The preset's yt-dlp arguments with the `remux_to` container applied. Unknown
+/// presets fall back to the first built-in one.
+fn effective_preset_args(
+    preset_id: &str,
+    formats: &FormatSettings,
+    ffmpeg_available: bool,
+) -> Vec<String> {
+    let mut args = Preset::get_by_id(preset_id)
+        .unwrap_or_else(|| Preset::builtin_presets()[0].clone())
+        .yt_dlp_args;
+    apply_remux(&mut args, formats, ffmpeg_available);
+    args
+}
+
+/// Codecs the first choice of a `-f` selector pins down, as (video, audio), from its
+/// `[ext=...]`, `[vcodec^=...]` and `[acodec^=...]` filters. `None` where it's open.
+fn selector_codecs(selector: &str) -> (Option<String>, Option<String>) {
+    static FILTER: std::sync::OnceLock<Option<Regex>> = std::sync::OnceLock::new();
+    let filter = FILTER.get_or_init(|| {
+        Regex::new(r"\[(ext|vcodec|acodec)\s*[\^$*]?=\s*'?([A-Za-z0-9.]+)'?\]").ok()
+    });
+    let Some(filter) = filter else {
+        return (None, None);
+    };
+    let first = selector.split('/').next().unwrap_or_default();
+
+    let (mut video, mut audio) = (None, None);
+    for part in first.split('+') {
+        let part = part.trim();
+        let is_audio =
+            part.starts_with("ba") || part.starts_with("wa") || part.starts_with("bestaudio");
+        let is_video =
+            part.starts_with("bv") || part.starts_with("wv") || part.starts_with("bestvideo");
+        for caps in filter.captures_iter(part) {
+            let value = caps[2].to_lowercase();
+            match (&caps[1], value.as_str()) {
+                ("vcodec", _) => video = Some(value),
+                ("acodec", _) => audio = Some(value),
+                ("ext", "mp4") if !is_audio => {
+                    video = Some("h264".to_string());
+                    if !is_video {
+                        audio = Some("aac".to_string());
                     }
                 }
-            }
-            line = stderr_reader.next_line() => {
-                match line {
-                    Ok(Some(l)) => {
-                        log::debug!("yt-dlp stderr: {}", l);
-                        stderr_lines.push(l);
-                    }
-                    Ok(None) => {}
-                    Err(e) => {
-                        log::error!("Error reading stderr: {}", e);
+                ("ext", "webm") if !is_audio => {
+                    video = Some("vp9".to_string());
+                    if !is_video {
+                        audio = Some("opus".to_string());
                     }
                 }
+                ("ext", "webm") => audio = Some("opus".to_string()),
+                ("ext", "m4a") | ("ext", "mp4") => audio = Some("aac".to_string()),
+                ("ext", "mp3") => audio = Some("mp3".to_string()),
+                _ => {}
             }
         }
     }
+    (video, audio)
+}
 
-    // Wait for process to exit
-    let status = child.wait().await.map_err(|e| DownloadError::Failed {
-        code: ErrorCode::Unknown,
-        message: format!("Failed to wait for yt-dlp: {}", e),
-        actions: vec![],
-    })?;
+/// Warning for a video download whose merge container likely can't hold the codecs
+/// it will get, judged from the preferred codec settings or, when those are empty,
+/// the preset's format selector. `preset_args` should already have `remux_to` applied.
+pub fn container_compatibility_warning(
+    preset_args: &[String],
+    formats: &FormatSettings,
+) -> Option<String> {
+    if preset_args
+        .iter()
+        .any(|a| a == "-x" || a == "--extract-audio")
+    {
+        return None;
+    }
+    let value_of = |flag: &str| {
+        preset_args
+            .windows(2)
+            .find(|w| w[0] == flag)
+            .map(|w| w[1].as_str())
+    };
+    let container = value_of("--merge-output-format")?;
+    let (selected_video, selected_audio) = selector_codecs(value_of("-f").unwrap_or_default());
 
-    if !status.success() {
-        let stderr_text = stderr_lines.join("\n");
-        let (code, message, actions) = classify_error(&stderr_text);
-        return Err(DownloadError::Failed {
-            code,
-            message,
-            actions,
-        });
+    let preferred = |codec: &str| Some(codec.trim().to_string()).filter(|c| !c.is_empty());
+    let video = preferred(&formats.preferred_video_codec).or(selected_video);
+    let audio = preferred(&formats.preferred_audio_codec).or(selected_audio);
+    container_codec_conflict(container, video.as_deref(), audio.as_deref())
+}
+
+/// Point a video preset at the `remux_to` container.
+///
+/// The preset's `--merge-output-format` is replaced so merged downloads are written in
+/// the target container directly, and `--remux-video` covers single-file downloads.
+/// Audio extraction presets are left alone, as is everything when ffmpeg is missing.
+fn apply_remux(preset_args: &mut Vec<String>, formats: &FormatSettings, ffmpeg_available: bool) {
+    let container = match validate_remux_container(&formats.remux_to) {
+        Ok(Some(container)) => container,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Ignoring remux setting: {e}");
+            return;
+        }
+    };
+    if preset_args
+        .iter()
+        .any(|a| a == "-x" || a == "--extract-audio")
+    {
+        return;
+    }
+    if !ffmpeg_available {
+        log::warn!("Remuxing needs ffmpeg, which was not found, skipping it");
+        return;
     }
 
-    Ok(final_path)
+    match preset_args
+        .iter()
+        .position(|a| a == "--merge-output-format")
+    {
+        Some(i) if i + 1 < preset_args.len() => preset_args[i + 1] = container.clone(),
+        _ => {
+            preset_args.push("--merge-output-format".to_string());
+            preset_args.push(container.clone());
+        }
+    }
+    preset_args.push("--remux-video".to_string());
+    preset_args.push(container);
 }
 
-/// Parse progress from our custom template output.
-fn parse_progress_line(caps: &regex::Captures) -> ParsedProgress {
-    let percent_str = caps.get(1).map(|m| m.as_str()).unwrap_or("");
-    let speed_str = caps.get(2).map(|m| m.as_str()).unwrap_or("");
-    let eta_str = caps.get(3).map(|m| m.as_str()).unwrap_or("");
-    let total_str = caps.get(4).map(|m| m.as_str()).unwrap_or("");
+/// ffmpeg `loudnorm` settings: EBU R128 streaming targets (-16 LUFS, -1.5 dBTP).
+const LOUDNORM_FILTER: &str = "loudnorm=I=-16:TP=-1.5:LRA=11";
 
-    ParsedProgress {
-        percent: parse_percent(percent_str),
-        speed_bps: parse_speed(speed_str),
-        eta_seconds: parse_eta(eta_str),
-        bytes_total: parse_bytes(total_str),
-        bytes_downloaded: None, // We can calculate from percent * total if needed
-        phase: Some("Downloading".to_string()),
+/// yt-dlp arguments that run `loudnorm` while extracting audio.
+///
+/// Only extraction presets (`-x`) go through the ExtractAudio postprocessor, so video
+/// presets get nothing. Neither do extractions that copy the audio stream as is,
+/// since ffmpeg can't filter a copied stream, nor anything without ffmpeg.
+fn audio_normalize_args(
+    formats: &FormatSettings,
+    preset_args: &[String],
+    ffmpeg_available: bool,
+) -> Vec<String> {
+    if !formats.audio_normalize {
+        return Vec::new();
+    }
+    if !preset_args
+        .iter()
+        .any(|a| a == "-x" || a == "--extract-audio")
+    {
+        return Vec::new();
+    }
+    if extracts_by_stream_copy(preset_args) {
+        log::info!("Audio normalization skipped: this preset keeps the original audio stream");
+        return Vec::new();
+    }
+    if !ffmpeg_available {
+        log::warn!("Audio normalization needs ffmpeg, which was not found, skipping it");
+        return Vec::new();
+    }
+    vec![
+        "--postprocessor-args".to_string(),
+        format!("ExtractAudio:-af {LOUDNORM_FILTER}"),
+    ]
+}
+
+/// Warning for an audio download that asked for normalization but won't get it, because
+/// the preset copies the audio stream and ffmpeg can't filter a copied stream.
+pub fn audio_normalize_warning(preset_args: &[String], formats: &FormatSettings) -> Option<String> {
+    let extracts = preset_args
+        .iter()
+        .any(|a| a == "-x" || a == "--extract-audio");
+    if !formats.audio_normalize || !extracts || !extracts_by_stream_copy(preset_args) {
+        return None;
+    }
+    Some(
+        "Audio normalization is skipped for this preset because it keeps the original \
+         audio stream; pick a preset that converts the audio to normalize it"
+            .to_string(),
+    )
+}
+
+/// The output template, relative to the download's folder, behind the subfolder
+/// `formats.organize_by` asks for.
+///
+/// yt-dlp sanitizes the field values it fills into folder names, and the playlist
+/// title recorded at expansion goes through [`template_segment`], so neither can
+/// lead out of the download folder.
+fn output_template(
+    download: &DownloadRow,
+    config: &DownloadConfig,
+    formats: &FormatSettings,
+) -> String {
+    let template = config.default_output_template.as_str();
+
+    match formats.organize_by.as_str() {
+        "uploader" => format!("%(uploader,channel|Unknown uploader)s/{template}"),
+        "date" => format!("%(upload_date>%Y-%m|Unknown date)s/{template}"),
+        "playlist" => {
+            // Expanded items are downloaded one by one, so yt-dlp doesn't know their
+            // playlist; use what was recorded when the playlist was expanded.
+            let folder = download
+                .playlist_title
+                .as_deref()
+                .and_then(template_segment)
+                .unwrap_or_else(|| "%(playlist_title,playlist|No playlist)s".to_string());
+            let number = match download.playlist_index {
+                Some(index) => format!("{index:02} - "),
+                None => "%(playlist_index&{} - |)s".to_string(),
+            };
+            format!("{folder}/{number}{template}")
+        }
+        _ => template.to_string(),
+    }
+}
+
+/// `value` as a literal folder name in an output template: characters filenames
+/// can't hold become `_`, surrounding dots and spaces go, and `%` is escaped.
+/// `None` if nothing is left.
+fn template_segment(value: &str) -> Option<String> {
+    let cleaned: String = value
+        .chars()
+        .map(|c| {
+            if c.is_control() || r#"/\:*?"<>|"#.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    (!cleaned.is_empty()).then(|| cleaned.replace('%', "%%"))
+}
+
+/// Build the full yt-dlp argument list for a download, without running anything.
+///
+/// Order: output/progress flags, preset args, tool locations, settings-driven flags,
+/// per-download options, then the URL last.
+pub fn build_download_args(
+    download: &DownloadRow,
+    config: &DownloadConfig,
+    settings: &UserSettings,
+    credentials_config: Option<&Path>,
+) -> Vec<String> {
+    let preset_args = effective_preset_args(
+        &download.preset_id,
+        &settings.formats,
+        config.ffmpeg_path.is_some(),
+    );
+
+    let template = output_template(download, config, &settings.formats);
+    let output_dir = write_dir(download, config, &settings.general);
+
+    let mut args = vec![
+        "--newline".to_string(),
+        "--no-warnings".to_string(),
+        "--no-call-home".to_string(),
+        "--progress".to_string(),
+        "--progress-template".to_string(),
+        "download:[downlink] %(progress._percent_str)s %(progress._speed_str)s %(progress._eta_str)s %(progress._total_bytes_str)s".to_string(),
+        "-o".to_string(),
+        format!("{}/{}", output_dir, template),
+    ];
+
+    let container = output_container(&preset_args).map(str::to_string);
+    let normalize_args = audio_normalize_args(
+        &settings.formats,
+        &preset_args,
+        config.ffmpeg_path.is_some(),
+    );
+
+    // Add preset args
+    args.extend(preset_args);
+
+    // Add ffmpeg location if configured
+    if let Some(ref ffmpeg_path) = config.ffmpeg_path {
+        args.push("--ffmpeg-location".to_string());
+        args.push(ffmpeg_path.to_string_lossy().to_string());
+    }
+
+    let network = NetworkSettings {
+        rate_limit_bps: download_rate_limit(download).unwrap_or(settings.network.rate_limit_bps),
+        ..settings.network.clone()
+    };
+    args.extend(network_args(&network));
+
+    let resuming = download.progress_percent.is_some_and(|p| p > 0.0);
+    let (part_args, warning) = part_file_args(&settings.network, resuming);
+    if let Some(warning) = warning {
+        log::warn!("Download {}: {}", download.id, warning);
+    }
+    args.extend(part_args);
+    args.extend(external_downloader_args(
+        &settings.network.external_downloader,
+        config.aria2c_path.as_deref(),
+        network.rate_limit_bps,
+    ));
+    args.extend(sponsorblock_args(
+        &settings.sponsorblock,
+        container.as_deref(),
+        config.ffmpeg_path.is_some(),
+    ));
+    args.extend(sidecar_args(&settings.formats));
+    args.extend(filename_args(&settings.formats));
+    args.extend(subtitle_args(
+        &settings.subtitles,
+        download.subtitle_langs.as_deref(),
+        config.ffmpeg_path.is_some(),
+    ));
+    args.extend(split_chapters_args(
+        &settings.formats,
+        &output_dir,
+        config.ffmpeg_path.is_some(),
+    ));
+    args.extend(normalize_args);
+
+    if let Some(ref sections) = download.sections {
+        args.extend(sections_args(sections));
+    }
+
+    args.extend(cookies_args(&settings.privacy));
+    // Keyed on the destination, not the per-download staging dir, so it dedupes
+    args.extend(download_archive_args(
+        &config.data_dir,
+        &settings.general,
+        &download.output_dir,
+    ));
+
+    args.extend(extractor_args(&settings.general));
+    args.extend(extra_ytdlp_args(&settings.general));
+
+    // Site login, read from a private file rather than the command line
+    if let Some(path) = credentials_config {
+        args.push("--config-locations".to_string());
+        args.push(path.to_string_lossy().to_string());
+    }
+
+    // Add URL last
+    args.push(download.source_url.clone());
+
+    args
+}
+
+/// The user's extra yt-dlp arguments. Settings are validated on save, so a parse
+/// error here means an older stored value; it's skipped rather than failing the download.
+pub fn extra_ytdlp_args(general: &GeneralSettings) -> Vec<String> {
+    parse_extra_args(&general.extra_ytdlp_args).unwrap_or_else(|e| {
+        log::warn!("Ignoring extra yt-dlp arguments: {}", e);
+        Vec::new()
+    })
+}
+
+/// One `--extractor-args EXTRACTOR:ARGS` pair per configured extractor. Entries that
+/// no longer validate are skipped, like bad extra arguments.
+pub fn extractor_args(general: &GeneralSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    for (extractor, value) in &general.extractor_args {
+        if let Err(e) = validate_extractor_args(extractor, value) {
+            log::warn!("Ignoring extractor arguments: {}", e);
+            continue;
+        }
+        args.push("--extractor-args".to_string());
+        args.push(format!("{extractor}:{value}"));
+    }
+    args
+}
+
+/// `--cookies-from-browser` for the selected browser profile, or `--cookies` for the
+/// configured cookies file if it's usable.
+///
+/// `start` already refuses to run with a bad file; this keeps previews and any
+/// other caller from passing one to yt-dlp.
+fn cookies_args(privacy: &PrivacySettings) -> Vec<String> {
+    if let Some(browser) = privacy.active_cookies_from_browser() {
+        return vec!["--cookies-from-browser".to_string(), browser.to_string()];
+    }
+    let Some(path) = privacy.active_cookies_path() else {
+        return Vec::new();
+    };
+    match validate_cookies_file(path) {
+        Ok(()) => vec!["--cookies".to_string(), path.to_string_lossy().to_string()],
+        Err(e) => {
+            log::warn!("Not passing cookies to yt-dlp: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Global download archive, in the app data dir.
+const DOWNLOAD_ARCHIVE_FILE: &str = "archive.txt";
+
+/// Subdirectory of the app data dir holding per-folder download archives.
+const DOWNLOAD_ARCHIVES_DIR: &str = "archives";
+
+/// Phase of a finished download yt-dlp skipped because the archive listed it.
+const ALREADY_IN_ARCHIVE: &str = "Already in archive";
+
+/// Archive file yt-dlp records downloads into for a download saved to `output_dir`.
+///
+/// Per-folder archives live in the app data dir too, named by a hash of the folder,
+/// so nothing extra is written next to the user's files.
+pub fn download_archive_path(
+    data_dir: &Path,
+    general: &GeneralSettings,
+    output_dir: &str,
+) -> PathBuf {
+    if general.download_archive_scope != "per_folder" {
+        return data_dir.join(DOWNLOAD_ARCHIVE_FILE);
+    }
+    use sha2::{Digest, Sha256};
+    let folder = output_dir.trim_end_matches(['/', '\\']);
+    let digest = hex::encode(Sha256::digest(folder.as_bytes()));
+    data_dir
+        .join(DOWNLOAD_ARCHIVES_DIR)
+        .join(format!("{}.txt", &digest[..16]))
+}
+
+/// `--download-archive` when the archive is turned on.
+fn download_archive_args(
+    data_dir: &Path,
+    general: &GeneralSettings,
+    output_dir: &str,
+) -> Vec<String> {
+    if !general.use_download_archive {
+        return Vec::new();
+    }
+    if data_dir.as_os_str().is_empty() {
+        log::warn!("No app data directory for the download archive, skipping it");
+        return Vec::new();
+    }
+    let path = download_archive_path(data_dir, general, output_dir);
+    if let Some(parent) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            log::warn!("Failed to create {}: {}", parent.display(), e);
+        }
+    }
+    vec![
+        "--download-archive".to_string(),
+        path.to_string_lossy().to_string(),
+    ]
+}
+
+/// Delete download archives so archived items download again.
+///
+/// `Some(path)` removes just that archive; `None` removes the global archive and every
+/// per-folder one. Returns how many files were deleted.
+pub fn clear_download_archives(data_dir: &Path, archive: Option<&Path>) -> std::io::Result<usize> {
+    let mut files = Vec::new();
+    match archive {
+        Some(path) => files.push(path.to_path_buf()),
+        None => {
+            files.push(data_dir.join(DOWNLOAD_ARCHIVE_FILE));
+            match std::fs::read_dir(data_dir.join(DOWNLOAD_ARCHIVES_DIR)) {
+                Ok(entries) => {
+                    for entry in entries {
+                        files.push(entry?.path());
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    let mut removed = 0;
+    for file in files {
+        match std::fs::remove_file(&file) {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Subfolder of the app's tmp dir holding in-progress `download_to_temp` downloads.
+pub const STAGING_DIR: &str = "downloads";
+
+/// Subfolder of the app's tmp dir holding the yt-dlp config files that carry site
+/// logins, one per running download.
+pub const CREDENTIALS_DIR: &str = "credentials";
+
+/// Where the login for download `id` is written, see [`CredentialsConfig`].
+fn credentials_config_path(tmp_dir: &Path, id: Uuid) -> PathBuf {
+    let tmp_dir = if tmp_dir.as_os_str().is_empty() {
+        std::env::temp_dir()
+    } else {
+        tmp_dir.to_path_buf()
+    };
+    tmp_dir.join(CREDENTIALS_DIR).join(format!("{id}.conf"))
+}
+
+/// A yt-dlp config file holding `--username`/`--password`, readable only by the
+/// user. Passed with `--config-locations` so the password never shows up in the
+/// process list. Deleted when dropped.
+struct CredentialsConfig {
+    path: PathBuf,
+}
+
+impl CredentialsConfig {
+    fn write(path: PathBuf, credentials: &SiteCredentials) -> std::io::Result<Self> {
+        use std::io::Write;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        let config = Self { path };
+        writeln!(file, "--username {}", config_quote(&credentials.username))?;
+        writeln!(file, "--password {}", config_quote(&credentials.password))?;
+        Ok(config)
+    }
+}
+
+impl Drop for CredentialsConfig {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to remove credentials file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Quote a value for a yt-dlp config file, which is split like a shell command line.
+fn config_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r#"'"'"'"#))
+}
+
+/// Where a download is written while in progress, if `download_to_temp` is on.
+/// One folder per download, so a stopped download resumes from its `.part` file.
+fn staging_dir(config: &DownloadConfig, general: &GeneralSettings, id: Uuid) -> Option<PathBuf> {
+    if !general.download_to_temp || config.tmp_dir.as_os_str().is_empty() {
+        return None;
+    }
+    Some(config.tmp_dir.join(STAGING_DIR).join(id.to_string()))
+}
+
+/// The folder yt-dlp writes `download` into: its staging dir, or else its output dir.
+fn write_dir(download: &DownloadRow, config: &DownloadConfig, general: &GeneralSettings) -> String {
+    staging_dir(config, general, download.id)
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_else(|| download.output_dir.clone())
+}
+
+/// Move everything a finished download left in `staging` into `dest`, keeping
+/// subfolders from the filename template, then remove `staging`. Leftover
+/// `.part`/`.ytdl` files are dropped.
+///
+/// Like yt-dlp writing straight into `dest`, files already there are never
+/// overwritten; the staged copy is dropped instead.
+fn move_staged_outputs(staging: &Path, dest: &Path) -> std::io::Result<()> {
+    for file in files_under(staging)? {
+        let name = file.to_string_lossy();
+        if name.ends_with(".part") || name.ends_with(".ytdl") {
+            continue;
+        }
+        let relative = file.strip_prefix(staging).unwrap_or(&file);
+        let target = dest.join(relative);
+        if target.exists() {
+            log::info!("{} already exists, keeping it", target.display());
+            continue;
+        }
+        move_file(&file, &target)?;
+    }
+    std::fs::remove_dir_all(staging)
+}
+
+/// Every file below `dir`, recursively.
+fn files_under(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(files_under(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// The destination path of a file reported inside `staging`.
+fn relocate_staged_path(path: &str, staging: &Path, dest: &Path) -> String {
+    match Path::new(path).strip_prefix(staging) {
+        Ok(relative) => dest.join(relative).to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// OS error for a rename across filesystems: EXDEV, or ERROR_NOT_SAME_DEVICE on Windows.
+#[cfg(not(windows))]
+const CROSS_DEVICE_ERROR: i32 = 18;
+#[cfg(windows)]
+const CROSS_DEVICE_ERROR: i32 = 17;
+
+/// Move a file, replacing any existing file at `to`.
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    move_file_with(from, to, |from, to| std::fs::rename(from, to))
+}
+
+/// `move_file` with the rename step injectable. Falls back to copy and delete
+/// when `rename` can't cross filesystems (the tmp dir is often on another disk).
+fn move_file_with(
+    from: &Path,
+    to: &Path,
+    rename: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // rename won't replace an existing file on Windows
+    if to.is_file() {
+        std::fs::remove_file(to)?;
+    }
+    match rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(CROSS_DEVICE_ERROR) => {
+            if let Err(e) = std::fs::copy(from, to) {
+                let _ = std::fs::remove_file(to);
+                return Err(e);
+            }
+            std::fs::remove_file(from)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Containers that can carry embedded chapters.
+const CHAPTER_CONTAINERS: &[&str] = &["mp4", "mkv", "m4a"];
+
+/// Whether ExtractAudio copies the audio stream instead of re-encoding it: there's
+/// no target format (or "best"), or the format selector already picks that format.
+fn extracts_by_stream_copy(preset_args: &[String]) -> bool {
+    let value = |flags: &[&str]| {
+        preset_args
+            .windows(2)
+            .find(|w| flags.contains(&w[0].as_str()))
+            .map(|w| w[1].as_str())
+    };
+    match value(&["--audio-format"]) {
+        None | Some("best") => true,
+        Some(format) => value(&["-f", "--format"])
+            .is_some_and(|selector| selector.contains(&format!("[ext={format}]"))),
+    }
+}
+
+/// Final container a preset produces, from its merge/audio format flag.
+fn output_container(preset_args: &[String]) -> Option<&str> {
+    preset_args
+        .windows(2)
+        .find(|w| w[0] == "--merge-output-format" || w[0] == "--audio-format")
+        .map(|w| w[1].as_str())
+}
+
+/// yt-dlp arguments for SponsorBlock.
+///
+/// "mark" adds chapters for the segments, which only end up in the file with
+/// `--embed-chapters`, so that's added when the container supports chapters.
+/// "remove" cuts the segments out and doesn't embed chapters for them. Both modes
+/// run through ffmpeg postprocessors, so nothing is added without ffmpeg.
+fn sponsorblock_args(
+    sponsorblock: &SponsorBlockSettings,
+    container: Option<&str>,
+    ffmpeg_available: bool,
+) -> Vec<String> {
+    if !sponsorblock.enabled_by_default {
+        return Vec::new();
+    }
+    // Unknown categories make yt-dlp refuse to start; settings warn about them
+    let (known, unknown): (Vec<&str>, Vec<&str>) = sponsorblock
+        .categories
+        .iter()
+        .map(String::as_str)
+        .partition(|c| SPONSORBLOCK_CATEGORIES.iter().any(|known| known.id == *c));
+    if !unknown.is_empty() {
+        log::warn!(
+            "Skipping unknown SponsorBlock categories: {}",
+            unknown.join(", ")
+        );
+    }
+    if known.is_empty() {
+        return Vec::new();
+    }
+    if !ffmpeg_available {
+        log::warn!("SponsorBlock is enabled but ffmpeg was not found, skipping it");
+        return Vec::new();
+    }
+
+    let categories = known.join(",");
+    match sponsorblock.mode.as_str() {
+        "mark" => {
+            let mut args = vec!["--sponsorblock-mark".to_string(), categories];
+            if container.is_some_and(|c| CHAPTER_CONTAINERS.contains(&c)) {
+                args.push("--embed-chapters".to_string());
+            }
+            args
+        }
+        "remove" => vec!["--sponsorblock-remove".to_string(), categories],
+        other => {
+            log::warn!("Unknown SponsorBlock mode {:?}, skipping it", other);
+            Vec::new()
+        }
+    }
+}
+
+/// The download's own speed limit, if it has one.
+fn download_rate_limit(download: &DownloadRow) -> Option<u64> {
+    download
+        .rate_limit_bps
+        .filter(|bps| *bps > 0)
+        .map(|bps| bps as u64)
+}
+
+/// The `--limit-rate` for a download starting while `sharing` downloads (itself
+/// included) split `total_bps`: an equal share, but at least 1 B/s since yt-dlp
+/// needs a positive limit.
+///
+/// This is not a cap on the combined rate. `--limit-rate` is fixed when yt-dlp
+/// starts and is never reissued, so each download keeps the share it started with:
+/// downloads started later can take the total over `total_bps` until earlier ones
+/// finish, and bandwidth freed by a finished download only goes to ones started
+/// afterwards. Downloads with their own limit don't take a share.
+pub fn rate_limit_share(total_bps: u64, sharing: usize) -> u64 {
+    (total_bps / sharing.max(1) as u64).max(1)
+}
+
+/// aria2c options: 16 connections per server, split into 1 MiB pieces.
+const ARIA2C_DOWNLOADER_ARGS: &str = "aria2c:-x 16 -s 16 -k 1M";
+
+/// yt-dlp arguments for the external downloader setting ("none" or "aria2c").
+///
+/// aria2c only speeds up plain HTTP(S) transfers; yt-dlp keeps using its native
+/// downloader for other protocols. If aria2c isn't installed, the native downloader
+/// is used rather than failing the download. yt-dlp's `--limit-rate` doesn't reach
+/// aria2c, so a non-zero `rate_limit_bps` is passed to it as its own overall limit.
+fn external_downloader_args(
+    external_downloader: &str,
+    aria2c_path: Option<&Path>,
+    rate_limit_bps: u64,
+) -> Vec<String> {
+    match external_downloader {
+        "" | "none" => Vec::new(),
+        "aria2c" => match aria2c_path {
+            Some(path) => {
+                let mut downloader_args = ARIA2C_DOWNLOADER_ARGS.to_string();
+                if rate_limit_bps > 0 {
+                    downloader_args
+                        .push_str(&format!(" --max-overall-download-limit={rate_limit_bps}"));
+                }
+                vec![
+                    "--downloader".to_string(),
+                    path.to_string_lossy().to_string(),
+                    "--downloader-args".to_string(),
+                    downloader_args,
+                ]
+            }
+            None => {
+                log::warn!("aria2c is selected but was not found, using the native downloader");
+                Vec::new()
+            }
+        },
+        other => {
+            log::warn!(
+                "Unknown external downloader {:?}, using the native downloader",
+                other
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// What yt-dlp prints when `--max-filesize` stops a download.
+const MAX_FILESIZE_MARKER: &str = "larger than max-filesize";
+
+/// yt-dlp arguments derived from the user's network settings.
+fn network_args(network: &NetworkSettings) -> Vec<String> {
+    let mut args = Vec::new();
+
+    if network.rate_limit_bps > 0 {
+        args.push("--limit-rate".to_string());
+        args.push(network.rate_limit_bps.to_string());
+    }
+
+    match network.ip_version.as_str() {
+        "ipv4" => args.push("--force-ipv4".to_string()),
+        "ipv6" => args.push("--force-ipv6".to_string()),
+        _ => {}
+    }
+
+    // Settings are validated on save; an invalid address is dropped rather than
+    // failing every download
+    match crate::network::validate_source_address(&network.source_address) {
+        Ok(Some(addr)) => {
+            args.push("--source-address".to_string());
+            args.push(addr.to_string());
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Ignoring source address: {}", e),
+    }
+
+    if network.geo_bypass {
+        args.push("--geo-bypass".to_string());
+    }
+    match crate::network::validate_country_code(&network.geo_bypass_country) {
+        Ok(Some(country)) => {
+            args.push("--geo-bypass-country".to_string());
+            args.push(country);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Ignoring geo-bypass country: {}", e),
+    }
+    match crate::network::validate_xff(&network.xff) {
+        Ok(Some(xff)) => {
+            args.push("--xff".to_string());
+            args.push(xff);
+        }
+        Ok(None) => {}
+        Err(e) => log::warn!("Ignoring X-Forwarded-For setting: {}", e),
+    }
+
+    if network.max_filesize_bytes > 0 {
+        args.push("--max-filesize".to_string());
+        args.push(network.max_filesize_bytes.to_string());
+    }
+
+    args.extend(polite_args(network));
+
+    args
+}
+
+/// yt-dlp arguments that pace downloads and requests in polite mode.
+fn polite_args(network: &NetworkSettings) -> Vec<String> {
+    let mut args = Vec::new();
+    if !network.polite_mode {
+        return args;
+    }
+
+    if network.sleep_interval_secs > 0 {
+        args.push("--sleep-interval".to_string());
+        args.push(network.sleep_interval_secs.to_string());
+        // yt-dlp rejects a maximum below the minimum; settings validation prevents it
+        if network.max_sleep_interval_secs > network.sleep_interval_secs {
+            args.push("--max-sleep-interval".to_string());
+            args.push(network.max_sleep_interval_secs.to_string());
+        }
+    }
+    if network.sleep_requests_secs > 0 {
+        args.push("--sleep-requests".to_string());
+        args.push(network.sleep_requests_secs.to_string());
+    }
+    args
+}
+
+/// Longest pause `polite_args` can make yt-dlp take between requests.
+fn polite_sleep(network: &NetworkSettings) -> Duration {
+    if !network.polite_mode {
+        return Duration::ZERO;
+    }
+    let longest = network
+        .sleep_interval_secs
+        .max(network.max_sleep_interval_secs)
+        .max(network.sleep_requests_secs);
+    Duration::from_secs(longest.into())
+}
+
+/// Shown when a stopped download is resumed with `no_part` on.
+const NO_PART_RESUME_WARNING: &str = "\"Don't use .part files\" is on, so this download can't \
+     resume where it stopped and will restart from the beginning";
+
+/// yt-dlp arguments for the fragment/`.part` debugging toggles, plus a warning
+/// when they change how the download behaves.
+///
+/// With `--no-part`, a stopped download leaves a truncated file under its final
+/// name, which yt-dlp would then skip as "already downloaded". Resumes therefore
+/// also get `--force-overwrites` so the file is downloaded again in full.
+fn part_file_args(
+    network: &NetworkSettings,
+    resuming: bool,
+) -> (Vec<String>, Option<&'static str>) {
+    let mut args = Vec::new();
+    let mut warning = None;
+
+    if network.keep_fragments {
+        args.push("--keep-fragments".to_string());
+    }
+    if network.no_part {
+        args.push("--no-part".to_string());
+        if resuming {
+            args.push("--force-overwrites".to_string());
+            warning = Some(NO_PART_RESUME_WARNING);
+        }
+    }
+
+    (args, warning)
+}
+
+/// Output files announced on yt-dlp's stdout.
+#[derive(Debug, Default)]
+struct OutputTracker {
+    /// Files yt-dlp downloaded or found already on disk, in order.
+    destinations: Vec<String>,
+    /// Files written by postprocessors (merge, audio extraction, remux), in order.
+    processed: Vec<String>,
+    /// Per-chapter files from `--split-chapters`.
+    chapters: Vec<String>,
+    /// yt-dlp skipped the item because the download archive already lists it.
+    in_archive: bool,
+}
+
+impl OutputTracker {
+    fn observe(&mut self, line: &str) {
+        if line.starts_with("[download] ")
+            && line.ends_with(" has already been recorded in the archive")
+        {
+            self.in_archive = true;
+        } else if let Some(path) = parse_chapter_destination(line) {
+            push_unique(&mut self.chapters, path);
+        } else if let Some(path) = parse_postprocessor_destination(line) {
+            push_unique(&mut self.processed, path);
+        } else if let Some(path) = parse_destination(line) {
+            push_unique(&mut self.destinations, path);
+        }
+    }
+
+    /// The main output file: the last postprocessor output (e.g. the merged file)
+    /// if there was one, else the last downloaded file.
+    fn final_path(&self) -> Option<&str> {
+        self.processed
+            .last()
+            .or_else(|| self.destinations.last())
+            .map(String::as_str)
+    }
+
+    /// Every file the run produced, main file first.
+    ///
+    /// Includes intermediates such as the separate streams of a merge, which yt-dlp
+    /// usually deletes; use [`existing_paths`] to drop those once the run is over.
+    fn final_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        if let Some(primary) = self.final_path() {
+            paths.push(primary.to_string());
+        }
+        for path in self
+            .processed
+            .iter()
+            .chain(&self.destinations)
+            .chain(&self.chapters)
+        {
+            push_unique(&mut paths, path);
+        }
+        paths
+    }
+}
+
+/// Keep the paths that exist on disk, resolved against `output_dir`.
+fn existing_paths(paths: Vec<String>, output_dir: &str) -> Vec<String> {
+    paths
+        .into_iter()
+        .filter(|p| resolve_final_path(p, Some(output_dir)).exists())
+        .collect()
+}
+
+fn push_unique(paths: &mut Vec<String>, path: &str) {
+    if !paths.iter().any(|p| p == path) {
+        paths.push(path.to_string());
+    }
+}
+
+/// Path from a `[download] Destination: ...` or `... has already been downloaded` line.
+fn parse_destination(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("[download] ")?;
+    rest.strip_prefix("Destination: ")
+        .or_else(|| rest.strip_suffix(" has already been downloaded"))
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+}
+
+/// Path from a postprocessor line such as `[Merger] Merging formats into "..."`,
+/// `[ExtractAudio] Destination: ...` or `[VideoRemuxer] Remuxing ...; Destination: ...`.
+fn parse_postprocessor_destination(line: &str) -> Option<&str> {
+    const POSTPROCESSORS: &[&str] = &[
+        "[Merger] ",
+        "[ExtractAudio] ",
+        "[VideoRemuxer] ",
+        "[VideoConvertor] ",
+    ];
+    let rest = POSTPROCESSORS
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))?;
+
+    let path = if let Some(path) = rest.strip_prefix("Merging formats into ") {
+        path.trim().trim_matches('"')
+    } else {
+        rest.split_once("Destination: ")?.1.trim()
+    };
+    Some(path).filter(|p| !p.is_empty())
+}
+
+/// Path from a `[SplitChapters] Chapter 001; Destination: ...` line.
+fn parse_chapter_destination(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("[SplitChapters] Chapter ")?;
+    let (_, path) = rest.split_once("; Destination: ")?;
+    Some(path.trim()).filter(|p| !p.is_empty())
+}
+
+/// Count a completed download's size towards this session and today's usage.
+fn record_usage(db: &mut Db, session_bytes: &AtomicU64, id: Uuid) {
+    let bytes = match db.get_download(id) {
+        Ok(Some(row)) => row.bytes_total.unwrap_or(0).max(0) as u64,
+        _ => return,
+    };
+    if bytes == 0 {
+        return;
+    }
+    session_bytes.fetch_add(bytes, Ordering::Relaxed);
+    if let Err(e) = db.add_usage(chrono::Utc::now(), bytes) {
+        log::warn!("Failed to record bandwidth usage for {}: {}", id, e);
+    }
+}
+
+/// Downloads waiting for a free slot, and whether they're on hold.
+#[derive(Default)]
+struct PendingQueue {
+    ids: RwLock<VecDeque<Uuid>>,
+    /// Set by `pause_all`: nothing starts until `resume_all`.
+    paused: std::sync::atomic::AtomicBool,
+    /// Set while the network is down: nothing starts until it's back.
+    offline: std::sync::atomic::AtomicBool,
+    /// Set while yt-dlp is being replaced: nothing starts until it's done.
+    held: std::sync::atomic::AtomicBool,
+}
+
+impl PendingQueue {
+    async fn push_back(&self, id: Uuid) {
+        let mut ids = self.ids.write().await;
+        if !ids.contains(&id) {
+            ids.push_back(id);
+        }
+    }
+
+    /// Remove `id`, returning whether it was waiting.
+    async fn remove(&self, id: Uuid) -> bool {
+        let mut ids = self.ids.write().await;
+        let before = ids.len();
+        ids.retain(|p| *p != id);
+        ids.len() != before
+    }
+
+    async fn contains(&self, id: Uuid) -> bool {
+        self.ids.read().await.contains(&id)
+    }
+
+    async fn len(&self) -> usize {
+        self.ids.read().await.len()
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    /// Returns whether it was offline before.
+    fn set_offline(&self, offline: bool) -> bool {
+        self.offline.swap(offline, Ordering::SeqCst)
+    }
+
+    fn is_held(&self) -> bool {
+        self.held.load(Ordering::SeqCst)
+    }
+
+    fn set_held(&self, held: bool) {
+        self.held.store(held, Ordering::SeqCst);
+    }
+
+    /// The next download to start, unless the queue is paused, offline or held.
+    async fn next(&self) -> Option<Uuid> {
+        if self.is_paused() || self.is_offline() || self.is_held() {
+            return None;
+        }
+        self.ids.write().await.pop_front()
+    }
+}
+
+/// Network failures within this window count towards going offline.
+const OFFLINE_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// This many network failures within [`OFFLINE_FAILURE_WINDOW`] means the machine,
+/// not one download, lost connectivity.
+const OFFLINE_FAILURE_THRESHOLD: usize = 3;
+
+/// How often to check whether connectivity is back while offline.
+const CONNECTIVITY_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Phase shown on downloads held until the network comes back.
+const WAITING_FOR_NETWORK: &str = "Waiting for network connection";
+
+/// Progress event for a download held until the network is back.
+fn waiting_for_network(id: Uuid, detail: Option<String>) -> DownlinkEvent {
+    DownlinkEvent::DownloadProgress {
+        id,
+        status: events::DownloadStatus::Queued,
+        progress: Progress {
+            percent: None,
+            bytes_downloaded: None,
+            bytes_total: None,
+            speed_bps: None,
+            eta_seconds: None,
+            phase: Some(Phase {
+                name: WAITING_FOR_NETWORK.to_string(),
+                detail,
+            }),
+        },
+    }
+}
+
+/// Recent `Network` failures, to tell the connection dropping apart from one
+/// flaky download.
+#[derive(Default)]
+struct NetworkFailures {
+    times: std::sync::Mutex<VecDeque<(std::time::Instant, Uuid)>>,
+}
+
+impl NetworkFailures {
+    /// Record a failure of `id` at `now`. True once enough failures happened close
+    /// together.
+    fn record(&self, id: Uuid, now: std::time::Instant) -> bool {
+        let Ok(mut times) = self.times.lock() else {
+            return false;
+        };
+        while times
+            .front()
+            .is_some_and(|(t, _)| now.saturating_duration_since(*t) > OFFLINE_FAILURE_WINDOW)
+        {
+            times.pop_front();
+        }
+        times.push_back((now, id));
+        times.len() >= OFFLINE_FAILURE_THRESHOLD
+    }
+
+    /// Downloads that failed within the window, oldest first.
+    fn recent(&self) -> Vec<Uuid> {
+        let Ok(times) = self.times.lock() else {
+            return Vec::new();
+        };
+        let mut ids: Vec<Uuid> = Vec::new();
+        for (_, id) in times.iter() {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+        ids
+    }
+
+    fn clear(&self) {
+        if let Ok(mut times) = self.times.lock() {
+            times.clear();
+        }
+    }
+}
+
+/// Hold the downloads that failed on the network before it was found to be down.
+///
+/// Those still waiting to retry are held by `start` when the retry comes in; the
+/// ones out of retries were marked failed and are queued again here.
+async fn hold_earlier_network_failures(
+    db: &DbHandle,
+    pending: &PendingQueue,
+    event_tx: &mpsc::Sender<DownlinkEvent>,
+    earlier: Vec<Uuid>,
+) {
+    for id in earlier {
+        let held = db
+            .call(move |db| {
+                let failed_offline = db.get_download(id)?.is_some_and(|row| {
+                    row.status == DownloadStatus::Failed
+                        && row.error_code.as_deref() == Some(ErrorCode::Network.as_str())
+                });
+                if failed_offline {
+                    db.set_status(id, DownloadStatus::Queued, Some(WAITING_FOR_NETWORK))?;
+                }
+                Ok(failed_offline)
+            })
+            .await
+            .unwrap_or(false);
+        if held {
+            log::info!(
+                "Download {} failed before the network was found down, holding it",
+                id
+            );
+            pending.push_back(id).await;
+            let _ = event_tx.send(waiting_for_network(id, None)).await;
+        }
+    }
+}
+
+/// Hand the next download waiting for a slot to the retry loop, which starts it.
+async fn start_next_pending(pending: &PendingQueue, retry_tx: &mpsc::UnboundedSender<Uuid>) {
+    if let Some(next) = pending.next().await {
+        log::info!("Starting queued download {}", next);
+        let _ = retry_tx.send(next);
+    }
+}
+
+/// Execute a single download.
+#[allow(clippy::too_many_arguments)]
+async fn execute_download(
+    download: &DownloadRow,
+    config: &DownloadConfig,
+    settings: &UserSettings,
+    credentials: Option<&SiteCredentials>,
+    outputs: &mut OutputTracker,
+    mut cancel_rx: broadcast::Receiver<()>,
+    event_tx: mpsc::Sender<DownlinkEvent>,
+    speed_samples: &RwLock<HashMap<Uuid, SpeedHistory>>,
+    db: &DbHandle,
+) -> Result<Option<String>, DownloadError> {
+    let id = download.id;
+
+    // Partial download: yt-dlp cuts sections with ffmpeg, so refuse early without it.
+    if download.sections.is_some() && config.ffmpeg_path.is_none() {
+        return Err(DownloadError::Failed {
+            code: ErrorCode::ToolMissing,
+            message: "Downloading a section of a video requires ffmpeg, which was not found."
+                .to_string(),
+            actions: vec![Action {
+                kind: ActionKind::UpdateFfmpeg,
+                label: "Install ffmpeg".to_string(),
+            }],
+            raw_error: None,
+        });
+    }
+
+    let credentials_config = match credentials {
+        Some(credentials) => Some(
+            CredentialsConfig::write(credentials_config_path(&config.tmp_dir, id), credentials)
+                .map_err(|e| DownloadError::Failed {
+                    code: ErrorCode::Unknown,
+                    message: format!("Failed to pass the site login to yt-dlp: {}", e),
+                    actions: vec![],
+                    raw_error: None,
+                })?,
+        ),
+        None => None,
+    };
+    let args = build_download_args(
+        download,
+        config,
+        settings,
+        credentials_config.as_ref().map(|c| c.path.as_path()),
+    );
+
+    log::info!(
+        "Starting download {} with args: {:?}",
+        id,
+        redact_args(&args)
+    );
+
+    let mut cmd = Command::new(&config.yt_dlp_path);
+    cmd.args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Hide console window on Windows
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let mut child = cmd.spawn().map_err(|e| DownloadError::Failed {
+        code: ErrorCode::ToolMissing,
+        message: format!("Failed to start yt-dlp: {}", e),
+        actions: vec![],
+        raw_error: None,
+    })?;
+
+    let stdout = child.stdout.take().ok_or_else(|| DownloadError::Failed {
+        code: ErrorCode::Unknown,
+        message: "Failed to capture stdout".to_string(),
+        actions: vec![],
+        raw_error: None,
+    })?;
+
+    let stderr = child.stderr.take().ok_or_else(|| DownloadError::Failed {
+        code: ErrorCode::Unknown,
+        message: "Failed to capture stderr".to_string(),
+        actions: vec![],
+        raw_error: None,
+    })?;
+
+    let mut stdout_reader = BufReader::new(stdout).lines();
+    let mut stderr_reader = BufReader::new(stderr).lines();
+
+    let mut stderr_lines: Vec<String> = Vec::new();
+    let mut over_size_limit = false;
+    let mut last_emitted: Option<(std::time::Instant, f64)> = None;
+    let mut eta_smoother = EtaSmoother::default();
+
+    // Progress regex for our custom template: [downlink] 50.5% 1.5MiB/s 00:30 100MiB
+    let progress_re = Regex::new(r"\[downlink\]\s+(\S+)\s+(\S+)\s+(\S+)\s+(\S+)").ok();
+    // Fallback: standard yt-dlp progress line: [download]  50.5% of 100.00MiB at 1.50MiB/s ETA 00:30
+    let fallback_progress_re =
+        Regex::new(r"\[download\]\s+(\d+\.?\d*)%\s+of\s+(\S+)\s+at\s+(\S+)\s+ETA\s+(\S+)").ok();
+    // Also match: [download]  50.5% of ~100.00MiB at 1.50MiB/s ETA 00:30
+    let fallback_progress_re2 = Regex::new(r"\[download\]\s+(\d+\.?\d*)%").ok();
+    let merge_re = Regex::new(r"\[Merger\]|Merging formats|\[ffmpeg\]").ok();
+    let postprocessor_re = Regex::new(POSTPROCESSOR_LINE).ok();
+    let sleep_re = Regex::new(SLEEP_LINE).ok();
+    let finished_re = Regex::new(r#"\[download\] 100%"#).ok();
+
+    // yt-dlp may pause up to the polite-mode sleep between requests, usually silently
+    let stall_timeout = config.stall_timeout + polite_sleep(&settings.network);
+    let mut watchdog = StallWatchdog::new(stall_timeout);
+
+    // Read until both streams close: stdout can close first with the error lines
+    // still buffered on stderr, and cancel and the watchdog apply all along
+    let mut stdout_open = true;
+    let mut stderr_open = true;
+    while stdout_open || stderr_open {
+        tokio::select! {
+            _ = cancel_rx.recv() => {
+                log::info!("Download {} received cancel signal", id);
+                let _ = child.kill().await;
+                return Err(DownloadError::Stopped);
+            }
+            _ = watchdog.stalled() => {
+                log::warn!(
+                    "Download {} produced no output for {:?}, killing stalled yt-dlp",
+                    id,
+                    stall_timeout
+                );
+                let _ = child.kill().await;
+                return Err(DownloadError::Failed {
+                    code: ErrorCode::Network,
+                    message: format!(
+                        "Download stalled: no progress for {} seconds. Check your connection and retry.",
+                        stall_timeout.as_secs()
+                    ),
+                    actions: vec![Action {
+                        kind: ActionKind::Retry,
+                        label: "Retry".to_string(),
+                    }],
+                    raw_error: None,
+                });
+            }
+            line = stdout_reader.next_line(), if stdout_open => {
+                match line {
+                    Ok(Some(l)) => {
+                        log::info!("yt-dlp stdout: {}", l);
+                        watchdog.reset();
+
+                        // yt-dlp reports --max-filesize aborts on stdout and still exits 0
+                        if l.contains(MAX_FILESIZE_MARKER) {
+                            over_size_limit = true;
+                            stderr_lines.push(l.clone());
+                        }
+
+                        // Try to parse progress from various formats
+                        let mut parsed: Option<ParsedProgress> = None;
+
+                        // Try our custom template first
+                        if let Some(ref re) = progress_re {
+                            if let Some(caps) = re.captures(&l) {
+                                parsed = Some(parse_progress_line(&caps));
+                            }
+                        }
+
+                        // Fallback to standard yt-dlp progress format
+                        if parsed.is_none() {
+                            if let Some(ref re) = fallback_progress_re {
+                                if let Some(caps) = re.captures(&l) {
+                                    let percent = caps.get(1).and_then(|m| m.as_str().parse::<f64>().ok());
+                                    let total = caps.get(2).map(|m| m.as_str().to_string());
+                                    let speed = caps.get(3).and_then(|m| parse_speed(m.as_str()));
+                                    let eta = caps.get(4).and_then(|m| parse_eta(m.as_str()));
+                                    parsed = Some(ParsedProgress {
+                                        percent,
+                                        bytes_total: total.as_deref().and_then(parse_bytes),
+                                        bytes_downloaded: None,
+                                        speed_bps: speed,
+                                        eta_seconds: eta,
+                                        phase: Some("Downloading".to_string()),
+                                    });
+                                }
+                            }
+                        }
+
+                        // Simple fallback - just get percent
+                        if parsed.is_none() {
+                            if let Some(ref re) = fallback_progress_re2 {
+                                if let Some(caps) = re.captures(&l) {
+                                    let percent = caps.get(1).and_then(|m| m.as_str().parse::<f64>().ok());
+                                    if percent.is_some() {
+                                        parsed = Some(ParsedProgress {
+                                            percent,
+                                            bytes_total: None,
+                                            bytes_downloaded: None,
+                                            speed_bps: None,
+                                            eta_seconds: None,
+                                            phase: Some("Downloading".to_string()),
+                                        });
+                                    }
+                                }
+                            }
+                        }
+
+                        // Send progress event if we parsed something
+                        if let Some(mut p) = parsed {
+                            // Transferring again, e.g. the next format after a fixup
+                            watchdog.rearm();
+                            // Every sample feeds the average, even ones the throttle drops
+                            p.eta_seconds = eta_smoother.eta(&p);
+
+                            // Throttle so small files don't flood the frontend with events
+                            let current_percent = p.percent.unwrap_or(0.0);
+                            let now = std::time::Instant::now();
+                            if config.progress_throttle.should_emit(last_emitted, now, current_percent) {
+                                last_emitted = Some((now, current_percent));
+                                log::info!("Progress: {}%", current_percent);
+                                if let Some(history) = speed_samples.write().await.get_mut(&id) {
+                                    history.push(SpeedSample {
+                                        at: Utc::now(),
+                                        speed_bps: p.speed_bps,
+                                        percent: p.percent,
+                                    });
+                                }
+                                db.record_progress(ProgressUpdate {
+                                    id,
+                                    percent: p.percent,
+                                    bytes_downloaded: p.bytes_downloaded.map(|b| b as i64),
+                                    bytes_total: p.bytes_total.map(|b| b as i64),
+                                    speed_bps: p.speed_bps.map(|b| b as i64),
+                                    eta_seconds: p.eta_seconds.map(|s| s as i64),
+                                });
+                                let _ = event_tx.send(DownlinkEvent::DownloadProgress {
+                                    id,
+                                    status: events::DownloadStatus::Downloading,
+                                    progress: Progress {
+                                        percent: p.percent,
+                                        bytes_downloaded: p.bytes_downloaded,
+                                        bytes_total: p.bytes_total,
+                                        speed_bps: p.speed_bps,
+                                        eta_seconds: p.eta_seconds,
+                                        phase: Some(Phase {
+                                            name: p.phase.clone().unwrap_or_else(|| "Downloading".to_string()),
+                                            detail: None,
+                                        }),
+                                    },
+                                }).await;
+                            }
+                        }
+
+                        if postprocessor_re.as_ref().is_some_and(|re| re.is_match(&l))
+                            || sleep_re.as_ref().is_some_and(|re| re.is_match(&l))
+                        {
+                            watchdog.disarm();
+                        }
+
+                        // Check for merge phase
+                        if let Some(ref re) = merge_re {
+                            if re.is_match(&l) {
+                                log::info!("Post-processing: merging streams");
+                                let _ = event_tx.send(DownlinkEvent::DownloadPostProcessing {
+                                    id,
+                                    step: "Merging streams".to_string(),
+                                    detail: None,
+                                }).await;
+                            }
+                        }
+
+                        // Check for 100% complete
+                        if let Some(ref re) = finished_re {
+                            if re.is_match(&l) {
+                                log::info!("Download complete, post-processing...");
+                                let _ = event_tx.send(DownlinkEvent::DownloadProgress {
+                                    id,
+                                    status: events::DownloadStatus::Downloading,
+                                    progress: Progress {
+                                        percent: Some(100.0),
+                                        bytes_downloaded: None,
+                                        bytes_total: None,
+                                        speed_bps: None,
+                                        eta_seconds: None,
+                                        phase: Some(Phase {
+                                            name: "Finishing...".to_string(),
+                                            detail: None,
+                                        }),
+                                    },
+                                }).await;
+                            }
+                        }
+
+                        // Capture destination paths
+                        outputs.observe(&l);
+                    }
+                    Ok(None) => stdout_open = false,
+                    Err(e) => {
+                        log::error!("Error reading stdout: {}", e);
+                        stdout_open = false;
+                    }
+                }
+            }
+            line = stderr_reader.next_line(), if stderr_open => {
+                match line {
+                    Ok(Some(l)) => {
+                        log::debug!("yt-dlp stderr: {}", l);
+                        stderr_lines.push(l);
+                    }
+                    Ok(None) => stderr_open = false,
+                    Err(e) => {
+                        log::error!("Error reading stderr: {}", e);
+                        stderr_open = false;
+                    }
+                }
+            }
+        }
+    }
+
+    // Wait for process to exit
+    let status = child.wait().await.map_err(|e| DownloadError::Failed {
+        code: ErrorCode::Unknown,
+        message: format!("Failed to wait for yt-dlp: {}", e),
+        actions: vec![],
+        raw_error: None,
+    })?;
+
+    if !status.success() || over_size_limit {
+        return Err(failure_from_stderr(&stderr_lines));
+    }
+
+    Ok(outputs.final_path().map(str::to_string))
+}
+
+/// Maximum number of characters of stderr kept as `raw_error`.
+const RAW_ERROR_MAX_CHARS: usize = 2000;
+
+/// Classify a failed yt-dlp run from its stderr, keeping a bounded tail as `raw_error`.
+fn failure_from_stderr(stderr_lines: &[String]) -> DownloadError {
+    let stderr_text = stderr_lines.join("\n");
+    let (code, message, actions) = classify_error(&stderr_text);
+    let raw_error = tail_chars(stderr_text.trim(), RAW_ERROR_MAX_CHARS);
+
+    DownloadError::Failed {
+        code,
+        message,
+        actions,
+        raw_error: (!raw_error.is_empty()).then(|| raw_error.to_string()),
+    }
+}
+
+/// The last `max` characters of `s`, respecting UTF-8 boundaries.
+fn tail_chars(s: &str, max: usize) -> &str {
+    match s.char_indices().rev().nth(max.saturating_sub(1)) {
+        Some((idx, _)) if max > 0 => &s[idx..],
+        _ if max == 0 => "",
+        _ => s,
+    }
+}
+
+/// Resolve a captured `final_path` to a concrete path.
+///
+/// yt-dlp's `Destination:` line may be relative to the output directory; absolute
+/// paths are returned unchanged.
+pub fn resolve_final_path(final_path: &str, output_dir: Option<&str>) -> PathBuf {
+    let path = PathBuf::from(final_path);
+    match output_dir {
+        Some(dir) if path.is_relative() && !dir.is_empty() => Path::new(dir).join(path),
+        _ => path,
+    }
+}
+
+/// Actual media figures read from a finished file with ffprobe.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProbedMedia {
+    pub duration_seconds: Option<f64>,
+    pub size_bytes: Option<u64>,
+}
+
+/// Run ffprobe against a finished file. Returns None if ffprobe fails or reports nothing.
+async fn probe_media(ffprobe: &Path, file: &Path) -> Option<ProbedMedia> {
+    let mut cmd = Command::new(ffprobe);
+    cmd.args(["-v", "quiet", "-print_format", "json", "-show_format"])
+        .arg(file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = match tokio::time::timeout(Duration::from_secs(30), cmd.output()).await {
+        Ok(Ok(output)) if output.status.success() => output,
+        Ok(Ok(output)) => {
+            log::warn!(
+                "ffprobe exited with {} for {}",
+                output.status,
+                file.display()
+            );
+            return None;
+        }
+        Ok(Err(e)) => {
+            log::warn!("Failed to run ffprobe: {}", e);
+            return None;
+        }
+        Err(_) => {
+            log::warn!("ffprobe timed out for {}", file.display());
+            return None;
+        }
+    };
+
+    parse_ffprobe_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse `ffprobe -print_format json -show_format` output.
+///
+/// ffprobe reports `duration` and `size` as strings inside `format`.
+fn parse_ffprobe_output(json: &str) -> Option<ProbedMedia> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let format = value.get("format")?;
+
+    let field = |key: &str| format.get(key).and_then(|v| v.as_str());
+    let probed = ProbedMedia {
+        duration_seconds: field("duration")
+            .and_then(|d| d.parse::<f64>().ok())
+            .filter(|d| d.is_finite() && *d >= 0.0),
+        size_bytes: field("size").and_then(|s| s.parse::<u64>().ok()),
+    };
+
+    if probed == ProbedMedia::default() {
+        None
+    } else {
+        Some(probed)
+    }
+}
+
+/// Parse progress from our custom template output.
+fn parse_progress_line(caps: &regex::Captures) -> ParsedProgress {
+    let percent_str = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+    let speed_str = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+    let eta_str = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+    let total_str = caps.get(4).map(|m| m.as_str()).unwrap_or("");
+
+    ParsedProgress {
+        percent: parse_percent(percent_str),
+        speed_bps: parse_speed(speed_str),
+        eta_seconds: parse_eta(eta_str),
+        bytes_total: parse_bytes(total_str),
+        bytes_downloaded: None, // We can calculate from percent * total if needed
+        phase: Some("Downloading".to_string()),
+    }
+}
+
+fn parse_percent(s: &str) -> Option<f64> {
+    let cleaned = s.trim_end_matches('%').trim();
+    cleaned.parse::<f64>().ok()
+}
+
+fn parse_speed(s: &str) -> Option<u64> {
+    // Format: "1.5MiB/s" or "500KiB/s"
+    let s = s.trim();
+    if s == "N/A" || s.is_empty() {
+        return None;
+    }
+
+    let re = Regex::new(r"([\d.]+)\s*(Ki?B|Mi?B|Gi?B|B)").ok()?;
+    let caps = re.captures(s)?;
+    let num: f64 = caps.get(1)?.as_str().parse().ok()?;
+    let unit = caps.get(2)?.as_str();
+
+    let multiplier: f64 = match unit {
+        "B" => 1.0,
+        "KB" | "KiB" => 1024.0,
+        "MB" | "MiB" => 1024.0 * 1024.0,
+        "GB" | "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => 1.0,
+    };
+
+    Some((num * multiplier) as u64)
+}
+
+fn parse_eta(s: &str) -> Option<u64> {
+    // Format: "00:05:30" or "05:30" or "30" or "N/A"
+    let s = s.trim();
+    if s == "N/A" || s.is_empty() {
+        return None;
+    }
+
+    let parts: Vec<&str> = s.split(':').collect();
+    match parts.len() {
+        1 => parts[0].parse::<u64>().ok(),
+        2 => {
+            let mins: u64 = parts[0].parse().ok()?;
+            let secs: u64 = parts[1].parse().ok()?;
+            Some(mins * 60 + secs)
+        }
+        3 => {
+            let hours: u64 = parts[0].parse().ok()?;
+            let mins: u64 = parts[1].parse().ok()?;
+            let secs: u64 = parts[2].parse().ok()?;
+            Some(hours * 3600 + mins * 60 + secs)
+        }
+        _ => None,
+    }
+}
+
+fn parse_bytes(s: &str) -> Option<u64> {
+    // Format: "1.5GiB" or "500MiB" or "N/A"
+    let s = s.trim();
+    if s == "N/A" || s.is_empty() {
+        return None;
+    }
+
+    let re = Regex::new(r"([\d.]+)\s*(Ki?B|Mi?B|Gi?B|B)").ok()?;
+    let caps = re.captures(s)?;
+    let num: f64 = caps.get(1)?.as_str().parse().ok()?;
+    let unit = caps.get(2)?.as_str();
+
+    let multiplier: f64 = match unit {
+        "B" => 1.0,
+        "KB" | "KiB" => 1024.0,
+        "MB" | "MiB" => 1024.0 * 1024.0,
+        "GB" | "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => 1.0,
+    };
+
+    Some((num * multiplier) as u64)
+}
+
+/// Classify yt-dlp errors into user-friendly categories with remediation actions.
+pub fn classify_error(stderr: &str) -> (ErrorCode, String, Vec<Action>) {
+    let stderr_lower = stderr.to_lowercase();
+
+    // Aborted by our own --max-filesize
+    if stderr_lower.contains(MAX_FILESIZE_MARKER) {
+        return (
+            ErrorCode::FileTooLarge,
+            "File exceeds your size limit. Raise the maximum file size in Settings to download it."
+                .to_string(),
+            vec![Action {
+                kind: ActionKind::OpenSettingsSizeLimit,
+                label: "Raise size limit".to_string(),
+            }],
+        );
+    }
+
+    // The filesystem rejected the filename (too long, or characters FAT/SMB don't allow)
+    if stderr_lower.contains("file name too long")
+        || stderr_lower.contains("filename too long")
+        || stderr_lower.contains("errno 36")
+        || stderr_lower.contains("illegal byte sequence")
+        || ((stderr_lower.contains("unable to open for writing")
+            || stderr_lower.contains("unable to create directory"))
+            && (stderr_lower.contains("invalid argument") || stderr_lower.contains("errno 22")))
+    {
+        return (
+            ErrorCode::OutputWriteFailed,
+            "The file couldn't be created because the filename is too long or has characters \
+             this drive doesn't allow. Turn on \"Restrict filenames\" or limit the filename \
+             length in Settings, then retry."
+                .to_string(),
+            vec![
+                Action {
+                    kind: ActionKind::OpenSettingsFilenames,
+                    label: "Filename settings".to_string(),
+                },
+                Action {
+                    kind: ActionKind::Retry,
+                    label: "Retry".to_string(),
+                },
+            ],
+        );
+    }
+
+    // Rate limited by the site (checked first: 429 pages often mention bots/sign-in too)
+    if stderr_lower.contains("http error 429") || stderr_lower.contains("too many requests") {
+        return (
+            ErrorCode::RateLimited,
+            format!(
+                "The site is rate limiting downloads (HTTP 429). Wait about {} seconds, then retry.",
+                RATE_LIMIT_COOLDOWN.as_secs()
+            ),
+            vec![Action {
+                kind: ActionKind::Retry,
+                label: "Retry after cooldown".to_string(),
+            }],
+        );
+    }
+
+    // ffmpeg post-processing (merge/convert/embed) failed after the download itself worked
+    if stderr_lower.contains("postprocessing")
+        || stderr_lower.contains("conversion failed")
+        || stderr_lower.contains("ffmpeg exited with code")
+    {
+        return (
+            ErrorCode::PostProcessingFailed,
+            "The download finished but processing the file with ffmpeg failed.".to_string(),
+            vec![
+                Action {
+                    kind: ActionKind::UpdateFfmpeg,
+                    label: "Update ffmpeg".to_string(),
+                },
+                Action {
+                    kind: ActionKind::OpenLogs,
+                    label: "View logs".to_string(),
+                },
+            ],
+        );
+    }
+
+    // Sign-in / cookies required
+    if stderr_lower.contains("sign in")
+        || stderr_lower.contains("login")
+        || stderr_lower.contains("cookies")
+        || stderr_lower.contains("age-restricted")
+    {
+        return (
+            ErrorCode::LoginRequired,
+            "This content requires sign-in. Import cookies from your browser and retry."
+                .to_string(),
+            vec![Action {
+                kind: ActionKind::ImportCookies,
+                label: "Import cookies from browser".to_string(),
+            }],
+        );
+    }
+
+    // Bot check / CAPTCHA
+    if stderr_lower.contains("bot")
+        || stderr_lower.contains("captcha")
+        || stderr_lower.contains("confirm you're not")
+    {
+        return (
+            ErrorCode::BotCheck,
+            "The site requires verification. Import cookies from a logged-in browser session."
+                .to_string(),
+            vec![Action {
+                kind: ActionKind::ImportCookies,
+                label: "Import cookies from browser".to_string(),
+            }],
+        );
+    }
+
+    // Geo-restriction
+    if stderr_lower.contains("not available in your country")
+        || stderr_lower.contains("geo")
+        || stderr_lower.contains("blocked")
+    {
+        return (
+            ErrorCode::GeoRestricted,
+            "This content is not available in your region.".to_string(),
+            vec![
+                Action {
+                    kind: ActionKind::TryGeoBypass,
+                    label: "Try geo-bypass".to_string(),
+                },
+                Action {
+                    kind: ActionKind::OpenSettingsProxy,
+                    label: "Configure proxy".to_string(),
+                },
+            ],
+        );
+    }
+
+    // Extractor outdated
+    if stderr_lower.contains("unsupported url")
+        || stderr_lower.contains("no video formats")
+        || stderr_lower.contains("extractor")
+    {
+        return (
+            ErrorCode::ExtractorOutdated,
+            "The downloader engine may be outdated for this site.".to_string(),
+            vec![
+                Action {
+                    kind: ActionKind::UpdateYtDlp,
+                    label: "Update yt-dlp".to_string(),
+                },
+                Action {
+                    kind: ActionKind::Retry,
+                    label: "Retry".to_string(),
+                },
+            ],
+        );
+    }
+
+    // Format unavailable
+    if stderr_lower.contains("requested format") || stderr_lower.contains("format not available") {
+        return (
+            ErrorCode::FormatUnavailable,
+            "The requested format is not available for this content.".to_string(),
+            vec![Action {
+                kind: ActionKind::RetryRecommended,
+                label: "Use Recommended preset".to_string(),
+            }],
+        );
+    }
+
+    // Network errors
+    if stderr_lower.contains("network")
+        || stderr_lower.contains("connection")
+        || stderr_lower.contains("timeout")
+        || stderr_lower.contains("timed out")
+    {
+        return (
+            ErrorCode::Network,
+            "Network error occurred. Check your connection and retry.".to_string(),
+            vec![Action {
+                kind: ActionKind::Retry,
+                label: "Retry".to_string(),
+            }],
+        );
+    }
+
+    // Default: unknown error
+    let message = match stderr.char_indices().nth(200) {
+        Some((end, _)) => format!("Download failed: {}…", &stderr[..end]),
+        None if stderr.is_empty() => "Download failed with unknown error.".to_string(),
+        None => format!("Download failed: {}", stderr),
+    };
+
+    (
+        ErrorCode::Unknown,
+        message,
+        vec![
+            Action {
+                kind: ActionKind::Retry,
+                label: "Retry".to_string(),
+            },
+            Action {
+                kind: ActionKind::OpenLogs,
+                label: "View logs".to_string(),
+            },
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("50.5%"), Some(50.5));
+        assert_eq!(parse_percent("100%"), Some(100.0));
+        assert_eq!(parse_percent("N/A"), None);
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(parse_speed("1.5MiB/s"), Some(1572864));
+        assert_eq!(parse_speed("500KiB/s"), Some(512000));
+        assert_eq!(parse_speed("N/A"), None);
+    }
+
+    #[test]
+    fn test_parse_eta() {
+        assert_eq!(parse_eta("30"), Some(30));
+        assert_eq!(parse_eta("05:30"), Some(330));
+        assert_eq!(parse_eta("01:05:30"), Some(3930));
+        assert_eq!(parse_eta("N/A"), None);
+    }
+
+    #[test]
+    fn test_classify_error_rate_limited() {
+        let (code, _, actions) = classify_error(
+            "ERROR: [youtube] abc: Unable to download webpage: HTTP Error 429: Too Many Requests",
+        );
+        assert!(matches!(code, ErrorCode::RateLimited));
+        assert!(matches!(actions[0].kind, ActionKind::Retry));
+    }
+
+    #[test]
+    fn test_classify_error_rate_limited_suggests_cooldown() {
+        let stderr = "WARNING: [youtube] abc: HTTP Error 429: Too Many Requests\n\
+                      ERROR: [youtube] abc: Unable to download API page: too many requests";
+        let (code, message, actions) = classify_error(stderr);
+
+        assert!(matches!(code, ErrorCode::RateLimited));
+        assert!(message.contains("60 seconds"));
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].label, "Retry after cooldown");
+    }
+
+    #[test]
+    fn test_retry_delay_rate_limited_uses_fixed_cooldown() {
+        let base = Duration::from_secs(5);
+        for attempt in 1..=3 {
+            assert_eq!(
+                retry_delay(&ErrorCode::RateLimited, attempt, 3, base),
+                Some(RATE_LIMIT_COOLDOWN)
+            );
+        }
+        assert_eq!(retry_delay(&ErrorCode::RateLimited, 4, 3, base), None);
+    }
+
+    #[test]
+    fn test_retry_delay_network_backs_off() {
+        let base = Duration::from_secs(5);
+        assert_eq!(
+            retry_delay(&ErrorCode::Network, 1, 3, base),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            retry_delay(&ErrorCode::Network, 2, 3, base),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            retry_delay(&ErrorCode::Network, 3, 3, base),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            retry_delay(&ErrorCode::Network, 10, 20, base),
+            Some(MAX_RETRY_DELAY)
+        );
+        assert_eq!(retry_delay(&ErrorCode::Network, 1, 0, base), None);
+    }
+
+    #[test]
+    fn test_retry_delay_skips_permanent_errors() {
+        let base = Duration::from_secs(5);
+        assert_eq!(retry_delay(&ErrorCode::LoginRequired, 1, 3, base), None);
+        assert_eq!(retry_delay(&ErrorCode::FormatUnavailable, 1, 3, base), None);
+        assert_eq!(retry_delay(&ErrorCode::Unknown, 1, 3, base), None);
+    }
+
+    #[test]
+    fn test_classify_error_file_too_large() {
+        let (code, message, actions) = classify_error(
+            "[download] File is larger than max-filesize (1073741824 bytes > 524288000 bytes). Aborting.",
+        );
+        assert!(matches!(code, ErrorCode::FileTooLarge));
+        assert!(message.contains("size limit"));
+        assert!(matches!(actions[0].kind, ActionKind::OpenSettingsSizeLimit));
+        assert_eq!(
+            retry_delay(&code, 1, 3, Duration::from_secs(5)),
+            None,
+            "a size-limit abort should not be auto-retried"
+        );
+    }
+
+    #[test]
+    fn test_classify_error_filesystem_rejects_filename() {
+        for stderr in [
+            "ERROR: unable to open for writing: [Errno 36] File name too long: '/dl/a very long title.mp4.part'",
+            "ERROR: unable to open for writing: [Errno 22] Invalid argument: 'E:\\dl\\Q&A: why?.webm.part'",
+            "ERROR: unable to create directory [Errno 22] Invalid argument: '/mnt/share/Live | 2024'",
+            "ERROR: unable to open for writing: [Errno 84] Illegal byte sequence",
+        ] {
+            let (code, message, actions) = classify_error(stderr);
+            assert!(matches!(code, ErrorCode::OutputWriteFailed), "{stderr}");
+            assert!(message.contains("Restrict filenames"));
+            assert!(matches!(actions[0].kind, ActionKind::OpenSettingsFilenames));
+        }
+    }
+
+    #[test]
+    fn test_classify_error_postprocessing() {
+        let (code, _, _) =
+            classify_error("ERROR: Postprocessing: Error opening output files: Invalid argument");
+        assert!(matches!(code, ErrorCode::PostProcessingFailed));
+
+        let (code, _, _) = classify_error("ERROR: ffmpeg exited with code 1");
+        assert!(matches!(code, ErrorCode::PostProcessingFailed));
+    }
+
+    #[test]
+    fn test_failure_from_stderr_populates_raw_error() {
+        let lines = vec![
+            "WARNING: [youtube] falling back to generic extractor".to_string(),
+            "ERROR: Postprocessing: Conversion failed!".to_string(),
+        ];
+
+        match failure_from_stderr(&lines) {
+            DownloadError::Failed {
+                code, raw_error, ..
+            } => {
+                assert!(matches!(code, ErrorCode::PostProcessingFailed));
+                assert_eq!(raw_error.as_deref(), Some(lines.join("\n").as_str()));
+            }
+            other => panic!("expected failure, got {:?}", other),
+        }
+
+        match failure_from_stderr(&[]) {
+            DownloadError::Failed { raw_error, .. } => assert_eq!(raw_error, None),
+            other => panic!("expected failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tail_chars() {
+        assert_eq!(tail_chars("hello", 10), "hello");
+        assert_eq!(tail_chars("hello", 3), "llo");
+        assert_eq!(tail_chars("héllo wörld", 5), "wörld");
+        assert_eq!(tail_chars("hello", 0), "");
+    }
+
+    #[test]
+    fn test_classify_error_fallback_truncates_on_a_char_boundary() {
+        // "é" is two bytes, so byte 200 falls inside one
+        let stderr = format!("x{}", "é".repeat(250));
+        let (code, message, _) = classify_error(&stderr);
+        assert_eq!(code, ErrorCode::Unknown);
+        assert_eq!(message, format!("Download failed: x{}…", "é".repeat(199)));
+
+        let (_, message, _) = classify_error("short é error");
+        assert_eq!(message, "Download failed: short é error");
+    }
+
+    #[test]
+    fn test_classify_error_login() {
+        let (code, _, _) = classify_error("Sign in to confirm your age");
+        assert!(matches!(code, ErrorCode::LoginRequired));
+    }
+
+    #[tokio::test]
+    async fn test_stall_watchdog_disarmed_never_fires() {
+        let mut watchdog = StallWatchdog::new(Duration::from_millis(10));
+        watchdog.disarm();
+
+        let fired = tokio::time::timeout(Duration::from_millis(100), watchdog.stalled()).await;
+        assert!(fired.is_err());
+
+        watchdog.rearm();
+        let fired = tokio::time::timeout(Duration::from_millis(100), watchdog.stalled()).await;
+        assert!(fired.is_ok());
+    }
+
+    #[test]
+    fn test_sleep_lines_disarm_the_watchdog() {
+        let re = Regex::new(SLEEP_LINE).unwrap();
+        assert!(re.is_match("[download] Sleeping 5.00 seconds ..."));
+        assert!(re.is_match("[youtube] Sleeping 2 seconds ..."));
+        assert!(!re.is_match("[download] Destination: Sleeping 5 seconds.mp4"));
+    }
+
+    #[test]
+    fn test_postprocessor_lines_disarm_the_watchdog() {
+        let re = Regex::new(POSTPROCESSOR_LINE).unwrap();
+        for line in [
+            "[Merger] Merging formats into \"clip.mkv\"",
+            "[ExtractAudio] Destination: clip.mp3",
+            "[VideoRemuxer] Remuxing video from webm to mp4",
+            "[SplitChapters] Splitting video by chapters; 12 chapters found",
+            "[FixupM3u8] Fixing MPEG-TS in MP4 container of \"clip.mp4\"",
+            "[ffmpeg] Applying loudnorm",
+            "[EmbedThumbnail] ffmpeg: Adding thumbnail to \"clip.mp4\"",
+        ] {
+            assert!(re.is_match(line), "{line}");
+        }
+        for line in [
+            "[download]  45.0% of 10.00MiB at 1.00MiB/s ETA 00:05",
+            "[downlink] 45.0% 1.0MiB/s 00:05 10MiB",
+            "[youtube] abc: Downloading webpage",
+            "[info] abc: Downloading 1 format(s): 137+140",
+        ] {
+            assert!(!re.is_match(line), "{line}");
+        }
+    }
+
+    fn sample_row(preset_id: &str) -> DownloadRow {
+        let now = chrono::Utc::now();
+        DownloadRow {
+            id: Uuid::new_v4(),
+            created_at: now,
+            updated_at: now,
+            source_url: "https://example.com/watch?v=abc".to_string(),
+            source_kind: crate::db::SourceKind::Single,
+            parent_id: None,
+            title: None,
+            uploader: None,
+            duration_seconds: None,
+            thumbnail_url: None,
+            status: DownloadStatus::Queued,
+            phase: None,
+            preset_id: preset_id.to_string(),
+            output_dir: "/downloads".to_string(),
+            final_path: None,
+            progress_percent: None,
+            bytes_downloaded: None,
+            bytes_total: None,
+            speed_bps: None,
+            eta_seconds: None,
+            error_code: None,
+            error_message: None,
+            sections: None,
+            raw_error: None,
+            thumbnail_path: None,
+            downloaded_files: Vec::new(),
+            final_paths: Vec::new(),
+            original_url: None,
+            subtitle_langs: None,
+            playlist_title: None,
+            playlist_index: None,
+            rate_limit_bps: None,
+        }
+    }
+
+    fn sample_config() -> DownloadConfig {
+        DownloadConfig {
+            yt_dlp_path: PathBuf::from("/usr/bin/yt-dlp"),
+            ffmpeg_path: Some(PathBuf::from("/usr/bin/ffmpeg")),
+            ffprobe_path: None,
+            max_concurrent: 2,
+            default_output_template: "%(title)s [%(id)s].%(ext)s".to_string(),
+            stall_timeout: Duration::from_secs(120),
+            max_auto_retries: 3,
+            retry_base_delay: Duration::from_secs(5),
+            aria2c_path: None,
+            progress_throttle: ProgressThrottle::default(),
+            data_dir: PathBuf::new(),
+            tmp_dir: PathBuf::new(),
+        }
+    }
+
+    /// The value following `flag`, if the flag is present.
+    fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1))
+            .map(String::as_str)
+    }
+
+    #[test]
+    fn test_build_download_args_preset_and_settings() {
+        let mut row = sample_row("mp4_1080p");
+        row.sections = Some("*00:01:00-00:02:00".to_string());
+
+        let mut settings = UserSettings::default();
+        settings.network.rate_limit_bps = 500_000;
+        settings.network.max_filesize_bytes = 500 * 1024 * 1024;
+
+        let args = build_download_args(&row, &sample_config(), &settings, None);
+
+        assert_eq!(
+            arg_value(&args, "-o"),
+            Some("/downloads/%(title)s [%(id)s].%(ext)s")
+        );
+        assert_eq!(
+            arg_value(&args, "-f"),
+            Some("bv*[height<=1080]+ba/b[height<=1080]")
+        );
+        assert_eq!(arg_value(&args, "--merge-output-format"), Some("mp4"));
+        assert_eq!(
+            arg_value(&args, "--ffmpeg-location"),
+            Some("/usr/bin/ffmpeg")
+        );
+        assert_eq!(arg_value(&args, "--limit-rate"), Some("500000"));
+        assert_eq!(arg_value(&args, "--max-filesize"), Some("524288000"));
+        assert_eq!(
+            arg_value(&args, "--download-sections"),
+            Some("*00:01:00-00:02:00")
+        );
+        assert_eq!(args.last().unwrap(), "https://example.com/watch?v=abc");
+    }
+
+    #[test]
+    fn test_build_download_args_per_download_rate_limit_wins() {
+        let mut settings = UserSettings::default();
+        settings.network.rate_limit_bps = 500_000;
+
+        let mut row = sample_row("mp4_1080p");
+        row.rate_limit_bps = Some(100_000);
+        let args = build_download_args(&row, &sample_config(), &settings, None);
+        assert_eq!(arg_value(&args, "--limit-rate"), Some("100000"));
+
+        row.rate_limit_bps = None;
+        let args = build_download_args(&row, &sample_config(), &settings, None);
+        assert_eq!(arg_value(&args, "--limit-rate"), Some("500000"));
+
+        // Own limit applies even with no global limit set
+        settings.network.rate_limit_bps = 0;
+        row.rate_limit_bps = Some(250_000);
+        let args = build_download_args(&row, &sample_config(), &settings, None);
+        assert_eq!(arg_value(&args, "--limit-rate"), Some("250000"));
+    }
+
+    #[test]
+    fn test_build_download_args_defaults() {
+        let mut config = sample_config();
+        config.ffmpeg_path = None;
+        let args = build_download_args(
+            &sample_row("unknown_preset"),
+            &config,
+            &UserSettings::default(),
+            None,
+        );
+
+        assert_eq!(
+            arg_value(&args, "-o"),
+            Some("/downloads/%(title)s [%(id)s].%(ext)s")
+        );
+        // Unknown presets fall back to the recommended one
+        assert_eq!(arg_value(&args, "-f"), Some("bv*+ba/b"));
+        assert!(!args.iter().any(|a| a == "--limit-rate"));
+        assert!(!args.iter().any(|a| a == "--max-filesize"));
+        assert!(!args.iter().any(|a| a == "--ffmpeg-location"));
+        assert!(!args.iter().any(|a| a == "--download-sections"));
+    }
+
+    fn organized_output(row: &DownloadRow, organize_by: &str) -> String {
+        let mut settings = UserSettings::default();
+        settings.formats.organize_by = organize_by.to_string();
+        let args = build_download_args(row, &sample_config(), &settings, None);
+        arg_value(&args, "-o").unwrap().to_string()
+    }
+
+    #[test]
+    fn test_organize_by_templates() {
+        let row = sample_row("mp4_best");
+        assert_eq!(
+            organized_output(&row, "none"),
+            "/downloads/%(title)s [%(id)s].%(ext)s"
+        );
+        assert_eq!(
+            organized_output(&row, "uploader"),
+            "/downloads/%(uploader,channel|Unknown uploader)s/%(title)s [%(id)s].%(ext)s"
+        );
+        assert_eq!(
+            organized_output(&row, "date"),
+            "/downloads/%(upload_date>%Y-%m|Unknown date)s/%(title)s [%(id)s].%(ext)s"
+        );
+        // A playlist URL downloaded directly: yt-dlp fills in the playlist
+        assert_eq!(
+            organized_output(&row, "playlist"),
+            "/downloads/%(playlist_title,playlist|No playlist)s/%(playlist_index&{} - |)s%(title)s [%(id)s].%(ext)s"
+        );
+    }
+
+    #[test]
+    fn test_organize_by_playlist_uses_expanded_position() {
+        let mut row = sample_row("mp4_best");
+        row.source_kind = crate::db::SourceKind::PlaylistItem;
+        row.playlist_title = Some("Mix: 100% Lo-Fi".to_string());
+        row.playlist_index = Some(7);
+        assert_eq!(
+            organized_output(&row, "playlist"),
+            "/downloads/Mix_ 100%% Lo-Fi/07 - %(title)s [%(id)s].%(ext)s"
+        );
+
+        // A title can't name a folder outside the download folder
+        row.playlist_title = Some("../../etc".to_string());
+        assert_eq!(
+            organized_output(&row, "playlist"),
+            "/downloads/_.._etc/07 - %(title)s [%(id)s].%(ext)s"
+        );
+        row.playlist_title = Some("..".to_string());
+        assert!(organized_output(&row, "playlist")
+            .starts_with("/downloads/%(playlist_title,playlist|No playlist)s/07 - "));
+    }
+
+    #[test]
+    fn test_build_download_args_match_the_original_command() {
+        // Saved settings that downloads have never passed to yt-dlp stay unused
+        let mut settings = UserSettings::default();
+        settings.formats.filename_template = "%(uploader)s - %(title)s.%(ext)s".to_string();
+        settings.network.use_proxy = true;
+        settings.network.proxy_url = "socks5://127.0.0.1:9050".to_string();
+        settings.network.retries = 5;
+        settings.network.concurrent_fragments = 4;
+        settings.network.socket_timeout = 10;
+
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        assert_eq!(
+            args,
+            vec![
+                "--newline",
+                "--no-warnings",
+                "--no-call-home",
+                "--progress",
+                "--progress-template",
+                "download:[downlink] %(progress._percent_str)s %(progress._speed_str)s %(progress._eta_str)s %(progress._total_bytes_str)s",
+                "-o",
+                "/downloads/%(title)s [%(id)s].%(ext)s",
+                "-f",
+                "bv*[ext=mp4]+ba[ext=m4a]/b[ext=mp4]",
+                "--merge-output-format",
+                "mp4",
+                "--ffmpeg-location",
+                "/usr/bin/ffmpeg",
+                "https://example.com/watch?v=abc",
+            ]
+        );
+    }
+
+    fn sponsorblock(mode: &str) -> SponsorBlockSettings {
+        SponsorBlockSettings {
+            enabled_by_default: true,
+            mode: mode.to_string(),
+            categories: vec!["sponsor".to_string(), "intro".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_credentials_are_passed_in_a_private_config_file() {
+        let tmp = std::env::temp_dir().join(format!("downlink-creds-{}", Uuid::new_v4()));
+        let path = credentials_config_path(&tmp, Uuid::new_v4());
+        let credentials = SiteCredentials {
+            username: "alice".to_string(),
+            password: "it's #hunter2".to_string(),
+        };
+        let config = CredentialsConfig::write(path.clone(), &credentials).unwrap();
+
+        let args = build_download_args(
+            &sample_row("mp4_best"),
+            &sample_config(),
+            &UserSettings::default(),
+            Some(&path),
+        );
+        assert_eq!(
+            arg_value(&args, "--config-locations"),
+            Some(path.to_str().unwrap())
+        );
+        assert_eq!(args.last().unwrap(), "https://example.com/watch?v=abc");
+        let command_line = args.join(" ");
+        assert!(!command_line.contains("alice"), "{command_line}");
+        assert!(!command_line.contains("hunter2"), "{command_line}");
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "--username 'alice'\n--password 'it'\"'\"'s #hunter2'\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        drop(config);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&tmp);
+    }
+
+    #[test]
+    fn test_progress_throttle_decisions() {
+        let throttle = ProgressThrottle {
+            min_interval: Duration::from_millis(250),
+            min_percent_delta: 0.5,
+        };
+        let start = std::time::Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // (time, percent, expected) for a download's progress lines in order
+        let sequence = [
+            (0, 0.1, true),     // first update always goes out
+            (50, 5.0, false),   // too soon, however big the jump
+            (300, 5.0, true),   // interval passed and moved enough since 0.1
+            (600, 5.2, false),  // interval passed but moved too little
+            (700, 5.6, true),   // 0.6 since the last emitted 5.0
+            (710, 99.9, true),  // completion is never throttled
+            (720, 100.0, true), // ...including repeats
+        ];
+
+        let mut last = None;
+        for (ms, percent, expected) in sequence {
+            let emit = throttle.should_emit(last, at(ms), percent);
+            assert_eq!(emit, expected, "at {ms}ms, {percent}%");
+            if emit {
+                last = Some((at(ms), percent));
+            }
+        }
+    }
+
+    #[test]
+    fn test_progress_throttle_zero_delta_is_time_based() {
+        let throttle = ProgressThrottle {
+            min_interval: Duration::from_secs(1),
+            min_percent_delta: 0.0,
+        };
+        let start = std::time::Instant::now();
+        let last = Some((start, 10.0));
+
+        assert!(!throttle.should_emit(last, start + Duration::from_millis(999), 10.0));
+        assert!(throttle.should_emit(last, start + Duration::from_secs(1), 10.0));
+    }
+
+    #[test]
+    fn test_eta_smoother_averages_speed() {
+        let mut smoother = EtaSmoother::new(0.5);
+        let progress = |percent: f64, speed: u64, raw_eta: u64| ParsedProgress {
+            percent: Some(percent),
+            bytes_total: Some(1000),
+            speed_bps: Some(speed),
+            eta_seconds: Some(raw_eta),
+            ..Default::default()
+        };
+
+        // First sample seeds the average: 900 bytes left at 100 B/s
+        assert_eq!(smoother.eta(&progress(10.0, 100, 9)), Some(9));
+        // Speed spikes to 300: average is 200, 800 left
+        assert_eq!(smoother.eta(&progress(20.0, 300, 3)), Some(4));
+        // Drops to 20: average is 110, 700 left
+        assert_eq!(smoother.eta(&progress(30.0, 20, 35)), Some(6));
+        // A line without a speed keeps the last average
+        let mut no_speed = progress(40.0, 0, 99);
+        no_speed.speed_bps = None;
+        assert_eq!(smoother.eta(&no_speed), Some(5));
+        // Explicit byte counts win over the percentage
+        let mut exact = progress(0.0, 110, 1);
+        exact.bytes_downloaded = Some(890);
+        assert_eq!(smoother.eta(&exact), Some(1));
+    }
+
+    #[test]
+    fn test_eta_smoother_falls_back_to_raw_eta() {
+        let mut smoother = EtaSmoother::default();
+        let unknown_total = ParsedProgress {
+            percent: Some(50.0),
+            speed_bps: Some(1000),
+            eta_seconds: Some(42),
+            ..Default::default()
+        };
+        assert_eq!(smoother.eta(&unknown_total), Some(42));
+
+        let mut fresh = EtaSmoother::default();
+        let no_speed = ParsedProgress {
+            percent: Some(50.0),
+            bytes_total: Some(1000),
+            eta_seconds: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(fresh.eta(&no_speed), Some(7));
+    }
+
+    fn speed_sample(speed_bps: u64) -> SpeedSample {
+        SpeedSample {
+            at: chrono::Utc::now(),
+            speed_bps: Some(speed_bps),
+            percent: None,
+        }
+    }
+
+    #[test]
+    fn test_speed_history_evicts_oldest() {
+        let mut history = SpeedHistory::new(3);
+        for speed in 1..=5 {
+            history.push(speed_sample(speed));
+        }
+
+        let speeds: Vec<_> = history.samples().iter().map(|s| s.speed_bps).collect();
+        assert_eq!(speeds, vec![Some(3), Some(4), Some(5)]);
+    }
+
+    #[test]
+    fn test_speed_history_capacity() {
+        let mut history = SpeedHistory::default();
+        for speed in 0..(SPEED_SAMPLE_CAPACITY as u64 + 10) {
+            history.push(speed_sample(speed));
+        }
+        let samples = history.samples();
+        assert_eq!(samples.len(), SPEED_SAMPLE_CAPACITY);
+        assert_eq!(samples[0].speed_bps, Some(10));
+
+        let mut empty = SpeedHistory::new(0);
+        empty.push(speed_sample(1));
+        assert!(empty.samples().is_empty());
+    }
+
+    #[test]
+    fn test_subtitle_args_download_langs_override_default() {
+        let mut subtitles = SubtitleSettings::default();
+
+        // Off by default and nothing chosen: no subtitles
+        assert!(subtitle_args(&subtitles, None, true).is_empty());
+        assert!(subtitle_args(&subtitles, Some(""), true).is_empty());
+
+        // A per-download choice turns them on
+        let args = subtitle_args(&subtitles, Some("ja"), true);
+        assert!(args.iter().any(|a| a == "--write-subs"));
+        assert_eq!(arg_value(&args, "--sub-langs"), Some("ja"));
+        assert_eq!(arg_value(&args, "--sub-format"), Some("srt/best"));
+
+        subtitles.enabled_by_default = true;
+        subtitles.default_language = "en".to_string();
+        let args = subtitle_args(&subtitles, None, true);
+        assert_eq!(arg_value(&args, "--sub-langs"), Some("en"));
+        let args = subtitle_args(&subtitles, Some("ja,pt-BR"), true);
+        assert_eq!(arg_value(&args, "--sub-langs"), Some("ja,pt-BR"));
+
+        // An invalid stored value is dropped rather than guessed at
+        assert!(subtitle_args(&subtitles, Some("japanese!"), true).is_empty());
+    }
+
+    #[test]
+    fn test_subtitle_args_auto_captions_and_embedding() {
+        let subtitles = SubtitleSettings {
+            enabled_by_default: true,
+            include_auto_captions: true,
+            embed_subtitles: true,
+            ..Default::default()
+        };
+        let args = subtitle_args(&subtitles, None, true);
+        assert!(args.iter().any(|a| a == "--write-auto-subs"));
+        assert!(args.iter().any(|a| a == "--embed-subs"));
+
+        let args = subtitle_args(&subtitles, None, false);
+        assert!(!args.iter().any(|a| a == "--embed-subs"));
+    }
+
+    #[test]
+    fn test_build_download_args_uses_download_subtitle_langs() {
+        let mut row = sample_row("recommended_best");
+        row.subtitle_langs = Some("ja".to_string());
+        let args = build_download_args(&row, &sample_config(), &UserSettings::default(), None);
+
+        assert_eq!(arg_value(&args, "--sub-langs"), Some("ja"));
+        assert_eq!(args.last().unwrap(), &row.source_url);
+    }
+
+    fn staging_fixture(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let root = std::env::temp_dir().join(format!("downlink-{name}-{}", Uuid::new_v4()));
+        let staging = root.join("tmp");
+        let dest = root.join("dest");
+        std::fs::create_dir_all(staging.join("Channel")).unwrap();
+        (root, staging, dest)
+    }
+
+    #[test]
+    fn test_move_staged_outputs_renames_into_destination() {
+        let (root, staging, dest) = staging_fixture("staged");
+        std::fs::write(staging.join("video.mp4"), "video").unwrap();
+        std::fs::write(staging.join("Channel").join("video.en.srt"), "subs").unwrap();
+        std::fs::write(staging.join("other.f137.mp4.part"), "partial").unwrap();
+
+        move_staged_outputs(&staging, &dest).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dest.join("video.mp4")).unwrap(),
+            "video"
+        );
+        assert!(dest.join("Channel").join("video.en.srt").exists());
+        assert!(!dest.join("other.f137.mp4.part").exists());
+        assert!(!staging.exists());
+
+        // An existing file in the destination is kept
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("video.mp4"), "newer").unwrap();
+        move_staged_outputs(&staging, &dest).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.join("video.mp4")).unwrap(),
+            "video"
+        );
+        assert!(!staging.exists());
+        assert_eq!(
+            relocate_staged_path(
+                &staging.join("video.mp4").to_string_lossy(),
+                &staging,
+                &dest
+            ),
+            dest.join("video.mp4").to_string_lossy()
+        );
+        // Paths outside the staging dir are left alone
+        assert_eq!(
+            relocate_staged_path("/elsewhere/video.mp4", &staging, &dest),
+            "/elsewhere/video.mp4"
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_move_file_copies_across_filesystems() {
+        let (root, staging, dest) = staging_fixture("xdev");
+        let from = staging.join("video.mp4");
+        let to = dest.join("video.mp4");
+        std::fs::write(&from, "video").unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(&to, "older download").unwrap();
+
+        let cross_device =
+            |_: &Path, _: &Path| Err(std::io::Error::from_raw_os_error(CROSS_DEVICE_ERROR));
+        move_file_with(&from, &to, cross_device).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "video");
+        assert!(!from.exists());
+
+        // Other rename errors are reported, and the source is kept
+        std::fs::write(&from, "video").unwrap();
+        let denied =
+            |_: &Path, _: &Path| Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(move_file_with(&from, &to, denied).is_err());
+        assert!(from.exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_staging_dir_only_when_enabled() {
+        let id = Uuid::new_v4();
+        let mut config = sample_config();
+        let mut general = GeneralSettings::default();
+        config.tmp_dir = PathBuf::from("/app/tmp");
+        assert_eq!(staging_dir(&config, &general, id), None);
+
+        general.download_to_temp = true;
+        assert_eq!(
+            staging_dir(&config, &general, id),
+            Some(PathBuf::from("/app/tmp/downloads").join(id.to_string()))
+        );
+        config.tmp_dir = PathBuf::new();
+        assert_eq!(staging_dir(&config, &general, id), None);
+    }
+
+    #[test]
+    fn test_sidecar_args_are_independent() {
+        let mut formats = FormatSettings::default();
+        assert!(sidecar_args(&formats).is_empty());
+
+        formats.write_description = true;
+        assert_eq!(sidecar_args(&formats), vec!["--write-description"]);
+
+        formats.write_description = false;
+        formats.write_thumbnail = true;
+        assert_eq!(sidecar_args(&formats), vec!["--write-thumbnail"]);
+
+        formats.write_description = true;
+        assert_eq!(
+            sidecar_args(&formats),
+            vec!["--write-description", "--write-thumbnail"]
+        );
+    }
+
+    #[test]
+    fn test_filename_args() {
+        let mut formats = FormatSettings::default();
+        assert!(filename_args(&formats).is_empty());
+
+        formats.restrict_filenames = true;
+        assert_eq!(filename_args(&formats), vec!["--restrict-filenames"]);
+
+        formats.trim_filenames = 120;
+        assert_eq!(
+            filename_args(&formats),
+            vec!["--restrict-filenames", "--trim-filenames", "120"]
+        );
+
+        let settings = UserSettings {
+            formats,
+            ..UserSettings::default()
+        };
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        assert!(args.iter().any(|a| a == "--trim-filenames"));
+    }
+
+    #[test]
+    fn test_sidecar_args_combine_with_embedding() {
+        let mut settings = UserSettings::default();
+        assert!(settings.formats.embed_thumbnail);
+        settings.formats.write_thumbnail = true;
+
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        assert!(args.iter().any(|a| a == "--write-thumbnail"));
+        assert!(!args.iter().any(|a| a == "--write-description"));
+        // write_info_json is a separate option and stays off
+        assert!(!args.iter().any(|a| a == "--write-info-json"));
+    }
+
+    #[test]
+    fn test_write_comments_implies_info_json() {
+        let mut formats = FormatSettings::default();
+        assert!(!formats.write_comments);
+        assert!(sidecar_args(&formats).is_empty());
+
+        formats.write_comments = true;
+        assert_eq!(
+            sidecar_args(&formats),
+            vec!["--write-info-json", "--write-comments"]
+        );
+
+        formats.write_comments = false;
+        formats.write_info_json = true;
+        assert_eq!(sidecar_args(&formats), vec!["--write-info-json"]);
+    }
+
+    #[test]
+    fn test_split_chapters_args() {
+        let mut formats = FormatSettings::default();
+        assert!(split_chapters_args(&formats, "/downloads", true).is_empty());
+
+        formats.split_chapters = true;
+        let args = split_chapters_args(&formats, "/downloads", true);
+        assert_eq!(args[0], "--split-chapters");
+        assert_eq!(
+            arg_value(&args, "-o"),
+            Some("chapter:/downloads/%(title)s - %(section_number)03d %(section_title)s [%(id)s].%(ext)s")
+        );
+
+        // Splitting is an ffmpeg postprocessor
+        assert!(split_chapters_args(&formats, "/downloads", false).is_empty());
+    }
+
+    #[test]
+    fn test_extra_ytdlp_args_come_before_url() {
+        let mut settings = UserSettings::default();
+        settings.general.extra_ytdlp_args = r#"--user-agent "Test Agent/1.0" --xattrs"#.to_string();
+        let row = sample_row("mp4_best");
+        let args = build_download_args(&row, &sample_config(), &settings, None);
+
+        assert_eq!(arg_value(&args, "--user-agent"), Some("Test Agent/1.0"));
+        assert!(args.iter().any(|a| a == "--xattrs"));
+        assert_eq!(args.last(), Some(&row.source_url));
+
+        // A stored value that no longer validates is dropped, not passed along
+        settings.general.extra_ytdlp_args = "--exec 'echo hi'".to_string();
+        let args = build_download_args(&row, &sample_config(), &settings, None);
+        assert!(!args.iter().any(|a| a == "--exec"));
+    }
+
+    #[test]
+    fn test_extractor_args_one_flag_per_extractor() {
+        let mut settings = UserSettings::default();
+        assert!(extractor_args(&settings.general).is_empty());
+
+        let map = &mut settings.general.extractor_args;
+        map.insert(
+            "youtube".to_string(),
+            "player_client=web,default;po_token=web+abc".to_string(),
+        );
+        map.insert("youtubetab".to_string(), "skip=webpage".to_string());
+        map.insert("generic".to_string(), "broken".to_string());
+
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        let values: Vec<&str> = args
+            .windows(2)
+            .filter(|w| w[0] == "--extractor-args")
+            .map(|w| w[1].as_str())
+            .collect();
+        assert_eq!(
+            values,
+            [
+                "youtube:player_client=web,default;po_token=web+abc",
+                "youtubetab:skip=webpage",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_download_archive_path_resolution() {
+        let data = Path::new("/appdata");
+        let mut general = GeneralSettings::default();
+        assert_eq!(
+            download_archive_path(data, &general, "/music"),
+            data.join("archive.txt")
+        );
+        assert_eq!(
+            download_archive_path(data, &general, "/videos"),
+            data.join("archive.txt")
+        );
+
+        general.download_archive_scope = "per_folder".to_string();
+        let music = download_archive_path(data, &general, "/music");
+        assert!(music.starts_with(data.join("archives")));
+        assert_eq!(music.extension().unwrap(), "txt");
+        assert_eq!(download_archive_path(data, &general, "/music/"), music);
+        assert_ne!(download_archive_path(data, &general, "/videos"), music);
+    }
+
+    #[test]
+    fn test_download_archive_flag_only_when_enabled() {
+        let data_dir = std::env::temp_dir().join(format!("downlink-archive-{}", Uuid::new_v4()));
+        let mut config = sample_config();
+        config.data_dir = data_dir.clone();
+        let mut settings = UserSettings::default();
+
+        let args = build_download_args(&sample_row("mp4_best"), &config, &settings, None);
+        assert!(!args.iter().any(|a| a == "--download-archive"));
+
+        settings.general.use_download_archive = true;
+        let args = build_download_args(&sample_row("mp4_best"), &config, &settings, None);
+        let expected = data_dir.join("archive.txt");
+        assert_eq!(
+            arg_value(&args, "--download-archive"),
+            Some(expected.to_str().unwrap())
+        );
+
+        // Staged downloads write to the staging dir but share the destination's archive
+        settings.general.download_archive_scope = "per_folder".to_string();
+        config.tmp_dir = data_dir.join("tmp");
+        settings.general.download_to_temp = true;
+        let row = sample_row("mp4_best");
+        let args = build_download_args(&row, &config, &settings, None);
+        let expected = download_archive_path(&data_dir, &settings.general, &row.output_dir);
+        assert_eq!(
+            arg_value(&args, "--download-archive"),
+            Some(expected.to_str().unwrap())
+        );
+        assert!(arg_value(&args, "-o").unwrap().starts_with(
+            &config
+                .tmp_dir
+                .join(STAGING_DIR)
+                .to_string_lossy()
+                .into_owned()
+        ));
+
+        // No data dir, no archive
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        let _ = std::fs::remove_dir_all(&data_dir);
+        assert!(!args.iter().any(|a| a == "--download-archive"));
+    }
+
+    #[test]
+    fn test_clear_download_archives() {
+        let data_dir = std::env::temp_dir().join(format!("downlink-archive-{}", Uuid::new_v4()));
+        let general = GeneralSettings {
+            download_archive_scope: "per_folder".to_string(),
+            ..Default::default()
+        };
+        let music = download_archive_path(&data_dir, &general, "/music");
+        let videos = download_archive_path(&data_dir, &general, "/videos");
+        std::fs::create_dir_all(music.parent().unwrap()).unwrap();
+        for file in [&music, &videos, &data_dir.join("archive.txt")] {
+            std::fs::write(file, "youtube abc\n").unwrap();
+        }
+
+        assert_eq!(clear_download_archives(&data_dir, Some(&music)).unwrap(), 1);
+        assert!(!music.exists());
+        assert!(videos.exists());
+        assert_eq!(clear_download_archives(&data_dir, None).unwrap(), 2);
+        assert_eq!(clear_download_archives(&data_dir, None).unwrap(), 0);
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    #[test]
+    fn test_remux_overrides_preset_merge_format() {
+        let mut settings = UserSettings::default();
+        settings.formats.remux_to = "mkv".to_string();
+
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        assert_eq!(arg_value(&args, "--merge-output-format"), Some("mkv"));
+        assert_eq!(arg_value(&args, "--remux-video"), Some("mkv"));
+        assert_eq!(
+            args.iter()
+                .filter(|a| *a == "--merge-output-format")
+                .count(),
+            1
+        );
+
+        // Presets without a merge format get one
+        let mut preset_args = vec!["-f".to_string(), "bv*+ba/b".to_string()];
+        apply_remux(&mut preset_args, &settings.formats, true);
+        assert_eq!(
+            arg_value(&preset_args, "--merge-output-format"),
+            Some("mkv")
+        );
+
+        // Audio extraction keeps its own format
+        let args = build_download_args(&sample_row("audio_m4a"), &sample_config(), &settings, None);
+        assert!(!args.iter().any(|a| a == "--remux-video"));
+        assert_eq!(arg_value(&args, "--audio-format"), Some("m4a"));
+
+        // No ffmpeg, no remux
+        let mut config = sample_config();
+        config.ffmpeg_path = None;
+        let args = build_download_args(&sample_row("mp4_best"), &config, &settings, None);
+        assert_eq!(arg_value(&args, "--merge-output-format"), Some("mp4"));
+        assert!(!args.iter().any(|a| a == "--remux-video"));
+    }
+
+    #[test]
+    fn test_remux_ignores_unsupported_container() {
+        let mut settings = UserSettings::default();
+        settings.formats.remux_to = "avi".to_string();
+
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        assert_eq!(arg_value(&args, "--merge-output-format"), Some("mp4"));
+        assert!(!args.iter().any(|a| a == "--remux-video"));
+    }
+
+    #[test]
+    fn test_started_event_includes_resolved_preset() {
+        let mut settings = UserSettings::default();
+        let row = sample_row("audio_mp3_320");
+        let DownlinkEvent::DownloadStarted {
+            id,
+            preset_name,
+            output_template,
+        } = started_event(&row, &sample_config(), &settings)
+        else {
+            panic!("expected DownloadStarted");
+        };
+        assert_eq!(id, row.id);
+        assert_eq!(preset_name.as_deref(), Some("Audio MP3 320"));
+        assert_eq!(
+            output_template,
+            Some(format!("/downloads/{}", settings.formats.filename_template))
+        );
+
+        // Unknown presets report the default they fall back to
+        let DownlinkEvent::DownloadStarted { preset_name, .. } =
+            started_event(&sample_row("gone"), &sample_config(), &settings)
+        else {
+            panic!("expected DownloadStarted");
+        };
+        assert_eq!(preset_name.as_deref(), Some("Recommended (Best)"));
+
+        // Staged downloads show the staging folder yt-dlp actually writes to
+        let mut config = sample_config();
+        config.tmp_dir = PathBuf::from("/app/tmp");
+        settings.general.download_to_temp = true;
+        let DownlinkEvent::DownloadStarted {
+            output_template, ..
+        } = started_event(&row, &config, &settings)
+        else {
+            panic!("expected DownloadStarted");
+        };
+        assert!(output_template
+            .unwrap()
+            .starts_with(&format!("/app/tmp/{STAGING_DIR}/{}/", row.id)));
+    }
+
+    #[test]
+    fn test_container_compatibility_warning() {
+        let mut formats = FormatSettings::default();
+        let warning = |preset: &str, formats: &FormatSettings| {
+            container_compatibility_warning(&effective_preset_args(preset, formats, true), formats)
+        };
+
+        // Defaults and open selectors are fine
+        assert_eq!(warning("mp4_best", &formats), None);
+        assert_eq!(warning("recommended_best", &formats), None);
+
+        // H.264/AAC from the MP4 preset can't go into WebM
+        formats.remux_to = "webm".to_string();
+        let message = warning("mp4_best", &formats).unwrap();
+        assert!(message.contains("H264 video") && message.contains("WEBM"));
+        assert_eq!(warning("audio_m4a", &formats), None);
+
+        // MKV takes anything
+        formats.remux_to = "mkv".to_string();
+        formats.preferred_video_codec = "vp9".to_string();
+        assert_eq!(warning("recommended_best", &formats), None);
+
+        // VP9 and Opus don't fit MOV; VP8 and Vorbis don't fit MP4
+        formats.remux_to = "mov".to_string();
+        assert!(warning("recommended_best", &formats)
+            .unwrap()
+            .contains("VP9 video"));
+        formats.preferred_video_codec = String::new();
+        formats.preferred_audio_codec = "opus".to_string();
+        assert!(warning("recommended_best", &formats)
+            .unwrap()
+            .contains("OPUS audio"));
+        formats.remux_to = String::new();
+        formats.preferred_audio_codec = "vorbis".to_string();
+        assert!(warning("mp4_1080p", &formats).is_some());
+        formats.preferred_audio_codec = String::new();
+        formats.preferred_video_codec = "vp8".to_string();
+        assert!(warning("mp4_1080p", &formats).is_some());
+    }
+
+    #[test]
+    fn test_selector_codecs() {
+        assert_eq!(
+            selector_codecs("bv*[ext=mp4]+ba[ext=m4a]/b[ext=mp4]"),
+            (Some("h264".to_string()), Some("aac".to_string()))
+        );
+        assert_eq!(
+            selector_codecs("bv*[vcodec^=av01]+ba[ext=webm]"),
+            (Some("av01".to_string()), Some("opus".to_string()))
+        );
+        assert_eq!(
+            selector_codecs("b[ext=webm]"),
+            (Some("vp9".to_string()), Some("opus".to_string()))
+        );
+        assert_eq!(selector_codecs("bv*+ba/b"), (None, None));
+    }
+
+    #[test]
+    fn test_audio_normalize_args() {
+        let mut formats = FormatSettings::default();
+        let audio = Preset::get_by_id("audio_mp3_320").unwrap().yt_dlp_args;
+        let video = Preset::get_by_id("mp4_1080p").unwrap().yt_dlp_args;
+        assert!(audio_normalize_args(&formats, &audio, true).is_empty());
+
+        formats.audio_normalize = true;
+        assert_eq!(
+            audio_normalize_args(&formats, &audio, true),
+            vec![
+                "--postprocessor-args",
+                "ExtractAudio:-af loudnorm=I=-16:TP=-1.5:LRA=11"
+            ]
+        );
+        // Video presets don't extract audio
+        assert!(audio_normalize_args(&formats, &video, true).is_empty());
+        assert!(audio_normalize_args(&formats, &audio, false).is_empty());
+        let best = ["-x".to_string()];
+        assert!(audio_normalize_args(&formats, &best, true).is_empty());
+
+        let mut settings = UserSettings::default();
+        settings.formats.audio_normalize = true;
+        let args = build_download_args(
+            &sample_row("audio_mp3_320"),
+            &sample_config(),
+            &settings,
+            None,
+        );
+        assert_eq!(
+            arg_value(&args, "--postprocessor-args"),
+            Some("ExtractAudio:-af loudnorm=I=-16:TP=-1.5:LRA=11")
+        );
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        assert!(!args.iter().any(|a| a == "--postprocessor-args"));
+        // The m4a preset copies the m4a stream, which ffmpeg can't filter
+        let args = build_download_args(&sample_row("audio_m4a"), &sample_config(), &settings, None);
+        assert!(!args.iter().any(|a| a == "--postprocessor-args"));
+    }
+
+    #[test]
+    fn test_audio_normalize_warning() {
+        let mut formats = FormatSettings::default();
+        let args = |preset: &str| Preset::get_by_id(preset).unwrap().yt_dlp_args;
+        assert_eq!(audio_normalize_warning(&args("audio_m4a"), &formats), None);
+
+        formats.audio_normalize = true;
+        assert!(audio_normalize_warning(&args("audio_m4a"), &formats)
+            .unwrap()
+            .contains("original audio stream"));
+        assert_eq!(
+            audio_normalize_warning(&args("audio_mp3_320"), &formats),
+            None
+        );
+        assert_eq!(audio_normalize_warning(&args("mp4_best"), &formats), None);
+    }
+
+    #[test]
+    fn test_output_tracker_collects_multiple_destinations() {
+        let mut outputs = OutputTracker::default();
+        for line in [
+            "[youtube] abc: Downloading webpage",
+            "[download] Destination: /downloads/talk [abc].f137.mp4",
+            "[download] 100% of 50.00MiB in 00:05",
+            "[download] Destination: /downloads/talk [abc].f140.m4a",
+            "[download] 100% of 5.00MiB in 00:01",
+            "[download] Destination: /downloads/talk [abc].f140.m4a",
+            "[SplitChapters] Splitting video by chapters; 2 chapters found",
+            "[SplitChapters] Chapter 001; Destination: /downloads/talk - 001 Intro [abc].mp4",
+            "[SplitChapters] Chapter 002; Destination: /downloads/talk - 002 Q&A [abc].mp4",
+        ] {
+            outputs.observe(line);
+        }
+
+        assert_eq!(
+            outputs.destinations,
+            vec![
+                "/downloads/talk [abc].f137.mp4",
+                "/downloads/talk [abc].f140.m4a",
+            ]
+        );
+        assert_eq!(
+            outputs.chapters,
+            vec![
+                "/downloads/talk - 001 Intro [abc].mp4",
+                "/downloads/talk - 002 Q&A [abc].mp4",
+            ]
+        );
+        assert_eq!(outputs.final_paths().len(), 4);
+    }
+
+    #[test]
+    fn test_output_tracker_prefers_merged_output() {
+        let mut outputs = OutputTracker::default();
+        for line in [
+            "[info] abc: Downloading 1 format(s): 137+140",
+            "[download] Destination: /downloads/talk [abc].en.vtt",
+            "[download] Destination: /downloads/talk [abc].f137.mp4",
+            "[download] Destination: /downloads/talk [abc].f140.m4a",
+            "[Merger] Merging formats into \"/downloads/talk [abc].mp4\"",
+            "Deleting original file /downloads/talk [abc].f137.mp4 (pass -k to keep)",
+        ] {
+            outputs.observe(line);
+        }
+
+        // Previously the last Destination line (the audio stream) won
+        assert_eq!(outputs.final_path(), Some("/downloads/talk [abc].mp4"));
+        assert_eq!(
+            outputs.final_paths(),
+            vec![
+                "/downloads/talk [abc].mp4",
+                "/downloads/talk [abc].en.vtt",
+                "/downloads/talk [abc].f137.mp4",
+                "/downloads/talk [abc].f140.m4a",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_tracker_notices_archive_skips() {
+        let mut outputs = OutputTracker::default();
+        outputs.observe("[youtube] abc: Downloading webpage");
+        assert!(!outputs.in_archive);
+        outputs.observe("[download] abc: Talk title has already been recorded in the archive");
+        assert!(outputs.in_archive);
+        assert_eq!(outputs.final_path(), None);
+    }
+
+    #[test]
+    fn test_output_tracker_already_downloaded_and_audio_extraction() {
+        let mut outputs = OutputTracker::default();
+        outputs.observe("[download] /downloads/talk [abc].mp4 has already been downloaded");
+        assert_eq!(outputs.final_path(), Some("/downloads/talk [abc].mp4"));
+
+        let mut outputs = OutputTracker::default();
+        for line in [
+            "[download] Destination: /downloads/song [xyz].webm",
+            "[ExtractAudio] Destination: /downloads/song [xyz].mp3",
+        ] {
+            outputs.observe(line);
+        }
+        assert_eq!(outputs.final_path(), Some("/downloads/song [xyz].mp3"));
+    }
+
+    #[test]
+    fn test_existing_paths_drops_deleted_intermediates() {
+        let dir = std::env::temp_dir().join(format!("downlink-outputs-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("talk.mp4"), b"video").unwrap();
+        std::fs::write(dir.join("talk.en.vtt"), b"subs").unwrap();
+
+        let kept = existing_paths(
+            vec![
+                "talk.mp4".to_string(),
+                "talk.f137.mp4".to_string(),
+                "talk.en.vtt".to_string(),
+            ],
+            dir.to_str().unwrap(),
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(kept, vec!["talk.mp4", "talk.en.vtt"]);
+    }
+
+    #[test]
+    fn test_parse_postprocessor_destination() {
+        assert_eq!(
+            parse_postprocessor_destination("[Merger] Merging formats into \"/d/a b.mkv\""),
+            Some("/d/a b.mkv")
+        );
+        assert_eq!(
+            parse_postprocessor_destination(
+                "[VideoRemuxer] Remuxing video from webm to mp4; Destination: /d/a.mp4"
+            ),
+            Some("/d/a.mp4")
+        );
+        assert_eq!(
+            parse_postprocessor_destination("[Merger] Some other message"),
+            None
+        );
+        assert_eq!(
+            parse_postprocessor_destination("[download] Destination: /d/a.mp4"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_destination_lines() {
+        assert_eq!(
+            parse_destination("[download] Destination: /downloads/a b.mp4"),
+            Some("/downloads/a b.mp4")
+        );
+        assert_eq!(
+            parse_destination("[download] /downloads/a.mp4 has already been downloaded"),
+            Some("/downloads/a.mp4")
+        );
+        assert_eq!(parse_destination("[download]  42.0% of 10MiB"), None);
+        assert_eq!(
+            parse_chapter_destination("[SplitChapters] Chapter 010; Destination: /d/x.mkv"),
+            Some("/d/x.mkv")
+        );
+        assert_eq!(
+            parse_chapter_destination(
+                "[SplitChapters] Splitting video by chapters; 3 chapters found"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cookies_args_only_for_valid_files() {
+        let path = std::env::temp_dir().join(format!("downlink-cookies-{}.txt", Uuid::new_v4()));
+        std::fs::write(&path, "# Netscape HTTP Cookie File\n").unwrap();
+        let mut privacy = PrivacySettings {
+            cookies_path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let valid = cookies_args(&privacy);
+        privacy.cookie_mode = "never".to_string();
+        let disabled = cookies_args(&privacy);
+        privacy.cookie_mode = "always".to_string();
+        std::fs::write(&path, "not cookies").unwrap();
+        let garbage = cookies_args(&privacy);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(valid, vec!["--cookies", path.to_str().unwrap()]);
+        assert!(disabled.is_empty());
+        assert!(garbage.is_empty());
+        assert!(cookies_args(&PrivacySettings::default()).is_empty());
+    }
+
+    #[test]
+    fn test_cookies_args_prefers_browser_profile() {
+        let mut privacy = PrivacySettings {
+            cookies_path: Some(PathBuf::from("/no/such/cookies.txt")),
+            cookies_from_browser: Some("chrome:Profile 1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            cookies_args(&privacy),
+            vec!["--cookies-from-browser", "chrome:Profile 1"]
+        );
+        assert!(privacy.active_cookies_path().is_none());
+
+        privacy.cookie_mode = "never".to_string();
+        assert!(cookies_args(&privacy).is_empty());
+    }
+
+    #[test]
+    fn test_part_file_args() {
+        let mut network = NetworkSettings::default();
+        assert_eq!(part_file_args(&network, false), (Vec::new(), None));
+
+        network.keep_fragments = true;
+        assert_eq!(
+            part_file_args(&network, true),
+            (vec!["--keep-fragments".to_string()], None)
+        );
+
+        network.keep_fragments = false;
+        network.no_part = true;
+        assert_eq!(
+            part_file_args(&network, false),
+            (vec!["--no-part".to_string()], None)
+        );
+    }
+
+    #[test]
+    fn test_part_file_args_warns_when_resuming_without_part_files() {
+        let network = NetworkSettings {
+            no_part: true,
+            ..Default::default()
+        };
+        let (args, warning) = part_file_args(&network, true);
+        assert_eq!(args, vec!["--no-part", "--force-overwrites"]);
+        assert_eq!(warning, Some(NO_PART_RESUME_WARNING));
+
+        // The resume is detected from the row's saved progress
+        let mut row = sample_row("mp4_best");
+        row.progress_percent = Some(42.0);
+        let settings = UserSettings {
+            network,
+            ..Default::default()
+        };
+        let args = build_download_args(&row, &sample_config(), &settings, None);
+        assert!(args.iter().any(|a| a == "--force-overwrites"));
+    }
+
+    #[test]
+    fn test_network_args_ip_options() {
+        let mut network = NetworkSettings::default();
+        let args = network_args(&network);
+        assert!(!args.iter().any(|a| a.starts_with("--force-ipv")));
+        assert!(!args.iter().any(|a| a == "--source-address"));
+
+        network.ip_version = "ipv4".to_string();
+        network.source_address = " 10.0.0.5 ".to_string();
+        let args = network_args(&network);
+        assert!(args.iter().any(|a| a == "--force-ipv4"));
+        assert_eq!(arg_value(&args, "--source-address"), Some("10.0.0.5"));
+
+        network.ip_version = "ipv6".to_string();
+        network.source_address = "not-an-ip".to_string();
+        let args = network_args(&network);
+        assert!(args.iter().any(|a| a == "--force-ipv6"));
+        assert!(!args.iter().any(|a| a == "--source-address"));
+    }
+
+    #[test]
+    fn test_network_args_geo_bypass() {
+        let mut network = NetworkSettings::default();
+        let args = network_args(&network);
+        assert!(!args
+            .iter()
+            .any(|a| a.starts_with("--geo-bypass") || a == "--xff"));
+
+        network.geo_bypass = true;
+        network.geo_bypass_country = "de".to_string();
+        network.xff = "203.0.113.0/24".to_string();
+        let args = network_args(&network);
+        assert!(args.iter().any(|a| a == "--geo-bypass"));
+        assert_eq!(arg_value(&args, "--geo-bypass-country"), Some("DE"));
+        assert_eq!(arg_value(&args, "--xff"), Some("203.0.113.0/24"));
+
+        // Invalid values are dropped rather than failing the download
+        network.geo_bypass_country = "Germany".to_string();
+        network.xff = "sometimes".to_string();
+        let args = network_args(&network);
+        assert!(!args
+            .iter()
+            .any(|a| a == "--geo-bypass-country" || a == "--xff"));
+    }
+
+    #[test]
+    fn test_polite_args() {
+        let mut network = NetworkSettings::default();
+        assert!(polite_args(&network).is_empty());
+        assert!(!network_args(&network)
+            .iter()
+            .any(|a| a.starts_with("--sleep") || a == "--max-sleep-interval"));
+
+        network.polite_mode = true;
+        network.sleep_interval_secs = 3;
+        network.max_sleep_interval_secs = 10;
+        network.sleep_requests_secs = 2;
+        assert_eq!(
+            polite_args(&network),
+            vec![
+                "--sleep-interval",
+                "3",
+                "--max-sleep-interval",
+                "10",
+                "--sleep-requests",
+                "2"
+            ]
+        );
+        assert_eq!(
+            arg_value(&network_args(&network), "--sleep-interval"),
+            Some("3")
+        );
+
+        // A fixed wait needs no maximum, and zero turns each pause off
+        network.max_sleep_interval_secs = 3;
+        network.sleep_requests_secs = 0;
+        assert_eq!(polite_args(&network), vec!["--sleep-interval", "3"]);
+
+        network.sleep_interval_secs = 0;
+        assert!(polite_args(&network).is_empty());
+    }
+
+    #[test]
+    fn test_polite_sleep_is_the_longest_pause() {
+        let mut network = NetworkSettings {
+            sleep_interval_secs: 300,
+            max_sleep_interval_secs: 600,
+            sleep_requests_secs: 30,
+            ..NetworkSettings::default()
+        };
+        assert_eq!(polite_sleep(&network), Duration::ZERO);
+
+        network.polite_mode = true;
+        assert_eq!(polite_sleep(&network), Duration::from_secs(600));
+        network.max_sleep_interval_secs = 0;
+        assert_eq!(polite_sleep(&network), Duration::from_secs(300));
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_geo_bypass_rejects_bad_country() {
+        let (event_tx, _event_rx) = mpsc::channel(8);
+        let manager = DownloadManager::new(
+            sample_config(),
+            DbHandle::spawn(Db::open_in_memory().unwrap()).unwrap(),
+            event_tx,
+        );
+        let id = Uuid::new_v4();
+
+        assert!(manager.retry_with_geo_bypass(id, "USA").await.is_err());
+        assert!(manager.retry_with_geo_bypass(id, " ").await.is_err());
+        assert!(manager.geo_bypass_countries.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_panicking_download_task_fails_the_row() {
+        let mut db = Db::open_in_memory().unwrap();
+        let id = db
+            .insert_download(
+                "https://example.com/v",
+                crate::db::SourceKind::Single,
+                None,
+                "recommended_best",
+                "/tmp",
+            )
+            .unwrap();
+        db.set_status(id, DownloadStatus::Downloading, Some("Starting…"))
+            .unwrap();
+
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let manager = DownloadManager::new(sample_config(), DbHandle::spawn(db).unwrap(), event_tx);
+        manager
+            .active_downloads
+            .write()
+            .await
+            .insert(id, broadcast::channel(1).0);
+
+        manager.spawn_supervised(id, async { panic!("execute_download blew up") });
+
+        match tokio::time::timeout(Duration::from_secs(5), event_rx.recv()).await {
+            Ok(Some(DownlinkEvent::DownloadFailed {
+                id: got,
+                error_code,
+                actions,
+                raw_error,
+                ..
+            })) => {
+                assert_eq!(got, id);
+                assert_eq!(error_code, ErrorCode::Unknown);
+                assert!(matches!(actions[0].kind, ActionKind::OpenLogs));
+                assert_eq!(raw_error.as_deref(), Some("execute_download blew up"));
+            }
+            other => panic!("expected DownloadFailed, got {other:?}"),
+        }
+        assert!(!manager.is_active(id).await);
+        let row = manager
+            .db
+            .call(move |db| db.get_download(id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.status, DownloadStatus::Failed);
+        assert_eq!(row.error_code.as_deref(), Some("UNKNOWN"));
+    }
+
+    #[tokio::test]
+    async fn test_queue_state() {
+        let (event_tx, _event_rx) = mpsc::channel(8);
+        let manager = DownloadManager::new(
+            sample_config(),
+            DbHandle::spawn(Db::open_in_memory().unwrap()).unwrap(),
+            event_tx,
+        );
+        let (active, pending, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        manager
+            .active_downloads
+            .write()
+            .await
+            .insert(active, broadcast::channel(1).0);
+        manager.pending.push_back(pending).await;
+
+        assert_eq!(manager.queue_state(active).await, QueueState::Active);
+        assert_eq!(manager.queue_state(pending).await, QueueState::Pending);
+        assert_eq!(manager.queue_state(other).await, QueueState::NotManaged);
+    }
+
+    #[test]
+    fn test_external_downloader_args_aria2c() {
+        assert_eq!(
+            external_downloader_args("aria2c", Some(Path::new("/usr/bin/aria2c")), 0),
+            vec![
+                "--downloader",
+                "/usr/bin/aria2c",
+                "--downloader-args",
+                "aria2c:-x 16 -s 16 -k 1M"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_external_downloader_args_aria2c_rate_limit() {
+        let args = external_downloader_args("aria2c", Some(Path::new("/usr/bin/aria2c")), 500_000);
+        assert_eq!(
+            arg_value(&args, "--downloader-args"),
+            Some("aria2c:-x 16 -s 16 -k 1M --max-overall-download-limit=500000")
+        );
+    }
+
+    #[test]
+    fn test_external_downloader_args_falls_back_to_native() {
+        // aria2c selected but not installed
+        assert!(external_downloader_args("aria2c", None, 0).is_empty());
+        assert!(external_downloader_args("none", Some(Path::new("/usr/bin/aria2c")), 0).is_empty());
+        assert!(external_downloader_args("curl", Some(Path::new("/usr/bin/aria2c")), 0).is_empty());
+    }
+
+    #[test]
+    fn test_build_download_args_with_aria2c() {
+        let mut config = sample_config();
+        config.aria2c_path = Some(PathBuf::from("/opt/homebrew/bin/aria2c"));
+        let mut settings = UserSettings::default();
+        settings.network.external_downloader = "aria2c".to_string();
+
+        let args = build_download_args(&sample_row("mp4_best"), &config, &settings, None);
+        assert_eq!(
+            arg_value(&args, "--downloader"),
+            Some("/opt/homebrew/bin/aria2c")
+        );
+        assert_eq!(args.last().unwrap(), "https://example.com/watch?v=abc");
+
+        // The per-download speed limit reaches aria2c too
+        let mut row = sample_row("mp4_best");
+        row.rate_limit_bps = Some(100_000);
+        let args = build_download_args(&row, &config, &settings, None);
+        assert!(arg_value(&args, "--downloader-args")
+            .unwrap()
+            .ends_with("--max-overall-download-limit=100000"));
+
+        // Default settings keep the native downloader even when aria2c is installed
+        let args = build_download_args(
+            &sample_row("mp4_best"),
+            &config,
+            &UserSettings::default(),
+            None,
+        );
+        assert!(!args.iter().any(|a| a == "--downloader"));
+    }
+
+    #[test]
+    fn test_output_container() {
+        let preset = |id: &str| Preset::get_by_id(id).unwrap().yt_dlp_args;
+        assert_eq!(output_container(&preset("mp4_1080p")), Some("mp4"));
+        assert_eq!(output_container(&preset("audio_m4a")), Some("m4a"));
+        assert_eq!(output_container(&preset("audio_mp3_320")), Some("mp3"));
+        assert_eq!(output_container(&["-f".to_string(), "b".to_string()]), None);
+    }
+
+    #[test]
+    fn test_sponsorblock_mark_embeds_chapters() {
+        for container in ["mp4", "mkv", "m4a"] {
+            assert_eq!(
+                sponsorblock_args(&sponsorblock("mark"), Some(container), true),
+                vec!["--sponsorblock-mark", "sponsor,intro", "--embed-chapters"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_sponsorblock_mark_skips_chapters_for_unsupported_container() {
+        assert_eq!(
+            sponsorblock_args(&sponsorblock("mark"), Some("mp3"), true),
+            vec!["--sponsorblock-mark", "sponsor,intro"]
+        );
+        assert_eq!(
+            sponsorblock_args(&sponsorblock("mark"), None, true),
+            vec!["--sponsorblock-mark", "sponsor,intro"]
+        );
+    }
+
+    #[test]
+    fn test_sponsorblock_remove_does_not_embed_chapters() {
+        assert_eq!(
+            sponsorblock_args(&sponsorblock("remove"), Some("mp4"), true),
+            vec!["--sponsorblock-remove", "sponsor,intro"]
+        );
+    }
+
+    #[test]
+    fn test_sponsorblock_args_skipped() {
+        // No ffmpeg to run the postprocessors
+        assert!(sponsorblock_args(&sponsorblock("mark"), Some("mp4"), false).is_empty());
+        // Disabled, no categories, or unknown mode
+        let mut disabled = sponsorblock("mark");
+        disabled.enabled_by_default = false;
+        assert!(sponsorblock_args(&disabled, Some("mp4"), true).is_empty());
+        let mut empty = sponsorblock("mark");
+        empty.categories.clear();
+        assert!(sponsorblock_args(&empty, Some("mp4"), true).is_empty());
+        assert!(sponsorblock_args(&sponsorblock("skip"), Some("mp4"), true).is_empty());
+        // Only unknown categories
+        let mut unknown = sponsorblock("mark");
+        unknown.categories = vec!["ads".to_string()];
+        assert!(sponsorblock_args(&unknown, Some("mp4"), true).is_empty());
+    }
+
+    #[test]
+    fn test_sponsorblock_args_drop_unknown_categories() {
+        let mut settings = sponsorblock("remove");
+        settings.categories.insert(1, "ads".to_string());
+        assert_eq!(
+            sponsorblock_args(&settings, Some("mp4"), true),
+            vec!["--sponsorblock-remove", "sponsor,intro"]
+        );
+    }
+
+    #[test]
+    fn test_build_download_args_sponsorblock_mark() {
+        let settings = UserSettings {
+            sponsorblock: sponsorblock("mark"),
+            ..Default::default()
+        };
+
+        let args = build_download_args(&sample_row("mp4_best"), &sample_config(), &settings, None);
+        assert_eq!(
+            arg_value(&args, "--sponsorblock-mark"),
+            Some("sponsor,intro")
+        );
+        assert!(args.iter().any(|a| a == "--embed-chapters"));
+        assert_eq!(args.last().unwrap(), "https://example.com/watch?v=abc");
+    }
+
+    #[test]
+    fn test_postprocessing_retry_possible_with_existing_files() {
+        let dir = std::env::temp_dir().join(format!("downlink-pp-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let video = dir.join("clip [abc].f137.mp4");
+        let audio = dir.join("clip [abc].f140.m4a");
+        std::fs::write(&video, b"video").unwrap();
+        std::fs::write(&audio, b"audio").unwrap();
+        let files = vec![video.clone(), audio.clone()];
+
+        let post = Some("POST_PROCESSING_FAILED");
+        let ok = postprocessing_retry_possible(post, &files);
+        let wrong_code = postprocessing_retry_possible(Some("NETWORK"), &files);
+        let no_code = postprocessing_retry_possible(None, &files);
+
+        std::fs::remove_file(&audio).unwrap();
+        let missing = postprocessing_retry_possible(post, &files);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(ok.is_ok());
+        assert!(wrong_code.is_err());
+        assert!(no_code.is_err());
+        assert!(missing.unwrap_err().contains("no longer on disk"));
+    }
+
+    #[test]
+    fn test_postprocessing_retry_not_possible_without_complete_files() {
+        let post = Some("POST_PROCESSING_FAILED");
+        assert!(postprocessing_retry_possible(post, &[])
+            .unwrap_err()
+            .contains("No downloaded files"));
+
+        let dir = std::env::temp_dir().join(format!("downlink-pp-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let partial = dir.join("clip [abc].f137.mp4.part");
+        std::fs::write(&partial, b"half").unwrap();
+        let result = postprocessing_retry_possible(post, &[partial]);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(result.unwrap_err().contains("didn't finish"));
+    }
+
+    /// Fake yt-dlp body that runs until the test creates `release` next to it.
+    #[cfg(unix)]
+    const RUN_UNTIL_RELEASED: &str = r#"while [ ! -e "$(dirname "$0")/release" ]; do sleep 0.05; done
+exit 0"#;
+
+    /// Fake yt-dlp body that fails with a network error until `online` exists next to it.
+    #[cfg(unix)]
+    const FAIL_UNTIL_ONLINE: &str = r#"[ -e "$(dirname "$0")/online" ] && exit 0
+echo 'ERROR: Unable to download webpage: Connection refused' >&2
+exit 1"#;
+
+    /// Manager over an in-memory database with `downloads` queued rows, running a fake
+    /// yt-dlp whose `sh` body is `script`. Returns the manager, the temp dir holding
+    /// yt-dlp and the downloads, and the row ids.
+    ///
+    /// The rows have a title, so starting them skips the metadata fetch.
+    #[cfg(unix)]
+    fn fake_manager(
+        script: &str,
+        downloads: usize,
+        config: DownloadConfig,
+        event_tx: mpsc::Sender<DownlinkEvent>,
+    ) -> (Arc<DownloadManager>, PathBuf, Vec<Uuid>) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("downlink-dm-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let yt_dlp = dir.join("yt-dlp");
+        std::fs::write(&yt_dlp, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&yt_dlp, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut db = Db::open_in_memory().unwrap();
+        let ids = (0..downloads)
+            .map(|_| {
+                let id = insert_sample_download(&mut db, &dir);
+                db.update_metadata(id, Some("Clip"), None, None, None)
+                    .unwrap();
+                id
+            })
+            .collect();
+
+        let config = DownloadConfig {
+            yt_dlp_path: yt_dlp,
+            ..config
+        };
+        let manager = Arc::new(DownloadManager::new(
+            config,
+            DbHandle::spawn(db).unwrap(),
+            event_tx,
+        ));
+        manager.start_retry_loop();
+        (manager, dir, ids)
+    }
+
+    #[cfg(unix)]
+    fn insert_sample_download(db: &mut Db, dir: &Path) -> Uuid {
+        db.insert_download(
+            "https://example.com/v",
+            crate::db::SourceKind::Single,
+            None,
+            "recommended_best",
+            dir.to_str().unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// Keep the manager's event channel from filling up in tests that don't read it.
+    #[cfg(unix)]
+    fn drain_events(mut event_rx: mpsc::Receiver<DownlinkEvent>) {
+        tokio::spawn(async move { while event_rx.recv().await.is_some() {} });
+    }
+
+    /// Poll until the download has `status`, failing the test after 10 seconds.
+    #[cfg(unix)]
+    async fn wait_for_status(
+        manager: &DownloadManager,
+        id: Uuid,
+        status: DownloadStatus,
+    ) -> DownloadRow {
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            let row = manager
+                .db
+                .call(move |db| Ok(db.get_download(id)?.unwrap()))
+                .await
+                .unwrap();
+            if row.status == status {
+                return row;
+            }
+            assert!(
+                std::time::Instant::now() < deadline,
+                "download never reached {status:?}"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_held_starts_wait_for_the_tool_update() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let (manager, dir, ids) = fake_manager("exit 0", 1, sample_config(), event_tx);
+        let id = ids[0];
+
+        // Idle, so the update may go ahead; a start meanwhile waits for it
+        assert!(manager.hold_starts_if_idle().await);
+        manager.start(id).await.unwrap();
+        assert_eq!(manager.queue_state(id).await, QueueState::Pending);
+        assert_eq!(manager.active_count().await, 0);
+
+        manager.release_starts().await;
+        wait_for_status(&manager, id, DownloadStatus::Done).await;
+
+        // A running download keeps the update waiting instead
+        manager
+            .active_downloads
+            .write()
+            .await
+            .insert(Uuid::new_v4(), broadcast::channel(1).0);
+        assert!(!manager.hold_starts_if_idle().await);
+        assert!(!manager.pending.is_held());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_paused_manager_does_not_start_queued_downloads() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let config = DownloadConfig {
+            max_concurrent: 1,
+            ..sample_config()
+        };
+        let (manager, dir, ids) = fake_manager(RUN_UNTIL_RELEASED, 2, config, event_tx);
+        let (first, second) = (ids[0], ids[1]);
+        let status_of = |id| {
+            let manager = manager.clone();
+            async move {
+                manager
+                    .db
+                    .call(move |db| Ok(db.get_download(id)?.unwrap().status))
+                    .await
+                    .unwrap()
+            }
+        };
+
+        manager.start(first).await.unwrap();
+        manager.start(second).await.unwrap();
+        manager.pause_all().await.unwrap();
+
+        // The first stops and the freed slot isn't handed on
+        wait_for_status(&manager, first, DownloadStatus::Stopped).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let status = manager.status().await;
+        assert!(status.paused);
+        assert_eq!((status.active, status.pending), (0, 2));
+        assert_eq!(status_of(second).await, DownloadStatus::Queued);
+
+        // Starting more while paused only queues them
+        manager.start(second).await.unwrap();
+        assert_eq!(manager.queue_state(second).await, QueueState::Pending);
+
+        // Resuming picks up the paused download first, then the rest
+        manager.resume_all().await.unwrap();
+        assert_eq!(manager.queue_state(first).await, QueueState::Active);
+        assert_eq!(manager.queue_state(second).await, QueueState::Pending);
+        std::fs::write(dir.join("release"), b"").unwrap();
+        wait_for_status(&manager, second, DownloadStatus::Done).await;
+        assert_eq!(status_of(first).await, DownloadStatus::Done);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_network_failures_need_several_close_together() {
+        let failures = NetworkFailures::default();
+        let start = std::time::Instant::now();
+
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(!failures.record(a, start));
+        assert!(!failures.record(b, start + Duration::from_secs(10)));
+        assert!(failures.record(a, start + Duration::from_secs(20)));
+        assert_eq!(failures.recent(), vec![a, b]);
+
+        // Old failures fall out of the window
+        failures.clear();
+        assert!(!failures.record(a, start));
+        assert!(!failures.record(b, start + Duration::from_secs(10)));
+        assert!(!failures.record(c, start + OFFLINE_FAILURE_WINDOW + Duration::from_secs(1)));
+        assert_eq!(failures.recent(), vec![b, c]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_offline_holds_downloads_and_online_resumes_them() {
+        let (event_tx, mut event_rx) = mpsc::channel(256);
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        {
+            let events = events.clone();
+            tokio::spawn(async move {
+                while let Some(event) = event_rx.recv().await {
+                    if let DownlinkEvent::ConnectivityChanged { online } = event {
+                        events.lock().unwrap().push(online);
+                    }
+                }
+            });
+        }
+        let config = DownloadConfig {
+            max_concurrent: 3,
+            retry_base_delay: Duration::from_millis(10),
+            ..sample_config()
+        };
+        let (manager, dir, ids) = fake_manager(FAIL_UNTIL_ONLINE, 4, config, event_tx);
+        let row_of = |id| {
+            let manager = manager.clone();
+            async move {
+                manager
+                    .db
+                    .call(move |db| Ok(db.get_download(id)?.unwrap()))
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // Three downloads failing on the network take the manager offline; none is
+        // marked failed, they wait for the network instead
+        for id in &ids[..3] {
+            manager.start(*id).await.unwrap();
+        }
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while manager.status().await.pending < 3 {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "downloads were never held"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!manager.status().await.online);
+        for id in &ids[..3] {
+            let row = row_of(*id).await;
+            assert_eq!(row.status, DownloadStatus::Queued);
+            assert_eq!(row.phase.as_deref(), Some(WAITING_FOR_NETWORK));
+        }
+
+        // Starting another while offline holds it too
+        manager.start(ids[3]).await.unwrap();
+        assert_eq!(manager.queue_state(ids[3]).await, QueueState::Pending);
+        assert_eq!(manager.status().await.active, 0);
+
+        // Back online, every held download runs
+        std::fs::write(dir.join("online"), b"").unwrap();
+        manager.set_online(true).await;
+        for id in &ids {
+            wait_for_status(&manager, *id, DownloadStatus::Done).await;
+        }
+        assert!(manager.status().await.online);
+        assert_eq!(*events.lock().unwrap(), vec![false, true]);
+
+        // The UI going offline holds new downloads without any failure
+        manager.set_online(false).await;
+        manager.set_online(false).await;
+        assert!(!manager.status().await.online);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*events.lock().unwrap(), vec![false, true, false]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_network_failures_before_going_offline_are_held_too() {
+        let (event_tx, event_rx) = mpsc::channel(256);
+        drain_events(event_rx);
+        // No retries, so the first failures are final before the third one
+        let config = DownloadConfig {
+            max_auto_retries: 0,
+            ..sample_config()
+        };
+        let (manager, dir, ids) = fake_manager(FAIL_UNTIL_ONLINE, 3, config, event_tx);
+
+        // One at a time: the first two fail outright, the third shows the network is down
+        for id in &ids[..2] {
+            manager.start(*id).await.unwrap();
+            wait_for_status(&manager, *id, DownloadStatus::Failed).await;
+        }
+        manager.start(ids[2]).await.unwrap();
+        for id in &ids {
+            let row = wait_for_status(&manager, *id, DownloadStatus::Queued).await;
+            assert_eq!(row.phase.as_deref(), Some(WAITING_FOR_NETWORK));
+            assert_eq!(manager.queue_state(*id).await, QueueState::Pending);
+        }
+
+        std::fs::write(dir.join("online"), b"").unwrap();
+        manager.set_online(true).await;
+        for id in &ids {
+            wait_for_status(&manager, *id, DownloadStatus::Done).await;
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_refresh_metadata_updates_row_and_emits_event() {
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let (manager, dir, _) = fake_manager(
+            r#"echo '{"title": "New title", "uploader": "Someone", "duration": 61.5, "thumbnail": "https://i.example.com/new.jpg"}'"#,
+            0,
+            sample_config(),
+            event_tx,
+        );
+        let (id, running) = {
+            let dir = dir.clone();
+            manager
+                .db
+                .call(move |db| {
+                    let id = insert_sample_download(db, &dir);
+                    db.update_metadata(
+                        id,
+                        Some("Old title"),
+                        None,
+                        None,
+                        Some("https://i.example.com/old.jpg"),
+                    )?;
+                    let running = insert_sample_download(db, &dir);
+                    db.set_status(running, DownloadStatus::Downloading, None)?;
+                    Ok((id, running))
+                })
+                .await
+                .unwrap()
+        };
+
+        let info = manager.refresh_metadata(id).await.unwrap();
+        assert_eq!(info.title.as_deref(), Some("New title"));
+
+        let row = manager
+            .db
+            .call(move |db| db.get_download(id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.title.as_deref(), Some("New title"));
+        assert_eq!(row.uploader.as_deref(), Some("Someone"));
+        assert_eq!(row.duration_seconds, Some(61));
+        assert_eq!(
+            row.thumbnail_url.as_deref(),
+            Some("https://i.example.com/new.jpg")
+        );
+        match event_rx.recv().await {
+            Some(DownlinkEvent::MetadataReady { id: got, info }) => {
+                assert_eq!(got, id);
+                assert_eq!(info.webpage_url.as_deref(), Some("https://example.com/v"));
+            }
+            other => panic!("expected MetadataReady, got {other:?}"),
+        }
+
+        let err = manager.refresh_metadata(running).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RequestError>(),
+            Some(RequestError::Invalid(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_manager_status_tracks_slots_and_pending_queue() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let config = DownloadConfig {
+            max_concurrent: 1,
+            ..sample_config()
+        };
+        let (manager, dir, ids) = fake_manager(RUN_UNTIL_RELEASED, 2, config, event_tx);
+        let (first, second) = (ids[0], ids[1]);
+
+        let status = |active, pending| ManagerStatus {
+            active,
+            max_concurrent: 1,
+            pending,
+            paused: false,
+            online: true,
+        };
+
+        assert_eq!(manager.status().await, status(0, 0));
+        assert_eq!(manager.queue_state(first).await, QueueState::NotManaged);
+        manager.start(first).await.unwrap();
+        manager.start(second).await.unwrap();
+        assert_eq!(manager.status().await, status(1, 1));
+        assert_eq!(manager.queue_state(first).await, QueueState::Active);
+        assert_eq!(manager.queue_state(second).await, QueueState::Pending);
+
+        // Starting the running one again doesn't queue a duplicate
+        manager.start(first).await.unwrap();
+        assert_eq!(manager.status().await, status(1, 1));
+
+        // The first one finishing hands its slot to the second
+        std::fs::write(dir.join("release"), b"").unwrap();
+        wait_for_status(&manager, second, DownloadStatus::Done).await;
+        assert_eq!(manager.status().await, status(0, 0));
+        assert_eq!(manager.queue_state(second).await, QueueState::NotManaged);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_concurrent_starts_respect_the_limit() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let config = DownloadConfig {
+            max_concurrent: 1,
+            ..sample_config()
+        };
+        let (manager, dir, ids) = fake_manager(RUN_UNTIL_RELEASED, 3, config, event_tx);
+
+        // Each start awaits the database between the slot check and spawning the task
+        let (a, b, c) = tokio::join!(
+            manager.start(ids[0]),
+            manager.start(ids[1]),
+            manager.start(ids[2])
+        );
+        a.unwrap();
+        b.unwrap();
+        c.unwrap();
+        let status = manager.status().await;
+
+        std::fs::write(dir.join("release"), b"").unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!((status.active, status.pending), (1, 2));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stalled_download_is_killed_as_a_network_error() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let config = DownloadConfig {
+            stall_timeout: Duration::from_millis(300),
+            max_auto_retries: 0,
+            ..sample_config()
+        };
+        // Hangs mid-transfer, like a stuck network read
+        let script = r#"echo $$ > "$(dirname "$0")/pid"
+echo '[downlink] 10.0% 1.0MiB/s 00:10 10MiB'
+exec sleep 30"#;
+        let (manager, dir, ids) = fake_manager(script, 1, config, event_tx);
+
+        let started = std::time::Instant::now();
+        manager.start(ids[0]).await.unwrap();
+        let row = wait_for_status(&manager, ids[0], DownloadStatus::Failed).await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(row.error_code.as_deref(), Some(ErrorCode::Network.as_str()));
+        assert!(row.error_message.unwrap().starts_with("Download stalled"));
+
+        let pid = std::fs::read_to_string(dir.join("pid")).unwrap();
+        let alive = std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+            .success();
+        assert!(!alive, "stalled yt-dlp was left running");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stall_watchdog_ignores_extraction_and_sleeps() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let config = DownloadConfig {
+            stall_timeout: Duration::from_millis(300),
+            max_auto_retries: 0,
+            ..sample_config()
+        };
+        // Silent longer than the timeout before the first progress line, and again
+        // after announcing a sleep
+        let script = r#"sleep 1
+echo '[downlink] 10.0% 1.0MiB/s 00:10 10MiB'
+echo '[download] Sleeping 1.00 seconds ...'
+sleep 1
+echo '[downlink] 100.0% 1.0MiB/s 00:00 10MiB'"#;
+        let (manager, dir, ids) = fake_manager(script, 1, config, event_tx);
+
+        manager.start(ids[0]).await.unwrap();
+        wait_for_status(&manager, ids[0], DownloadStatus::Done).await;
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
-}
 
-fn parse_percent(s: &str) -> Option<f64> {
-    let cleaned = s.trim_end_matches('%').trim();
-    cleaned.parse::<f64>().ok()
-}
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_polite_sleeps_longer_than_the_stall_timeout_are_not_stalls() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let config = DownloadConfig {
+            stall_timeout: Duration::from_millis(300),
+            max_auto_retries: 0,
+            ..sample_config()
+        };
+        // A silent one-second pause mid-transfer, as a --sleep-requests wait can be
+        let script = r#"echo '[downlink] 10.0% 1.0MiB/s 00:10 10MiB'
+sleep 1
+echo '[downlink] 100.0% 1.0MiB/s 00:00 10MiB'"#;
+        let (manager, dir, ids) = fake_manager(script, 1, config, event_tx);
 
-fn parse_speed(s: &str) -> Option<u64> {
-    // Format: "1.5MiB/s" or "500KiB/s"
-    let s = s.trim();
-    if s == "N/A" || s.is_empty() {
-        return None;
+        let mut settings = UserSettings::default();
+        settings.network.polite_mode = true;
+        settings.network.sleep_requests_secs = 1;
+        manager
+            .db
+            .call(move |db| SettingsManager::new(db.conn()).save_user_settings(&settings))
+            .await
+            .unwrap();
+
+        manager.start(ids[0]).await.unwrap();
+        wait_for_status(&manager, ids[0], DownloadStatus::Done).await;
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    let re = Regex::new(r"([\d.]+)\s*(Ki?B|Mi?B|Gi?B|B)").ok()?;
-    let caps = re.captures(s)?;
-    let num: f64 = caps.get(1)?.as_str().parse().ok()?;
-    let unit = caps.get(2)?.as_str();
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shared_rate_limit_skips_downloads_with_their_own() {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        drain_events(event_rx);
+        let config = DownloadConfig {
+            max_concurrent: 3,
+            ..sample_config()
+        };
+        let script = format!("echo \"$@\" >> \"$(dirname \"$0\")/args\"\n{RUN_UNTIL_RELEASED}");
+        let (manager, dir, ids) = fake_manager(&script, 3, config, event_tx);
 
-    let multiplier: f64 = match unit {
-        "B" => 1.0,
-        "KB" | "KiB" => 1024.0,
-        "MB" | "MiB" => 1024.0 * 1024.0,
-        "GB" | "GiB" => 1024.0 * 1024.0 * 1024.0,
-        _ => 1.0,
-    };
+        let mut settings = UserSettings::default();
+        settings.network.rate_limit_bps = 1_000_000;
+        settings.network.global_rate_limit = true;
+        let own = ids[0];
+        manager
+            .db
+            .call(move |db| {
+                SettingsManager::new(db.conn()).save_user_settings(&settings)?;
+                db.set_rate_limit(own, Some(50_000))
+            })
+            .await
+            .unwrap();
 
-    Some((num * multiplier) as u64)
-}
+        for id in &ids {
+            manager.start(*id).await.unwrap();
+        }
+        let args_file = dir.join("args");
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let limits = loop {
+            let lines = std::fs::read_to_string(&args_file).unwrap_or_default();
+            if lines.lines().count() == 3 {
+                let mut limits: Vec<u64> = lines
+                    .lines()
+                    .filter_map(|line| {
+                        let args: Vec<String> = line.split(' ').map(str::to_string).collect();
+                        arg_value(&args, "--limit-rate").and_then(|v| v.parse().ok())
+                    })
+                    .collect();
+                limits.sort();
+                break limits;
+            }
+            assert!(std::time::Instant::now() < deadline, "downloads never ran");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+        // The own-limit download doesn't count: the others split the limit two ways
+        // at most
+        assert_eq!(limits, vec![50_000, 500_000, 1_000_000]);
 
-fn parse_eta(s: &str) -> Option<u64> {
-    // Format: "00:05:30" or "05:30" or "30" or "N/A"
-    let s = s.trim();
-    if s == "N/A" || s.is_empty() {
-        return None;
+        std::fs::write(dir.join("release"), b"").unwrap();
+        for id in &ids {
+            wait_for_status(&manager, *id, DownloadStatus::Done).await;
+        }
+        assert!(manager.rate_limit_sharers.read().await.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    let parts: Vec<&str> = s.split(':').collect();
-    match parts.len() {
-        1 => parts[0].parse::<u64>().ok(),
-        2 => {
-            let mins: u64 = parts[0].parse().ok()?;
-            let secs: u64 = parts[1].parse().ok()?;
-            Some(mins * 60 + secs)
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_metadata_started_emitted_before_ytdlp_spawns() {
+        // A one-slot channel the test doesn't read yet: the manager blocks on its
+        // second event, so anything it spawns after that can't have run.
+        let (event_tx, mut event_rx) = mpsc::channel(1);
+        let (manager, dir, _) = fake_manager(
+            r#"touch "$(dirname "$0")/spawned"
+exit 1"#,
+            0,
+            sample_config(),
+            event_tx,
+        );
+        let marker = dir.join("spawned");
+        // No title, so starting it fetches metadata first
+        let id = {
+            let dir = dir.clone();
+            manager
+                .db
+                .call(move |db| Ok(insert_sample_download(db, &dir)))
+                .await
+                .unwrap()
+        };
+        let task = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.start(id).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!marker.exists());
+        match event_rx.recv().await {
+            Some(DownlinkEvent::MetadataStarted { id: got, url }) => {
+                assert_eq!(got, id);
+                assert_eq!(url, "https://example.com/v");
+            }
+            other => panic!("expected MetadataStarted, got {other:?}"),
         }
-        3 => {
-            let hours: u64 = parts[0].parse().ok()?;
-            let mins: u64 = parts[1].parse().ok()?;
-            let secs: u64 = parts[2].parse().ok()?;
-            Some(hours * 3600 + mins * 60 + secs)
+
+        // Once events flow again yt-dlp gets spawned
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !marker.exists() && std::time::Instant::now() < deadline {
+            let _ = tokio::time::timeout(Duration::from_millis(50), event_rx.recv()).await;
         }
-        _ => None,
+        assert!(marker.exists());
+
+        task.abort();
+        let _ = std::fs::remove_dir_all(&dir);
     }
-}
 
-fn parse_bytes(s: &str) -> Option<u64> {
-    // Format: "1.5GiB" or "500MiB" or "N/A"
-    let s = s.trim();
-    if s == "N/A" || s.is_empty() {
-        return None;
+    #[test]
+    fn test_record_usage_accumulates() {
+        let mut db = Db::open_in_memory().unwrap();
+        let session = AtomicU64::new(0);
+        let completed = |db: &mut Db, size: Option<i64>| {
+            let id = db
+                .insert_download(
+                    "https://example.com/v",
+                    crate::db::SourceKind::Single,
+                    None,
+                    "recommended_best",
+                    "/downloads",
+                )
+                .unwrap();
+            if let Some(size) = size {
+                db.set_actual_filesize(id, size).unwrap();
+            }
+            record_usage(db, &session, id);
+        };
+
+        completed(&mut db, Some(1_000));
+        completed(&mut db, Some(500));
+        // Unknown size adds nothing
+        completed(&mut db, None);
+
+        assert_eq!(session.load(Ordering::Relaxed), 1_500);
+        let usage = db.get_usage(None).unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].bytes, 1_500);
     }
 
-    let re = Regex::new(r"([\d.]+)\s*(Ki?B|Mi?B|Gi?B|B)").ok()?;
-    let caps = re.captures(s)?;
-    let num: f64 = caps.get(1)?.as_str().parse().ok()?;
-    let unit = caps.get(2)?.as_str();
+    #[tokio::test]
+    async fn test_shutdown_marks_active_downloads_stopped() {
+        let mut db = Db::open_in_memory().unwrap();
+        let active = db
+            .insert_download(
+                "https://example.com/a",
+                crate::db::SourceKind::Single,
+                None,
+                "recommended_best",
+                "/downloads",
+            )
+            .unwrap();
+        let finished = db
+            .insert_download(
+                "https://example.com/b",
+                crate::db::SourceKind::Single,
+                None,
+                "recommended_best",
+                "/downloads",
+            )
+            .unwrap();
+        let user_stopped = db
+            .insert_download(
+                "https://example.com/c",
+                crate::db::SourceKind::Single,
+                None,
+                "recommended_best",
+                "/downloads",
+            )
+            .unwrap();
+        db.set_status(active, DownloadStatus::Downloading, Some("50%"))
+            .unwrap();
+        db.set_status(finished, DownloadStatus::Done, Some("Completed"))
+            .unwrap();
+        db.set_status(user_stopped, DownloadStatus::Downloading, Some("10%"))
+            .unwrap();
 
-    let multiplier: f64 = match unit {
-        "B" => 1.0,
-        "KB" | "KiB" => 1024.0,
-        "MB" | "MiB" => 1024.0 * 1024.0,
-        "GB" | "GiB" => 1024.0 * 1024.0 * 1024.0,
-        _ => 1.0,
-    };
+        let (event_tx, _event_rx) = mpsc::channel(8);
+        let manager = DownloadManager::new(sample_config(), DbHandle::spawn(db).unwrap(), event_tx);
 
-    Some((num * multiplier) as u64)
-}
+        // Simulate a running download whose task never reports back in time
+        let (cancel_tx, mut cancel_rx) = broadcast::channel::<()>(1);
+        manager
+            .active_downloads
+            .write()
+            .await
+            .insert(active, cancel_tx);
+        manager
+            .active_downloads
+            .write()
+            .await
+            .insert(finished, broadcast::channel(1).0);
+        // Stopped by the user just before quitting; its task writes "Stopped"
+        let (stop_tx, _stop_rx) = broadcast::channel::<()>(1);
+        manager
+            .active_downloads
+            .write()
+            .await
+            .insert(user_stopped, stop_tx);
+        manager.stop(user_stopped).await.unwrap();
+        manager
+            .db
+            .call(move |db| db.set_status(user_stopped, DownloadStatus::Stopped, Some("Stopped")))
+            .await
+            .unwrap();
 
-/// Classify yt-dlp errors into user-friendly categories with remediation actions.
-fn classify_error(stderr: &str) -> (ErrorCode, String, Vec<Action>) {
-    let stderr_lower = stderr.to_lowercase();
+        manager
+            .shutdown_within(Duration::from_millis(100))
+            .await
+            .unwrap();
 
-    // Sign-in / cookies required
-    if stderr_lower.contains("sign in")
-        || stderr_lower.contains("login")
-        || stderr_lower.contains("cookies")
-        || stderr_lower.contains("age-restricted")
-    {
-        return (
-            ErrorCode::LoginRequired,
-            "This content requires sign-in. Import cookies from your browser and retry."
-                .to_string(),
-            vec![Action {
-                kind: ActionKind::ImportCookies,
-                label: "Import cookies from browser".to_string(),
-            }],
-        );
+        assert!(cancel_rx.try_recv().is_ok(), "stop signal should be sent");
+        let (row, finished) = manager
+            .db
+            .call(move |db| Ok((db.get_download(active)?, db.get_download(finished)?)))
+            .await
+            .unwrap();
+        let row = row.unwrap();
+        assert_eq!(row.status, DownloadStatus::Stopped);
+        assert_eq!(row.phase.as_deref(), Some(INTERRUPTED_PHASE));
+        assert_eq!(finished.unwrap().status, DownloadStatus::Done);
+        let stopped = manager
+            .db
+            .call(move |db| db.get_download(user_stopped))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stopped.status, DownloadStatus::Stopped);
+        assert_eq!(stopped.phase.as_deref(), Some("Stopped"));
     }
 
-    // Bot check / CAPTCHA
-    if stderr_lower.contains("bot")
-        || stderr_lower.contains("captcha")
-        || stderr_lower.contains("confirm you're not")
-    {
-        return (
-            ErrorCode::BotCheck,
-            "The site requires verification. Import cookies from a logged-in browser session."
-                .to_string(),
-            vec![Action {
-                kind: ActionKind::ImportCookies,
-                label: "Import cookies from browser".to_string(),
-            }],
+    #[test]
+    fn test_completion_notice_respects_setting() {
+        assert_eq!(completion_notice(false, None), CompletionNotice::Skip);
+        assert_eq!(
+            completion_notice(false, Some(PlaylistTally::default())),
+            CompletionNotice::Skip
         );
+        assert_eq!(completion_notice(true, None), CompletionNotice::Single);
     }
 
-    // Geo-restriction
-    if stderr_lower.contains("not available in your country")
-        || stderr_lower.contains("geo")
-        || stderr_lower.contains("blocked")
-    {
-        return (
-            ErrorCode::GeoRestricted,
-            "This content is not available in your region.".to_string(),
-            vec![Action {
-                kind: ActionKind::OpenSettingsProxy,
-                label: "Configure proxy".to_string(),
-            }],
+    #[test]
+    fn test_completion_notice_batches_playlist_children() {
+        let pending = PlaylistTally {
+            unfinished: 2,
+            completed: 3,
+            failed: 0,
+        };
+        assert_eq!(
+            completion_notice(true, Some(pending)),
+            CompletionNotice::Skip
         );
-    }
 
-    // Extractor outdated
-    if stderr_lower.contains("unsupported url")
-        || stderr_lower.contains("no video formats")
-        || stderr_lower.contains("extractor")
-    {
-        return (
-            ErrorCode::ExtractorOutdated,
-            "The downloader engine may be outdated for this site.".to_string(),
-            vec![
-                Action {
-                    kind: ActionKind::UpdateYtDlp,
-                    label: "Update yt-dlp".to_string(),
-                },
-                Action {
-                    kind: ActionKind::Retry,
-                    label: "Retry".to_string(),
-                },
-            ],
+        let finished = PlaylistTally {
+            unfinished: 0,
+            completed: 4,
+            failed: 1,
+        };
+        assert_eq!(
+            completion_notice(true, Some(finished)),
+            CompletionNotice::PlaylistSummary {
+                completed: 4,
+                failed: 1
+            }
         );
     }
 
-    // Format unavailable
-    if stderr_lower.contains("requested format") || stderr_lower.contains("format not available") {
-        return (
-            ErrorCode::FormatUnavailable,
-            "The requested format is not available for this content.".to_string(),
-            vec![Action {
-                kind: ActionKind::RetryRecommended,
-                label: "Use Recommended preset".to_string(),
-            }],
-        );
+    #[test]
+    fn test_failed_last_child_still_sends_playlist_summary() {
+        use crate::db::SourceKind;
+
+        let mut db = Db::open_in_memory().unwrap();
+        let url = "https://example.com/list";
+        let parent = db
+            .insert_download(url, SourceKind::PlaylistParent, None, "mp4_best", "/d")
+            .unwrap();
+        let child = |db: &mut Db| {
+            db.insert_download(
+                url,
+                SourceKind::PlaylistItem,
+                Some(parent),
+                "mp4_best",
+                "/d",
+            )
+            .unwrap()
+        };
+        let (first, last) = (child(&mut db), child(&mut db));
+        db.set_status(first, DownloadStatus::Done, None).unwrap();
+        db.set_error(last, Some("network"), Some("gone"), None)
+            .unwrap();
+        let last = db.get_download(last).unwrap().unwrap();
+
+        let Some(DownlinkEvent::NotifyCompleted { id, body, .. }) =
+            completion_notification(&mut db, &last, false, None, true)
+        else {
+            panic!("expected a playlist summary");
+        };
+        assert_eq!(id, parent);
+        assert!(body.ends_with("1 item downloaded, 1 failed"), "{body}");
+
+        // A failed download outside a playlist doesn't notify as complete
+        let single = db
+            .insert_download(url, SourceKind::Single, None, "mp4_best", "/d")
+            .unwrap();
+        let single = db.get_download(single).unwrap().unwrap();
+        assert!(completion_notification(&mut db, &single, false, None, true).is_none());
     }
 
-    // Network errors
-    if stderr_lower.contains("network")
-        || stderr_lower.contains("connection")
-        || stderr_lower.contains("timeout")
-        || stderr_lower.contains("timed out")
-    {
-        return (
-            ErrorCode::Network,
-            "Network error occurred. Check your connection and retry.".to_string(),
-            vec![Action {
-                kind: ActionKind::Retry,
-                label: "Retry".to_string(),
-            }],
+    #[test]
+    fn test_playlist_tally() {
+        let children: Vec<DownloadRow> = [
+            DownloadStatus::Done,
+            DownloadStatus::Done,
+            DownloadStatus::Failed,
+            DownloadStatus::Canceled,
+            DownloadStatus::Downloading,
+            DownloadStatus::Queued,
+        ]
+        .into_iter()
+        .map(|status| DownloadRow {
+            status,
+            ..sample_row("recommended_best")
+        })
+        .collect();
+
+        assert_eq!(
+            playlist_tally(&children),
+            PlaylistTally {
+                unfinished: 2,
+                completed: 2,
+                failed: 1
+            }
         );
     }
 
-    // Default: unknown error
-    let message = if stderr.len() > 200 {
-        format!("Download failed: {}…", &stderr[..200])
-    } else if stderr.is_empty() {
-        "Download failed with unknown error.".to_string()
-    } else {
-        format!("Download failed: {}", stderr)
-    };
+    #[test]
+    fn test_rate_limit_share() {
+        assert_eq!(rate_limit_share(1_000_000, 1), 1_000_000);
+        assert_eq!(rate_limit_share(1_000_000, 4), 250_000);
+        assert_eq!(rate_limit_share(1_000_000, 3), 333_333);
+        // Zero active is treated as one so the first download gets the whole cap
+        assert_eq!(rate_limit_share(1_000_000, 0), 1_000_000);
+        assert_eq!(rate_limit_share(100_000, 50), 2_000);
+        assert_eq!(rate_limit_share(4_096, 2), 2_048);
+        // yt-dlp needs a positive limit, even if that puts the total over
+        assert_eq!(rate_limit_share(3, 8), 1);
+    }
 
-    (
-        ErrorCode::Unknown,
-        message,
-        vec![
-            Action {
-                kind: ActionKind::Retry,
-                label: "Retry".to_string(),
-            },
-            Action {
-                kind: ActionKind::OpenLogs,
-                label: "View logs".to_string(),
-            },
-        ],
-    )
-}
+    #[test]
+    fn test_validate_sections_accepts_time_ranges() {
+        assert!(validate_sections("*00:10:00-00:15:00").is_ok());
+        assert!(validate_sections("*10:15-15:00").is_ok());
+        assert!(validate_sections("*90-120.5").is_ok());
+        assert!(validate_sections("*1:02:03-inf").is_ok());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_validate_sections_rejects_malformed_ranges() {
+        assert!(validate_sections("00:10:00-00:15:00").is_err());
+        assert!(validate_sections("*00:10:00").is_err());
+        assert!(validate_sections("*00:15:00-00:10:00").is_err());
+        assert!(validate_sections("*00:61:00-01:00:00").is_err());
+        assert!(validate_sections("*ab:cd-ef").is_err());
+        assert!(validate_sections("*1:2:3:4-inf").is_err());
+        assert!(validate_sections("*inf-10").is_err());
+    }
 
     #[test]
-    fn test_parse_percent() {
-        assert_eq!(parse_percent("50.5%"), Some(50.5));
-        assert_eq!(parse_percent("100%"), Some(100.0));
-        assert_eq!(parse_percent("N/A"), None);
+    fn test_sections_args() {
+        assert_eq!(
+            sections_args(" *00:10:00-00:15:00 "),
+            vec![
+                "--download-sections",
+                "*00:10:00-00:15:00",
+                "--force-keyframes-at-cuts"
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_speed() {
-        assert_eq!(parse_speed("1.5MiB/s"), Some(1572864));
-        assert_eq!(parse_speed("500KiB/s"), Some(512000));
-        assert_eq!(parse_speed("N/A"), None);
+    fn test_resolve_final_path_relative_joins_output_dir() {
+        let dir = std::env::temp_dir();
+        let resolved = resolve_final_path("clip [abc].mp4", dir.to_str());
+        assert_eq!(resolved, dir.join("clip [abc].mp4"));
     }
 
     #[test]
-    fn test_parse_eta() {
-        assert_eq!(parse_eta("30"), Some(30));
-        assert_eq!(parse_eta("05:30"), Some(330));
-        assert_eq!(parse_eta("01:05:30"), Some(3930));
-        assert_eq!(parse_eta("N/A"), None);
+    fn test_resolve_final_path_absolute_is_unchanged() {
+        let absolute = std::env::temp_dir().join("clip [abc].mp4");
+        let resolved = resolve_final_path(absolute.to_str().unwrap(), Some("/elsewhere"));
+        assert_eq!(resolved, absolute);
     }
 
     #[test]
-    fn test_classify_error_login() {
-        let (code, _, _) = classify_error("Sign in to confirm your age");
-        assert!(matches!(code, ErrorCode::LoginRequired));
+    fn test_resolve_final_path_without_output_dir() {
+        assert_eq!(
+            resolve_final_path("clip.mp4", None),
+            PathBuf::from("clip.mp4")
+        );
+        assert_eq!(
+            resolve_final_path("clip.mp4", Some("")),
+            PathBuf::from("clip.mp4")
+        );
+    }
+
+    #[test]
+    fn test_parse_ffprobe_output() {
+        let json = r#"{
+            "format": {
+                "filename": "/tmp/video [abc].mp4",
+                "nb_streams": 2,
+                "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+                "duration": "212.091000",
+                "size": "15582718",
+                "bit_rate": "587771"
+            }
+        }"#;
+
+        let probed = parse_ffprobe_output(json).unwrap();
+        assert_eq!(probed.duration_seconds, Some(212.091));
+        assert_eq!(probed.size_bytes, Some(15_582_718));
+    }
+
+    #[test]
+    fn test_parse_ffprobe_output_missing_fields() {
+        assert_eq!(parse_ffprobe_output(r#"{"format": {}}"#), None);
+        assert_eq!(parse_ffprobe_output("{}"), None);
+        assert_eq!(parse_ffprobe_output("not json"), None);
+
+        let probed = parse_ffprobe_output(r#"{"format": {"size": "1024"}}"#).unwrap();
+        assert_eq!(probed.duration_seconds, None);
+        assert_eq!(probed.size_bytes, Some(1024));
     }
 
     #[test]
     fn test_classify_error_geo() {
-        let (code, _, _) = classify_error("Video not available in your country");
+        let (code, _, actions) = classify_error("Video not available in your country");
         assert!(matches!(code, ErrorCode::GeoRestricted));
+        assert!(matches!(actions[0].kind, ActionKind::TryGeoBypass));
+        assert!(matches!(actions[1].kind, ActionKind::OpenSettingsProxy));
     }
 }
@@ -15,9 +15,14 @@ pub enum ActionKind {
     UpdateYtDlp,
     UpdateFfmpeg,
     OpenSettingsProxy,
+    OpenSettingsSizeLimit,
     RetryRecommended,
     Retry,
     OpenLogs,
+    /// Retry with `--geo-bypass-country` (see `retry_with_geo_bypass`).
+    TryGeoBypass,
+    /// The `restrict_filenames`/`trim_filenames` settings.
+    OpenSettingsFilenames,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -27,7 +32,7 @@ pub struct Action {
 }
 
 /// Stable error codes to allow UX mapping and analytics (if added later).
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ErrorCode {
     Unknown,
@@ -41,7 +46,42 @@ pub enum ErrorCode {
     ToolMissing,
     ToolUnhealthy,
     PostProcessingFailed,
+    OutputWriteFailed,
+    RateLimited,
+    FileTooLarge,
     Canceled,
+    /// The download (or other item) a command referred to doesn't exist.
+    NotFound,
+    /// The request can't be carried out as given, e.g. retrying a download that
+    /// didn't fail.
+    InvalidRequest,
+    Database,
+}
+
+impl ErrorCode {
+    /// Stable string form, matching the serialized name. Used when persisting.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Unknown => "UNKNOWN",
+            ErrorCode::InvalidUrl => "INVALID_URL",
+            ErrorCode::Network => "NETWORK",
+            ErrorCode::GeoRestricted => "GEO_RESTRICTED",
+            ErrorCode::LoginRequired => "LOGIN_REQUIRED",
+            ErrorCode::BotCheck => "BOT_CHECK",
+            ErrorCode::ExtractorOutdated => "EXTRACTOR_OUTDATED",
+            ErrorCode::FormatUnavailable => "FORMAT_UNAVAILABLE",
+            ErrorCode::ToolMissing => "TOOL_MISSING",
+            ErrorCode::ToolUnhealthy => "TOOL_UNHEALTHY",
+            ErrorCode::PostProcessingFailed => "POST_PROCESSING_FAILED",
+            ErrorCode::OutputWriteFailed => "OUTPUT_WRITE_FAILED",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::FileTooLarge => "FILE_TOO_LARGE",
+            ErrorCode::Canceled => "CANCELED",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::InvalidRequest => "INVALID_REQUEST",
+            ErrorCode::Database => "DATABASE",
+        }
+    }
 }
 
 /// Download status reflected in the UI.
@@ -73,6 +113,8 @@ pub struct MediaInfo {
     pub uploader: Option<String>,
     pub duration_seconds: Option<u64>,
     pub thumbnail_url: Option<String>,
+    /// Every thumbnail size on offer, smallest first.
+    pub thumbnails: Vec<crate::ytdlp::Thumbnail>,
     pub webpage_url: Option<String>,
 }
 
@@ -98,7 +140,7 @@ pub struct ToolVersions {
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ToolUpdateInfo {
-    pub tool: String, // "yt-dlp" | "ffmpeg"
+    pub tool: String, // "yt-dlp" | "ffmpeg" | "ffprobe" | "app"
     pub current: Option<String>,
     pub latest: Option<String>,
 }
@@ -124,6 +166,9 @@ pub enum DownlinkEvent {
     },
 
     // Metadata / preview
+    /// yt-dlp is about to be asked for metadata. For previews that have no queue
+    /// row yet (`fetch_metadata`), `id` is transient: the request id when it is a
+    /// UUID, otherwise a fresh one.
     MetadataStarted {
         id: Uuid,
         url: String,
@@ -146,6 +191,16 @@ pub enum DownlinkEvent {
     },
     DownloadStarted {
         id: Uuid,
+        /// Name of the preset it runs with; unknown preset ids fall back to the default.
+        preset_name: Option<String>,
+        /// yt-dlp's `-o` value: the output folder (the staging folder with
+        /// `download_to_temp`) joined with the filename template.
+        output_template: Option<String>,
+    },
+    /// Something about the download's settings is likely to make it fail; it runs anyway.
+    DownloadWarning {
+        id: Uuid,
+        message: String,
     },
     DownloadProgress {
         id: Uuid,
@@ -168,12 +223,28 @@ pub enum DownlinkEvent {
         final_path: String,
     },
 
+    // System notification for a finished download (or a whole playlist).
+    // Only emitted when `notify_on_complete` is enabled.
+    NotifyCompleted {
+        id: Uuid,
+        title: String,
+        body: String,
+        final_path: Option<String>,
+    },
+
     // Failure with remediation actions
     DownloadFailed {
         id: Uuid,
         error_code: ErrorCode,
         user_message: String,
         actions: Vec<Action>,
+        /// Truncated tail of yt-dlp's stderr, if any, for support/diagnostics.
+        raw_error: Option<String>,
+    },
+
+    // Connectivity: downloads are held while offline and resume when it's back
+    ConnectivityChanged {
+        online: bool,
     },
 
     // Tools
@@ -222,6 +293,7 @@ pub mod helpers {
                 ActionKind::ImportCookies,
                 "Import cookies from browser",
             )],
+            raw_error: None,
         }
     }
 
@@ -235,6 +307,7 @@ pub mod helpers {
                 action(ActionKind::UpdateYtDlp, "Update yt-dlp"),
                 action(ActionKind::Retry, "Retry"),
             ],
+            raw_error: None,
         }
     }
 
@@ -249,6 +322,7 @@ pub mod helpers {
                 ActionKind::RetryRecommended,
                 "Use Recommended preset",
             )],
+            raw_error: None,
         }
     }
 
@@ -258,6 +332,7 @@ pub mod helpers {
             error_code: ErrorCode::Unknown,
             user_message: message.into(),
             actions: vec![action(ActionKind::OpenLogs, "View logs")],
+            raw_error: None,
         }
     }
 }
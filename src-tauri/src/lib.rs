@@ -1,6 +1,8 @@
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize};
 
 /// Helper to deserialize null as None for optional fields
@@ -12,30 +14,127 @@ where
     Ok(Option::<T>::deserialize(deserializer).unwrap_or(None))
 }
 use tauri::{AppHandle, Manager, State};
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{mpsc, watch, Mutex, RwLock};
 use uuid::Uuid;
 
+mod browser_cookies;
+mod checksum;
+mod command_error;
 mod db;
+mod db_actor;
+mod diagnostics;
+mod disk_space;
 mod download_manager;
 mod events;
 mod models;
+mod network;
 mod settings;
+mod temp_cleanup;
+mod thumbnail_cache;
 mod tool_manager;
+mod update_scheduler;
 mod url_utils;
+mod window_state;
 mod ytdlp;
 
+use command_error::CommandError;
 use download_manager::{DownloadConfig, DownloadManager, Preset};
-use events::DownlinkEvent;
-use settings::{SettingsManager, UserSettings, WindowState};
-use tool_manager::{ToolManager, ToolManagerConfig, ToolchainStatus};
+use events::{DownlinkEvent, ErrorCode};
+use settings::{PrivacySettings, SettingsManager, UserSettings, WindowState};
+use tool_manager::{
+    RepairAction, Tool, ToolManager, ToolManagerConfig, ToolStatus, ToolchainStatus,
+};
 
 /// Shared application state.
 /// Uses lazy initialization for components that need the async runtime.
 pub struct AppState {
-    db: Arc<Mutex<db::Db>>,
+    db: db_actor::DbHandle,
     download_manager: RwLock<Option<Arc<DownloadManager>>>,
     tool_manager: RwLock<Option<Arc<ToolManager>>>,
     event_tx: Arc<Mutex<Option<mpsc::Sender<DownlinkEvent>>>>,
+    metadata_fetches: MetadataFetches,
+    extractors: ExtractorCache,
+}
+
+/// yt-dlp's extractor names, loaded once per yt-dlp binary for `is_url_supported`.
+#[derive(Default)]
+struct ExtractorCache(Mutex<Option<CachedExtractors>>);
+
+struct CachedExtractors {
+    /// The binary the list came from and its modification time, so an update reloads it.
+    yt_dlp_path: PathBuf,
+    modified: Option<std::time::SystemTime>,
+    names: Arc<Vec<String>>,
+}
+
+impl ExtractorCache {
+    /// The cached list for `runner`'s yt-dlp, loading it if needed. Empty if yt-dlp
+    /// can't list its extractors.
+    async fn get(&self, runner: &ytdlp::YtDlpRunner) -> Arc<Vec<String>> {
+        let path = runner.yt_dlp_path().to_path_buf();
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+        let mut cached = self.0.lock().await;
+        if let Some(ref c) = *cached {
+            if c.yt_dlp_path == path && c.modified == modified {
+                return c.names.clone();
+            }
+        }
+
+        match runner.list_extractors().await {
+            Ok(names) => {
+                let names = Arc::new(names);
+                *cached = Some(CachedExtractors {
+                    yt_dlp_path: path,
+                    modified,
+                    names: names.clone(),
+                });
+                names
+            }
+            Err(e) => {
+                log::warn!("Failed to list yt-dlp extractors: {}", e);
+                Arc::new(Vec::new())
+            }
+        }
+    }
+}
+
+/// Cancel switches for in-flight metadata fetches, keyed by the UI's request id.
+#[derive(Default)]
+struct MetadataFetches(Mutex<HashMap<String, watch::Sender<bool>>>);
+
+impl MetadataFetches {
+    /// Register a fetch and return the receiver its runner should watch.
+    async fn register(&self, request_id: &str) -> watch::Receiver<bool> {
+        let (tx, rx) = watch::channel(false);
+        self.0.lock().await.insert(request_id.to_string(), tx);
+        rx
+    }
+
+    async fn finish(&self, request_id: &str) {
+        self.0.lock().await.remove(request_id);
+    }
+
+    /// Signal a fetch to stop. Returns false if no such fetch is running.
+    async fn cancel(&self, request_id: &str) -> bool {
+        match self.0.lock().await.remove(request_id) {
+            Some(tx) => tx.send(true).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Runner for a metadata command, cancelable via `cancel_metadata_fetch` when the UI
+/// passed a request id.
+async fn build_cancelable_runner(
+    state: &State<'_, AppState>,
+    request_id: Option<&str>,
+) -> ytdlp::YtDlpRunner {
+    let runner = build_ytdlp_runner(state).await;
+    match request_id {
+        Some(request_id) => runner.with_cancel(state.metadata_fetches.register(request_id).await),
+        None => runner,
+    }
 }
 
 /// Helper to get or create the download manager lazily.
@@ -81,8 +180,14 @@ async fn get_or_init_download_manager(
     });
 
     // Create download manager
-    let config = DownloadConfig::default();
+    let mut config = DownloadConfig::default();
+    if let Some(ref tm) = *state.tool_manager.read().await {
+        if let Some(ffprobe) = tm.ffprobe_path().await {
+            config.ffprobe_path = Some(ffprobe);
+        }
+    }
     let manager = Arc::new(DownloadManager::new(config, state.db.clone(), event_tx));
+    manager.start_retry_loop();
 
     *dm = Some(manager.clone());
     manager
@@ -102,6 +207,9 @@ pub struct AddUrlsResult {
 /// Options for adding URLs.
 #[derive(Debug, Deserialize)]
 pub struct AddUrlsOptions {
+    /// Empty means "not chosen": each URL gets its site's preset or the default
+    /// (see `GeneralSettings::preset_for_url`).
+    #[serde(default)]
     preset_id: String,
     output_dir: String,
     /// If present, create all children under this playlist parent id.
@@ -119,13 +227,29 @@ pub struct AddUrlsOptions {
     thumbnail_url: Option<String>,
     #[serde(default, deserialize_with = "deserialize_null_as_none")]
     duration_seconds: Option<i64>,
+    /// Optional time range to download instead of the whole media (e.g. `*00:10:00-00:15:00`).
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    sections: Option<String>,
+    /// Subtitle languages for these downloads (e.g. `["en", "ja"]`), overriding
+    /// the default subtitle language. Empty or absent keeps the settings.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    subtitle_langs: Option<Vec<String>>,
+    /// Speed limit in bytes/sec for these downloads, replacing the global one.
+    /// Absent or 0 uses the network settings.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    rate_limit_bps: Option<u64>,
 }
 
 /// Options for fetching metadata.
 #[derive(Debug, Deserialize)]
 pub struct FetchMetadataOptions {
+    /// Empty means "not chosen"; the result then suggests the site's preset.
+    #[serde(default)]
     preset_id: String,
     output_dir: String,
+    /// Caller-chosen id for `cancel_metadata_fetch`.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    request_id: Option<String>,
 }
 
 /// Result from fetching metadata.
@@ -138,9 +262,22 @@ pub struct FetchMetadataResult {
     uploader: Option<String>,
     duration_seconds: Option<u64>,
     thumbnail_url: Option<String>,
+    /// Every thumbnail size yt-dlp offers, smallest first; `thumbnail_url` is the default.
+    thumbnails: Vec<ytdlp::Thumbnail>,
     filesize_bytes: Option<u64>,
     playlist_title: Option<String>,
     playlist_count_hint: Option<u64>,
+    /// Preset to preselect: the one the caller chose, else the site's preset or the default.
+    preset_id: String,
+}
+
+/// Per-URL outcome of a metadata batch. Exactly one of `metadata`/`error` is set.
+#[derive(Debug, Serialize)]
+pub struct FetchMetadataBatchItem {
+    /// The URL as passed in.
+    url: String,
+    metadata: Option<FetchMetadataResult>,
+    error: Option<String>,
 }
 
 /// Result from expanding a playlist.
@@ -149,6 +286,8 @@ pub struct ExpandPlaylistResult {
     parent_id: Uuid,
     item_ids: Vec<Uuid>,
     count: usize,
+    /// Entries left out by the duration filters.
+    skipped: usize,
 }
 
 /// Options for expanding a playlist.
@@ -156,25 +295,188 @@ pub struct ExpandPlaylistResult {
 pub struct ExpandPlaylistOptions {
     preset_id: String,
     output_dir: String,
+    /// First playlist item to include (1-based). Open-ended if `end` is absent.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    start: Option<u32>,
+    /// Last playlist item to include (1-based, inclusive).
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    end: Option<u32>,
+    /// Explicit `--playlist-items` spec, e.g. `"1-10,15"`. Mutually exclusive with start/end.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    items: Option<String>,
+    /// Skip entries shorter than this many seconds (e.g. shorts).
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    min_duration: Option<u64>,
+    /// Skip entries longer than this many seconds.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    max_duration: Option<u64>,
+    /// Keep entries whose duration isn't known when filtering by duration. Default true.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    keep_unknown_duration: Option<bool>,
+    /// Caller-chosen id for `cancel_metadata_fetch`.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    request_id: Option<String>,
+}
+
+/// Options for previewing a playlist.
+#[derive(Debug, Default, Deserialize)]
+pub struct PreviewPlaylistOptions {
+    /// First playlist item to include (1-based). Open-ended if `end` is absent.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    start: Option<u32>,
+    /// Last playlist item to include (1-based, inclusive).
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    end: Option<u32>,
+    /// Explicit `--playlist-items` spec, e.g. `"1-10,15"`. Mutually exclusive with start/end.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    items: Option<String>,
+    /// Caller-chosen id for `cancel_metadata_fetch`.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    request_id: Option<String>,
+}
+
+/// One entry of a playlist preview.
+#[derive(Debug, Serialize)]
+pub struct PlaylistPreviewEntry {
+    url: String,
+    title: Option<String>,
+    uploader: Option<String>,
+    duration_seconds: Option<u64>,
+    thumbnail_url: Option<String>,
+}
+
+impl From<ytdlp::PlaylistEntry> for PlaylistPreviewEntry {
+    fn from(entry: ytdlp::PlaylistEntry) -> Self {
+        Self {
+            url: entry.url,
+            title: entry.title,
+            uploader: entry.uploader,
+            duration_seconds: entry.duration_seconds,
+            thumbnail_url: entry.thumbnail_url,
+        }
+    }
+}
+
+/// Result from previewing a playlist.
+#[derive(Debug, Serialize)]
+pub struct PlaylistPreview {
+    url: String,
+    entries: Vec<PlaylistPreviewEntry>,
+    count: usize,
 }
 
 /// Queue item for UI display.
 #[derive(Debug, Serialize)]
 pub struct QueueItem {
     id: Uuid,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
     source_url: String,
+    /// "single", "playlist_parent" or "playlist_item".
+    source_kind: String,
+    /// Playlist row this item was expanded from.
+    parent_id: Option<Uuid>,
     title: Option<String>,
     uploader: Option<String>,
+    duration_seconds: Option<i64>,
     thumbnail_url: Option<String>,
+    /// Locally cached copy of the thumbnail, once `cache_thumbnail` has run.
+    thumbnail_path: Option<String>,
     status: String,
     phase: Option<String>,
     progress_percent: Option<f64>,
+    bytes_downloaded: Option<i64>,
+    bytes_total: Option<i64>,
     speed_bps: Option<i64>,
     eta_seconds: Option<i64>,
     preset_id: String,
     output_dir: String,
     final_path: Option<String>,
+    /// Every output file (chapter splits, subtitles), `final_path` first.
+    final_paths: Vec<String>,
+    error_code: Option<String>,
     error_message: Option<String>,
+    /// Whether the download manager is running it or holding it for a slot.
+    queue_state: download_manager::QueueState,
+}
+
+/// A queue item with the playlist items expanded from it, for `get_queue_tree`.
+#[derive(Debug, Serialize)]
+pub struct QueueItemNode {
+    item: QueueItem,
+    children: Vec<QueueItemNode>,
+}
+
+/// Nest items under their playlist parent, keeping the order of `items`. Items
+/// whose parent isn't among them (e.g. filtered out) stay at the top level.
+fn build_queue_tree(items: Vec<QueueItem>) -> Vec<QueueItemNode> {
+    use std::collections::HashSet;
+
+    let ids: HashSet<Uuid> = items.iter().map(|item| item.id).collect();
+    let mut children: HashMap<Uuid, Vec<QueueItemNode>> = HashMap::new();
+    let mut roots = Vec::new();
+    for item in items {
+        match item.parent_id.filter(|parent| ids.contains(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(QueueItemNode {
+                item,
+                children: Vec::new(),
+            }),
+            None => roots.push(item),
+        }
+    }
+
+    roots
+        .into_iter()
+        .map(|item| QueueItemNode {
+            children: children.remove(&item.id).unwrap_or_default(),
+            item,
+        })
+        .collect()
+}
+
+impl From<db::DownloadRow> for QueueItem {
+    fn from(row: db::DownloadRow) -> Self {
+        Self {
+            id: row.id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            source_url: row.source_url,
+            source_kind: row.source_kind.as_str().to_string(),
+            parent_id: row.parent_id,
+            title: row.title,
+            uploader: row.uploader,
+            duration_seconds: row.duration_seconds,
+            thumbnail_url: row.thumbnail_url,
+            thumbnail_path: row.thumbnail_path,
+            status: row.status.as_str().to_string(),
+            phase: row.phase,
+            progress_percent: row.progress_percent,
+            bytes_downloaded: row.bytes_downloaded,
+            bytes_total: row.bytes_total,
+            speed_bps: row.speed_bps,
+            eta_seconds: row.eta_seconds,
+            preset_id: row.preset_id,
+            output_dir: row.output_dir,
+            final_path: row.final_path,
+            final_paths: row.final_paths,
+            error_code: row.error_code,
+            error_message: row.error_message,
+            queue_state: download_manager::QueueState::NotManaged,
+        }
+    }
+}
+
+/// Fill in `queue_state` from the download manager, if it has been started.
+async fn with_queue_states(
+    state: &State<'_, AppState>,
+    mut items: Vec<QueueItem>,
+) -> Vec<QueueItem> {
+    if let Some(manager) = state.download_manager.read().await.clone() {
+        for item in &mut items {
+            item.queue_state = manager.queue_state(item.id).await;
+        }
+    }
+    items
 }
 
 /// Preset info for UI.
@@ -190,6 +492,7 @@ pub struct PresetInfo {
 
 #[tauri::command]
 fn add_urls(
+    app: AppHandle,
     state: State<'_, AppState>,
     urls_text: String,
     options: AddUrlsOptions,
@@ -197,11 +500,131 @@ fn add_urls(
     log::info!("add_urls called with urls_text: {:?}", urls_text);
     log::info!("add_urls options: {:?}", options);
 
-    let urls = url_utils::extract_urls(&urls_text);
+    let urls = url_utils::extract_urls_with_originals(&urls_text);
     if urls.is_empty() {
         return Err("No valid http(s) URLs found.".to_string());
     }
 
+    let queued: Vec<String> = urls.iter().map(|u| u.url.clone()).collect();
+    let ids = state
+        .db
+        .blocking_call(move |db| {
+            Ok(insert_queued_urls(db, &urls, &options, |event| {
+                let _ = events::emit_event(&app, event);
+            }))
+        })
+        .map_err(|e| format!("Database unavailable: {e}"))??;
+
+    Ok(AddUrlsResult { ids, urls: queued })
+}
+
+/// The URL of a download exactly as it was pasted, fragment (e.g. a timestamp)
+/// included. Falls back to the normalized URL for rows queued without one.
+#[tauri::command]
+async fn get_original_url(state: State<'_, AppState>, id: Uuid) -> Result<String, String> {
+    let row = with_db(&state, move |db| {
+        db.get_download(id)
+            .map_err(|e| format!("Failed to get download: {e}"))
+    })
+    .await?
+    .ok_or_else(|| format!("Download {id} not found"))?;
+    Ok(row.original_url.unwrap_or(row.source_url))
+}
+
+/// Most URLs `add_urls_from_file` will queue from one file.
+const MAX_URLS_FROM_FILE: usize = 10_000;
+
+/// Longest line `add_urls_from_file` reads, so a file without newlines (or not a
+/// text file at all) can't be buffered whole.
+const MAX_URL_FILE_LINE_BYTES: u64 = 64 * 1024;
+
+/// Queue every http(s) URL found in a text file, like `add_urls` does for pasted text.
+#[tauri::command]
+async fn add_urls_from_file(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    options: AddUrlsOptions,
+) -> Result<AddUrlsResult, String> {
+    log::info!("add_urls_from_file called with path: {:?}", path);
+
+    let urls = tokio::task::spawn_blocking(move || {
+        read_urls_from_file(Path::new(&path), MAX_URLS_FROM_FILE)
+    })
+    .await
+    .map_err(|e| format!("Failed to read URL file: {e}"))??;
+    if urls.is_empty() {
+        return Err("No valid http(s) URLs found in the file.".to_string());
+    }
+
+    let queued: Vec<String> = urls.iter().map(|u| u.url.clone()).collect();
+    let ids = with_db(&state, move |db| {
+        insert_queued_urls(db, &urls, &options, |event| {
+            let _ = events::emit_event(&app, event);
+        })
+    })
+    .await?;
+
+    Ok(AddUrlsResult { ids, urls: queued })
+}
+
+/// Extract unique URLs from a file, a line at a time so large files aren't loaded
+/// whole. Fails once more than `max_urls` distinct URLs are found, or on a line
+/// longer than `MAX_URL_FILE_LINE_BYTES`.
+fn read_urls_from_file(
+    path: &Path,
+    max_urls: usize,
+) -> Result<Vec<url_utils::ExtractedUrl>, String> {
+    use std::collections::HashSet;
+    use std::io::{BufRead, Read};
+
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let mut reader = std::io::BufReader::new(file);
+
+    let mut urls = Vec::new();
+    let mut seen = HashSet::new();
+    let mut line = Vec::new();
+    for line_number in 1.. {
+        line.clear();
+        let read = (&mut reader)
+            .take(MAX_URL_FILE_LINE_BYTES + 1)
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        if read as u64 > MAX_URL_FILE_LINE_BYTES && line.last() != Some(&b'\n') {
+            return Err(format!(
+                "Line {line_number} of {} is longer than {} KiB. Is it a text file?",
+                path.display(),
+                MAX_URL_FILE_LINE_BYTES / 1024
+            ));
+        }
+
+        // Tolerate stray non-UTF-8 bytes; URLs themselves are ASCII once encoded
+        for url in url_utils::extract_urls_with_originals(&String::from_utf8_lossy(&line)) {
+            if seen.insert(url.url.clone()) {
+                if urls.len() == max_urls {
+                    return Err(format!(
+                        "The file contains more than {max_urls} URLs. Split it into smaller files."
+                    ));
+                }
+                urls.push(url);
+            }
+        }
+    }
+
+    Ok(urls)
+}
+
+/// Insert one queued row per URL and report each new id as `DownloadQueued`.
+fn insert_queued_urls(
+    db: &mut db::Db,
+    urls: &[url_utils::ExtractedUrl],
+    options: &AddUrlsOptions,
+    mut emit: impl FnMut(DownlinkEvent),
+) -> Result<Vec<Uuid>, String> {
     let source_kind = match options.source_kind.as_deref() {
         Some("playlist_parent") => db::SourceKind::PlaylistParent,
         Some("playlist_item") => db::SourceKind::PlaylistItem,
@@ -209,19 +632,48 @@ fn add_urls(
         Some(_) => db::SourceKind::Single,
     };
 
-    let mut db = state.db.blocking_lock();
+    let sections = options
+        .sections
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    if let Some(spec) = sections {
+        download_manager::validate_sections(spec)?;
+    }
+    let subtitle_langs = match options.subtitle_langs {
+        Some(ref langs) => settings::validate_subtitle_langs(&langs.join(","))?,
+        None => None,
+    };
+
+    // Only needed to pick per-site presets when the caller didn't choose one
+    let general = if options.preset_id.trim().is_empty() {
+        Some(
+            SettingsManager::new(db.conn())
+                .get_user_settings()
+                .map(|s| s.general)
+                .unwrap_or_default(),
+        )
+    } else {
+        None
+    };
 
     let mut ids = Vec::with_capacity(urls.len());
-    for u in &urls {
+    for u in urls {
+        let preset_id = match general {
+            Some(ref general) => general.preset_for_url(&u.url),
+            None => options.preset_id.trim(),
+        };
         let id = db
             .insert_download(
-                u,
+                &u.url,
                 source_kind,
                 options.parent_id,
-                &options.preset_id,
+                preset_id,
                 &options.output_dir,
             )
             .map_err(|e| format!("Failed to insert download: {e}"))?;
+        db.set_original_url(id, &u.original)
+            .map_err(|e| format!("Failed to save original URL: {e}"))?;
 
         // If we have metadata from preview, update the row
         if options.title.is_some() || options.uploader.is_some() || options.thumbnail_url.is_some()
@@ -235,18 +687,32 @@ fn add_urls(
             );
         }
 
+        if sections.is_some() {
+            db.set_sections(id, sections)
+                .map_err(|e| format!("Failed to save sections: {e}"))?;
+        }
+        if subtitle_langs.is_some() {
+            db.set_subtitle_langs(id, subtitle_langs.as_deref())
+                .map_err(|e| format!("Failed to save subtitle languages: {e}"))?;
+        }
+        if let Some(bps) = options.rate_limit_bps.filter(|bps| *bps > 0) {
+            db.set_rate_limit(id, Some(bps as i64))
+                .map_err(|e| format!("Failed to save speed limit: {e}"))?;
+        }
+
+        emit(DownlinkEvent::DownloadQueued { id });
         ids.push(id);
     }
 
-    Ok(AddUrlsResult { ids, urls })
+    Ok(ids)
 }
 
 #[tauri::command]
 async fn fetch_metadata(
-    _app: AppHandle,
+    app: AppHandle,
     state: State<'_, AppState>,
     url: String,
-    _options: FetchMetadataOptions,
+    options: FetchMetadataOptions,
 ) -> Result<FetchMetadataResult, String> {
     let urls = url_utils::extract_urls(&url);
     let first = urls
@@ -256,25 +722,328 @@ async fn fetch_metadata(
 
     // Just fetch metadata - do NOT insert into database
     // The item will only be added to the queue when the user clicks "Download"
+    let request_id = options.request_id.as_deref();
+    let _ = events::emit_event(
+        &app,
+        DownlinkEvent::MetadataStarted {
+            id: transient_metadata_id(request_id),
+            url: first.clone(),
+        },
+    );
+    let runner = build_cancelable_runner(&state, request_id).await;
+    let result = runner.fetch_metadata(&first).await;
+    if let Some(request_id) = request_id {
+        state.metadata_fetches.finish(request_id).await;
+    }
+
+    let (meta, _output) = result.map_err(|e| {
+        if ytdlp::is_canceled(&e) {
+            "Metadata fetch canceled".to_string()
+        } else {
+            format!("yt-dlp metadata failed: {e}")
+        }
+    })?;
+
+    let preset_id = match options.preset_id.trim() {
+        "" => load_general_settings(&state)
+            .await
+            .preset_for_url(&meta.url)
+            .to_string(),
+        chosen => chosen.to_string(),
+    };
+    Ok(FetchMetadataResult::new(meta, preset_id))
+}
+
+/// Id for a `MetadataStarted` event of a preview with no queue row: the caller's
+/// request id when it is a UUID, so the UI can match it up, else a fresh one.
+fn transient_metadata_id(request_id: Option<&str>) -> Uuid {
+    request_id
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .unwrap_or_else(Uuid::new_v4)
+}
+
+/// Stop a `fetch_metadata` or `expand_playlist` started with this request id,
+/// killing its yt-dlp process. Returns false if it already finished.
+#[tauri::command]
+async fn cancel_metadata_fetch(
+    state: State<'_, AppState>,
+    request_id: String,
+) -> Result<bool, String> {
+    Ok(state.metadata_fetches.cancel(&request_id).await)
+}
+
+/// Lines kept from each of stdout/stderr in a `diagnose_url` report.
+const DIAGNOSE_TAIL_LINES: usize = 200;
+
+/// Result of `diagnose_url`: yt-dlp's verbose output plus the classified failure, if any.
+#[derive(Debug, Serialize)]
+pub struct UrlDiagnosis {
+    pub url: String,
+    /// None if yt-dlp timed out.
+    pub exit_code: Option<i32>,
+    pub stdout_tail: Vec<String>,
+    pub stderr_tail: Vec<String>,
+    pub error_code: Option<events::ErrorCode>,
+    pub user_message: Option<String>,
+    pub actions: Vec<events::Action>,
+}
+
+fn tail_lines(lines: &[String], max: usize) -> Vec<String> {
+    lines[lines.len().saturating_sub(max)..].to_vec()
+}
+
+fn build_url_diagnosis(url: String, output: &ytdlp::YtDlpOutput) -> UrlDiagnosis {
+    let (error_code, user_message, actions) = if output.exit_code == Some(0) {
+        (None, None, Vec::new())
+    } else {
+        let (code, message, actions) =
+            download_manager::classify_error(&output.stderr_lines.join("\n"));
+        (Some(code), Some(message), actions)
+    };
+
+    UrlDiagnosis {
+        url,
+        exit_code: output.exit_code,
+        stdout_tail: tail_lines(&output.stdout_lines, DIAGNOSE_TAIL_LINES),
+        stderr_tail: tail_lines(&output.stderr_lines, DIAGNOSE_TAIL_LINES),
+        error_code,
+        user_message,
+        actions,
+    }
+}
+
+/// Run yt-dlp in simulate/verbose mode against a URL for support diagnostics.
+/// Nothing is downloaded and no queue row is created.
+#[tauri::command]
+async fn diagnose_url(state: State<'_, AppState>, url: String) -> Result<UrlDiagnosis, String> {
+    let first = url_utils::extract_urls(&url)
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No valid http(s) URL found.".to_string())?;
+
+    let runner = build_ytdlp_runner(&state).await;
+    let output = runner
+        .simulate(&first)
+        .await
+        .map_err(|e| format!("Failed to run yt-dlp: {e}"))?;
+
+    Ok(build_url_diagnosis(first, &output))
+}
+
+/// Whether yt-dlp has an extractor for the URL's site, so the UI can warn before
+/// queueing it.
+#[tauri::command]
+async fn is_url_supported(
+    state: State<'_, AppState>,
+    url: String,
+) -> Result<url_utils::UrlSupport, String> {
+    let runner = build_ytdlp_runner(&state).await;
+    let extractors = state.extractors.get(&runner).await;
+    Ok(url_utils::url_support(&url, &extractors))
+}
+
+/// The filename a template would give, evaluated by yt-dlp against sample video
+/// fields (`sample_metadata` overrides them), so a template can be checked before saving.
+#[tauri::command]
+async fn test_filename_template(
+    state: State<'_, AppState>,
+    template: String,
+    sample_metadata: Option<serde_json::Value>,
+) -> Result<String, String> {
+    let template = template.trim();
+    if template.is_empty() {
+        return Err("Filename template is empty".to_string());
+    }
+    settings::validate_filename_template(template)?;
+
+    let runner = build_ytdlp_runner(&state).await;
+    runner
+        .evaluate_filename_template(template, sample_metadata.as_ref())
+        .await
+        .map_err(|e| format!("Invalid filename template: {e}"))
+}
+
+/// Subtitle languages available for a video, so the UI can pick `subtitle_langs`
+/// before queueing it. Empty if the video has none.
+#[tauri::command]
+async fn list_subtitles(
+    state: State<'_, AppState>,
+    url: String,
+) -> Result<Vec<ytdlp::SubtitleTrack>, String> {
+    let first = url_utils::extract_urls(&url)
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No valid http(s) URL found.".to_string())?;
+
+    let runner = build_ytdlp_runner(&state).await;
+    let (tracks, _output) = runner
+        .list_subtitles(&first)
+        .await
+        .map_err(|e| format!("yt-dlp subtitle listing failed: {e}"))?;
+    Ok(tracks)
+}
+
+impl FetchMetadataResult {
+    fn new(meta: ytdlp::PreviewMetadata, preset_id: String) -> Self {
+        // Return a placeholder ID (empty UUID) since we're not storing in DB yet
+        // The real ID will be created when add_urls is called
+        Self {
+            id: Uuid::nil(),
+            url: meta.url,
+            is_playlist: meta.is_playlist,
+            title: meta.title,
+            uploader: meta.uploader,
+            duration_seconds: meta.duration_seconds,
+            thumbnail_url: meta.thumbnail_url,
+            thumbnails: meta.thumbnails,
+            filesize_bytes: meta.filesize_bytes,
+            playlist_title: meta.playlist_title,
+            playlist_count_hint: meta.playlist_count_hint,
+            preset_id,
+        }
+    }
+}
+
+/// Maximum number of yt-dlp metadata processes `fetch_metadata_batch` runs at once.
+const METADATA_BATCH_CONCURRENCY: usize = 4;
+
+/// Fetch metadata for many URLs concurrently (bounded), returning one result per URL.
+///
+/// Unlike `fetch_metadata`, a failing URL does not fail the batch; its error is
+/// reported in the corresponding item. Results keep the input order.
+#[tauri::command]
+async fn fetch_metadata_batch(
+    state: State<'_, AppState>,
+    urls: Vec<String>,
+) -> Result<Vec<FetchMetadataBatchItem>, String> {
     let runner = build_ytdlp_runner(&state).await;
-    let (meta, _output) = runner
-        .fetch_metadata(&first)
+    let general = Arc::new(load_general_settings(&state).await);
+
+    Ok(
+        fetch_metadata_concurrently(urls, METADATA_BATCH_CONCURRENCY, move |url| {
+            let runner = runner.clone();
+            let general = general.clone();
+            async move {
+                runner
+                    .fetch_metadata(&url)
+                    .await
+                    .map(|(meta, _output)| {
+                        let preset_id = general.preset_for_url(&meta.url).to_string();
+                        FetchMetadataResult::new(meta, preset_id)
+                    })
+                    .map_err(|e| format!("yt-dlp metadata failed: {e}"))
+            }
+        })
+        .await,
+    )
+}
+
+/// Run `fetch` for each URL with at most `limit` in flight.
+async fn fetch_metadata_concurrently<F, Fut>(
+    urls: Vec<String>,
+    limit: usize,
+    fetch: F,
+) -> Vec<FetchMetadataBatchItem>
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = Result<FetchMetadataResult, String>> + Send + 'static,
+{
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(limit.max(1)));
+
+    let mut tasks = Vec::with_capacity(urls.len());
+    for url in urls {
+        let Some(normalized) = url_utils::normalize_http_url(&url) else {
+            tasks.push((url, None));
+            continue;
+        };
+
+        let semaphore = semaphore.clone();
+        let fut = fetch(normalized);
+        let handle = tokio::spawn(async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .map_err(|e| format!("Metadata fetch aborted: {e}"))?;
+            fut.await
+        });
+        tasks.push((url, Some(handle)));
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (url, handle) in tasks {
+        let outcome = match handle {
+            Some(handle) => handle
+                .await
+                .unwrap_or_else(|e| Err(format!("Metadata fetch task failed: {e}"))),
+            None => Err("Not a valid http(s) URL.".to_string()),
+        };
+        results.push(match outcome {
+            Ok(metadata) => FetchMetadataBatchItem {
+                url,
+                metadata: Some(metadata),
+                error: None,
+            },
+            Err(error) => FetchMetadataBatchItem {
+                url,
+                metadata: None,
+                error: Some(error),
+            },
+        });
+    }
+
+    results
+}
+
+/// List a playlist's entries without queueing anything, so the user can pick items.
+///
+/// Nothing is written to the DB. To queue the selection, call `add_urls` once for
+/// the playlist URL with `source_kind: "playlist_parent"`, then with the chosen
+/// entry URLs, `source_kind: "playlist_item"` and the returned `parent_id`.
+#[tauri::command]
+async fn preview_playlist(
+    state: State<'_, AppState>,
+    url: String,
+    options: Option<PreviewPlaylistOptions>,
+) -> Result<PlaylistPreview, String> {
+    let options = options.unwrap_or_default();
+    let playlist = url_utils::extract_urls(&url)
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No valid http(s) playlist URL found.".to_string())?;
+    let items = ytdlp::playlist_items_spec(options.start, options.end, options.items.as_deref())?;
+
+    let request_id = options.request_id.as_deref();
+    let runner = build_cancelable_runner(&state, request_id).await;
+    let result = enumerate_playlist_preview(&runner, &playlist, items.as_deref()).await;
+    if let Some(request_id) = request_id {
+        state.metadata_fetches.finish(request_id).await;
+    }
+    result
+}
+
+async fn enumerate_playlist_preview(
+    runner: &ytdlp::YtDlpRunner,
+    playlist_url: &str,
+    items: Option<&str>,
+) -> Result<PlaylistPreview, String> {
+    let (entries, _output) = runner
+        .enumerate_playlist(playlist_url, items)
         .await
-        .map_err(|e| format!("yt-dlp metadata failed: {e}"))?;
-
-    // Return a placeholder ID (empty UUID) since we're not storing in DB yet
-    // The real ID will be created when add_urls is called
-    Ok(FetchMetadataResult {
-        id: Uuid::nil(),
-        url: meta.url,
-        is_playlist: meta.is_playlist,
-        title: meta.title,
-        uploader: meta.uploader,
-        duration_seconds: meta.duration_seconds,
-        thumbnail_url: meta.thumbnail_url,
-        filesize_bytes: meta.filesize_bytes,
-        playlist_title: meta.playlist_title,
-        playlist_count_hint: meta.playlist_count_hint,
+        .map_err(|e| {
+            if ytdlp::is_canceled(&e) {
+                "Playlist preview canceled".to_string()
+            } else {
+                format!("yt-dlp playlist enumeration failed: {e}")
+            }
+        })?;
+
+    Ok(PlaylistPreview {
+        url: playlist_url.to_string(),
+        count: entries.len(),
+        entries: entries
+            .into_iter()
+            .map(PlaylistPreviewEntry::from)
+            .collect(),
     })
 }
 
@@ -291,36 +1060,81 @@ async fn expand_playlist(
         .next()
         .ok_or_else(|| "No valid http(s) playlist URL found.".to_string())?;
 
+    let items = ytdlp::playlist_items_spec(options.start, options.end, options.items.as_deref())?;
+    let duration_filter = ytdlp::DurationFilter::new(
+        options.min_duration,
+        options.max_duration,
+        options.keep_unknown_duration.unwrap_or(true),
+    )?;
+
     // Create parent row
     let parent_id = {
-        let mut db = state.db.lock().await;
-        let parent_id = db
-            .insert_download(
-                &playlist,
-                db::SourceKind::PlaylistParent,
-                None,
-                &options.preset_id,
-                &options.output_dir,
+        let playlist = playlist.clone();
+        let preset_id = options.preset_id.clone();
+        let output_dir = options.output_dir.clone();
+        with_db(&state, move |db| {
+            let parent_id = db
+                .insert_download(
+                    &playlist,
+                    db::SourceKind::PlaylistParent,
+                    None,
+                    &preset_id,
+                    &output_dir,
+                )
+                .map_err(|e| format!("Failed to insert playlist parent: {e}"))?;
+
+            db.set_status(
+                parent_id,
+                db::DownloadStatus::Fetching,
+                Some("Fetching playlist…"),
             )
-            .map_err(|e| format!("Failed to insert playlist parent: {e}"))?;
+            .map_err(|e| format!("Failed to update playlist status: {e}"))?;
+            Ok(parent_id)
+        })
+        .await?
+    };
 
-        db.set_status(
-            parent_id,
-            db::DownloadStatus::Fetching,
-            Some("Fetching playlist…"),
-        )
-        .map_err(|e| format!("Failed to update playlist status: {e}"))?;
-        parent_id
+    let _ = events::emit_event(
+        &app,
+        DownlinkEvent::MetadataStarted {
+            id: parent_id,
+            url: playlist.clone(),
+        },
+    );
+    let request_id = options.request_id.as_deref();
+    let runner = build_cancelable_runner(&state, request_id).await;
+    let result = runner.enumerate_playlist(&playlist, items.as_deref()).await;
+    if let Some(request_id) = request_id {
+        state.metadata_fetches.finish(request_id).await;
+    }
+
+    let (entries, _output) = match result {
+        Ok(result) => result,
+        Err(e) if ytdlp::is_canceled(&e) => {
+            let _ = state
+                .db
+                .call(move |db| {
+                    db.set_status(parent_id, db::DownloadStatus::Canceled, Some("Canceled"))
+                })
+                .await;
+            return Err("Playlist expansion canceled".to_string());
+        }
+        Err(e) => return Err(format!("yt-dlp playlist enumeration failed: {e}")),
     };
 
-    let runner = build_ytdlp_runner(&state).await;
-    let (entries, _output) = runner
-        .enumerate_playlist(&playlist)
-        .await
-        .map_err(|e| format!("yt-dlp playlist enumeration failed: {e}"))?;
+    // Without an item selection, entries come back in playlist order; number them
+    // before filtering so positions match the playlist
+    let mut entries = entries;
+    if items.is_none() {
+        for (position, entry) in entries.iter_mut().enumerate() {
+            entry.playlist_index.get_or_insert(position as u32 + 1);
+        }
+    }
+    let total = entries.len();
+    entries.retain(|entry| duration_filter.matches(entry.duration_seconds));
+    let skipped = total - entries.len();
 
-    let item_ids = {
-        let mut db = state.db.lock().await;
+    let item_ids = with_db(&state, move |db| {
         let mut item_ids = Vec::with_capacity(entries.len());
         for entry in &entries {
             let item_id = db
@@ -341,14 +1155,24 @@ async fn expand_playlist(
                 entry.duration_seconds.map(|d| d as i64),
                 entry.thumbnail_url.as_deref(),
             );
+            let _ = db.set_playlist_position(
+                item_id,
+                entry.playlist_title.as_deref(),
+                entry.playlist_index.map(i64::from),
+            );
 
             item_ids.push(item_id);
         }
 
         db.set_status(parent_id, db::DownloadStatus::Ready, Some("Ready"))
             .map_err(|e| format!("Failed to update playlist status: {e}"))?;
-        item_ids
-    };
+        Ok(item_ids)
+    })
+    .await?;
+
+    for id in &item_ids {
+        let _ = events::emit_event(&app, DownlinkEvent::DownloadQueued { id: *id });
+    }
 
     let _ = events::emit_event(
         &app,
@@ -363,6 +1187,7 @@ async fn expand_playlist(
         parent_id,
         item_ids: item_ids.clone(),
         count: item_ids.len(),
+        skipped,
     })
 }
 
@@ -375,22 +1200,26 @@ async fn start_download(
     app: AppHandle,
     state: State<'_, AppState>,
     id: Uuid,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let manager = get_or_init_download_manager(&state, &app).await;
     manager
         .start(id)
         .await
-        .map_err(|e| format!("Failed to start download: {e}"))?;
+        .map_err(|e| CommandError::wrap("Failed to start download", e))?;
     Ok(())
 }
 
 #[tauri::command]
-async fn stop_download(app: AppHandle, state: State<'_, AppState>, id: Uuid) -> Result<(), String> {
+async fn stop_download(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: Uuid,
+) -> Result<(), CommandError> {
     let manager = get_or_init_download_manager(&state, &app).await;
     manager
         .stop(id)
         .await
-        .map_err(|e| format!("Failed to stop download: {e}"))?;
+        .map_err(|e| CommandError::wrap("Failed to stop download", e))?;
     Ok(())
 }
 
@@ -399,12 +1228,12 @@ async fn cancel_download(
     app: AppHandle,
     state: State<'_, AppState>,
     id: Uuid,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let manager = get_or_init_download_manager(&state, &app).await;
     manager
         .cancel(id)
         .await
-        .map_err(|e| format!("Failed to cancel download: {e}"))?;
+        .map_err(|e| CommandError::wrap("Failed to cancel download", e))?;
     Ok(())
 }
 
@@ -413,22 +1242,86 @@ async fn retry_download(
     app: AppHandle,
     state: State<'_, AppState>,
     id: Uuid,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let manager = get_or_init_download_manager(&state, &app).await;
     manager
         .retry(id)
         .await
-        .map_err(|e| format!("Failed to retry download: {e}"))?;
+        .map_err(|e| CommandError::wrap("Failed to retry download", e))?;
     Ok(())
 }
 
+/// Retry a geo-restricted download as if from another country. Without a
+/// `country`, the `geo_bypass_country` setting is used.
 #[tauri::command]
-async fn start_all_downloads(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
-    let ids = {
-        let mut db = state.db.lock().await;
-        db.get_queued_download_ids()
-            .map_err(|e| format!("Failed to get queued downloads: {e}"))?
+async fn retry_with_geo_bypass(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: Uuid,
+    country: Option<String>,
+) -> Result<(), CommandError> {
+    let country = match country {
+        Some(country) => country,
+        None => state
+            .db
+            .call(|db| SettingsManager::new(db.conn()).get_user_settings())
+            .await
+            .map(|s| s.network.geo_bypass_country)
+            .unwrap_or_default(),
     };
+    if country.trim().is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidRequest,
+            "Choose a country to bypass the geo-restriction from",
+        ));
+    }
+
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager
+        .retry_with_geo_bypass(id, &country)
+        .await
+        .map_err(|e| CommandError::wrap("Failed to retry download", e))?;
+    Ok(())
+}
+
+/// Re-run only the merge/convert step of a download that failed post-processing.
+#[tauri::command]
+async fn retry_postprocessing(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: Uuid,
+) -> Result<(), CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager
+        .retry_postprocessing(id)
+        .await
+        .map_err(|e| CommandError::wrap("Failed to retry post-processing", e))
+}
+
+/// Fetch a download's title, uploader, duration and thumbnail again.
+#[tauri::command]
+async fn refresh_metadata(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: Uuid,
+) -> Result<events::MediaInfo, CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager
+        .refresh_metadata(id)
+        .await
+        .map_err(|e| CommandError::wrap("Failed to refresh metadata", e))
+}
+
+#[tauri::command]
+async fn start_all_downloads(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let ids = state
+        .db
+        .call(|db| db.get_queued_download_ids())
+        .await
+        .map_err(|e| CommandError::wrap("Failed to get queued downloads", e))?;
 
     let manager = get_or_init_download_manager(&state, &app).await;
     for id in ids {
@@ -438,9 +1331,90 @@ async fn start_all_downloads(app: AppHandle, state: State<'_, AppState>) -> Resu
 }
 
 #[tauri::command]
-async fn stop_all_downloads(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+async fn retry_all_failed(app: AppHandle, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let ids = state
+        .db
+        .call(|db| db.get_failed_download_ids())
+        .await
+        .map_err(|e| CommandError::wrap("Failed to get failed downloads", e))?;
+
     let manager = get_or_init_download_manager(&state, &app).await;
-    let _ = manager.shutdown().await;
+    for id in ids {
+        let _ = manager.retry(id).await;
+    }
+    Ok(())
+}
+
+/// Recent speed/progress samples for an active download, for the live speed graph.
+#[tauri::command]
+async fn get_speed_samples(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: Uuid,
+) -> Result<Vec<download_manager::SpeedSample>, CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    Ok(manager.speed_samples(id).await)
+}
+
+/// Download slot usage, e.g. "2/4 active, 3 queued". Doesn't touch the database.
+#[tauri::command]
+async fn get_manager_status(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<download_manager::ManagerStatus, CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    Ok(manager.status().await)
+}
+
+/// Hard stop: stop the active downloads and drop everything waiting for a slot.
+#[tauri::command]
+async fn stop_all_downloads(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager
+        .stop_all()
+        .await
+        .map_err(|e| CommandError::wrap("Failed to stop downloads", e))
+}
+
+/// Stop the active downloads but keep them, and the rest of the queue, waiting
+/// until `resume_all_downloads`.
+#[tauri::command]
+async fn pause_all_downloads(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager
+        .pause_all()
+        .await
+        .map_err(|e| CommandError::wrap("Failed to pause downloads", e))
+}
+
+#[tauri::command]
+async fn resume_all_downloads(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager
+        .resume_all()
+        .await
+        .map_err(|e| CommandError::wrap("Failed to resume downloads", e))
+}
+
+/// Report the browser's online/offline events. Going offline holds downloads and
+/// retries; coming back online resumes the ones that were waiting for the network.
+#[tauri::command]
+async fn set_online_status(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    online: bool,
+) -> Result<(), CommandError> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager.set_online(online).await;
     Ok(())
 }
 
@@ -448,34 +1422,73 @@ async fn stop_all_downloads(app: AppHandle, state: State<'_, AppState>) -> Resul
 // Tauri Commands - Queue and History
 // ============================================================================
 
-#[tauri::command]
-async fn get_queue(state: State<'_, AppState>) -> Result<Vec<QueueItem>, String> {
-    let mut db = state.db.lock().await;
-    let rows = db
-        .get_active_downloads()
-        .map_err(|e| format!("Failed to get queue: {e}"))?;
+/// Optional `get_queue` filters, as sent by the UI.
+#[derive(Debug, Default, Deserialize)]
+pub struct QueueFilterOptions {
+    /// Only rows with this status, e.g. `"failed"`.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    status: Option<String>,
+    /// Only rows of this kind: `"single"`, `"playlist_parent"` or `"playlist_item"`.
+    #[serde(default, deserialize_with = "deserialize_null_as_none")]
+    source_kind: Option<String>,
+    /// Hide playlist items while their parent is in the queue.
+    #[serde(default)]
+    collapse_playlists: bool,
+}
 
-    let items: Vec<QueueItem> = rows
-        .into_iter()
-        .map(|row| QueueItem {
-            id: row.id,
-            source_url: row.source_url,
-            title: row.title,
-            uploader: row.uploader,
-            thumbnail_url: row.thumbnail_url,
-            status: row.status.as_str().to_string(),
-            phase: row.phase,
-            progress_percent: row.progress_percent,
-            speed_bps: row.speed_bps,
-            eta_seconds: row.eta_seconds,
-            preset_id: row.preset_id,
-            output_dir: row.output_dir,
-            final_path: row.final_path,
-            error_message: row.error_message,
+impl TryFrom<QueueFilterOptions> for db::QueueFilter {
+    type Error = String;
+
+    fn try_from(options: QueueFilterOptions) -> Result<Self, String> {
+        let status = options
+            .status
+            .map(|s| db::DownloadStatus::from_str(&s).ok_or(format!("Unknown status: {s}")))
+            .transpose()?;
+        let source_kind = options
+            .source_kind
+            .map(|s| db::SourceKind::from_str(&s).ok_or(format!("Unknown source kind: {s}")))
+            .transpose()?;
+        Ok(db::QueueFilter {
+            status,
+            source_kind,
+            collapse_playlists: options.collapse_playlists,
         })
-        .collect();
+    }
+}
 
-    Ok(items)
+#[tauri::command]
+async fn get_queue(
+    state: State<'_, AppState>,
+    filter: Option<QueueFilterOptions>,
+) -> Result<Vec<QueueItem>, String> {
+    let filter = db::QueueFilter::try_from(filter.unwrap_or_default())?;
+    let rows = with_db(&state, move |db| {
+        db.get_queue(&filter)
+            .map_err(|e| format!("Failed to get queue: {e}"))
+    })
+    .await?;
+
+    let items: Vec<QueueItem> = rows.into_iter().map(QueueItem::from).collect();
+
+    Ok(with_queue_states(&state, items).await)
+}
+
+/// The queue with playlist items nested under their parent row.
+#[tauri::command]
+async fn get_queue_tree(
+    state: State<'_, AppState>,
+    filter: Option<QueueFilterOptions>,
+) -> Result<Vec<QueueItemNode>, String> {
+    let filter = db::QueueFilter::try_from(filter.unwrap_or_default())?;
+    let rows = with_db(&state, move |db| {
+        db.get_queue(&filter)
+            .map_err(|e| format!("Failed to get queue: {e}"))
+    })
+    .await?;
+
+    Ok(build_queue_tree(
+        with_queue_states(&state, rows.into_iter().map(QueueItem::from).collect()).await,
+    ))
 }
 
 #[tauri::command]
@@ -483,48 +1496,296 @@ async fn get_history(
     state: State<'_, AppState>,
     limit: Option<u32>,
 ) -> Result<Vec<QueueItem>, String> {
-    let mut db = state.db.lock().await;
-    let rows = db
-        .get_completed_downloads(limit.unwrap_or(100))
-        .map_err(|e| format!("Failed to get history: {e}"))?;
+    let rows = with_db(&state, move |db| {
+        db.get_completed_downloads(limit.unwrap_or(100))
+            .map_err(|e| format!("Failed to get history: {e}"))
+    })
+    .await?;
+
+    let items: Vec<QueueItem> = rows.into_iter().map(QueueItem::from).collect();
+
+    Ok(items)
+}
+
+/// A completed download whose file is no longer where it was saved.
+#[derive(Debug, Serialize)]
+pub struct MissingFile {
+    id: Uuid,
+    title: Option<String>,
+    final_path: String,
+}
+
+/// Point a completed download at the file's new location.
+fn relink_download_file(db: &mut db::Db, id: Uuid, new_path: &str) -> Result<(), String> {
+    let path = Path::new(new_path);
+    if !path.is_absolute() {
+        return Err(format!("{new_path} is not an absolute path"));
+    }
+    if !path.is_file() {
+        return Err(format!("File does not exist: {new_path}"));
+    }
+
+    let row = db
+        .get_download(id)
+        .map_err(|e| format!("Failed to get download: {e}"))?
+        .ok_or_else(|| format!("Download {id} not found"))?;
+    if row.status != db::DownloadStatus::Done {
+        return Err("Only completed downloads can be relinked".to_string());
+    }
+
+    db.relink_final_path(id, new_path)
+        .map_err(|e| format!("Failed to relink download: {e}"))
+}
 
-    let items: Vec<QueueItem> = rows
+/// Completed downloads whose `final_path` doesn't exist on disk anymore.
+fn find_missing_files(db: &mut db::Db) -> anyhow::Result<Vec<MissingFile>> {
+    let rows = db.get_completed_downloads(u32::MAX)?;
+    Ok(rows
         .into_iter()
-        .map(|row| QueueItem {
-            id: row.id,
-            source_url: row.source_url,
-            title: row.title,
-            uploader: row.uploader,
-            thumbnail_url: row.thumbnail_url,
-            status: row.status.as_str().to_string(),
-            phase: row.phase,
-            progress_percent: row.progress_percent,
-            speed_bps: row.speed_bps,
-            eta_seconds: row.eta_seconds,
-            preset_id: row.preset_id,
-            output_dir: row.output_dir,
-            final_path: row.final_path,
-            error_message: row.error_message,
+        .filter_map(|row| {
+            let final_path = row.final_path?;
+            let resolved = download_manager::resolve_final_path(&final_path, Some(&row.output_dir));
+            (!resolved.exists()).then_some(MissingFile {
+                id: row.id,
+                title: row.title,
+                final_path,
+            })
         })
-        .collect();
+        .collect())
+}
 
-    Ok(items)
+/// Update a completed download's file location after the user moved it.
+#[tauri::command]
+async fn relink_download(
+    state: State<'_, AppState>,
+    id: Uuid,
+    new_path: String,
+) -> Result<(), String> {
+    with_db(&state, move |db| relink_download_file(db, id, &new_path)).await
+}
+
+/// List completed downloads whose file has been moved or deleted.
+#[tauri::command]
+async fn verify_files(state: State<'_, AppState>) -> Result<Vec<MissingFile>, String> {
+    with_db(&state, |db| {
+        find_missing_files(db).map_err(|e| format!("Failed to verify files: {e}"))
+    })
+    .await
+}
+
+/// Hash a completed download's file, optionally comparing it to a published digest.
+#[tauri::command]
+async fn verify_download_checksum(
+    state: State<'_, AppState>,
+    id: Uuid,
+    algorithm: checksum::ChecksumAlgorithm,
+    expected: Option<String>,
+) -> Result<checksum::ChecksumResult, String> {
+    let row = with_db(&state, move |db| {
+        db.get_download(id)
+            .map_err(|e| format!("Failed to get download: {e}"))
+    })
+    .await?
+    .ok_or_else(|| format!("Download {id} not found"))?;
+    let final_path = row
+        .final_path
+        .ok_or_else(|| "Download has no output file".to_string())?;
+    let path = download_manager::resolve_final_path(&final_path, Some(&row.output_dir));
+    if !path.is_file() {
+        return Err(format!("File does not exist: {}", path.display()));
+    }
+
+    let digest = tokio::task::spawn_blocking(move || checksum::hash_file(&path, algorithm))
+        .await
+        .map_err(|e| format!("Failed to hash file: {e}"))?
+        .map_err(|e| format!("Failed to hash file: {e}"))?;
+    Ok(checksum::ChecksumResult::new(
+        algorithm,
+        digest,
+        expected.as_deref(),
+    ))
+}
+
+/// Totals and per-status/per-day counts for the statistics dashboard.
+#[tauri::command]
+async fn get_statistics(state: State<'_, AppState>) -> Result<db::DownloadStatistics, String> {
+    with_db(&state, |db| {
+        db.get_statistics()
+            .map_err(|e| format!("Failed to get statistics: {e}"))
+    })
+    .await
+}
+
+/// Period covered by `get_bandwidth_usage`, ending today (UTC).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum UsageRange {
+    Today,
+    Week,
+    Month,
+    All,
+}
+
+impl UsageRange {
+    /// Start of the range, or None for all recorded history.
+    fn since(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let days = match self {
+            Self::Today => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::All => return None,
+        };
+        Some(now - chrono::Duration::days(days - 1))
+    }
+}
+
+/// Result of `get_bandwidth_usage`.
+#[derive(Debug, Serialize)]
+struct BandwidthUsage {
+    /// Completed since the app started.
+    session_bytes: u64,
+    /// Sum of `per_day`.
+    total_bytes: u64,
+    /// Days with completed downloads in the range, oldest first.
+    per_day: Vec<db::DailyUsage>,
+}
+
+#[tauri::command]
+async fn get_bandwidth_usage(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    range: UsageRange,
+) -> Result<BandwidthUsage, String> {
+    let session_bytes = get_or_init_download_manager(&state, &app)
+        .await
+        .session_bytes();
+    let per_day = with_db(&state, move |db| {
+        db.get_usage(range.since(Utc::now()))
+            .map_err(|e| format!("Failed to get bandwidth usage: {e}"))
+    })
+    .await?;
+
+    Ok(BandwidthUsage {
+        session_bytes,
+        total_bytes: per_day.iter().map(|d| d.bytes).sum(),
+        per_day,
+    })
 }
 
 #[tauri::command]
 async fn clear_queue(state: State<'_, AppState>) -> Result<(), String> {
-    let mut db = state.db.lock().await;
-    db.clear_queued_downloads()
-        .map_err(|e| format!("Failed to clear queue: {e}"))?;
-    Ok(())
+    with_db(&state, |db| {
+        db.clear_queued_downloads()
+            .map_err(|e| format!("Failed to clear queue: {e}"))
+    })
+    .await
 }
 
 #[tauri::command]
 async fn clear_history(state: State<'_, AppState>) -> Result<(), String> {
-    let mut db = state.db.lock().await;
-    db.clear_completed_downloads()
-        .map_err(|e| format!("Failed to clear history: {e}"))?;
-    Ok(())
+    with_db(&state, |db| {
+        db.clear_completed_downloads()
+            .map_err(|e| format!("Failed to clear history: {e}"))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn clear_failed_downloads(state: State<'_, AppState>) -> Result<(), String> {
+    with_db(&state, |db| {
+        db.clear_failed_downloads()
+            .map_err(|e| format!("Failed to clear failed downloads: {e}"))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn clear_canceled_downloads(state: State<'_, AppState>) -> Result<(), String> {
+    with_db(&state, |db| {
+        db.clear_canceled_downloads()
+            .map_err(|e| format!("Failed to clear canceled downloads: {e}"))
+    })
+    .await
+}
+
+/// Forget which items the download archive has recorded, so they download again.
+///
+/// With `output_dir`, only the archive that folder's downloads use is cleared (the
+/// global one when archives aren't per folder); without it, every archive is.
+#[tauri::command]
+async fn clear_download_archive(
+    state: State<'_, AppState>,
+    output_dir: Option<String>,
+) -> Result<usize, String> {
+    let data_dir =
+        db::app_data_dir().map_err(|e| format!("Failed to resolve app data dir: {e}"))?;
+    let archive = match output_dir {
+        Some(dir) => {
+            let general = load_general_settings(&state).await;
+            Some(download_manager::download_archive_path(
+                &data_dir, &general, &dir,
+            ))
+        }
+        None => None,
+    };
+    download_manager::clear_download_archives(&data_dir, archive.as_deref())
+        .map_err(|e| format!("Failed to clear download archive: {e}"))
+}
+
+/// Delete everything in the app's tmp dir that isn't in use: staging folders of
+/// downloads that aren't running or queued, and tool update files that aren't
+/// downloading. Anything written to in the last few minutes is kept too.
+#[tauri::command]
+async fn clear_temp_files(
+    state: State<'_, AppState>,
+) -> Result<temp_cleanup::CleanupReport, String> {
+    let tmp_dir = db::ensure_app_dirs()
+        .map_err(|e| format!("Failed to resolve temp dir: {e}"))?
+        .tmp;
+
+    let mut keep: HashSet<PathBuf> = HashSet::new();
+    if let Some(manager) = state.download_manager.read().await.clone() {
+        keep.extend(staging_dirs(&tmp_dir, &manager.managed_ids().await));
+    }
+    if let Some(ref manager) = *state.tool_manager.read().await {
+        keep.extend(manager.in_flight_temp_files());
+    }
+
+    tokio::task::spawn_blocking(move || {
+        temp_cleanup::cleanup_temp_files(&tmp_dir, temp_cleanup::IN_USE_GRACE, &keep)
+    })
+    .await
+    .map_err(|e| format!("Failed to clear temp files: {e}"))?
+    .map_err(|e| format!("Failed to clear temp files: {e}"))
+}
+
+/// Staging folders `download_to_temp` uses for `ids`.
+fn staging_dirs(tmp_dir: &Path, ids: &[Uuid]) -> Vec<PathBuf> {
+    ids.iter()
+        .map(|id| {
+            tmp_dir
+                .join(download_manager::STAGING_DIR)
+                .join(id.to_string())
+        })
+        .collect()
+}
+
+/// Switch a download that isn't running to a different preset (e.g. Best -> Audio MP3).
+#[tauri::command]
+async fn change_preset(
+    state: State<'_, AppState>,
+    id: Uuid,
+    preset_id: String,
+) -> Result<(), String> {
+    if Preset::get_by_id(&preset_id).is_none() {
+        return Err(format!("Unknown preset: {preset_id}"));
+    }
+
+    with_db(&state, move |db| {
+        db.set_preset(id, &preset_id)
+            .map_err(|e| format!("Failed to change preset: {e}"))
+    })
+    .await
 }
 
 #[tauri::command]
@@ -538,10 +1799,11 @@ async fn remove_download(state: State<'_, AppState>, id: Uuid) -> Result<(), Str
     }
 
     // Then remove from DB
-    let mut db = state.db.lock().await;
-    db.delete_download(id)
-        .map_err(|e| format!("Failed to remove download: {e}"))?;
-    Ok(())
+    with_db(&state, move |db| {
+        db.delete_download(id)
+            .map_err(|e| format!("Failed to remove download: {e}"))
+    })
+    .await
 }
 
 // ============================================================================
@@ -550,29 +1812,91 @@ async fn remove_download(state: State<'_, AppState>, id: Uuid) -> Result<(), Str
 
 #[tauri::command]
 async fn get_settings(state: State<'_, AppState>) -> Result<UserSettings, String> {
-    let db = state.db.lock().await;
-    let manager = SettingsManager::new(db.conn());
-    manager
-        .get_user_settings()
-        .map_err(|e| format!("Failed to get settings: {e}"))
+    with_db(&state, |db| {
+        SettingsManager::new(db.conn())
+            .get_user_settings()
+            .map_err(|e| format!("Failed to get settings: {e}"))
+    })
+    .await
 }
 
+/// Settings with defaults filled in, and which fields are still at their default.
 #[tauri::command]
-async fn save_settings(state: State<'_, AppState>, settings: UserSettings) -> Result<(), String> {
-    let db = state.db.lock().await;
-    let manager = SettingsManager::new(db.conn());
-    manager
-        .save_user_settings(&settings)
-        .map_err(|e| format!("Failed to save settings: {e}"))
+async fn get_effective_settings(
+    state: State<'_, AppState>,
+) -> Result<settings::EffectiveSettings, String> {
+    with_db(&state, |db| {
+        SettingsManager::new(db.conn())
+            .get_user_settings()
+            .map(settings::EffectiveSettings::new)
+            .map_err(|e| format!("Failed to get settings: {e}"))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn save_settings(
+    state: State<'_, AppState>,
+    mut settings: UserSettings,
+) -> Result<(), String> {
+    settings.sanitize();
+    let errors = settings.validate();
+    if !errors.is_empty() {
+        let details: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(format!("Invalid settings: {}", details.join("; ")));
+    }
+
+    let level = settings::parse_log_level(&settings.general.log_level)?;
+    with_db(&state, move |db| {
+        SettingsManager::new(db.conn())
+            .save_user_settings(&settings)
+            .map_err(|e| format!("Failed to save settings: {e}"))
+    })
+    .await?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Change log verbosity now and remember it for later launches.
+#[tauri::command]
+async fn set_log_level(state: State<'_, AppState>, level: String) -> Result<(), String> {
+    let filter = settings::parse_log_level(&level)?;
+    with_db(&state, move |db| {
+        let manager = SettingsManager::new(db.conn());
+        let mut settings = manager
+            .get_user_settings()
+            .map_err(|e| format!("Failed to get settings: {e}"))?;
+        settings.general.log_level = level;
+        manager
+            .save_user_settings(&settings)
+            .map_err(|e| format!("Failed to save settings: {e}"))
+    })
+    .await?;
+    log::set_max_level(filter);
+    log::info!("Log level set to {}", filter);
+    Ok(())
+}
+
+/// Switch to the log level saved in settings. Bad or unreadable values keep the default.
+fn apply_saved_log_level(db: &db::Db) {
+    let level = SettingsManager::new(db.conn())
+        .get_user_settings()
+        .map_err(|e| e.to_string())
+        .and_then(|s| settings::parse_log_level(&s.general.log_level));
+    match level {
+        Ok(level) => log::set_max_level(level),
+        Err(e) => log::warn!("Keeping default log level: {}", e),
+    }
 }
 
 #[tauri::command]
 async fn get_window_state(state: State<'_, AppState>) -> Result<WindowState, String> {
-    let db = state.db.lock().await;
-    let manager = SettingsManager::new(db.conn());
-    manager
-        .get_window_state()
-        .map_err(|e| format!("Failed to get window state: {e}"))
+    with_db(&state, |db| {
+        SettingsManager::new(db.conn())
+            .get_window_state()
+            .map_err(|e| format!("Failed to get window state: {e}"))
+    })
+    .await
 }
 
 #[tauri::command]
@@ -580,11 +1904,85 @@ async fn save_window_state(
     state: State<'_, AppState>,
     window_state: WindowState,
 ) -> Result<(), String> {
-    let db = state.db.lock().await;
-    let manager = SettingsManager::new(db.conn());
-    manager
-        .save_window_state(&window_state)
-        .map_err(|e| format!("Failed to save window state: {e}"))
+    with_db(&state, move |db| {
+        SettingsManager::new(db.conn())
+            .save_window_state(&window_state)
+            .map_err(|e| format!("Failed to save window state: {e}"))
+    })
+    .await
+}
+
+/// Use `preset_id` by default for URLs on `site` (a host such as `music.example.com`;
+/// subdomains are included). Returns the normalized site key.
+#[tauri::command]
+async fn set_site_preset(
+    state: State<'_, AppState>,
+    site: String,
+    preset_id: String,
+) -> Result<String, String> {
+    let site = site_preset_key(&site).ok_or_else(|| format!("Invalid site: {site}"))?;
+    if Preset::get_by_id(&preset_id).is_none() {
+        return Err(format!("Unknown preset: {preset_id}"));
+    }
+
+    with_db(&state, move |db| {
+        let manager = SettingsManager::new(db.conn());
+        let mut settings = manager
+            .get_user_settings()
+            .map_err(|e| format!("Failed to get settings: {e}"))?;
+        settings
+            .general
+            .site_preset_overrides
+            .insert(site.clone(), preset_id);
+        manager
+            .save_user_settings(&settings)
+            .map_err(|e| format!("Failed to save settings: {e}"))?;
+        Ok(site)
+    })
+    .await
+}
+
+/// Drop the preset override for `site`. Returns false if there was none.
+#[tauri::command]
+async fn remove_site_preset(state: State<'_, AppState>, site: String) -> Result<bool, String> {
+    let site = site_preset_key(&site).ok_or_else(|| format!("Invalid site: {site}"))?;
+
+    with_db(&state, move |db| {
+        let manager = SettingsManager::new(db.conn());
+        let mut settings = manager
+            .get_user_settings()
+            .map_err(|e| format!("Failed to get settings: {e}"))?;
+        if !remove_site_override(&mut settings.general.site_preset_overrides, &site) {
+            return Ok(false);
+        }
+        manager
+            .save_user_settings(&settings)
+            .map_err(|e| format!("Failed to save settings: {e}"))?;
+        Ok(true)
+    })
+    .await
+}
+
+/// Key a site preset override is stored under: the host of a URL or site pattern.
+fn site_preset_key(site: &str) -> Option<String> {
+    db::site_key(&settings::normalize_site_pattern(site))
+}
+
+/// Drop every override keyed on `site`, including ones saved in another spelling.
+fn remove_site_override(
+    overrides: &mut std::collections::BTreeMap<String, String>,
+    site: &str,
+) -> bool {
+    let before = overrides.len();
+    overrides.retain(|pattern, _| site_preset_key(pattern).as_deref() != Some(site));
+    overrides.len() != before
+}
+
+#[tauri::command]
+async fn list_site_presets(
+    state: State<'_, AppState>,
+) -> Result<std::collections::BTreeMap<String, String>, String> {
+    Ok(load_general_settings(&state).await.site_preset_overrides)
 }
 
 // ============================================================================
@@ -597,10 +1995,33 @@ async fn get_toolchain_status(state: State<'_, AppState>) -> Result<ToolchainSta
     if let Some(ref manager) = *tm {
         Ok(manager.get_toolchain_status().await)
     } else {
-        Err("Tool manager not initialized".to_string())
+        // Keep the Tools page usable even if the manager failed to construct.
+        log::warn!("Tool manager not initialized, using standalone tool discovery");
+        let (yt_dlp, ffmpeg) = tokio::task::spawn_blocking(|| {
+            (
+                download_manager::find_ytdlp_binary(),
+                download_manager::find_ffmpeg_binary(),
+            )
+        })
+        .await
+        .map_err(|e| format!("Failed to discover tools: {e}"))?;
+
+        Ok(tool_manager::standalone_toolchain_status(Some(yt_dlp), ffmpeg).await)
     }
 }
 
+/// Exact binaries (path, version, where they came from) the app will run.
+#[tauri::command]
+async fn get_tool_paths(state: State<'_, AppState>) -> Result<tool_manager::ToolPaths, String> {
+    let manager = state
+        .tool_manager
+        .read()
+        .await
+        .clone()
+        .ok_or_else(|| "Tool manager not initialized".to_string())?;
+    Ok(manager.get_tool_paths().await)
+}
+
 #[tauri::command]
 async fn check_for_updates(state: State<'_, AppState>) -> Result<Vec<String>, String> {
     let tm = state.tool_manager.read().await;
@@ -615,6 +2036,70 @@ async fn check_for_updates(state: State<'_, AppState>) -> Result<Vec<String>, St
     }
 }
 
+/// Install a tool update, emitting `ToolUpdateProgress` while downloading and
+/// `ToolUpdateCompleted` or `ToolUpdateFailed` at the end. Shared by `update_tool` and
+/// scheduled auto-updates.
+async fn apply_tool_update(
+    app: &AppHandle,
+    manager: &ToolManager,
+    entry: &tool_manager::ToolManifestEntry,
+) -> anyhow::Result<PathBuf> {
+    let app_handle = app.clone();
+    let tool_name = entry.tool.clone();
+    let result = manager
+        .update_tool(entry, move |progress| {
+            let _ = events::emit_event(
+                &app_handle,
+                DownlinkEvent::ToolUpdateProgress {
+                    info: events::ToolUpdateProgressInfo {
+                        tool: tool_name.clone(),
+                        percent: progress,
+                    },
+                },
+            );
+        })
+        .await;
+
+    let path = match result {
+        Ok(path) => path,
+        Err(e) => {
+            if matches!(e, tool_manager::ToolUpdateError::Canceled) {
+                log::info!("Update of {} to {} canceled", entry.tool, entry.version);
+            } else {
+                log::error!(
+                    "Update of {} to {} failed: {}",
+                    entry.tool,
+                    entry.version,
+                    e
+                );
+            }
+            emit_tool_update_failed(app, &entry.tool, e.user_message(&entry.tool));
+            return Err(e.into());
+        }
+    };
+
+    let _ = events::emit_event(
+        app,
+        DownlinkEvent::ToolUpdateCompleted {
+            tool: entry.tool.clone(),
+            version: entry.version.clone(),
+        },
+    );
+
+    Ok(path)
+}
+
+/// Tell the UI a tool update or repair failed. Every such failure goes through here.
+fn emit_tool_update_failed(app: &AppHandle, tool: &str, user_message: String) {
+    let _ = events::emit_event(
+        app,
+        DownlinkEvent::ToolUpdateFailed {
+            tool: tool.to_string(),
+            user_message,
+        },
+    );
+}
+
 #[tauri::command]
 async fn update_tool(
     app: AppHandle,
@@ -623,71 +2108,281 @@ async fn update_tool(
 ) -> Result<String, String> {
     let tm = state.tool_manager.read().await;
     if let Some(ref manager) = *tm {
-        let updates = manager
-            .check_for_updates()
-            .await
-            .map_err(|e| format!("Failed to check for updates: {e}"))?;
+        let entry = match manager.check_for_updates().await {
+            Ok(updates) => updates
+                .into_iter()
+                .find(|u| u.tool == tool_name)
+                .ok_or_else(|| format!("No update available for {}", tool_name)),
+            Err(e) => Err(format!("Failed to check for updates: {e}")),
+        };
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(message) => {
+                emit_tool_update_failed(&app, &tool_name, message.clone());
+                return Err(message);
+            }
+        };
 
-        let entry = updates
-            .into_iter()
-            .find(|u| u.tool == tool_name)
-            .ok_or_else(|| format!("No update available for {}", tool_name))?;
-
-        let app_handle = app.clone();
-        let tool_name_clone = tool_name.clone();
-        let path = manager
-            .update_tool(&entry, move |progress| {
-                let _ = events::emit_event(
-                    &app_handle,
-                    DownlinkEvent::ToolUpdateProgress {
-                        info: events::ToolUpdateProgressInfo {
-                            tool: tool_name_clone.clone(),
-                            percent: progress,
-                        },
-                    },
-                );
-            })
+        let path = apply_tool_update(&app, manager, &entry)
             .await
             .map_err(|e| format!("Failed to update {}: {e}", tool_name))?;
 
-        let _ = events::emit_event(
-            &app,
-            DownlinkEvent::ToolUpdateCompleted {
-                tool: tool_name.clone(),
-                version: entry.version.clone(),
-            },
-        );
-
         Ok(path.to_string_lossy().to_string())
     } else {
         Err("Tool manager not initialized".to_string())
     }
 }
 
+/// Abort a running tool update. Returns false if none is running for `tool_name`.
+#[tauri::command]
+async fn cancel_tool_update(state: State<'_, AppState>, tool_name: String) -> Result<bool, String> {
+    let tm = state.tool_manager.read().await;
+    if let Some(ref manager) = *tm {
+        Ok(manager.cancel_update(&tool_name))
+    } else {
+        Err("Tool manager not initialized".to_string())
+    }
+}
+
+/// Remove a tool's updated copy and report the bundled or system version now in use.
+#[tauri::command]
+async fn reset_tool(
+    state: State<'_, AppState>,
+    tool_name: String,
+) -> Result<tool_manager::ToolInfo, String> {
+    let tool = Tool::from_name(&tool_name).ok_or_else(|| format!("Unknown tool: {tool_name}"))?;
+    let manager = state
+        .tool_manager
+        .read()
+        .await
+        .clone()
+        .ok_or_else(|| "Tool manager not initialized".to_string())?;
+    manager
+        .reset_tool(tool)
+        .await
+        .map_err(|e| format!("Failed to reset {tool_name}: {e}"))
+}
+
+/// Health-check every tool and repair broken ones: reset to the bundled copy when it
+/// works, otherwise re-download the manifest version. Emits the usual tool update events.
+#[tauri::command]
+async fn verify_and_repair_tools(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<tool_manager::ToolRepairResult>, String> {
+    let manager = state
+        .tool_manager
+        .read()
+        .await
+        .clone()
+        .ok_or_else(|| "Tool manager not initialized".to_string())?;
+
+    let mut results = Vec::new();
+    for tool in [Tool::YtDlp, Tool::Ffmpeg, Tool::Ffprobe] {
+        results.push(repair_tool(&app, &manager, tool).await);
+    }
+    Ok(results)
+}
+
+async fn repair_tool(
+    app: &AppHandle,
+    manager: &ToolManager,
+    tool: Tool,
+) -> tool_manager::ToolRepairResult {
+    let check = manager.verify_tool(tool).await;
+
+    // Only look at the manifest when a download is the remaining option
+    let entry = if check.status == ToolStatus::Broken && !check.bundled_healthy {
+        match manager.manifest_entry(tool).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!(
+                    "Couldn't load the manifest to repair {}: {e:#}",
+                    tool.as_str()
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let action = tool_manager::plan_repair(&check, entry.is_some());
+    if action != RepairAction::None {
+        log::warn!(
+            "{} at {:?} is broken, repairing: {action:?}",
+            tool.as_str(),
+            check.path
+        );
+    }
+
+    let error = match action {
+        RepairAction::None => None,
+        RepairAction::ResetToBundled => {
+            let _ = events::emit_event(
+                app,
+                DownlinkEvent::ToolUpdateProgress {
+                    info: events::ToolUpdateProgressInfo {
+                        tool: tool.as_str().to_string(),
+                        percent: 0.0,
+                    },
+                },
+            );
+            match manager.reset_tool(tool).await {
+                Ok(info) => {
+                    let _ = events::emit_event(
+                        app,
+                        DownlinkEvent::ToolUpdateCompleted {
+                            tool: tool.as_str().to_string(),
+                            version: info.version.unwrap_or_default(),
+                        },
+                    );
+                    None
+                }
+                Err(e) => {
+                    let message =
+                        format!("Couldn't remove the broken {} update: {e}", tool.as_str());
+                    emit_tool_update_failed(app, tool.as_str(), message.clone());
+                    Some(message)
+                }
+            }
+        }
+        RepairAction::Redownload => {
+            let entry = entry.expect("plan_repair only redownloads with a manifest entry");
+            // apply_tool_update emits progress, completion and failure itself
+            apply_tool_update(app, manager, &entry)
+                .await
+                .err()
+                .map(|e| format!("Failed to re-download {}: {e}", tool.as_str()))
+        }
+        RepairAction::Unrepairable => {
+            let message = format!(
+                "{} is broken and there's no replacement to restore. Reinstall Downlink or install {} on your PATH.",
+                tool.as_str(),
+                tool.as_str()
+            );
+            emit_tool_update_failed(app, tool.as_str(), message.clone());
+            Some(message)
+        }
+    };
+
+    tool_manager::ToolRepairResult {
+        tool,
+        status: check.status,
+        action,
+        error,
+        info: manager.get_tool_info(tool).await,
+    }
+}
+
 // ============================================================================
 // Tauri Commands - Presets
 // ============================================================================
 
 #[tauri::command]
-fn get_presets() -> Vec<PresetInfo> {
-    Preset::builtin_presets()
-        .into_iter()
-        .map(|p| PresetInfo {
-            id: p.id,
-            name: p.name,
-        })
-        .collect()
+fn get_presets() -> Vec<PresetInfo> {
+    Preset::builtin_presets()
+        .into_iter()
+        .map(|p| PresetInfo {
+            id: p.id,
+            name: p.name,
+        })
+        .collect()
+}
+
+/// SponsorBlock categories the settings accept, with labels for the UI.
+#[tauri::command]
+fn get_sponsorblock_categories() -> Vec<settings::SponsorBlockCategory> {
+    settings::SPONSORBLOCK_CATEGORIES.to_vec()
+}
+
+// ============================================================================
+// Tauri Commands - Utilities
+// ============================================================================
+
+#[tauri::command]
+fn get_app_data_dir() -> Result<String, String> {
+    db::app_data_dir()
+        .map(|p: PathBuf| p.to_string_lossy().to_string())
+        .map_err(|e| format!("Failed to resolve app data dir: {e}"))
+}
+
+/// Location of the SQLite database, for support and inspection.
+#[tauri::command]
+fn get_database_path() -> Result<String, String> {
+    db::db_path()
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| format!("Failed to resolve database path: {e}"))
+}
+
+/// Write a consistent copy of the database to `dest_path`; safe while downloads run.
+/// Saved site passwords are plain text, so they're only copied with
+/// `include_credentials`.
+#[tauri::command]
+async fn backup_database(
+    state: State<'_, AppState>,
+    dest_path: String,
+    include_credentials: Option<bool>,
+) -> Result<(), String> {
+    let include_credentials = include_credentials.unwrap_or(false);
+    if include_credentials {
+        log::warn!("Backing up the database with saved site passwords in plain text");
+    }
+    with_db(&state, move |db| {
+        db.backup_to(Path::new(&dest_path), include_credentials)
+            .map_err(|e| format!("Failed to back up database: {e:#}"))
+    })
+    .await
+}
+
+/// Reveal the logs directory (backs the "View logs" remediation action).
+#[tauri::command]
+fn open_logs_folder() -> Result<(), String> {
+    let logs = db::ensure_app_dirs()
+        .map_err(|e| format!("Failed to resolve logs dir: {e}"))?
+        .logs;
+    open::that(&logs).map_err(|e| format!("Failed to open logs folder: {e}"))
 }
 
-// ============================================================================
-// Tauri Commands - Utilities
-// ============================================================================
+/// Write a redacted diagnostics bundle (versions, settings, toolchain, recent log) to
+/// the logs folder and return its path, for attaching to support requests.
+#[tauri::command]
+async fn collect_diagnostics(state: State<'_, AppState>) -> Result<String, String> {
+    let toolchain = get_toolchain_status(state.clone()).await?;
+    let settings = with_db(&state, |db| {
+        SettingsManager::new(db.conn())
+            .get_user_settings()
+            .map_err(|e| format!("Failed to get settings: {e}"))
+    })
+    .await?;
+
+    tokio::task::spawn_blocking(move || {
+        let log_path = db::log_file_path()?;
+        let log_text = diagnostics::read_log_tail(&log_path)?;
+        let bundle = diagnostics::DiagnosticsBundle::new(
+            env!("CARGO_PKG_VERSION"),
+            toolchain,
+            &settings,
+            &log_text,
+        );
+        let dir = log_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(std::env::temp_dir);
+        bundle.write_to(&dir)
+    })
+    .await
+    .map_err(|e| format!("Failed to collect diagnostics: {e}"))?
+    .map(|p| p.to_string_lossy().to_string())
+    .map_err(|e| format!("Failed to collect diagnostics: {e}"))
+}
 
+/// Path of the current log file, for attaching to bug reports.
 #[tauri::command]
-fn get_app_data_dir() -> Result<String, String> {
-    db::app_data_dir()
-        .map(|p: PathBuf| p.to_string_lossy().to_string())
-        .map_err(|e| format!("Failed to resolve app data dir: {e}"))
+fn get_log_file_path() -> Result<String, String> {
+    db::log_file_path()
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| format!("Failed to resolve log file path: {e}"))
 }
 
 #[tauri::command]
@@ -703,14 +2398,183 @@ fn get_default_download_dir() -> String {
         .to_string()
 }
 
+/// Free/total space for the filesystem holding `path` (e.g. the output folder).
+#[tauri::command]
+async fn get_disk_space(path: String) -> Result<disk_space::DiskSpace, String> {
+    tokio::task::spawn_blocking(move || disk_space::disk_space(Path::new(&path)))
+        .await
+        .map_err(|e| format!("Failed to get disk space: {e}"))?
+        .map_err(|e| format!("Failed to get disk space: {e}"))
+}
+
+/// Browser profiles found on this machine, for picking where to read cookies from.
+#[tauri::command]
+async fn list_browser_profiles() -> Result<Vec<browser_cookies::BrowserProfile>, String> {
+    tokio::task::spawn_blocking(browser_cookies::detect_browser_profiles)
+        .await
+        .map_err(|e| format!("Failed to list browser profiles: {e}"))
+}
+
+/// Read cookies from `selection`'s browser profile from now on, or stop reading
+/// them from a browser with `None`. Returns the stored `--cookies-from-browser` value.
+#[tauri::command]
+async fn set_cookies_from_browser(
+    state: State<'_, AppState>,
+    selection: Option<browser_cookies::BrowserSelection>,
+) -> Result<Option<String>, String> {
+    let value = selection
+        .as_ref()
+        .map(browser_cookies::cookies_from_browser_value)
+        .transpose()?;
+
+    with_db(&state, move |db| {
+        let manager = SettingsManager::new(db.conn());
+        let mut settings = manager
+            .get_user_settings()
+            .map_err(|e| format!("Failed to get settings: {e}"))?;
+        settings.privacy.cookies_from_browser = value.clone();
+        manager
+            .save_user_settings(&settings)
+            .map_err(|e| format!("Failed to save settings: {e}"))?;
+        Ok(value)
+    })
+    .await
+}
+
+/// Check that a proxy URL works (e.g. before saving network settings).
+#[tauri::command]
+async fn test_proxy(proxy_url: String) -> Result<network::ProxyTestResult, String> {
+    network::test_proxy(&proxy_url).await
+}
+
+/// Download a queue item's thumbnail into the local cache and remember the path.
+/// Returns the local file path for the UI to load via `convertFileSrc`.
+#[tauri::command]
+async fn cache_thumbnail(
+    state: State<'_, AppState>,
+    id: Uuid,
+    url: String,
+) -> Result<String, String> {
+    let network = state
+        .db
+        .call(|db| SettingsManager::new(db.conn()).get_user_settings())
+        .await
+        .map(|s| s.network)
+        .unwrap_or_default();
+    let proxy = (network.use_proxy && !network.proxy_url.trim().is_empty())
+        .then(|| network.proxy_url.trim().to_string());
+
+    let cache = thumbnail_cache::ThumbnailCache::open_default()
+        .map_err(|e| format!("Failed to open thumbnail cache: {e}"))?;
+    let cached = cache
+        .get_or_fetch(&url, proxy.as_deref())
+        .await
+        .map_err(|e| format!("Failed to cache thumbnail: {e}"))?;
+    let path = cached.path.to_string_lossy().to_string();
+    let evicted: Vec<String> = cached
+        .evicted
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    {
+        let path = path.clone();
+        with_db(&state, move |db| {
+            db.clear_thumbnail_paths(&evicted)
+                .map_err(|e| format!("Failed to forget evicted thumbnails: {e}"))?;
+            db.set_thumbnail_path(id, Some(&path))
+                .map_err(|e| format!("Failed to save thumbnail path: {e}"))
+        })
+        .await?;
+    }
+
+    Ok(path)
+}
+
 #[tauri::command]
 fn extract_urls_from_text(text: String) -> Vec<String> {
     url_utils::extract_urls(&text)
 }
 
+/// A site with stored credentials, as listed to the frontend (no password).
+#[derive(Debug, Clone, Serialize)]
+struct SiteCredentialsInfo {
+    site: String,
+    username: String,
+}
+
+/// Save a username/password that downloads from `site` (a host or URL) log in with.
+///
+/// The password is stored unencrypted in the app database (see `password_plaintext`)
+/// and reaches yt-dlp through a private config file, never its command line.
+#[tauri::command]
+async fn set_site_credentials(
+    state: State<'_, AppState>,
+    site: String,
+    username: String,
+    password: String,
+) -> Result<String, String> {
+    let site = db::site_key(&site).ok_or_else(|| format!("Invalid site: {site}"))?;
+    if username.trim().is_empty() {
+        return Err("Username is empty".to_string());
+    }
+
+    with_db(&state, move |db| {
+        db.set_site_credentials(&site, username.trim(), &password)
+            .map_err(|e| format!("Failed to save credentials: {e}"))?;
+        Ok(site)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn remove_site_credentials(state: State<'_, AppState>, site: String) -> Result<bool, String> {
+    let site = db::site_key(&site).ok_or_else(|| format!("Invalid site: {site}"))?;
+    with_db(&state, move |db| {
+        db.remove_site_credentials(&site)
+            .map_err(|e| format!("Failed to remove credentials: {e}"))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn list_site_credentials(
+    state: State<'_, AppState>,
+) -> Result<Vec<SiteCredentialsInfo>, String> {
+    let sites = with_db(&state, |db| {
+        db.list_site_credentials()
+            .map_err(|e| format!("Failed to list credentials: {e}"))
+    })
+    .await?;
+    Ok(sites
+        .into_iter()
+        .map(|(site, username)| SiteCredentialsInfo { site, username })
+        .collect())
+}
+
+/// Validate one pasted URL and return its normalized form.
+#[tauri::command]
+fn normalize_url(url: String) -> Result<String, url_utils::InvalidUrl> {
+    url_utils::validate_http_url(&url)
+}
+
+/// Show the exact yt-dlp command a queued download would run, without running it.
+#[tauri::command]
+async fn preview_command(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: Uuid,
+) -> Result<download_manager::CommandPreview, String> {
+    let manager = get_or_init_download_manager(&state, &app).await;
+    manager
+        .preview_command(id)
+        .await
+        .map_err(|e| format!("Failed to build command: {e}"))
+}
+
 #[tauri::command]
-async fn open_file(path: String) -> Result<(), String> {
-    let path = PathBuf::from(&path);
+async fn open_file(path: String, output_dir: Option<String>) -> Result<(), String> {
+    let path = download_manager::resolve_final_path(&path, output_dir.as_deref());
 
     // Check if file exists
     if !path.exists() {
@@ -734,8 +2598,8 @@ async fn open_file(path: String) -> Result<(), String> {
 }
 
 #[tauri::command]
-async fn open_folder(path: String) -> Result<(), String> {
-    let path = PathBuf::from(&path);
+async fn open_folder(path: String, output_dir: Option<String>) -> Result<(), String> {
+    let path = download_manager::resolve_final_path(&path, output_dir.as_deref());
 
     // Determine the folder to open
     let folder = if path.is_file() {
@@ -744,6 +2608,9 @@ async fn open_folder(path: String) -> Result<(), String> {
             .unwrap_or_else(|| path.clone())
     } else if path.exists() {
         path.clone()
+    } else if let Some(dir) = output_dir.as_deref().filter(|d| Path::new(d).is_dir()) {
+        // File was moved or deleted; fall back to the download's output directory
+        PathBuf::from(dir)
     } else {
         // If path doesn't exist, try to open the parent directory
         path.parent()
@@ -914,6 +2781,93 @@ async fn restart_app(app: AppHandle) -> Result<(), String> {
 // Helper Functions
 // ============================================================================
 
+/// Interrupted downloads to restart on launch, if `auto_resume_on_launch` is on.
+/// All of them: those beyond `concurrency` wait in the manager's queue.
+fn interrupted_downloads_to_resume(db: &mut db::Db) -> Vec<Uuid> {
+    let general = SettingsManager::new(db.conn())
+        .get_user_settings()
+        .map(|s| s.general)
+        .unwrap_or_default();
+    if !general.auto_resume_on_launch {
+        return Vec::new();
+    }
+    db.get_interrupted_download_ids().unwrap_or_else(|e| {
+        log::error!("Failed to list interrupted downloads: {}", e);
+        Vec::new()
+    })
+}
+
+/// Delete temp files left over from earlier runs, in the background. The staging
+/// folders of downloads about to be resumed are kept.
+fn cleanup_stale_temp_files(resume_ids: &[Uuid]) {
+    let tmp_dir = match db::ensure_app_dirs() {
+        Ok(dirs) => dirs.tmp,
+        Err(e) => {
+            log::warn!("Skipping temp file cleanup: {}", e);
+            return;
+        }
+    };
+    let keep: HashSet<PathBuf> = staging_dirs(&tmp_dir, resume_ids).into_iter().collect();
+    std::thread::spawn(move || {
+        // Site logins outlive their download only if the app died mid-run
+        let credentials = tmp_dir.join(download_manager::CREDENTIALS_DIR);
+        if let Err(e) = temp_cleanup::cleanup_temp_files(
+            &credentials,
+            temp_cleanup::IN_USE_GRACE,
+            &HashSet::new(),
+        ) {
+            log::warn!("Failed to remove leftover credentials files: {}", e);
+        }
+        match temp_cleanup::cleanup_temp_files(&tmp_dir, temp_cleanup::STARTUP_MAX_AGE, &keep) {
+            Ok(report) if report.removed > 0 => log::info!(
+                "Removed {} stale temp file(s), freeing {} bytes",
+                report.removed,
+                report.freed_bytes
+            ),
+            Ok(_) => {}
+            Err(e) => log::warn!("Temp file cleanup failed: {}", e),
+        }
+    });
+}
+
+/// Start `ids` in the background once the app is up.
+fn resume_downloads(app: AppHandle, ids: Vec<Uuid>) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let manager = get_or_init_download_manager(&state, &app).await;
+        log::info!("Resuming {} interrupted download(s)", ids.len());
+        for id in ids {
+            if let Err(e) = manager.start(id).await {
+                log::warn!("Failed to resume download {}: {}", id, e);
+            }
+        }
+    });
+}
+
+/// Current general settings, or the defaults if they can't be read.
+async fn load_general_settings(state: &State<'_, AppState>) -> settings::GeneralSettings {
+    state
+        .db
+        .call(|db| SettingsManager::new(db.conn()).get_user_settings())
+        .await
+        .map(|s| s.general)
+        .unwrap_or_default()
+}
+
+/// Run a command's database work on the DB thread. `f` reports errors the way
+/// commands do, as messages for the UI.
+async fn with_db<T, F>(state: &AppState, f: F) -> Result<T, String>
+where
+    F: FnOnce(&mut db::Db) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    state
+        .db
+        .call(move |db| Ok(f(db)))
+        .await
+        .map_err(|e| format!("Database unavailable: {e}"))?
+}
+
 async fn build_ytdlp_runner(state: &State<'_, AppState>) -> ytdlp::YtDlpRunner {
     let yt_dlp_path = {
         let tm = state.tool_manager.read().await;
@@ -925,7 +2879,11 @@ async fn build_ytdlp_runner(state: &State<'_, AppState>) -> ytdlp::YtDlpRunner {
     }
     .unwrap_or_else(download_manager::find_ytdlp_binary);
 
-    let cfg = ytdlp::YtDlpConfig::new(yt_dlp_path);
+    let mut cfg = ytdlp::YtDlpConfig::new(yt_dlp_path);
+    let general = load_general_settings(state).await;
+    cfg.global_args = download_manager::extractor_args(&general);
+    cfg.global_args
+        .extend(download_manager::extra_ytdlp_args(&general));
     ytdlp::YtDlpRunner::new(cfg)
 }
 
@@ -954,15 +2912,37 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_process::init())
         .setup(|app| {
-            // Enable logging in both debug and release modes
+            // Enable logging in both debug and release modes. The file goes to our own
+            // logs dir so `open_logs_folder` / `get_log_file_path` can point at it.
+            // The plugin passes everything through; the active filter is the global max
+            // level, so `set_log_level` can change it without a restart.
+            let logs_dir = db::ensure_app_dirs().map_err(tauri::Error::Anyhow)?.logs;
             app.handle().plugin(
                 tauri_plugin_log::Builder::default()
-                    .level(log::LevelFilter::Info)
+                    .level(log::LevelFilter::Trace)
+                    .targets([
+                        tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Stdout),
+                        tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Folder {
+                            path: logs_dir,
+                            file_name: Some(db::LOG_FILE_STEM.to_string()),
+                        }),
+                    ])
                     .build(),
             )?;
+            log::set_max_level(log::LevelFilter::Info);
 
             // Initialize per-user dirs + SQLite
-            let db = db::Db::open().map_err(|e| tauri::Error::Anyhow(e))?;
+            let mut db = db::Db::open().map_err(|e| tauri::Error::Anyhow(e))?;
+            apply_saved_log_level(&db);
+
+            // Rows still "active" from a previous run were interrupted (crash/kill)
+            match db.reconcile_interrupted_downloads() {
+                Ok(0) => {}
+                Ok(n) => log::info!("Reset {} interrupted download(s) to stopped", n),
+                Err(e) => log::error!("Failed to reconcile interrupted downloads: {}", e),
+            }
+            let resume_ids = interrupted_downloads_to_resume(&mut db);
+            cleanup_stale_temp_files(&resume_ids);
 
             // Initialize tool manager with bundled_dir set to executable directory
             // In production, Tauri places sidecar binaries next to the executable
@@ -983,51 +2963,117 @@ pub fn run() {
 
             // Store state - download manager will be lazily initialized on first use
             app.manage(AppState {
-                db: Arc::new(Mutex::new(db)),
+                db: db_actor::DbHandle::spawn(db).map_err(tauri::Error::Anyhow)?,
                 download_manager: RwLock::new(None),
                 tool_manager: RwLock::new(tool_manager),
                 event_tx: Arc::new(Mutex::new(None)),
+                metadata_fetches: MetadataFetches::default(),
+                extractors: ExtractorCache::default(),
             });
 
+            // Restore the window and keep its position/size saved as it changes
+            window_state::install(app.handle());
+
+            if !resume_ids.is_empty() {
+                resume_downloads(app.handle().clone(), resume_ids);
+            }
+
             // Emit ready event synchronously
             emit_app_ready(&app.handle(), None, None);
 
+            // Periodic yt-dlp/ffmpeg/app update checks per the user's interval
+            update_scheduler::spawn(app.handle().clone());
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // URL and queue management
             add_urls,
+            add_urls_from_file,
+            get_original_url,
             fetch_metadata,
+            cancel_metadata_fetch,
+            fetch_metadata_batch,
+            preview_playlist,
             expand_playlist,
             extract_urls_from_text,
+            normalize_url,
+            diagnose_url,
+            list_subtitles,
+            test_filename_template,
+            is_url_supported,
             // Download control
             start_download,
             stop_download,
             cancel_download,
             retry_download,
+            retry_postprocessing,
+            refresh_metadata,
+            retry_with_geo_bypass,
             start_all_downloads,
+            retry_all_failed,
             stop_all_downloads,
+            pause_all_downloads,
+            resume_all_downloads,
+            set_online_status,
+            get_speed_samples,
+            get_manager_status,
             // Queue and history
             get_queue,
+            get_queue_tree,
             get_history,
+            relink_download,
+            verify_files,
+            verify_download_checksum,
+            get_statistics,
+            get_bandwidth_usage,
             clear_queue,
             clear_history,
+            clear_failed_downloads,
+            clear_canceled_downloads,
+            clear_download_archive,
             remove_download,
+            change_preset,
             // Settings
             get_settings,
+            get_effective_settings,
             save_settings,
+            set_log_level,
             get_window_state,
             save_window_state,
+            set_site_preset,
+            remove_site_preset,
+            list_site_presets,
+            set_site_credentials,
+            remove_site_credentials,
+            list_site_credentials,
             // Tools
             get_toolchain_status,
+            get_tool_paths,
             check_for_updates,
             update_tool,
+            cancel_tool_update,
+            reset_tool,
+            verify_and_repair_tools,
             // Presets
             get_presets,
+            get_sponsorblock_categories,
             // Utilities
             get_app_data_dir,
+            get_database_path,
+            collect_diagnostics,
+            backup_database,
+            open_logs_folder,
+            get_log_file_path,
             get_app_version,
             get_default_download_dir,
+            get_disk_space,
+            test_proxy,
+            list_browser_profiles,
+            set_cookies_from_browser,
+            cache_thumbnail,
+            clear_temp_files,
+            preview_command,
             open_file,
             open_folder,
             // App updates
@@ -1035,6 +3081,758 @@ pub fn run() {
             install_app_update,
             restart_app,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                tauri::async_runtime::block_on(on_exit(app));
+            }
+        });
+}
+
+/// Stop running downloads and apply exit-time privacy settings before the process ends.
+async fn on_exit(app: &AppHandle) {
+    let state = app.state::<AppState>();
+
+    let manager = state.download_manager.read().await.clone();
+    if let Some(manager) = manager {
+        log::info!("Stopping active downloads before exit");
+        if let Err(e) = manager.shutdown().await {
+            log::error!("Failed to stop downloads on exit: {}", e);
+        }
+    }
+
+    let cleared = state
+        .db
+        .call(|db| {
+            let manager = SettingsManager::new(db.conn());
+            let mut settings = manager.get_user_settings()?;
+            if !clear_cookies_on_exit(&settings.privacy, &db::app_data_dir()?)? {
+                return Ok(false);
+            }
+            // Forget the file too, or every download would fail on the missing cookies
+            settings.privacy.cookies_path = None;
+            manager.save_user_settings(&settings)?;
+            manager.set_cookies_imported(false)?;
+            Ok(true)
+        })
+        .await;
+    match cleared {
+        Ok(true) => log::info!("Cleared stored cookies on exit"),
+        Ok(false) => {}
+        Err(e) => log::error!("Failed to clear cookies on exit: {}", e),
+    }
+}
+
+/// Delete the stored cookies file when `clear_cookies_on_exit` is set. Only copies
+/// the app keeps under `app_data_dir` are removed; a file the user picked elsewhere
+/// is theirs and stays.
+///
+/// Returns whether the cookies setting should be cleared (the file is gone).
+fn clear_cookies_on_exit(privacy: &PrivacySettings, app_data_dir: &Path) -> std::io::Result<bool> {
+    let Some(path) = privacy.cookies_path.as_ref() else {
+        return Ok(false);
+    };
+    if !privacy.clear_cookies_on_exit || !path.starts_with(app_data_dir) {
+        return Ok(false);
+    }
+
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(preset_id: &str) -> AddUrlsOptions {
+        AddUrlsOptions {
+            preset_id: preset_id.to_string(),
+            output_dir: "/tmp/downloads".to_string(),
+            parent_id: None,
+            source_kind: None,
+            title: None,
+            uploader: None,
+            thumbnail_url: None,
+            duration_seconds: None,
+            sections: None,
+            subtitle_langs: None,
+            rate_limit_bps: None,
+        }
+    }
+
+    #[test]
+    fn test_interrupted_downloads_to_resume_follows_settings() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let ids: Vec<Uuid> = (0..3)
+            .map(|i| {
+                let id = db
+                    .insert_download(
+                        &format!("https://example.com/{i}"),
+                        db::SourceKind::Single,
+                        None,
+                        "recommended_best",
+                        "/tmp/downloads",
+                    )
+                    .unwrap();
+                db.set_status(id, db::DownloadStatus::Downloading, None)
+                    .unwrap();
+                id
+            })
+            .collect();
+        db.reconcile_interrupted_downloads().unwrap();
+
+        // Off by default
+        assert!(interrupted_downloads_to_resume(&mut db).is_empty());
+
+        let mut settings = UserSettings::default();
+        settings.general.auto_resume_on_launch = true;
+        settings.general.concurrency = 2;
+        SettingsManager::new(db.conn())
+            .save_user_settings(&settings)
+            .unwrap();
+        // More than fit in the slots; the rest are queued once started
+        assert_eq!(interrupted_downloads_to_resume(&mut db), ids);
+    }
+
+    #[test]
+    fn test_build_queue_tree_groups_children_under_parent() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let mut insert = |url: &str, kind, parent| {
+            db.insert_download(url, kind, parent, "recommended_best", "/tmp/downloads")
+                .unwrap()
+        };
+        let single = insert("https://example.com/a", db::SourceKind::Single, None);
+        let parent = insert(
+            "https://example.com/list",
+            db::SourceKind::PlaylistParent,
+            None,
+        );
+        let first = insert(
+            "https://example.com/1",
+            db::SourceKind::PlaylistItem,
+            Some(parent),
+        );
+        let other = insert("https://example.com/b", db::SourceKind::Single, None);
+        let second = insert(
+            "https://example.com/2",
+            db::SourceKind::PlaylistItem,
+            Some(parent),
+        );
+        let mut item = |id: &Uuid| QueueItem::from(db.get_download(*id).unwrap().unwrap());
+
+        let tree = build_queue_tree(
+            [single, parent, first, other, second]
+                .iter()
+                .map(&mut item)
+                .collect(),
+        );
+        let roots: Vec<Uuid> = tree.iter().map(|node| node.item.id).collect();
+        assert_eq!(roots, vec![single, parent, other]);
+        let children: Vec<Uuid> = tree[1].children.iter().map(|node| node.item.id).collect();
+        assert_eq!(children, vec![first, second]);
+        assert!(tree[0].children.is_empty());
+        assert!(tree[2].children.is_empty());
+
+        // Items whose parent was filtered out stay at the top level
+        let tree = build_queue_tree([single, first, second].iter().map(&mut item).collect());
+        let roots: Vec<Uuid> = tree.iter().map(|node| node.item.id).collect();
+        assert_eq!(roots, vec![single, first, second]);
+    }
+
+    #[test]
+    fn test_queue_item_from_row_maps_all_fields() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let parent = db
+            .insert_download(
+                "https://example.com/playlist",
+                db::SourceKind::PlaylistParent,
+                None,
+                "recommended_best",
+                "/tmp/downloads",
+            )
+            .unwrap();
+        let id = db
+            .insert_download(
+                "https://example.com/a",
+                db::SourceKind::PlaylistItem,
+                Some(parent),
+                "recommended_best",
+                "/tmp/downloads",
+            )
+            .unwrap();
+        db.update_progress(id, Some(40.0), Some(400), Some(1000), Some(50), Some(12))
+            .unwrap();
+        db.set_error(id, Some("network"), Some("Connection reset"), None)
+            .unwrap();
+
+        let row = db.get_download(id).unwrap().unwrap();
+        let (created_at, updated_at) = (row.created_at, row.updated_at);
+        let item = QueueItem::from(row);
+
+        assert_eq!(item.created_at, created_at);
+        assert_eq!(item.updated_at, updated_at);
+        assert!(item.updated_at >= item.created_at);
+        assert_eq!(item.source_kind, "playlist_item");
+        assert_eq!(item.parent_id, Some(parent));
+        assert_eq!(item.bytes_downloaded, Some(400));
+        assert_eq!(item.bytes_total, Some(1000));
+        assert_eq!(item.error_code.as_deref(), Some("network"));
+        assert_eq!(item.error_message.as_deref(), Some("Connection reset"));
+
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["source_kind"], "playlist_item");
+        assert_eq!(json["parent_id"], parent.to_string());
+        assert!(json["created_at"].is_string());
+    }
+
+    /// A completed download whose file is `path`, inside a fresh temp dir.
+    fn completed_download(db: &mut db::Db, path: &Path) -> Uuid {
+        let id = db
+            .insert_download(
+                "https://example.com/a",
+                db::SourceKind::Single,
+                None,
+                "recommended_best",
+                &path.parent().unwrap().to_string_lossy(),
+            )
+            .unwrap();
+        let path = path.to_string_lossy().to_string();
+        db.set_final_path(id, &path).unwrap();
+        db.set_final_paths(id, &[path, "/elsewhere/a.en.srt".to_string()])
+            .unwrap();
+        db.set_status(id, db::DownloadStatus::Done, None).unwrap();
+        id
+    }
+
+    #[test]
+    fn test_relink_download_file() {
+        let dir = std::env::temp_dir().join(format!("downlink-relink-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old_path = dir.join("a.mp4");
+        let new_path = dir.join("moved.mp4");
+        std::fs::write(&new_path, b"video").unwrap();
+
+        let mut db = db::Db::open_in_memory().unwrap();
+        let id = completed_download(&mut db, &old_path);
+
+        let missing = dir.join("nope.mp4");
+        let err = relink_download_file(&mut db, id, &missing.to_string_lossy()).unwrap_err();
+        assert!(err.contains("does not exist"), "{err}");
+        assert!(relink_download_file(&mut db, id, "moved.mp4")
+            .unwrap_err()
+            .contains("absolute"));
+
+        relink_download_file(&mut db, id, &new_path.to_string_lossy()).unwrap();
+        let row = db.get_download(id).unwrap().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let new_path = new_path.to_string_lossy().to_string();
+        assert_eq!(row.final_path.as_deref(), Some(new_path.as_str()));
+        assert_eq!(
+            row.final_paths,
+            vec![new_path, "/elsewhere/a.en.srt".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_relink_rejects_unfinished_download() {
+        let file = std::env::temp_dir().join(format!("downlink-relink-{}.mp4", Uuid::new_v4()));
+        std::fs::write(&file, b"video").unwrap();
+        let mut db = db::Db::open_in_memory().unwrap();
+        let id = db
+            .insert_download(
+                "https://example.com/a",
+                db::SourceKind::Single,
+                None,
+                "recommended_best",
+                "/tmp",
+            )
+            .unwrap();
+
+        let result = relink_download_file(&mut db, id, &file.to_string_lossy());
+        let _ = std::fs::remove_file(&file);
+        assert!(result.unwrap_err().contains("Only completed"));
+    }
+
+    #[test]
+    fn test_find_missing_files() {
+        let dir = std::env::temp_dir().join(format!("downlink-verify-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let present = dir.join("here.mp4");
+        std::fs::write(&present, b"video").unwrap();
+
+        let mut db = db::Db::open_in_memory().unwrap();
+        completed_download(&mut db, &present);
+        let gone = completed_download(&mut db, &dir.join("gone.mp4"));
+        // Still queued: not checked
+        db.insert_download(
+            "https://example.com/b",
+            db::SourceKind::Single,
+            None,
+            "recommended_best",
+            "/tmp",
+        )
+        .unwrap();
+
+        let missing = find_missing_files(&mut db).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].id, gone);
+        assert!(missing[0].final_path.ends_with("gone.mp4"));
+    }
+
+    #[test]
+    fn test_insert_queued_urls_emits_event_per_id() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let urls: Vec<url_utils::ExtractedUrl> = vec![
+            "https://example.com/a".to_string().into(),
+            "https://example.com/b".to_string().into(),
+        ];
+
+        let mut queued = Vec::new();
+        let ids = insert_queued_urls(&mut db, &urls, &options("recommended_best"), |event| {
+            if let DownlinkEvent::DownloadQueued { id } = event {
+                queued.push(id);
+            }
+        })
+        .unwrap();
+
+        assert_eq!(ids.len(), 2);
+        assert_eq!(queued, ids);
+    }
+
+    #[test]
+    fn test_insert_queued_urls_uses_site_preset_when_unset() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let mut settings = UserSettings::default();
+        settings
+            .general
+            .site_preset_overrides
+            .insert("music.example.com".to_string(), "audio_m4a".to_string());
+        SettingsManager::new(db.conn())
+            .save_user_settings(&settings)
+            .unwrap();
+
+        let urls: Vec<url_utils::ExtractedUrl> = vec![
+            "https://music.example.com/watch?v=a".to_string().into(),
+            "https://example.com/watch?v=b".to_string().into(),
+        ];
+        let ids = insert_queued_urls(&mut db, &urls, &options(""), |_| {}).unwrap();
+        let presets: Vec<String> = ids
+            .iter()
+            .map(|id| db.get_download(*id).unwrap().unwrap().preset_id)
+            .collect();
+        assert_eq!(presets, vec!["audio_m4a", "recommended_best"]);
+
+        // An explicit choice wins over the site mapping
+        let ids = insert_queued_urls(&mut db, &urls[..1], &options("mp4_best"), |_| {}).unwrap();
+        assert_eq!(
+            db.get_download(ids[0]).unwrap().unwrap().preset_id,
+            "mp4_best"
+        );
+    }
+
+    #[test]
+    fn test_remove_site_override_matches_how_it_was_set() {
+        let mut overrides = std::collections::BTreeMap::new();
+        let key = site_preset_key("https://www.YouTube.com/watch?v=abc").unwrap();
+        assert_eq!(key, "youtube.com");
+        overrides.insert(key, "audio_m4a".to_string());
+        // Hand-edited settings may hold other spellings of the same site
+        overrides.insert("*.YouTube.com".to_string(), "mp4_best".to_string());
+        overrides.insert("vimeo.com".to_string(), "mp4_best".to_string());
+
+        let site = site_preset_key("www.youtube.com").unwrap();
+        assert!(remove_site_override(&mut overrides, &site));
+        assert_eq!(overrides.keys().collect::<Vec<_>>(), vec!["vimeo.com"]);
+        assert!(!remove_site_override(&mut overrides, &site));
+    }
+
+    fn write_temp_file(contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("downlink-urls-{}.txt", Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_urls_from_file_mixed_content() {
+        let path = write_temp_file(
+            b"# watch later\n\
+              https://example.com/a\n\
+              \n\
+              see (https://example.com/b), and https://example.com/a again\r\n\
+              ftp://example.com/skip not-a-url \xff\xfe\n\
+              https://EXAMPLE.com:443/b#t=1\n\
+              https://example.com/c",
+        );
+        let urls = read_urls_from_file(&path, MAX_URLS_FROM_FILE).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(urls[1].original, "https://example.com/b");
+        assert_eq!(
+            urls.into_iter().map(|u| u.url).collect::<Vec<_>>(),
+            vec![
+                "https://example.com/a".to_string(),
+                "https://example.com/b".to_string(),
+                "https://example.com/c".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_urls_from_file_caps_unique_urls() {
+        // Duplicates don't count toward the cap
+        let path = write_temp_file(
+            b"https://example.com/1\nhttps://example.com/1\nhttps://example.com/2\n",
+        );
+        assert_eq!(read_urls_from_file(&path, 2).unwrap().len(), 2);
+
+        std::fs::write(
+            &path,
+            b"https://example.com/1\nhttps://example.com/2\nhttps://example.com/3\n",
+        )
+        .unwrap();
+        let err = read_urls_from_file(&path, 2).unwrap_err();
+        let _ = std::fs::remove_file(&path);
+        assert!(err.contains("more than 2 URLs"), "{err}");
+    }
+
+    #[test]
+    fn test_read_urls_from_file_rejects_overlong_lines() {
+        let max = MAX_URL_FILE_LINE_BYTES as usize;
+        let mut content = b"https://example.com/a\n".to_vec();
+        content.extend(std::iter::repeat(b'x').take(max));
+        content.extend(b"\nhttps://example.com/b\n");
+        let path = write_temp_file(&content);
+        assert_eq!(read_urls_from_file(&path, 10).unwrap().len(), 2);
+
+        content.extend(std::iter::repeat(b'x').take(max + 1));
+        std::fs::write(&path, &content).unwrap();
+        let err = read_urls_from_file(&path, 10).unwrap_err();
+        let _ = std::fs::remove_file(&path);
+        assert!(err.starts_with("Line 4 of"), "{err}");
+    }
+
+    #[test]
+    fn test_read_urls_from_file_missing() {
+        let err = read_urls_from_file(Path::new("/nonexistent/downlink/urls.txt"), 10).unwrap_err();
+        assert!(err.starts_with("Failed to open"));
+    }
+
+    #[test]
+    fn test_insert_queued_urls_keeps_original_url() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let urls = url_utils::extract_urls_with_originals(
+            "https://YouTube.com:443/watch?v=abc#t=90 https://example.com/b",
+        );
+        let ids = insert_queued_urls(&mut db, &urls, &options("recommended_best"), |_| {}).unwrap();
+
+        let row = db.get_download(ids[0]).unwrap().unwrap();
+        assert_eq!(row.source_url, "https://youtube.com/watch?v=abc");
+        assert_eq!(
+            row.original_url.as_deref(),
+            Some("https://YouTube.com:443/watch?v=abc#t=90")
+        );
+        let row = db.get_download(ids[1]).unwrap().unwrap();
+        assert_eq!(row.original_url.as_deref(), Some("https://example.com/b"));
+
+        // Rows queued some other way have no original form
+        let other = db
+            .insert_download(
+                "https://example.com/c",
+                db::SourceKind::Single,
+                None,
+                "recommended_best",
+                "/tmp",
+            )
+            .unwrap();
+        assert_eq!(db.get_download(other).unwrap().unwrap().original_url, None);
+    }
+
+    #[test]
+    fn test_insert_queued_urls_persists_sections() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let urls = vec![url_utils::ExtractedUrl::from(
+            "https://example.com/a".to_string(),
+        )];
+        let mut opts = options("recommended_best");
+        opts.sections = Some("*00:10:00-00:15:00".to_string());
+
+        let ids = insert_queued_urls(&mut db, &urls, &opts, |_| {}).unwrap();
+
+        let row = db.get_download(ids[0]).unwrap().unwrap();
+        assert_eq!(row.sections.as_deref(), Some("*00:10:00-00:15:00"));
+    }
+
+    #[test]
+    fn test_insert_queued_urls_persists_rate_limit() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let urls = vec![url_utils::ExtractedUrl::from(
+            "https://example.com/a".to_string(),
+        )];
+        let mut opts = options("recommended_best");
+        opts.rate_limit_bps = Some(100_000);
+        let ids = insert_queued_urls(&mut db, &urls, &opts, |_| {}).unwrap();
+        assert_eq!(
+            db.get_download(ids[0]).unwrap().unwrap().rate_limit_bps,
+            Some(100_000)
+        );
+
+        opts.rate_limit_bps = Some(0);
+        let ids = insert_queued_urls(&mut db, &urls, &opts, |_| {}).unwrap();
+        assert_eq!(
+            db.get_download(ids[0]).unwrap().unwrap().rate_limit_bps,
+            None
+        );
+    }
+
+    #[test]
+    fn test_insert_queued_urls_persists_subtitle_langs() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let urls = vec![url_utils::ExtractedUrl::from(
+            "https://example.com/a".to_string(),
+        )];
+        let mut opts = options("recommended_best");
+        opts.subtitle_langs = Some(vec!["ja".to_string(), " en ".to_string()]);
+
+        let ids = insert_queued_urls(&mut db, &urls, &opts, |_| {}).unwrap();
+        let row = db.get_download(ids[0]).unwrap().unwrap();
+        assert_eq!(row.subtitle_langs.as_deref(), Some("ja,en"));
+
+        // Without a choice the row follows the settings
+        let ids = insert_queued_urls(&mut db, &urls, &options("recommended_best"), |_| {}).unwrap();
+        assert_eq!(
+            db.get_download(ids[0]).unwrap().unwrap().subtitle_langs,
+            None
+        );
+
+        opts.subtitle_langs = Some(vec!["japanese!".to_string()]);
+        assert!(insert_queued_urls(&mut db, &urls, &opts, |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_insert_queued_urls_rejects_malformed_sections() {
+        let mut db = db::Db::open_in_memory().unwrap();
+        let urls = vec![url_utils::ExtractedUrl::from(
+            "https://example.com/a".to_string(),
+        )];
+        let mut opts = options("recommended_best");
+        opts.sections = Some("*00:15:00-00:10:00".to_string());
+
+        assert!(insert_queued_urls(&mut db, &urls, &opts, |_| {}).is_err());
+        assert!(db
+            .get_queue(&db::QueueFilter::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    fn sample_metadata(url: &str) -> FetchMetadataResult {
+        FetchMetadataResult {
+            id: Uuid::nil(),
+            url: url.to_string(),
+            is_playlist: false,
+            title: Some("Title".to_string()),
+            uploader: None,
+            duration_seconds: None,
+            thumbnail_url: None,
+            thumbnails: Vec::new(),
+            filesize_bytes: None,
+            playlist_title: None,
+            playlist_count_hint: None,
+            preset_id: "recommended_best".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_concurrently_reports_per_url_results() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let urls = vec![
+            "https://example.com/good1".to_string(),
+            "https://example.com/bad".to_string(),
+            "not a url".to_string(),
+            "https://example.com/good2".to_string(),
+            "https://example.com/good3".to_string(),
+        ];
+
+        let (current, max) = (in_flight.clone(), max_in_flight.clone());
+        let results = fetch_metadata_concurrently(urls, 2, move |url| {
+            let (current, max) = (current.clone(), max.clone());
+            async move {
+                let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                current.fetch_sub(1, Ordering::SeqCst);
+
+                if url.contains("bad") {
+                    Err("yt-dlp metadata failed: unsupported URL".to_string())
+                } else {
+                    Ok(sample_metadata(&url))
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 5);
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 2);
+
+        assert_eq!(results[0].url, "https://example.com/good1");
+        assert!(results[0].metadata.is_some() && results[0].error.is_none());
+        assert!(results[1].metadata.is_none());
+        assert_eq!(
+            results[1].error.as_deref(),
+            Some("yt-dlp metadata failed: unsupported URL")
+        );
+        assert_eq!(results[2].url, "not a url");
+        assert!(results[2].error.is_some());
+        assert!(results[3].metadata.is_some());
+        assert!(results[4].metadata.is_some());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_diagnose_bad_url_reports_classified_error() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("downlink-diagnose-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = dir.join("yt-dlp");
+        std::fs::write(
+            &fake,
+            "#!/bin/sh\n\
+             echo '[debug] Command-line config: [\"--simulate\", \"--verbose\"]' >&2\n\
+             echo '[generic] Extracting URL: https://example.com/not-a-video' >&2\n\
+             echo 'ERROR: Unsupported URL: https://example.com/not-a-video' >&2\n\
+             exit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let runner = ytdlp::YtDlpRunner::new(ytdlp::YtDlpConfig::new(fake));
+        let output = runner
+            .simulate("https://example.com/not-a-video")
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let diagnosis = build_url_diagnosis("https://example.com/not-a-video".to_string(), &output);
+        assert_eq!(diagnosis.exit_code, Some(1));
+        assert_eq!(diagnosis.stderr_tail.len(), 3);
+        assert!(diagnosis.stderr_tail[2].contains("Unsupported URL"));
+        assert!(matches!(
+            diagnosis.error_code,
+            Some(events::ErrorCode::ExtractorOutdated)
+        ));
+        assert!(diagnosis.user_message.is_some());
+        assert!(!diagnosis.actions.is_empty());
+    }
+
+    #[test]
+    fn test_transient_metadata_id() {
+        let request_id = Uuid::new_v4();
+        assert_eq!(
+            transient_metadata_id(Some(&request_id.to_string())),
+            request_id
+        );
+        assert!(!transient_metadata_id(Some("preview-1")).is_nil());
+        assert_ne!(transient_metadata_id(None), transient_metadata_id(None));
+    }
+
+    #[test]
+    fn test_build_url_diagnosis_success_and_tail_bound() {
+        let output = ytdlp::YtDlpOutput {
+            stdout_lines: vec![],
+            stderr_lines: (0..500).map(|i| format!("[debug] line {i}")).collect(),
+            exit_code: Some(0),
+        };
+
+        let diagnosis = build_url_diagnosis("https://example.com/v".to_string(), &output);
+        assert!(diagnosis.error_code.is_none());
+        assert!(diagnosis.user_message.is_none());
+        assert_eq!(diagnosis.stderr_tail.len(), DIAGNOSE_TAIL_LINES);
+        assert_eq!(diagnosis.stderr_tail.last().unwrap(), "[debug] line 499");
+    }
+
+    #[test]
+    fn test_sponsorblock_categories_match_validator() {
+        let ids: Vec<String> = get_sponsorblock_categories()
+            .iter()
+            .map(|c| c.id.to_string())
+            .collect();
+        assert_eq!(ids.len(), 8);
+        assert!(settings::validate_sponsorblock_categories(&ids).is_ok());
+        for id in &ids {
+            assert!(settings::validate_sponsorblock_categories(std::slice::from_ref(id)).is_ok());
+        }
+        assert!(
+            settings::validate_sponsorblock_categories(&["poi_highlight".to_string()]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_clear_cookies_on_exit() {
+        let dir = std::env::temp_dir().join(format!("downlink-cookies-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cookies = dir.join("cookies.txt");
+        std::fs::write(&cookies, "# Netscape HTTP Cookie File\n").unwrap();
+
+        let mut privacy = PrivacySettings {
+            cookies_path: Some(cookies.clone()),
+            ..Default::default()
+        };
+
+        // Setting off: file is kept
+        assert!(!clear_cookies_on_exit(&privacy, &dir).unwrap());
+        assert!(cookies.exists());
+
+        // A file the user picked outside the app's data dir is never deleted
+        privacy.clear_cookies_on_exit = true;
+        let elsewhere = std::env::temp_dir().join(format!("downlink-appdata-{}", Uuid::new_v4()));
+        assert!(!clear_cookies_on_exit(&privacy, &elsewhere).unwrap());
+        assert!(cookies.exists());
+
+        assert!(clear_cookies_on_exit(&privacy, &dir).unwrap());
+        assert!(!cookies.exists());
+
+        // Already gone still clears the setting
+        assert!(clear_cookies_on_exit(&privacy, &dir).unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_playlist_preview_returns_entries() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("downlink-preview-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let yt_dlp = dir.join("yt-dlp");
+        std::fs::write(
+            &yt_dlp,
+            r#"#!/bin/sh
+echo '{"webpage_url": "https://example.com/watch?v=a", "title": "First", "duration": 61}'
+echo '{"url": "https://example.com/watch?v=b", "title": "Second", "uploader": "Someone"}'
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&yt_dlp, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let runner = ytdlp::YtDlpRunner::new(ytdlp::YtDlpConfig::new(yt_dlp));
+        let preview =
+            enumerate_playlist_preview(&runner, "https://example.com/playlist?list=x", None).await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let preview = preview.unwrap();
+        assert_eq!(preview.count, 2);
+        assert_eq!(preview.entries[0].url, "https://example.com/watch?v=a");
+        assert_eq!(preview.entries[0].duration_seconds, Some(61));
+        assert_eq!(preview.entries[1].uploader.as_deref(), Some("Someone"));
+    }
 }
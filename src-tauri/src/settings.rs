@@ -3,12 +3,19 @@
 //! Handles persistence and retrieval of user preferences using SQLite.
 //! Settings are stored as JSON values keyed by setting name.
 
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+use crate::network::{
+    validate_country_code, validate_proxy_url, validate_source_address, validate_xff,
+};
+use crate::ytdlp::{parse_extra_args, redact_args, redact_url_credentials};
+
 /// User settings structure with all configurable options.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSettings {
@@ -55,6 +62,410 @@ impl Default for UserSettings {
     }
 }
 
+/// Range `general.concurrency` is clamped to.
+pub const CONCURRENCY_RANGE: std::ops::RangeInclusive<u32> = 1..=10;
+
+/// Range `network.concurrent_fragments` is clamped to.
+pub const CONCURRENT_FRAGMENTS_RANGE: std::ops::RangeInclusive<u32> = 1..=16;
+
+/// Range `formats.trim_filenames` must be in when it isn't 0 (off). Shorter names
+/// leave too little of the title to tell files apart.
+pub const TRIM_FILENAMES_RANGE: std::ops::RangeInclusive<u32> = 20..=255;
+
+/// Range the polite-mode sleep settings must be in, in seconds.
+pub const SLEEP_SECS_RANGE: std::ops::RangeInclusive<u32> = 0..=600;
+
+/// A settings value that can't be saved. `field` is "section.field", e.g. "network.proxy_url".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingsFieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SettingsFieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl UserSettings {
+    /// Clamp numeric values that have a safe range instead of rejecting them, and
+    /// expand a download folder saved as `~/…`.
+    pub fn sanitize(&mut self) {
+        self.general.download_folder = expand_home(&self.general.download_folder);
+        self.general.concurrency = self
+            .general
+            .concurrency
+            .clamp(*CONCURRENCY_RANGE.start(), *CONCURRENCY_RANGE.end());
+        self.network.concurrent_fragments = self.network.concurrent_fragments.clamp(
+            *CONCURRENT_FRAGMENTS_RANGE.start(),
+            *CONCURRENT_FRAGMENTS_RANGE.end(),
+        );
+    }
+
+    /// Copy that is safe to share: proxy credentials, the cookies file and browser
+    /// profile, secret extra arguments and session-like extractor args are masked.
+    pub fn redacted(&self) -> UserSettings {
+        let mut settings = self.clone();
+        settings.network.proxy_url = redact_url_credentials(&settings.network.proxy_url);
+        if settings.privacy.cookies_path.is_some() {
+            settings.privacy.cookies_path = Some(PathBuf::from("****"));
+        }
+        if let Some(browser) = settings.privacy.cookies_from_browser.as_mut() {
+            // Keep the browser name; the profile path can identify the user
+            if let Some((name, _)) = browser.split_once([':', '+']) {
+                *browser = format!("{name}:****");
+            }
+        }
+        settings.general.extra_ytdlp_args = match parse_extra_args(&self.general.extra_ytdlp_args) {
+            Ok(args) => redact_args(&args).join(" "),
+            Err(_) => "****".to_string(),
+        };
+        for value in settings.general.extractor_args.values_mut() {
+            *value = value
+                .split(';')
+                .map(|pair| match pair.split_once('=') {
+                    Some((key, _)) if is_secret_extractor_arg(key) => format!("{key}=****"),
+                    _ => pair.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(";");
+        }
+        settings
+    }
+
+    /// Check every field that would break downloads if stored. Empty means valid.
+    ///
+    /// Values `sanitize` fixes up are reported too, so call it first when clamping is wanted.
+    pub fn validate(&self) -> Vec<SettingsFieldError> {
+        let mut errors = Vec::new();
+        let mut check = |field: &str, result: std::result::Result<(), String>| {
+            if let Err(message) = result {
+                errors.push(SettingsFieldError {
+                    field: field.to_string(),
+                    message,
+                });
+            }
+        };
+
+        // A folder that's missing now (an unplugged drive) is only a warning
+        check(
+            "general.download_folder",
+            if self.general.download_folder.as_os_str().is_empty() {
+                Err("Choose a download folder".to_string())
+            } else {
+                Ok(())
+            },
+        );
+        check(
+            "general.concurrency",
+            in_range(
+                self.general.concurrency,
+                &CONCURRENCY_RANGE,
+                "Concurrent downloads",
+            ),
+        );
+
+        check(
+            "general.download_archive_scope",
+            one_of(
+                &self.general.download_archive_scope,
+                &["global", "per_folder"],
+                "Download archive scope",
+            ),
+        );
+
+        check(
+            "general.extra_ytdlp_args",
+            parse_extra_args(&self.general.extra_ytdlp_args).map(|_| ()),
+        );
+        check(
+            "general.log_level",
+            parse_log_level(&self.general.log_level).map(|_| ()),
+        );
+        check(
+            "general.extractor_args",
+            self.general
+                .extractor_args
+                .iter()
+                .try_for_each(|(extractor, args)| validate_extractor_args(extractor, args)),
+        );
+
+        check(
+            "formats.trim_filenames",
+            match self.formats.trim_filenames {
+                0 => Ok(()),
+                n => in_range(n, &TRIM_FILENAMES_RANGE, "Filename length limit"),
+            },
+        );
+        check(
+            "formats.organize_by",
+            one_of(
+                &self.formats.organize_by,
+                &["none", "uploader", "playlist", "date"],
+                "Folder organization",
+            ),
+        );
+        check(
+            "formats.remux_to",
+            validate_remux_container(&self.formats.remux_to).map(|_| ()),
+        );
+        check(
+            "sponsorblock.mode",
+            one_of(
+                &self.sponsorblock.mode,
+                &["remove", "mark"],
+                "SponsorBlock mode",
+            ),
+        );
+        check(
+            "updates.check_interval_hours",
+            if self.updates.check_interval_hours == 0 {
+                Err("Update check interval must be at least 1 hour".to_string())
+            } else {
+                Ok(())
+            },
+        );
+        check(
+            "privacy.cookie_mode",
+            one_of(
+                &self.privacy.cookie_mode,
+                &["on_demand", "always", "never"],
+                "Cookie mode",
+            ),
+        );
+        if let Some(value) = self.privacy.active_cookies_from_browser() {
+            check(
+                "privacy.cookies_from_browser",
+                crate::browser_cookies::parse_cookies_from_browser(value).map(|_| ()),
+            );
+        }
+
+        check(
+            "subtitles.default_language",
+            validate_subtitle_langs(&self.subtitles.default_language).map(|_| ()),
+        );
+
+        let network = &self.network;
+        // The proxy URL is kept while the proxy is switched off, so only check it in use
+        if network.use_proxy {
+            check(
+                "network.proxy_url",
+                validate_proxy_url(&network.proxy_url).map(|_| ()),
+            );
+        }
+        check(
+            "network.source_address",
+            validate_source_address(&network.source_address).map(|_| ()),
+        );
+        if network.polite_mode {
+            check(
+                "network.sleep_interval_secs",
+                in_range(
+                    network.sleep_interval_secs,
+                    &SLEEP_SECS_RANGE,
+                    "Minimum wait between downloads",
+                ),
+            );
+            check(
+                "network.max_sleep_interval_secs",
+                if network.max_sleep_interval_secs < network.sleep_interval_secs {
+                    Err("Maximum wait can't be shorter than the minimum wait".to_string())
+                } else {
+                    in_range(
+                        network.max_sleep_interval_secs,
+                        &SLEEP_SECS_RANGE,
+                        "Maximum wait between downloads",
+                    )
+                },
+            );
+            check(
+                "network.sleep_requests_secs",
+                in_range(
+                    network.sleep_requests_secs,
+                    &SLEEP_SECS_RANGE,
+                    "Wait between requests",
+                ),
+            );
+        }
+        check(
+            "network.geo_bypass_country",
+            validate_country_code(&network.geo_bypass_country).map(|_| ()),
+        );
+        check("network.xff", validate_xff(&network.xff).map(|_| ()));
+        check(
+            "network.socket_timeout",
+            if network.socket_timeout == 0 {
+                Err("Socket timeout must be at least 1 second".to_string())
+            } else {
+                Ok(())
+            },
+        );
+        check(
+            "network.concurrent_fragments",
+            in_range(
+                network.concurrent_fragments,
+                &CONCURRENT_FRAGMENTS_RANGE,
+                "Concurrent fragments",
+            ),
+        );
+        check(
+            "network.external_downloader",
+            one_of(
+                &network.external_downloader,
+                &["none", "aria2c"],
+                "External downloader",
+            ),
+        );
+        check(
+            "network.ip_version",
+            one_of(&network.ip_version, &["any", "ipv4", "ipv6"], "IP version"),
+        );
+
+        errors
+    }
+
+    /// Valid choices the user should know the cost of, keyed like [`validate`](Self::validate).
+    pub fn warnings(&self) -> Vec<SettingsFieldError> {
+        let mut warnings = Vec::new();
+        let folder = &self.general.download_folder;
+        if !folder.as_os_str().is_empty() && !folder.is_dir() {
+            warnings.push(SettingsFieldError {
+                field: "general.download_folder".to_string(),
+                message: format!(
+                    "The folder {} doesn't exist right now; downloads fail until it's available",
+                    folder.display()
+                ),
+            });
+        }
+        // Downloads already fall back to the default template, so a saved unsafe one
+        // doesn't block saving anything else
+        if let Err(message) = validate_filename_template(&self.formats.filename_template) {
+            warnings.push(SettingsFieldError {
+                field: "formats.filename_template".to_string(),
+                message: format!("{message}; the default template is used instead"),
+            });
+        }
+        if let Err(message) = validate_sponsorblock_categories(&self.sponsorblock.categories) {
+            warnings.push(SettingsFieldError {
+                field: "sponsorblock.categories".to_string(),
+                message: format!("{message}; they're skipped"),
+            });
+        }
+        if self.formats.write_comments {
+            warnings.push(SettingsFieldError {
+                field: "formats.write_comments".to_string(),
+                message: "Fetching comments can add minutes per video on popular uploads"
+                    .to_string(),
+            });
+        }
+        if let Ok(Some(container)) = validate_remux_container(&self.formats.remux_to) {
+            if let Some(message) = container_codec_conflict(
+                &container,
+                Some(&self.formats.preferred_video_codec),
+                Some(&self.formats.preferred_audio_codec),
+            ) {
+                warnings.push(SettingsFieldError {
+                    field: "formats.remux_to".to_string(),
+                    message,
+                });
+            }
+        }
+        warnings
+    }
+}
+
+/// Parts of extractor-arg keys that carry tokens or tie requests to a session or
+/// account, like YouTube's `po_token`, `visitor_data` and `data_sync_id`.
+const SECRET_EXTRACTOR_ARG_KEYS: &[&str] = &[
+    "token",
+    "visitor",
+    "data_sync",
+    "session",
+    "cookie",
+    "secret",
+    "password",
+    "key",
+];
+
+fn is_secret_extractor_arg(key: &str) -> bool {
+    let key = key.trim().to_ascii_lowercase();
+    SECRET_EXTRACTOR_ARG_KEYS
+        .iter()
+        .any(|part| key.contains(part))
+}
+
+fn in_range(
+    value: u32,
+    range: &std::ops::RangeInclusive<u32>,
+    label: &str,
+) -> std::result::Result<(), String> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{label} must be between {} and {}",
+            range.start(),
+            range.end()
+        ))
+    }
+}
+
+fn one_of(value: &str, allowed: &[&str], label: &str) -> std::result::Result<(), String> {
+    if allowed.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{label} \"{value}\" isn't supported. Use one of: {}",
+            allowed.join(", ")
+        ))
+    }
+}
+
+/// Settings with every field filled in, plus which fields still hold their default.
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveSettings {
+    pub settings: UserSettings,
+    /// Keyed by "section.field" (e.g. "network.retries"); `true` when the value
+    /// equals the built-in default.
+    pub at_default: BTreeMap<String, bool>,
+    /// See [`UserSettings::warnings`].
+    pub warnings: Vec<SettingsFieldError>,
+}
+
+impl EffectiveSettings {
+    pub fn new(settings: UserSettings) -> Self {
+        let at_default = fields_at_default(&settings);
+        let warnings = settings.warnings();
+        Self {
+            settings,
+            at_default,
+            warnings,
+        }
+    }
+}
+
+/// Compare every field of `settings` against `UserSettings::default()`.
+fn fields_at_default(settings: &UserSettings) -> BTreeMap<String, bool> {
+    let current = serde_json::to_value(settings).unwrap_or_default();
+    let defaults = serde_json::to_value(UserSettings::default()).unwrap_or_default();
+
+    let mut map = BTreeMap::new();
+    let Some(sections) = current.as_object() else {
+        return map;
+    };
+    for (section, fields) in sections {
+        let Some(fields) = fields.as_object() else {
+            continue;
+        };
+        for (field, value) in fields {
+            let default = defaults.get(section).and_then(|s| s.get(field));
+            map.insert(format!("{section}.{field}"), default == Some(value));
+        }
+    }
+    map
+}
+
 /// General application settings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneralSettings {
@@ -74,6 +485,11 @@ pub struct GeneralSettings {
     #[serde(default = "default_true")]
     pub auto_start: bool,
 
+    /// On launch, restart downloads that were running when the app last exited or
+    /// crashed, up to `concurrency` of them. yt-dlp continues their `.part` files.
+    #[serde(default)]
+    pub auto_resume_on_launch: bool,
+
     /// Show notification when download completes.
     #[serde(default = "default_true")]
     pub notify_on_complete: bool,
@@ -93,6 +509,115 @@ pub struct GeneralSettings {
     /// Show advanced options by default.
     #[serde(default)]
     pub show_advanced_by_default: bool,
+
+    /// Preset to use instead of `default_preset` for particular sites, keyed by
+    /// host (see [`GeneralSettings::preset_for_url`]).
+    #[serde(default)]
+    pub site_preset_overrides: BTreeMap<String, String>,
+
+    /// Record finished downloads in a yt-dlp archive and skip them on later runs.
+    #[serde(default)]
+    pub use_download_archive: bool,
+
+    /// Archive scope: "global" (one archive for everything) or "per_folder" (one per
+    /// download folder).
+    #[serde(default = "default_archive_scope")]
+    pub download_archive_scope: String,
+
+    /// Download into the app's tmp dir and move finished files into the download
+    /// folder, so partial files never show up there.
+    #[serde(default)]
+    pub download_to_temp: bool,
+
+    /// Extra arguments passed verbatim to every yt-dlp call, metadata and downloads
+    /// alike (see [`crate::ytdlp::parse_extra_args`] for quoting).
+    #[serde(default)]
+    pub extra_ytdlp_args: String,
+
+    /// Per-extractor `--extractor-args`, keyed by extractor name (`youtube`), each in
+    /// yt-dlp's `key=value;key2=value2` form, e.g. `player_client=web,default`. Lets
+    /// users work around site changes without waiting for an app release.
+    #[serde(default)]
+    pub extractor_args: BTreeMap<String, String>,
+
+    /// Log verbosity, one of [`LOG_LEVELS`]. Applied at startup and whenever it changes.
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+}
+
+impl GeneralSettings {
+    /// Preset a URL should use when the user didn't pick one.
+    ///
+    /// A key matches its host and every subdomain of it (`example.com` covers
+    /// `music.example.com`); when several match, the most specific key wins.
+    /// Falls back to `default_preset`.
+    pub fn preset_for_url(&self, url: &str) -> &str {
+        let Some(host) = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_ascii_lowercase))
+        else {
+            return &self.default_preset;
+        };
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+
+        self.site_preset_overrides
+            .iter()
+            .filter_map(|(pattern, preset_id)| {
+                let pattern = normalize_site_pattern(pattern);
+                let matches = host == pattern
+                    || host
+                        .strip_suffix(pattern.as_str())
+                        .is_some_and(|rest| rest.ends_with('.'));
+                matches.then_some((pattern.len(), preset_id.as_str()))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map_or(&self.default_preset, |(_, preset_id)| preset_id)
+    }
+}
+
+/// Canonical form of a `site_preset_overrides` key: lowercase host without a
+/// leading `*.` or `www.`.
+pub fn normalize_site_pattern(pattern: &str) -> String {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let pattern = pattern.strip_prefix("*.").unwrap_or(&pattern);
+    pattern.strip_prefix("www.").unwrap_or(pattern).to_string()
+}
+
+/// Accepted `log_level` values, least to most verbose.
+pub const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+/// The filter for a `log_level` value.
+pub fn parse_log_level(level: &str) -> std::result::Result<log::LevelFilter, String> {
+    one_of(level, &LOG_LEVELS, "Log level")?;
+    level
+        .parse()
+        .map_err(|_| format!("Log level \"{level}\" isn't supported"))
+}
+
+/// Check one `extractor_args` entry: a lowercase extractor name and one or more
+/// `key=value` pairs separated by `;`. Values may hold anything but `;`.
+pub fn validate_extractor_args(extractor: &str, args: &str) -> std::result::Result<(), String> {
+    let name_ok = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    };
+    if !name_ok(extractor) {
+        return Err(format!(
+            "Invalid extractor name \"{extractor}\" (use lowercase names like \"youtube\")"
+        ));
+    }
+    for pair in args.split(';') {
+        let valid = pair.split_once('=').is_some_and(|(key, _)| {
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        if !valid {
+            return Err(format!(
+                "Extractor arguments for {extractor} must be key=value pairs separated by ; (got \"{pair}\")"
+            ));
+        }
+    }
+    Ok(())
 }
 
 impl Default for GeneralSettings {
@@ -102,11 +627,19 @@ impl Default for GeneralSettings {
             default_preset: default_preset_id(),
             concurrency: default_concurrency(),
             auto_start: true,
+            auto_resume_on_launch: false,
             notify_on_complete: true,
             minimize_to_tray: false,
             start_minimized: false,
             remember_window_state: true,
             show_advanced_by_default: false,
+            site_preset_overrides: BTreeMap::new(),
+            use_download_archive: false,
+            download_archive_scope: default_archive_scope(),
+            download_to_temp: false,
+            extra_ytdlp_args: String::new(),
+            extractor_args: BTreeMap::new(),
+            log_level: default_log_level(),
         }
     }
 }
@@ -142,9 +675,48 @@ pub struct FormatSettings {
     #[serde(default)]
     pub write_info_json: bool,
 
+    /// Save the video's comments into the `.info.json` (so it implies `write_info_json`).
+    /// yt-dlp pages through every comment first, which is slow for popular videos.
+    #[serde(default)]
+    pub write_comments: bool,
+
+    /// Save the video description to a separate `.description` file.
+    #[serde(default)]
+    pub write_description: bool,
+
+    /// Save the thumbnail to a separate image file (kept even when it's also embedded).
+    #[serde(default)]
+    pub write_thumbnail: bool,
+
+    /// Also split the video into one file per chapter (needs ffmpeg).
+    #[serde(default)]
+    pub split_chapters: bool,
+
+    /// Normalize loudness with ffmpeg's `loudnorm` filter. Audio-only presets only.
+    #[serde(default)]
+    pub audio_normalize: bool,
+
+    /// Container to remux video into, overriding the preset's merge format (empty = keep).
+    #[serde(default)]
+    pub remux_to: String,
+
     /// Output filename template.
     #[serde(default = "default_filename_template")]
     pub filename_template: String,
+
+    /// Keep filenames to ASCII without spaces or `&` (`--restrict-filenames`), for
+    /// FAT drives and network shares that reject other characters.
+    #[serde(default)]
+    pub restrict_filenames: bool,
+
+    /// Shorten filenames to this many characters, extension excluded (0 = off).
+    #[serde(default)]
+    pub trim_filenames: u32,
+
+    /// Sort downloads into subfolders of the download folder: "none", "uploader",
+    /// "playlist" or "date" (upload month).
+    #[serde(default = "default_organize_by")]
+    pub organize_by: String,
 }
 
 impl Default for FormatSettings {
@@ -157,7 +729,16 @@ impl Default for FormatSettings {
             embed_metadata: true,
             embed_thumbnail: true,
             write_info_json: false,
+            write_comments: false,
+            write_description: false,
+            write_thumbnail: false,
+            split_chapters: false,
+            audio_normalize: false,
+            remux_to: String::new(),
             filename_template: default_filename_template(),
+            restrict_filenames: false,
+            trim_filenames: 0,
+            organize_by: default_organize_by(),
         }
     }
 }
@@ -271,6 +852,11 @@ pub struct PrivacySettings {
     #[serde(default)]
     pub cookies_path: Option<PathBuf>,
 
+    /// Read cookies straight from a browser instead, as a `--cookies-from-browser`
+    /// value such as "chrome:Profile 1". Takes precedence over `cookies_path`.
+    #[serde(default)]
+    pub cookies_from_browser: Option<String>,
+
     /// Clear cookies on app exit.
     #[serde(default)]
     pub clear_cookies_on_exit: bool,
@@ -284,11 +870,275 @@ pub struct PrivacySettings {
     pub max_history_entries: u32,
 }
 
+impl PrivacySettings {
+    /// Cookies file to pass to yt-dlp, unless cookies are turned off or read from
+    /// a browser.
+    pub fn active_cookies_path(&self) -> Option<&Path> {
+        if self.cookie_mode == "never" || self.active_cookies_from_browser().is_some() {
+            return None;
+        }
+        self.cookies_path.as_deref()
+    }
+
+    /// `--cookies-from-browser` value to pass to yt-dlp, unless cookies are turned off.
+    pub fn active_cookies_from_browser(&self) -> Option<&str> {
+        if self.cookie_mode == "never" {
+            return None;
+        }
+        self.cookies_from_browser
+            .as_deref()
+            .filter(|v| !v.trim().is_empty())
+    }
+}
+
+/// Containers `remux_to` accepts. All can hold the usual codecs without re-encoding.
+pub const REMUX_CONTAINERS: &[&str] = &["mp4", "mkv", "webm", "mov"];
+
+/// Parse the `remux_to` setting. Empty means "keep the preset's container".
+pub fn validate_remux_container(container: &str) -> std::result::Result<Option<String>, String> {
+    let container = container.trim().trim_start_matches('.').to_lowercase();
+    if container.is_empty() {
+        return Ok(None);
+    }
+    if REMUX_CONTAINERS.contains(&container.as_str()) {
+        Ok(Some(container))
+    } else {
+        Err(format!(
+            "Can't remux to \"{container}\". Choose one of: {}",
+            REMUX_CONTAINERS.join(", ")
+        ))
+    }
+}
+
+/// A SponsorBlock segment category yt-dlp can mark or remove.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SponsorBlockCategory {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+}
+
+/// Categories `sponsorblock.categories` accepts, in display order.
+pub const SPONSORBLOCK_CATEGORIES: &[SponsorBlockCategory] = &[
+    SponsorBlockCategory {
+        id: "sponsor",
+        label: "Sponsor",
+        description: "Paid promotion",
+    },
+    SponsorBlockCategory {
+        id: "intro",
+        label: "Intro",
+        description: "Intro animation/sequence",
+    },
+    SponsorBlockCategory {
+        id: "outro",
+        label: "Outro",
+        description: "Outro/end cards",
+    },
+    SponsorBlockCategory {
+        id: "selfpromo",
+        label: "Self-promo",
+        description: "Self-promotion",
+    },
+    SponsorBlockCategory {
+        id: "interaction",
+        label: "Interaction",
+        description: "Subscribe reminders",
+    },
+    SponsorBlockCategory {
+        id: "music_offtopic",
+        label: "Non-music",
+        description: "Non-music in music videos",
+    },
+    SponsorBlockCategory {
+        id: "preview",
+        label: "Preview",
+        description: "Preview/recap",
+    },
+    SponsorBlockCategory {
+        id: "filler",
+        label: "Filler",
+        description: "Filler content",
+    },
+];
+
+/// Check every category is one of [`SPONSORBLOCK_CATEGORIES`].
+pub fn validate_sponsorblock_categories(categories: &[String]) -> std::result::Result<(), String> {
+    let unknown: Vec<&str> = categories
+        .iter()
+        .map(String::as_str)
+        .filter(|c| !SPONSORBLOCK_CATEGORIES.iter().any(|known| known.id == *c))
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unknown SponsorBlock categories: {}",
+            unknown.join(", ")
+        ))
+    }
+}
+
+/// Video and audio codecs a container holds without re-encoding, or `None` when it
+/// takes anything (mkv).
+fn container_codecs(container: &str) -> Option<(&'static [&'static str], &'static [&'static str])> {
+    match container {
+        "mp4" => Some((
+            &["h264", "hevc", "vp9", "av1"],
+            &["aac", "mp3", "opus", "flac", "alac", "ac3", "eac3"],
+        )),
+        "mov" => Some((&["h264", "hevc"], &["aac", "mp3", "alac", "ac3"])),
+        "webm" => Some((&["vp8", "vp9", "av1"], &["opus", "vorbis"])),
+        _ => None,
+    }
+}
+
+/// Canonical name for a codec as yt-dlp or a user might spell it ("avc1.64001F",
+/// "H.264", "mp4a.40.2", ...).
+pub fn normalize_codec(codec: &str) -> String {
+    let codec = codec.trim().to_lowercase().replace("h.26", "h26");
+    match codec.split('.').next().unwrap_or_default() {
+        "avc" | "avc1" | "avc3" | "h264" | "x264" => "h264",
+        "hevc" | "hev1" | "hvc1" | "h265" | "x265" => "hevc",
+        "vp09" | "vp9" => "vp9",
+        "vp08" | "vp8" => "vp8",
+        "av01" | "av1" => "av1",
+        "mp4a" | "aac" | "m4a" => "aac",
+        "mp3" => "mp3",
+        "ec-3" | "eac3" => "eac3",
+        "ac-3" | "ac3" => "ac3",
+        other => other,
+    }
+    .to_string()
+}
+
+/// A user-facing warning when `container` likely can't hold `video`/`audio`
+/// without re-encoding, which makes yt-dlp's merge or remux step fail. Unknown
+/// codecs and containers are assumed to be fine.
+pub fn container_codec_conflict(
+    container: &str,
+    video: Option<&str>,
+    audio: Option<&str>,
+) -> Option<String> {
+    let container = container.trim().trim_start_matches('.').to_lowercase();
+    let (video_ok, audio_ok) = container_codecs(&container)?;
+    let known = [
+        "h264", "hevc", "vp8", "vp9", "av1", "aac", "mp3", "opus", "vorbis", "flac", "alac", "ac3",
+        "eac3",
+    ];
+
+    let clash = [(video, video_ok, "video"), (audio, audio_ok, "audio")]
+        .into_iter()
+        .find_map(|(codec, allowed, kind)| {
+            let codec = normalize_codec(codec.filter(|c| !c.trim().is_empty())?);
+            (known.contains(&codec.as_str()) && !allowed.contains(&codec.as_str()))
+                .then_some((codec, kind))
+        })?;
+    Some(format!(
+        "{} {} can't be stored in {} without re-encoding, so the download will likely fail. Use MKV, or a preset that picks compatible formats.",
+        clash.0.to_uppercase(),
+        clash.1,
+        container.to_uppercase()
+    ))
+}
+
+/// Check that a filename template stays inside the download folder: it must be
+/// relative and can't contain `..` segments. Subfolders (`a/b.%(ext)s`) are fine.
+pub fn validate_filename_template(template: &str) -> std::result::Result<(), String> {
+    let template = template.trim();
+    let bytes = template.as_bytes();
+    let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if template.starts_with(['/', '\\']) || has_drive {
+        return Err("Filename template must be relative to the download folder".to_string());
+    }
+    if template
+        .split(['/', '\\'])
+        .any(|segment| segment.trim() == "..")
+    {
+        return Err("Filename template can't point outside the download folder".to_string());
+    }
+    Ok(())
+}
+
+/// Parse a comma-separated list of subtitle language codes for `--sub-langs`
+/// (e.g. "en,pt-BR", or "all"). Empty means "none chosen". Duplicates are dropped.
+pub fn validate_subtitle_langs(langs: &str) -> std::result::Result<Option<String>, String> {
+    let mut codes: Vec<&str> = Vec::new();
+    for code in langs.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        let mut parts = code.split('-');
+        let primary = parts.next().unwrap_or_default();
+        let valid = code == "all"
+            || ((2..=3).contains(&primary.len())
+                && primary.chars().all(|c| c.is_ascii_alphabetic())
+                && parts.all(|p| {
+                    (1..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric())
+                }));
+        if !valid {
+            return Err(format!(
+                "\"{code}\" is not a subtitle language code (e.g. en, ja, pt-BR)"
+            ));
+        }
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+    Ok((!codes.is_empty()).then(|| codes.join(",")))
+}
+
+/// Headers yt-dlp accepts on the first line of a cookies file.
+const COOKIE_FILE_HEADERS: &[&str] = &["# Netscape HTTP Cookie File", "# HTTP Cookie File"];
+
+/// Check that `path` exists and looks like a Netscape cookie jar, which is the
+/// only format `--cookies` reads. Errors are user-facing.
+pub fn validate_cookies_file(path: &Path) -> std::result::Result<(), String> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!(
+                "The cookies file {} no longer exists. Import cookies again.",
+                path.display()
+            ));
+        }
+        Err(e) => {
+            return Err(format!(
+                "Couldn't read the cookies file {}: {e}",
+                path.display()
+            ))
+        }
+    };
+
+    // The header is on the first line; no need to read a large jar in full
+    let mut head = [0u8; 256];
+    let len = file
+        .read(&mut head)
+        .map_err(|e| format!("Couldn't read the cookies file {}: {e}", path.display()))?;
+    let head = String::from_utf8_lossy(&head[..len]);
+    let first_line = head
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .next()
+        .unwrap_or("")
+        .trim();
+
+    if COOKIE_FILE_HEADERS
+        .iter()
+        .any(|header| first_line.starts_with(header))
+    {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} isn't a Netscape-format cookies file. Export cookies in \"Netscape\" / cookies.txt format, or import them from your browser.",
+            path.display()
+        ))
+    }
+}
+
 impl Default for PrivacySettings {
     fn default() -> Self {
         Self {
             cookie_mode: default_cookie_mode(),
             cookies_path: None,
+            cookies_from_browser: None,
             clear_cookies_on_exit: false,
             keep_history: true,
             max_history_entries: default_max_history(),
@@ -311,6 +1161,12 @@ pub struct NetworkSettings {
     #[serde(default)]
     pub rate_limit_bps: u64,
 
+    /// Split `rate_limit_bps` between the downloads running when each one starts,
+    /// instead of giving every download the full limit. Shares are fixed at start, so
+    /// this isn't a hard cap on the combined rate (see `rate_limit_share`).
+    #[serde(default)]
+    pub global_rate_limit: bool,
+
     /// Number of retries on failure.
     #[serde(default = "default_retries")]
     pub retries: u32,
@@ -322,6 +1178,62 @@ pub struct NetworkSettings {
     /// Socket timeout in seconds.
     #[serde(default = "default_socket_timeout")]
     pub socket_timeout: u32,
+
+    /// Abort downloads larger than this many bytes (0 = no limit).
+    #[serde(default)]
+    pub max_filesize_bytes: u64,
+
+    /// External downloader: "none" (yt-dlp's own) or "aria2c".
+    #[serde(default = "default_external_downloader")]
+    pub external_downloader: String,
+
+    /// IP version to connect with: "any", "ipv4" or "ipv6".
+    #[serde(default = "default_ip_version")]
+    pub ip_version: String,
+
+    /// Local IP address to bind to (empty = system default).
+    #[serde(default)]
+    pub source_address: String,
+
+    /// Keep downloaded fragments after they're joined (debugging aid).
+    #[serde(default)]
+    pub keep_fragments: bool,
+
+    /// Write straight to the final file name instead of a `.part` file. Breaks
+    /// resuming a stopped download, which then restarts from scratch.
+    #[serde(default)]
+    pub no_part: bool,
+
+    /// Pass `--geo-bypass` so yt-dlp fakes an X-Forwarded-For header.
+    #[serde(default)]
+    pub geo_bypass: bool,
+
+    /// Two-letter country code to appear to be in (empty = none).
+    #[serde(default)]
+    pub geo_bypass_country: String,
+
+    /// yt-dlp `--xff` value: "default", "never", a country code or a CIDR block
+    /// (empty = yt-dlp's default).
+    #[serde(default)]
+    pub xff: String,
+
+    /// Pause between downloads and requests so sites are less likely to ban the
+    /// user, at the cost of speed. Mostly matters for playlists.
+    #[serde(default)]
+    pub polite_mode: bool,
+
+    /// Seconds to wait before each download in polite mode. With a larger
+    /// `max_sleep_interval_secs`, the wait is random between the two.
+    #[serde(default = "default_sleep_interval")]
+    pub sleep_interval_secs: u32,
+
+    /// Upper bound of the random wait before each download in polite mode.
+    #[serde(default = "default_max_sleep_interval")]
+    pub max_sleep_interval_secs: u32,
+
+    /// Seconds to wait between the requests made while extracting (0 = none).
+    #[serde(default = "default_sleep_requests")]
+    pub sleep_requests_secs: u32,
 }
 
 impl Default for NetworkSettings {
@@ -330,16 +1242,57 @@ impl Default for NetworkSettings {
             use_proxy: false,
             proxy_url: String::new(),
             rate_limit_bps: 0,
+            global_rate_limit: false,
             retries: default_retries(),
             concurrent_fragments: default_concurrent_fragments(),
             socket_timeout: default_socket_timeout(),
+            max_filesize_bytes: 0,
+            external_downloader: default_external_downloader(),
+            ip_version: default_ip_version(),
+            source_address: String::new(),
+            keep_fragments: false,
+            no_part: false,
+            geo_bypass: false,
+            geo_bypass_country: String::new(),
+            xff: String::new(),
+            polite_mode: false,
+            sleep_interval_secs: default_sleep_interval(),
+            max_sleep_interval_secs: default_max_sleep_interval(),
+            sleep_requests_secs: default_sleep_requests(),
         }
     }
 }
 
 // Default value functions
 fn default_download_folder() -> PathBuf {
-    dirs::download_dir().unwrap_or_else(|| PathBuf::from("~/Downloads"))
+    dirs::download_dir()
+        .or_else(|| dirs::home_dir().map(|home| home.join("Downloads")))
+        .unwrap_or_else(|| PathBuf::from("Downloads"))
+}
+
+/// `path` with a leading `~` replaced by the home directory. Older versions saved the
+/// unexpanded `~/Downloads` fallback, which nothing else expands.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn default_archive_scope() -> String {
+    "global".to_string()
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_external_downloader() -> String {
+    "none".to_string()
+}
+
+fn default_ip_version() -> String {
+    "any".to_string()
 }
 
 fn default_preset_id() -> String {
@@ -358,6 +1311,10 @@ fn default_filename_template() -> String {
     "%(title)s [%(id)s].%(ext)s".to_string()
 }
 
+fn default_organize_by() -> String {
+    "none".to_string()
+}
+
 fn default_sponsorblock_mode() -> String {
     "remove".to_string()
 }
@@ -398,6 +1355,18 @@ fn default_socket_timeout() -> u32 {
     30
 }
 
+fn default_sleep_interval() -> u32 {
+    5
+}
+
+fn default_max_sleep_interval() -> u32 {
+    15
+}
+
+fn default_sleep_requests() -> u32 {
+    1
+}
+
 /// Settings keys used in the database.
 pub mod keys {
     pub const USER_SETTINGS: &str = "user_settings";
@@ -587,6 +1556,470 @@ impl UserSettings {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn temp_cookies(contents: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("downlink-cookies-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_validate_cookies_file_accepts_netscape_jars() {
+        for contents in [
+            "# Netscape HTTP Cookie File\n.example.com\tTRUE\t/\tTRUE\t0\tsid\tabc\n".as_bytes(),
+            b"# HTTP Cookie File\n",
+            "\u{feff}# Netscape HTTP Cookie File\r\n".as_bytes(),
+        ] {
+            let path = temp_cookies(contents);
+            let result = validate_cookies_file(&path);
+            let _ = std::fs::remove_file(&path);
+            assert_eq!(result, Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_validate_cookies_file_rejects_invalid() {
+        let missing = std::env::temp_dir().join("downlink-no-such-cookies.txt");
+        assert!(validate_cookies_file(&missing)
+            .unwrap_err()
+            .contains("no longer exists"));
+
+        for contents in [
+            b"".as_slice(),
+            b"[{\"name\": \"sid\", \"value\": \"abc\"}]",
+            b".example.com\tTRUE\t/\tTRUE\t0\tsid\tabc\n",
+        ] {
+            let path = temp_cookies(contents);
+            let result = validate_cookies_file(&path);
+            let _ = std::fs::remove_file(&path);
+            assert!(result.unwrap_err().contains("Netscape-format"));
+        }
+    }
+
+    /// Defaults with a download folder that exists on this machine.
+    fn valid_settings() -> UserSettings {
+        let mut settings = UserSettings::default();
+        settings.general.download_folder = std::env::temp_dir();
+        settings
+    }
+
+    fn invalid_fields(settings: &UserSettings) -> Vec<String> {
+        settings.validate().into_iter().map(|e| e.field).collect()
+    }
+
+    #[test]
+    fn test_validate_accepts_defaults() {
+        assert_eq!(valid_settings().validate(), Vec::new());
+    }
+
+    #[test]
+    fn test_write_comments_warns_but_is_valid() {
+        let mut settings = valid_settings();
+        assert!(settings.warnings().is_empty());
+
+        settings.formats.write_comments = true;
+        assert!(settings.validate().is_empty());
+        let warnings = settings.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "formats.write_comments");
+    }
+
+    #[test]
+    fn test_container_codec_conflict() {
+        assert!(container_codec_conflict("webm", Some("avc1.64001F"), Some("opus")).is_some());
+        assert!(container_codec_conflict("webm", Some("vp9"), Some("mp4a.40.2")).is_some());
+        assert!(container_codec_conflict("mov", Some("av01.0.08M.08"), None).is_some());
+        assert!(container_codec_conflict("mp4", None, Some("vorbis")).is_some());
+        assert!(container_codec_conflict("mp4", Some("H.264"), Some("aac")).is_none());
+        assert!(container_codec_conflict("mp4", Some("vp9"), Some("opus")).is_none());
+        assert!(container_codec_conflict("mkv", Some("vp8"), Some("vorbis")).is_none());
+        // Codecs we know nothing about aren't flagged
+        assert!(container_codec_conflict("mp4", Some("prores"), Some("")).is_none());
+    }
+
+    #[test]
+    fn test_remux_to_warns_about_preferred_codecs() {
+        let mut settings = valid_settings();
+        settings.formats.remux_to = "webm".to_string();
+        settings.formats.preferred_video_codec = "h264".to_string();
+
+        assert!(settings.validate().is_empty());
+        let warnings = settings.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "formats.remux_to");
+
+        settings.formats.preferred_video_codec = "vp9".to_string();
+        assert!(settings.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_parse_log_level() {
+        assert_eq!(parse_log_level("error"), Ok(log::LevelFilter::Error));
+        assert_eq!(parse_log_level("warn"), Ok(log::LevelFilter::Warn));
+        assert_eq!(parse_log_level("info"), Ok(log::LevelFilter::Info));
+        assert_eq!(parse_log_level("debug"), Ok(log::LevelFilter::Debug));
+        assert_eq!(parse_log_level("trace"), Ok(log::LevelFilter::Trace));
+
+        for bad in ["", "off", "Debug", "verbose", "warning"] {
+            assert!(parse_log_level(bad).is_err(), "{bad} should be rejected");
+        }
+        assert_eq!(
+            parse_log_level(&GeneralSettings::default().log_level),
+            Ok(log::LevelFilter::Info)
+        );
+    }
+
+    #[test]
+    fn test_validate_extractor_args() {
+        assert!(validate_extractor_args("youtube", "player_client=web,default").is_ok());
+        assert!(
+            validate_extractor_args("youtube", "player_client=web;po_token=web+AbC/1=").is_ok()
+        );
+        assert!(validate_extractor_args("youtubetab", "skip=webpage").is_ok());
+
+        assert!(validate_extractor_args("", "skip=webpage").is_err());
+        assert!(validate_extractor_args("YouTube", "skip=webpage").is_err());
+        assert!(validate_extractor_args("youtube:tab", "skip=webpage").is_err());
+        assert!(validate_extractor_args("youtube", "").is_err());
+        assert!(validate_extractor_args("youtube", "player_client").is_err());
+        assert!(validate_extractor_args("youtube", "=web").is_err());
+        assert!(validate_extractor_args("youtube", "player_client=web;").is_err());
+        assert!(validate_extractor_args("youtube", "player client=web").is_err());
+    }
+
+    #[test]
+    fn test_validate_catches_each_invalid_field() {
+        type Breaker = fn(&mut UserSettings);
+        let cases: Vec<(&str, Breaker)> = vec![
+            ("general.download_folder", |s| {
+                s.general.download_folder = PathBuf::new()
+            }),
+            ("general.concurrency", |s| s.general.concurrency = 0),
+            ("general.concurrency", |s| s.general.concurrency = 50),
+            ("general.extra_ytdlp_args", |s| {
+                s.general.extra_ytdlp_args = "--exec 'echo done'".to_string()
+            }),
+            ("general.log_level", |s| {
+                s.general.log_level = "verbose".to_string()
+            }),
+            ("general.extractor_args", |s| {
+                s.general
+                    .extractor_args
+                    .insert("youtube".to_string(), "player_client".to_string());
+            }),
+            ("general.download_archive_scope", |s| {
+                s.general.download_archive_scope = "per_site".to_string()
+            }),
+            ("formats.remux_to", |s| {
+                s.formats.remux_to = "avi".to_string()
+            }),
+            ("formats.organize_by", |s| {
+                s.formats.organize_by = "genre".to_string()
+            }),
+            ("formats.trim_filenames", |s| s.formats.trim_filenames = 5),
+            ("formats.trim_filenames", |s| {
+                s.formats.trim_filenames = 1000
+            }),
+            ("sponsorblock.mode", |s| {
+                s.sponsorblock.mode = "skip".to_string()
+            }),
+            ("updates.check_interval_hours", |s| {
+                s.updates.check_interval_hours = 0
+            }),
+            ("privacy.cookie_mode", |s| {
+                s.privacy.cookie_mode = "sometimes".to_string()
+            }),
+            ("network.proxy_url", |s| {
+                s.network.use_proxy = true;
+                s.network.proxy_url = "proxy.local:3128".to_string();
+            }),
+            ("network.source_address", |s| {
+                s.network.source_address = "eth0".to_string()
+            }),
+            ("subtitles.default_language", |s| {
+                s.subtitles.default_language = "english".to_string()
+            }),
+            ("network.geo_bypass_country", |s| {
+                s.network.geo_bypass_country = "USA".to_string()
+            }),
+            ("network.xff", |s| s.network.xff = "10.0.0.0/40".to_string()),
+            ("network.max_sleep_interval_secs", |s| {
+                s.network.polite_mode = true;
+                s.network.sleep_interval_secs = 30;
+                s.network.max_sleep_interval_secs = 10;
+            }),
+            ("network.sleep_requests_secs", |s| {
+                s.network.polite_mode = true;
+                s.network.sleep_requests_secs = 3600;
+            }),
+            ("network.socket_timeout", |s| s.network.socket_timeout = 0),
+            ("network.concurrent_fragments", |s| {
+                s.network.concurrent_fragments = 0
+            }),
+            ("network.external_downloader", |s| {
+                s.network.external_downloader = "wget".to_string()
+            }),
+            ("network.ip_version", |s| {
+                s.network.ip_version = "ipv5".to_string()
+            }),
+        ];
+
+        for (field, break_it) in cases {
+            let mut settings = valid_settings();
+            break_it(&mut settings);
+            assert_eq!(invalid_fields(&settings), vec![field]);
+        }
+    }
+
+    #[test]
+    fn test_missing_download_folder_warns_but_is_valid() {
+        let mut settings = valid_settings();
+        settings.general.download_folder = std::env::temp_dir().join("downlink-no-such-folder");
+
+        assert!(settings.validate().is_empty());
+        let warnings = settings.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "general.download_folder");
+    }
+
+    #[test]
+    fn test_unsafe_filename_template_warns_but_is_valid() {
+        let mut settings = valid_settings();
+        settings.formats.filename_template = "../%(title)s.%(ext)s".to_string();
+
+        assert!(settings.validate().is_empty());
+        let warnings = settings.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "formats.filename_template");
+    }
+
+    #[test]
+    fn test_unknown_sponsorblock_category_warns_but_is_valid() {
+        let mut settings = valid_settings();
+        settings.sponsorblock.categories = vec!["sponsor".to_string(), "ads".to_string()];
+
+        assert!(settings.validate().is_empty());
+        let warnings = settings.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "sponsorblock.categories");
+    }
+
+    #[test]
+    fn test_sanitize_expands_home_in_download_folder() {
+        let Some(home) = dirs::home_dir() else {
+            return;
+        };
+        let mut settings = valid_settings();
+        settings.general.download_folder = PathBuf::from("~/Downloads");
+        settings.sanitize();
+        assert_eq!(settings.general.download_folder, home.join("Downloads"));
+
+        // Only a leading `~` component is expanded
+        settings.general.download_folder = PathBuf::from("/data/~backup");
+        settings.sanitize();
+        assert_eq!(
+            settings.general.download_folder,
+            PathBuf::from("/data/~backup")
+        );
+    }
+
+    #[test]
+    fn test_validate_ignores_proxy_url_when_proxy_is_off() {
+        let mut settings = valid_settings();
+        settings.network.proxy_url = "not a url".to_string();
+        assert!(settings.validate().is_empty());
+    }
+
+    #[test]
+    fn test_sanitize_clamps_ranges() {
+        let mut settings = valid_settings();
+        settings.general.concurrency = 0;
+        settings.network.concurrent_fragments = 64;
+        settings.sanitize();
+        assert_eq!(settings.general.concurrency, 1);
+        assert_eq!(settings.network.concurrent_fragments, 16);
+        assert!(settings.validate().is_empty());
+
+        settings.general.concurrency = 25;
+        settings.sanitize();
+        assert_eq!(settings.general.concurrency, 10);
+    }
+
+    #[test]
+    fn test_partial_settings_json_fills_defaults() {
+        // Stored by an older version: missing sections and newer fields
+        let stored = r#"{
+            "general": { "concurrency": 4 },
+            "network": { "retries": 10, "use_proxy": true }
+        }"#;
+        let settings: UserSettings = serde_json::from_str(stored).unwrap();
+
+        assert_eq!(settings.general.concurrency, 4);
+        assert_eq!(settings.general.default_preset, "recommended_best");
+        assert!(settings.general.auto_start);
+        assert_eq!(settings.network.retries, 10);
+        assert_eq!(settings.network.socket_timeout, 30);
+        assert_eq!(settings.network.external_downloader, "none");
+        assert_eq!(
+            settings.formats.filename_template,
+            "%(title)s [%(id)s].%(ext)s"
+        );
+        assert!(settings.formats.remux_to.is_empty());
+        assert_eq!(settings.sponsorblock.categories, vec!["sponsor"]);
+        assert_eq!(settings.privacy.max_history_entries, 1000);
+
+        // The default Downloads folder may not exist on a test machine
+        let mut settings = settings;
+        settings.general.download_folder = std::env::temp_dir();
+        let effective = EffectiveSettings::new(settings);
+        assert!(effective.warnings.is_empty());
+        let at_default = &effective.at_default;
+        assert!(!at_default["general.concurrency"]);
+        assert!(!at_default["network.retries"]);
+        assert!(!at_default["network.use_proxy"]);
+        assert!(at_default["general.auto_start"]);
+        assert!(at_default["formats.remux_to"]);
+        assert!(at_default["updates.check_interval_hours"]);
+        // Every field of every section is listed
+        let field_count = serde_json::to_value(UserSettings::default())
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|s| s.as_object().unwrap().len())
+            .sum::<usize>();
+        assert_eq!(at_default.len(), field_count);
+    }
+
+    #[test]
+    fn test_get_user_settings_fills_defaults_from_stored_json() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE settings (key TEXT PRIMARY KEY, value_json TEXT NOT NULL);",
+        )
+        .unwrap();
+        let manager = SettingsManager::new(&conn);
+        assert!(EffectiveSettings::new(manager.get_user_settings().unwrap())
+            .at_default
+            .values()
+            .all(|d| *d));
+
+        conn.execute(
+            "INSERT INTO settings (key, value_json) VALUES (?1, ?2)",
+            params![keys::USER_SETTINGS, r#"{"formats": {"prefer_mp4": false}}"#],
+        )
+        .unwrap();
+        let settings = manager.get_user_settings().unwrap();
+        assert!(!settings.formats.prefer_mp4);
+        assert!(settings.formats.embed_metadata);
+        assert_eq!(settings.subtitles.preferred_format, "srt");
+    }
+
+    #[test]
+    fn test_validate_subtitle_langs() {
+        assert_eq!(validate_subtitle_langs(""), Ok(None));
+        assert_eq!(validate_subtitle_langs(" , "), Ok(None));
+        assert_eq!(
+            validate_subtitle_langs("en, pt-BR,en,zh-Hans"),
+            Ok(Some("en,pt-BR,zh-Hans".to_string()))
+        );
+        assert_eq!(validate_subtitle_langs("all"), Ok(Some("all".to_string())));
+        for bad in ["english", "e", "en_US", "en-", "ja;rm"] {
+            assert!(validate_subtitle_langs(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_validate_filename_template() {
+        for ok in [
+            "%(title)s [%(id)s].%(ext)s",
+            "%(uploader)s/%(title)s.%(ext)s",
+            "..%(title)s.%(ext)s",
+        ] {
+            assert_eq!(validate_filename_template(ok), Ok(()), "{ok}");
+        }
+        for bad in [
+            "/tmp/%(title)s.%(ext)s",
+            "\\\\server\\share\\%(title)s.%(ext)s",
+            "C:%(title)s.%(ext)s",
+            "../%(title)s.%(ext)s",
+            "%(uploader)s/../../%(title)s.%(ext)s",
+            "a\\..\\%(title)s.%(ext)s",
+        ] {
+            assert!(validate_filename_template(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_validate_remux_container() {
+        assert_eq!(validate_remux_container(""), Ok(None));
+        assert_eq!(
+            validate_remux_container(" MKV "),
+            Ok(Some("mkv".to_string()))
+        );
+        assert_eq!(
+            validate_remux_container(".mov"),
+            Ok(Some("mov".to_string()))
+        );
+        for bad in ["avi", "mp3", "mkv,mp4", "flv"] {
+            let err = validate_remux_container(bad).unwrap_err();
+            assert!(err.contains("mp4, mkv, webm, mov"), "{bad}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_preset_for_url_site_overrides() {
+        let mut general = GeneralSettings::default();
+        general
+            .site_preset_overrides
+            .insert("music.example.com".to_string(), "audio_m4a".to_string());
+
+        assert_eq!(
+            general.preset_for_url("https://music.example.com/watch?v=abc"),
+            "audio_m4a"
+        );
+        assert_eq!(
+            general.preset_for_url("https://www.music.example.com/track/1"),
+            "audio_m4a"
+        );
+        // Subdomains of the key match too
+        assert_eq!(
+            general.preset_for_url("https://eu.music.example.com/track/1"),
+            "audio_m4a"
+        );
+        // Other hosts use the global default
+        assert_eq!(
+            general.preset_for_url("https://www.example.com/watch?v=abc"),
+            "recommended_best"
+        );
+        assert_eq!(
+            general.preset_for_url("https://notmusic.example.com/x"),
+            "recommended_best"
+        );
+        assert_eq!(general.preset_for_url("not a url"), "recommended_best");
+    }
+
+    #[test]
+    fn test_preset_for_url_most_specific_wins() {
+        let mut general = GeneralSettings::default();
+        general
+            .site_preset_overrides
+            .insert("*.Example.com".to_string(), "mp4_1080p".to_string());
+        general
+            .site_preset_overrides
+            .insert("music.example.com".to_string(), "audio_mp3_320".to_string());
+
+        assert_eq!(
+            general.preset_for_url("https://music.example.com/a"),
+            "audio_mp3_320"
+        );
+        assert_eq!(general.preset_for_url("https://example.com/a"), "mp4_1080p");
+        assert_eq!(
+            general.preset_for_url("https://video.example.com/a"),
+            "mp4_1080p"
+        );
+    }
     use rusqlite::Connection;
 
     fn setup_test_db() -> Connection {
@@ -0,0 +1,222 @@
+//! Temp File Cleanup
+//!
+//! Interrupted tool updates leave `.download` files in the app's tmp dir, and
+//! stopped `download_to_temp` downloads leave staging folders. Old ones are removed
+//! at startup; `clear_temp_files` removes everything not in use.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::download_manager::STAGING_DIR;
+
+/// Temp files untouched for this long are deleted at startup.
+pub const STARTUP_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Anything written to more recently than this may still belong to a running tool
+/// update or download, so even a manual clear leaves it alone.
+pub const IN_USE_GRACE: Duration = Duration::from_secs(10 * 60);
+
+/// A file or folder in the tmp dir, as seen by the cleanup pass. Each download's
+/// staging folder is one entry so its `.part` files are removed together.
+#[derive(Debug, Clone)]
+pub struct TempEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Newest mtime of the file, or of anything inside the folder.
+    pub modified: SystemTime,
+}
+
+/// What a cleanup pass removed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CleanupReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+/// Entries not modified within `max_age` of `now`, skipping anything in `keep`.
+/// Entries with an mtime in the future count as fresh.
+pub fn select_expired(
+    entries: &[TempEntry],
+    max_age: Duration,
+    now: SystemTime,
+    keep: &HashSet<PathBuf>,
+) -> Vec<TempEntry> {
+    entries
+        .iter()
+        .filter(|e| !keep.contains(&e.path))
+        .filter(|e| {
+            now.duration_since(e.modified)
+                .is_ok_and(|age| age >= max_age)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Everything cleanup may delete in `tmp_dir`: top-level files and folders, with
+/// the staging folder split into one entry per download. Entries that vanish or
+/// can't be read while listing are left out.
+pub fn list_entries(tmp_dir: &Path) -> Result<Vec<TempEntry>> {
+    let mut paths = Vec::new();
+    for item in fs::read_dir(tmp_dir)? {
+        let path = item?.path();
+        if path.is_dir() && path.file_name().is_some_and(|n| n == STAGING_DIR) {
+            for child in fs::read_dir(&path)? {
+                paths.push(child?.path());
+            }
+        } else {
+            paths.push(path);
+        }
+    }
+
+    Ok(paths
+        .iter()
+        .filter_map(|path| match describe(path) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::debug!("Skipping temp file {:?}: {}", path, e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Delete entries of `tmp_dir` older than `max_age`, leaving `keep` alone. Failures
+/// on single entries are logged and skipped.
+pub fn cleanup_temp_files(
+    tmp_dir: &Path,
+    max_age: Duration,
+    keep: &HashSet<PathBuf>,
+) -> Result<CleanupReport> {
+    if !tmp_dir.exists() {
+        return Ok(CleanupReport::default());
+    }
+
+    let entries = list_entries(tmp_dir)?;
+    let mut report = CleanupReport::default();
+    for entry in select_expired(&entries, max_age, SystemTime::now(), keep) {
+        let result = if entry.path.is_dir() {
+            fs::remove_dir_all(&entry.path)
+        } else {
+            fs::remove_file(&entry.path)
+        };
+        match result {
+            Ok(()) => {
+                log::debug!("Removed temp file {:?}", entry.path);
+                report.removed += 1;
+                report.freed_bytes += entry.size;
+            }
+            Err(e) => log::warn!("Failed to remove temp file {:?}: {}", entry.path, e),
+        }
+    }
+    Ok(report)
+}
+
+/// Size and newest mtime of a file, or of a folder's whole tree.
+fn describe(path: &Path) -> Result<TempEntry> {
+    let meta = fs::symlink_metadata(path)?;
+    let mut entry = TempEntry {
+        path: path.to_path_buf(),
+        size: meta.len(),
+        modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+    };
+    if meta.is_dir() {
+        entry.size = 0;
+        for child in fs::read_dir(path)? {
+            let child = describe(&child?.path())?;
+            entry.size += child.size;
+            entry.modified = entry.modified.max(child.modified);
+        }
+    }
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW_SECS: u64 = 1_000_000;
+
+    fn entry(name: &str, age_secs: u64) -> TempEntry {
+        TempEntry {
+            path: PathBuf::from(name),
+            size: 100,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(NOW_SECS - age_secs),
+        }
+    }
+
+    fn names(entries: Vec<TempEntry>) -> Vec<PathBuf> {
+        entries.into_iter().map(|e| e.path).collect()
+    }
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(NOW_SECS)
+    }
+
+    #[test]
+    fn test_select_expired_by_age() {
+        let entries = vec![
+            entry("fresh", 60),
+            entry("exactly-max", 3600),
+            entry("old", 7200),
+        ];
+
+        assert_eq!(
+            names(select_expired(
+                &entries,
+                Duration::from_secs(3600),
+                now(),
+                &HashSet::new()
+            )),
+            vec![PathBuf::from("exactly-max"), PathBuf::from("old")]
+        );
+    }
+
+    #[test]
+    fn test_select_expired_skips_kept_and_future_entries() {
+        let mut future = entry("future", 0);
+        future.modified = now() + Duration::from_secs(600);
+        let entries = vec![entry("in-use", 7200), entry("old", 7200), future];
+        let keep = HashSet::from([PathBuf::from("in-use")]);
+
+        assert_eq!(
+            names(select_expired(&entries, Duration::ZERO, now(), &keep)),
+            vec![PathBuf::from("old")]
+        );
+    }
+
+    #[test]
+    fn test_cleanup_treats_each_staging_folder_as_one_entry() {
+        let tmp = std::env::temp_dir().join(format!("downlink-tmp-{}", uuid::Uuid::new_v4()));
+        let staging = tmp.join(STAGING_DIR);
+        fs::create_dir_all(staging.join("a")).unwrap();
+        fs::create_dir_all(staging.join("b")).unwrap();
+        fs::write(staging.join("a").join("video.part"), b"12345").unwrap();
+        fs::write(tmp.join("yt-dlp-2024.01.01.download"), b"123").unwrap();
+
+        let mut listed = names(list_entries(&tmp).unwrap());
+        listed.sort();
+        assert_eq!(
+            listed,
+            vec![
+                staging.join("a"),
+                staging.join("b"),
+                tmp.join("yt-dlp-2024.01.01.download"),
+            ]
+        );
+
+        let keep = HashSet::from([staging.join("a")]);
+        let report = cleanup_temp_files(&tmp, Duration::ZERO, &keep).unwrap();
+        assert_eq!(report.removed, 2);
+        assert_eq!(report.freed_bytes, 3);
+        assert!(staging.join("a").join("video.part").exists());
+        assert!(!staging.join("b").exists());
+        assert!(staging.exists());
+
+        let _ = fs::remove_dir_all(&tmp);
+    }
+}
@@ -0,0 +1,520 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use url::Url;
+
+use crate::db::ensure_app_dirs;
+
+/// Subdirectory of the app data dir holding cached thumbnails.
+pub const THUMBNAILS_DIR: &str = "thumbnails";
+
+/// Total size the cache may grow to before least-recently-used files are evicted.
+const MAX_CACHE_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Refuse anything larger; thumbnails are normally well under 1 MB.
+const MAX_THUMBNAIL_BYTES: u64 = 10 * 1024 * 1024;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Redirects followed when fetching a thumbnail; each target is checked like the URL.
+const MAX_REDIRECTS: usize = 5;
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif"];
+
+/// A file in the cache directory, as seen by the eviction pass.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// File mtime; bumped on every cache hit so it tracks last use.
+    pub last_used: SystemTime,
+}
+
+/// A thumbnail in the cache, and whatever was evicted to make room for it.
+#[derive(Debug, Clone)]
+pub struct CachedThumbnail {
+    pub path: PathBuf,
+    /// Files deleted by the eviction pass; anything still pointing at them is stale.
+    pub evicted: Vec<PathBuf>,
+}
+
+/// Parse a thumbnail URL, accepting only http(s) to a public host.
+///
+/// The URL comes from the frontend, so local and private addresses are refused
+/// rather than fetched on its behalf.
+pub fn check_thumbnail_url(url: &str) -> Result<Url> {
+    let parsed = Url::parse(url).with_context(|| format!("invalid thumbnail URL: {url}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow!(
+            "unsupported thumbnail URL scheme: {}",
+            parsed.scheme()
+        ));
+    }
+    let private = match parsed.host() {
+        None => true,
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost") || domain.ends_with(".local")
+        }
+        Some(url::Host::Ipv4(ip)) => is_private_ip(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => is_private_ip(IpAddr::V6(ip)),
+    };
+    if private {
+        return Err(anyhow!("thumbnail URL must point at a public host: {url}"));
+    }
+    Ok(parsed)
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // fc00::/7 unique local, fe80::/10 link-local
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(|v4| is_private_ip(IpAddr::V4(v4)))
+        }
+    }
+}
+
+/// Cache file name for a thumbnail URL.
+///
+/// The name is a hash of the full URL, so the same thumbnail shared by several
+/// downloads is stored once. The extension is kept from the URL when it's a known
+/// image type so the webview serves it with the right content type.
+pub fn cache_file_name(url: &str) -> String {
+    let digest = Sha256::digest(url.as_bytes());
+    let extension = Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            Path::new(u.path())
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+        })
+        .filter(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+        .unwrap_or_else(|| "jpg".to_string());
+
+    format!("{}.{}", hex::encode(&digest[..16]), extension)
+}
+
+/// Least-recently-used entries to delete so the cache fits in `max_bytes`.
+pub fn select_evictions(mut entries: Vec<CacheEntry>, max_bytes: u64) -> Vec<PathBuf> {
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    if total <= max_bytes {
+        return Vec::new();
+    }
+
+    entries.sort_by_key(|e| e.last_used);
+
+    let mut evicted = Vec::new();
+    for entry in entries {
+        if total <= max_bytes {
+            break;
+        }
+        total = total.saturating_sub(entry.size);
+        evicted.push(entry.path);
+    }
+    evicted
+}
+
+/// On-disk cache of remote thumbnails under the app data dir.
+#[derive(Debug, Clone)]
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            max_bytes: MAX_CACHE_BYTES,
+        }
+    }
+
+    /// Cache in `<app data>/thumbnails`.
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(ensure_app_dirs()?.data.join(THUMBNAILS_DIR)))
+    }
+
+    /// Return the local copy of `url`, downloading it on a cache miss, through
+    /// `proxy` when one is configured.
+    pub async fn get_or_fetch(&self, url: &str, proxy: Option<&str>) -> Result<CachedThumbnail> {
+        check_thumbnail_url(url)?;
+
+        let file_name = cache_file_name(url);
+        let path = self.dir.join(&file_name);
+
+        if fs::try_exists(&path).await.unwrap_or(false) {
+            if let Err(e) = touch(&path) {
+                log::debug!("Failed to update thumbnail access time {:?}: {}", path, e);
+            }
+            return Ok(CachedThumbnail {
+                path,
+                evicted: Vec::new(),
+            });
+        }
+
+        let bytes = fetch_image(url, proxy).await?;
+
+        fs::create_dir_all(&self.dir).await?;
+        // Unique temp name so concurrent fetches of the same URL don't clobber each other
+        let tmp = self
+            .dir
+            .join(format!("{}.{}.part", file_name, uuid::Uuid::new_v4()));
+        fs::write(&tmp, &bytes).await?;
+        fs::rename(&tmp, &path).await?;
+
+        let evicted = self.evict().await.unwrap_or_else(|e| {
+            log::warn!("Thumbnail cache eviction failed: {}", e);
+            Vec::new()
+        });
+
+        Ok(CachedThumbnail { path, evicted })
+    }
+
+    /// Delete least-recently-used thumbnails until the cache is under its size limit,
+    /// returning the files removed.
+    async fn evict(&self) -> Result<Vec<PathBuf>> {
+        let mut entries = Vec::new();
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(item) = dir.next_entry().await? {
+            let path = item.path();
+            if path.extension().is_some_and(|e| e == "part") {
+                continue;
+            }
+            let meta = item.metadata().await?;
+            if !meta.is_file() {
+                continue;
+            }
+            entries.push(CacheEntry {
+                path,
+                size: meta.len(),
+                last_used: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+
+        let mut evicted = Vec::new();
+        for path in select_evictions(entries, self.max_bytes) {
+            log::debug!("Evicting cached thumbnail {:?}", path);
+            if fs::remove_file(&path).await.is_ok() {
+                evicted.push(path);
+            }
+        }
+        Ok(evicted)
+    }
+}
+
+/// Mark a cache file as just used (LRU order is by mtime).
+fn touch(path: &Path) -> std::io::Result<()> {
+    std::fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+/// The addresses a thumbnail host resolved to, refused if any is local or private so
+/// a public-looking name can't reach the local network.
+fn public_addrs(host: &str, addrs: impl Iterator<Item = SocketAddr>) -> Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = addrs.collect();
+    if addrs.iter().any(|addr| is_private_ip(addr.ip())) {
+        return Err(anyhow!(
+            "thumbnail host {host} resolves to a local or private address"
+        ));
+    }
+    Ok(addrs)
+}
+
+/// DNS resolver for thumbnail fetches, see [`public_addrs`].
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0)).await?;
+            let addrs: reqwest::dns::Addrs = Box::new(public_addrs(&host, addrs)?.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Download a thumbnail, refusing redirects and resolved addresses that
+/// [`check_thumbnail_url`] would refuse, and bodies over `MAX_THUMBNAIL_BYTES`.
+///
+/// Through a proxy, the proxy resolves host names, so only the URL checks apply.
+async fn fetch_image(url: &str, proxy: Option<&str>) -> Result<Vec<u8>> {
+    let redirects = reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error(format!("more than {MAX_REDIRECTS} redirects"));
+        }
+        match check_thumbnail_url(attempt.url().as_str()) {
+            Ok(_) => attempt.follow(),
+            Err(e) => attempt.error(e.to_string()),
+        }
+    });
+    let mut builder = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(redirects)
+        .dns_resolver(Arc::new(PublicResolver));
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    let client = builder.build()?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if !content_type.is_empty() && !content_type.starts_with("image/") {
+        return Err(anyhow!(
+            "thumbnail URL returned {content_type}, not an image"
+        ));
+    }
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_THUMBNAIL_BYTES)
+    {
+        return Err(anyhow!(
+            "thumbnail is larger than {MAX_THUMBNAIL_BYTES} bytes"
+        ));
+    }
+
+    // Content-Length may be missing or wrong, so count while reading
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if (bytes.len() + chunk.len()) as u64 > MAX_THUMBNAIL_BYTES {
+            return Err(anyhow!(
+                "thumbnail is larger than {MAX_THUMBNAIL_BYTES} bytes"
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u64, age_secs: u64) -> CacheEntry {
+        CacheEntry {
+            path: PathBuf::from(name),
+            size,
+            last_used: SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 - age_secs),
+        }
+    }
+
+    #[test]
+    fn test_cache_file_name_is_stable_per_url() {
+        let a = cache_file_name("https://i.ytimg.com/vi/abc/maxresdefault.jpg");
+        let b = cache_file_name("https://i.ytimg.com/vi/abc/maxresdefault.jpg");
+        let c = cache_file_name("https://i.ytimg.com/vi/def/maxresdefault.jpg");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.len(), 32 + ".jpg".len());
+    }
+
+    #[test]
+    fn test_cache_file_name_extension() {
+        assert!(cache_file_name("https://i.ytimg.com/vi_webp/abc/hq.webp").ends_with(".webp"));
+        assert!(cache_file_name("https://example.com/thumb.PNG?size=large").ends_with(".png"));
+        assert!(cache_file_name("https://example.com/thumb?id=1").ends_with(".jpg"));
+        assert!(cache_file_name("https://example.com/thumb.php").ends_with(".jpg"));
+    }
+
+    #[test]
+    fn test_select_evictions_under_limit() {
+        let entries = vec![entry("a", 100, 10), entry("b", 100, 5)];
+        assert!(select_evictions(entries, 200).is_empty());
+    }
+
+    #[test]
+    fn test_select_evictions_removes_least_recently_used_first() {
+        let entries = vec![
+            entry("newest", 100, 1),
+            entry("oldest", 100, 300),
+            entry("middle", 100, 60),
+        ];
+
+        assert_eq!(
+            select_evictions(entries.clone(), 250),
+            vec![PathBuf::from("oldest")]
+        );
+        assert_eq!(
+            select_evictions(entries, 100),
+            vec![PathBuf::from("oldest"), PathBuf::from("middle")]
+        );
+    }
+
+    #[test]
+    fn test_select_evictions_large_old_file_frees_enough() {
+        let entries = vec![
+            entry("big-old", 1000, 500),
+            entry("small-new", 10, 1),
+            entry("small-mid", 10, 50),
+        ];
+        assert_eq!(
+            select_evictions(entries, 100),
+            vec![PathBuf::from("big-old")]
+        );
+    }
+
+    #[test]
+    fn test_check_thumbnail_url_refuses_local_hosts() {
+        assert!(check_thumbnail_url("https://i.ytimg.com/vi/abc/hq.jpg").is_ok());
+        assert!(check_thumbnail_url("http://93.184.216.34/thumb.jpg").is_ok());
+
+        for url in [
+            "file:///etc/passwd",
+            "ftp://example.com/thumb.jpg",
+            "http://localhost:8080/thumb.jpg",
+            "http://127.0.0.1/thumb.jpg",
+            "http://10.0.0.5/thumb.jpg",
+            "http://192.168.1.1/thumb.jpg",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/thumb.jpg",
+            "http://[fd00::1]/thumb.jpg",
+            "http://[::ffff:127.0.0.1]/thumb.jpg",
+            "http://printer.local/thumb.jpg",
+        ] {
+            assert!(check_thumbnail_url(url).is_err(), "{url} should be refused");
+        }
+    }
+
+    /// Serve one canned HTTP response on a local port, standing in for a proxy.
+    fn serve_once(response: Vec<u8>) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.read(&mut [0u8; 1024]);
+                let _ = stream.write_all(&response);
+            }
+        });
+        format!("http://127.0.0.1:{port}")
+    }
+
+    #[tokio::test]
+    async fn test_fetch_image_refuses_redirects_to_private_hosts() {
+        let proxy = serve_once(
+            b"HTTP/1.1 302 Found\r\nLocation: http://192.168.1.10/x.jpg\r\nContent-Length: 0\r\n\r\n"
+                .to_vec(),
+        );
+
+        let err = fetch_image("http://example.com/t.jpg", Some(&proxy))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("public host"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_image_caps_bodies_without_content_length() {
+        let mut response =
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nConnection: close\r\n\r\n".to_vec();
+        response.resize(
+            response.len() + MAX_THUMBNAIL_BYTES as usize + 1024 * 1024,
+            0,
+        );
+        let proxy = serve_once(response);
+
+        let err = fetch_image("http://example.com/t.jpg", Some(&proxy))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("larger than"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn test_public_resolver_refuses_private_addresses() {
+        use reqwest::dns::Resolve;
+
+        let public: SocketAddr = "93.184.216.34:0".parse().unwrap();
+        let private: SocketAddr = "10.0.0.5:0".parse().unwrap();
+        assert_eq!(
+            public_addrs("example.com", [public].into_iter()).unwrap(),
+            vec![public]
+        );
+        assert!(public_addrs("example.com", [public, private].into_iter()).is_err());
+
+        assert!(PublicResolver
+            .resolve("localhost".parse().unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_or_fetch_returns_cached_file_and_bumps_mtime() {
+        let dir = std::env::temp_dir().join(format!("downlink-thumbs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Pre-populated entry: served without touching the network
+        let url = "https://thumbs.invalid/vi/abc/hq.jpg";
+        let cached = dir.join(cache_file_name(url));
+        std::fs::write(&cached, b"jpeg").unwrap();
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&cached)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let cache = ThumbnailCache::new(dir.clone());
+        let result = cache.get_or_fetch(url, None).await.unwrap();
+        let path = result.path;
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(path, cached);
+        assert!(result.evicted.is_empty());
+        assert!(modified > old);
+    }
+
+    #[tokio::test]
+    async fn test_evict_trims_directory_to_limit() {
+        let dir = std::env::temp_dir().join(format!("downlink-thumbs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let now = SystemTime::now();
+        for (name, age) in [("old.jpg", 300), ("mid.jpg", 200), ("new.jpg", 100)] {
+            let path = dir.join(name);
+            std::fs::write(&path, vec![0u8; 100]).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        let cache = ThumbnailCache {
+            dir: dir.clone(),
+            max_bytes: 200,
+        };
+        let evicted = cache.evict().await.unwrap();
+        assert_eq!(evicted, vec![dir.join("old.jpg")]);
+
+        let remaining = ["old.jpg", "mid.jpg", "new.jpg"].map(|n| dir.join(n).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(remaining, [false, true, true]);
+    }
+}
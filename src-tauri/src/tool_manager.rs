@@ -3,8 +3,10 @@
 //! Handles discovery, version checking, health validation, and updates for
 //! bundled tools (yt-dlp, ffmpeg, ffprobe).
 
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Mutex as StdMutex;
 use std::time::Duration;
 
 #[cfg(windows)]
@@ -17,11 +19,12 @@ const CREATE_NO_WINDOW: u32 = 0x08000000;
 use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
+use tokio::sync::watch;
 
+use crate::checksum::{self, ChecksumAlgorithm};
 use crate::db::{ensure_app_dirs, AppDirs};
 
 /// Tool identifiers managed by Downlink.
@@ -42,6 +45,16 @@ impl Tool {
         }
     }
 
+    /// Parse the name used in the update manifest (inverse of `as_str`).
+    pub fn from_name(name: &str) -> Option<Tool> {
+        match name {
+            "yt-dlp" => Some(Tool::YtDlp),
+            "ffmpeg" => Some(Tool::Ffmpeg),
+            "ffprobe" => Some(Tool::Ffprobe),
+            _ => None,
+        }
+    }
+
     pub fn binary_name(&self) -> &'static str {
         #[cfg(target_os = "windows")]
         {
@@ -91,6 +104,55 @@ pub struct ToolInfo {
     pub last_checked: Option<DateTime<Utc>>,
 }
 
+/// How `verify_and_repair_tools` fixes a tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairAction {
+    /// The installed binary runs; nothing to do.
+    None,
+    /// Remove the broken updated copy so the healthy bundled one takes over.
+    ResetToBundled,
+    /// Download the manifest's version over the broken binary.
+    Redownload,
+    /// Broken, with neither a bundled fallback nor a manifest entry to fetch.
+    Unrepairable,
+}
+
+/// Health of the binary a tool resolves to before any PATH fallback.
+#[derive(Debug, Clone)]
+pub struct ToolCheck {
+    pub status: ToolStatus,
+    /// Path that was checked, if an updated or bundled copy exists.
+    pub path: Option<PathBuf>,
+    /// A bundled copy exists, runs, and isn't the broken binary.
+    pub bundled_healthy: bool,
+}
+
+/// Outcome of verifying (and possibly repairing) one tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolRepairResult {
+    pub tool: Tool,
+    /// Status before any repair.
+    pub status: ToolStatus,
+    pub action: RepairAction,
+    pub error: Option<String>,
+    /// The tool as resolved after the repair.
+    pub info: ToolInfo,
+}
+
+/// Decide how to repair a tool from its check and whether the manifest lists it.
+pub fn plan_repair(check: &ToolCheck, in_manifest: bool) -> RepairAction {
+    if check.status != ToolStatus::Broken {
+        RepairAction::None
+    } else if check.bundled_healthy {
+        RepairAction::ResetToBundled
+    } else if in_manifest {
+        RepairAction::Redownload
+    } else {
+        RepairAction::Unrepairable
+    }
+}
+
 /// Combined toolchain status for UI display.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolchainStatus {
@@ -100,6 +162,35 @@ pub struct ToolchainStatus {
     pub overall_status: ToolStatus,
 }
 
+/// Where a resolved tool binary comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    /// Installed by an in-app update into the user tools directory.
+    Updated,
+    /// Shipped with the app.
+    Bundled,
+    /// Found on the system PATH.
+    System,
+}
+
+/// The binary the app will run for one tool. All fields are `None` if it wasn't found.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolPath {
+    pub tool: Tool,
+    pub path: Option<PathBuf>,
+    pub version: Option<String>,
+    pub source: Option<ToolSource>,
+}
+
+/// Resolved paths for every managed tool.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolPaths {
+    pub yt_dlp: ToolPath,
+    pub ffmpeg: ToolPath,
+    pub ffprobe: ToolPath,
+}
+
 /// Update manifest entry for a tool.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolManifestEntry {
@@ -129,6 +220,8 @@ pub struct ToolManagerConfig {
     pub manifest_url: Option<String>,
     /// How long to wait for version checks.
     pub version_timeout: Duration,
+    /// How long a fetched manifest is reused before hitting the network again.
+    pub manifest_cache_ttl: Duration,
 }
 
 impl Default for ToolManagerConfig {
@@ -138,6 +231,7 @@ impl Default for ToolManagerConfig {
             updated_dir: PathBuf::new(),
             manifest_url: None,
             version_timeout: Duration::from_secs(5),
+            manifest_cache_ttl: Duration::from_secs(60 * 60),
         }
     }
 }
@@ -146,6 +240,10 @@ impl Default for ToolManagerConfig {
 pub struct ToolManager {
     config: ToolManagerConfig,
     app_dirs: AppDirs,
+    manifest_cache: ManifestCache,
+    /// Updates currently downloading or installing, keyed by tool name. Their temp
+    /// files are left alone by temp cleanup.
+    in_flight: StdMutex<HashMap<String, InFlightUpdate>>,
 }
 
 impl ToolManager {
@@ -161,8 +259,17 @@ impl ToolManager {
             },
             ..config
         };
+        let manifest_cache = ManifestCache::load(
+            app_dirs.data.join(MANIFEST_CACHE_FILE),
+            config.manifest_cache_ttl,
+        );
 
-        Ok(Self { config, app_dirs })
+        Ok(Self {
+            config,
+            app_dirs,
+            manifest_cache,
+            in_flight: StdMutex::default(),
+        })
     }
 
     /// Get the path to the tools directory.
@@ -255,31 +362,117 @@ impl ToolManager {
         }
     }
 
+    /// Where a path returned by `find_tool` lives.
+    pub fn tool_source(&self, path: &Path) -> ToolSource {
+        tool_source(
+            path,
+            &self.config.updated_dir,
+            self.config.bundled_dir.as_deref(),
+        )
+    }
+
+    /// Resolved path, version and source for a tool.
+    pub async fn get_tool_path(&self, tool: Tool) -> ToolPath {
+        let Some(path) = self.find_tool(tool).await else {
+            return ToolPath {
+                tool,
+                path: None,
+                version: None,
+                source: None,
+            };
+        };
+
+        ToolPath {
+            tool,
+            version: self.get_version(&path, tool).await.ok(),
+            source: Some(self.tool_source(&path)),
+            path: Some(path),
+        }
+    }
+
+    /// Resolved paths for yt-dlp, ffmpeg and ffprobe.
+    pub async fn get_tool_paths(&self) -> ToolPaths {
+        ToolPaths {
+            yt_dlp: self.get_tool_path(Tool::YtDlp).await,
+            ffmpeg: self.get_tool_path(Tool::Ffmpeg).await,
+            ffprobe: self.get_tool_path(Tool::Ffprobe).await,
+        }
+    }
+
     /// Get the complete toolchain status.
     pub async fn get_toolchain_status(&self) -> ToolchainStatus {
         let yt_dlp = self.get_tool_info(Tool::YtDlp).await;
         let ffmpeg = self.get_tool_info(Tool::Ffmpeg).await;
         let ffprobe = self.get_tool_info(Tool::Ffprobe).await;
 
-        // Determine overall status
-        let overall_status = if yt_dlp.status == ToolStatus::Missing {
-            ToolStatus::Missing
-        } else if yt_dlp.status == ToolStatus::Broken || ffmpeg.status == ToolStatus::Broken {
-            ToolStatus::Broken
-        } else if yt_dlp.status == ToolStatus::Outdated || ffmpeg.status == ToolStatus::Outdated {
-            ToolStatus::Outdated
-        } else {
-            ToolStatus::Ok
-        };
-
         ToolchainStatus {
+            overall_status: overall_status(&yt_dlp, &ffmpeg),
             yt_dlp: Some(yt_dlp),
             ffmpeg: Some(ffmpeg),
             ffprobe: Some(ffprobe),
-            overall_status,
         }
     }
 
+    /// Health-check the copy of `tool` that would be used ahead of the system PATH.
+    ///
+    /// `find_tool` silently skips a broken updated or bundled binary; this reports it.
+    pub async fn verify_tool(&self, tool: Tool) -> ToolCheck {
+        let updated_path = self.config.updated_dir.join(tool.binary_name());
+        let bundled_path = self
+            .config
+            .bundled_dir
+            .as_ref()
+            .map(|d| d.join(tool.binary_name()))
+            .filter(|p| p.exists());
+
+        let bundled_ok = match bundled_path {
+            Some(ref p) => self.check_health(p, tool).await.is_ok(),
+            None => false,
+        };
+
+        let (path, healthy, bundled_healthy) = if updated_path.exists() {
+            let healthy = self.check_health(&updated_path, tool).await.is_ok();
+            (Some(updated_path), healthy, bundled_ok)
+        } else if let Some(p) = bundled_path {
+            (Some(p), bundled_ok, false)
+        } else {
+            let info = self.get_tool_info(tool).await;
+            return ToolCheck {
+                status: info.status,
+                path: None,
+                bundled_healthy: false,
+            };
+        };
+
+        ToolCheck {
+            status: if healthy {
+                ToolStatus::Ok
+            } else {
+                ToolStatus::Broken
+            },
+            path,
+            bundled_healthy,
+        }
+    }
+
+    /// Manifest entry for `tool`, whether or not it's newer than the installed version.
+    pub async fn manifest_entry(&self, tool: Tool) -> Result<Option<ToolManifestEntry>> {
+        let manifest_url = self
+            .config
+            .manifest_url
+            .as_ref()
+            .ok_or_else(|| anyhow!("No manifest URL configured"))?;
+
+        let manifest = self
+            .manifest_cache
+            .get_or_fetch(manifest_url, Utc::now(), || fetch_manifest(manifest_url))
+            .await?;
+        Ok(manifest
+            .tools
+            .into_iter()
+            .find(|e| Tool::from_name(&e.tool) == Some(tool)))
+    }
+
     /// Check if a tool binary is healthy (can execute and return version).
     async fn check_health(&self, path: &Path, tool: Tool) -> Result<()> {
         if !path.exists() {
@@ -293,32 +486,7 @@ impl ToolManager {
 
     /// Get the version string from a tool.
     pub async fn get_version(&self, path: &Path, tool: Tool) -> Result<String> {
-        let mut cmd = Command::new(path);
-        cmd.args(tool.version_args())
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        // Hide console window on Windows
-        #[cfg(windows)]
-        cmd.creation_flags(CREATE_NO_WINDOW);
-
-        let output = tokio::time::timeout(self.config.version_timeout, cmd.output())
-            .await
-            .context("Version check timed out")?
-            .context("Failed to execute tool")?;
-
-        if !output.status.success() {
-            return Err(anyhow!(
-                "Tool returned non-zero exit code: {}",
-                output.status
-            ));
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let version = parse_version(&stdout, tool);
-
-        version.ok_or_else(|| anyhow!("Could not parse version from output"))
+        probe_version(path, tool, self.config.version_timeout).await
     }
 
     /// Check for available updates.
@@ -329,15 +497,15 @@ impl ToolManager {
             .as_ref()
             .ok_or_else(|| anyhow!("No manifest URL configured"))?;
 
-        let manifest = fetch_manifest(manifest_url).await?;
+        let manifest = self
+            .manifest_cache
+            .get_or_fetch(manifest_url, Utc::now(), || fetch_manifest(manifest_url))
+            .await?;
         let mut updates = Vec::new();
 
         for entry in manifest.tools {
-            let tool = match entry.tool.as_str() {
-                "yt-dlp" => Tool::YtDlp,
-                "ffmpeg" => Tool::Ffmpeg,
-                "ffprobe" => Tool::Ffprobe,
-                _ => continue,
+            let Some(tool) = Tool::from_name(&entry.tool) else {
+                continue;
             };
 
             let current_info = self.get_tool_info(tool).await;
@@ -361,66 +529,118 @@ impl ToolManager {
         &self,
         entry: &ToolManifestEntry,
         progress_callback: impl Fn(f64) + Send + 'static,
-    ) -> Result<PathBuf> {
-        let tool = match entry.tool.as_str() {
-            "yt-dlp" => Tool::YtDlp,
-            "ffmpeg" => Tool::Ffmpeg,
-            "ffprobe" => Tool::Ffprobe,
-            _ => return Err(anyhow!("Unknown tool: {}", entry.tool)),
-        };
+    ) -> std::result::Result<PathBuf, ToolUpdateError> {
+        let tool = Tool::from_name(&entry.tool)
+            .ok_or_else(|| ToolUpdateError::UnknownTool(entry.tool.clone()))?;
 
         // Ensure tools directory exists
-        fs::create_dir_all(&self.config.updated_dir).await?;
-
-        // Download to temp file
-        let temp_path = self
-            .app_dirs
-            .tmp
-            .join(format!("{}.download", tool.binary_name()));
+        fs::create_dir_all(&self.config.updated_dir)
+            .await
+            .map_err(ToolUpdateError::Install)?;
+
+        // Download to temp file. The name includes the version so an interrupted download
+        // is only resumed for the same release.
+        let temp_path =
+            self.app_dirs
+                .tmp
+                .join(format!("{}-{}.download", tool.binary_name(), entry.version));
         let final_path = self.config.updated_dir.join(tool.binary_name());
+        let in_flight = InFlightGuard::new(&self.in_flight, &entry.tool, temp_path.clone());
 
+        // Only the download can be canceled; once it's done the install runs to the end
         download_file(
             &entry.download_url,
             &temp_path,
             entry.size_bytes,
             progress_callback,
+            in_flight.cancel.clone(),
         )
         .await?;
 
         // Verify checksum
-        let actual_hash = compute_sha256(&temp_path).await?;
+        let actual_hash = compute_sha256(&temp_path)
+            .await
+            .map_err(ToolUpdateError::Download)?;
         if actual_hash != entry.sha256 {
-            fs::remove_file(&temp_path).await?;
-            return Err(anyhow!(
-                "Checksum mismatch: expected {}, got {}",
-                entry.sha256,
-                actual_hash
-            ));
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(ToolUpdateError::ChecksumMismatch {
+                expected: entry.sha256.clone(),
+                actual: actual_hash,
+            });
         }
 
-        // Atomic rename (move temp to final)
-        // On some platforms, we need to remove the old file first
-        if final_path.exists() {
-            // Backup old version
-            let backup_path = final_path.with_extension("bak");
-            let _ = fs::rename(&final_path, &backup_path).await;
+        // Move the current binary aside; some platforms can't rename over it
+        let backup_path = final_path.with_extension("bak");
+        let has_backup = final_path.exists() && fs::rename(&final_path, &backup_path).await.is_ok();
+
+        if let Err(e) = self.install(&temp_path, &final_path, tool).await {
+            // Whatever step failed, put the previous version back. The temp file
+            // still being there means the new binary never replaced anything.
+            if !temp_path.exists() {
+                let _ = fs::remove_file(&final_path).await;
+            }
+            let _ = fs::remove_file(&temp_path).await;
+            if has_backup {
+                if let Err(restore) = fs::rename(&backup_path, &final_path).await {
+                    log::error!(
+                        "Failed to restore {:?} from its backup: {}",
+                        final_path,
+                        restore
+                    );
+                }
+            }
+            return Err(e);
         }
 
-        fs::rename(&temp_path, &final_path).await?;
+        Ok(final_path)
+    }
+
+    /// Move a verified download into place, make it executable and check that it runs.
+    async fn install(
+        &self,
+        temp_path: &Path,
+        final_path: &Path,
+        tool: Tool,
+    ) -> std::result::Result<(), ToolUpdateError> {
+        fs::rename(temp_path, final_path)
+            .await
+            .map_err(ToolUpdateError::Install)?;
 
         // Set executable permissions on Unix
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&final_path).await?.permissions();
+            let mut perms = fs::metadata(final_path)
+                .await
+                .map_err(ToolUpdateError::Install)?
+                .permissions();
             perms.set_mode(0o755);
-            fs::set_permissions(&final_path, perms).await?;
+            fs::set_permissions(final_path, perms)
+                .await
+                .map_err(ToolUpdateError::Install)?;
         }
 
-        // Verify the new binary works
-        self.check_health(&final_path, tool).await?;
+        self.check_health(final_path, tool)
+            .await
+            .map_err(ToolUpdateError::HealthCheck)
+    }
 
-        Ok(final_path)
+    /// Temp files of updates that are downloading or being installed right now.
+    pub fn in_flight_temp_files(&self) -> Vec<PathBuf> {
+        self.in_flight
+            .lock()
+            .map(|map| map.values().map(|u| u.temp_path.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Abort the running update of `tool`, discarding its partial download. The
+    /// installed version is untouched. Returns false if no update is running.
+    pub fn cancel_update(&self, tool: &str) -> bool {
+        self.in_flight
+            .lock()
+            .ok()
+            .and_then(|map| map.get(tool).map(|u| u.cancel.send(true).is_ok()))
+            .unwrap_or(false)
     }
 
     /// Remove updated tools and fall back to bundled versions.
@@ -431,6 +651,169 @@ impl ToolManager {
         }
         Ok(())
     }
+
+    /// Remove the updated copy of `tool` and report the version that's active now.
+    pub async fn reset_tool(&self, tool: Tool) -> Result<ToolInfo> {
+        self.reset_to_bundled(tool).await?;
+        Ok(self.get_tool_info(tool).await)
+    }
+}
+
+/// Why a tool update failed. Each variant maps to its own user-facing message.
+#[derive(Debug, thiserror::Error)]
+pub enum ToolUpdateError {
+    #[error("unknown tool: {0}")]
+    UnknownTool(String),
+    #[error("download failed: {0:#}")]
+    Download(anyhow::Error),
+    #[error("checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("failed to install: {0}")]
+    Install(std::io::Error),
+    #[error("health check failed: {0:#}")]
+    HealthCheck(anyhow::Error),
+    #[error("update canceled")]
+    Canceled,
+}
+
+impl ToolUpdateError {
+    /// Message for the `ToolUpdateFailed` event.
+    pub fn user_message(&self, tool: &str) -> String {
+        match self {
+            ToolUpdateError::UnknownTool(_) => {
+                format!("{tool} can't be updated from within Downlink.")
+            }
+            ToolUpdateError::Download(_) => format!(
+                "Couldn't download the {tool} update. Check your connection and try again."
+            ),
+            ToolUpdateError::ChecksumMismatch { .. } => format!(
+                "The downloaded {tool} update didn't match its checksum and was discarded. Try again later."
+            ),
+            ToolUpdateError::Install(e) => format!(
+                "Couldn't install the {tool} update ({e}). Check that Downlink's data folder is writable."
+            ),
+            ToolUpdateError::HealthCheck(_) => format!(
+                "The new {tool} version failed to run, so the previous version was kept."
+            ),
+            ToolUpdateError::Canceled => {
+                format!("The {tool} update was canceled. The previous version was kept.")
+            }
+        }
+    }
+}
+
+/// Run a tool's version command and parse the result.
+async fn probe_version(path: &Path, tool: Tool, timeout: Duration) -> Result<String> {
+    let mut cmd = Command::new(path);
+    cmd.args(tool.version_args())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Hide console window on Windows
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = tokio::time::timeout(timeout, cmd.output())
+        .await
+        .context("Version check timed out")?
+        .context("Failed to execute tool")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Tool returned non-zero exit code: {}",
+            output.status
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = parse_version(&stdout, tool);
+
+    version.ok_or_else(|| anyhow!("Could not parse version from output"))
+}
+
+/// Classify a tool path by the directory it's in. The updated dir wins if the
+/// directories are nested, matching `find_tool`'s priority.
+fn tool_source(path: &Path, updated_dir: &Path, bundled_dir: Option<&Path>) -> ToolSource {
+    if !updated_dir.as_os_str().is_empty() && path.starts_with(updated_dir) {
+        ToolSource::Updated
+    } else if bundled_dir.is_some_and(|dir| path.starts_with(dir)) {
+        ToolSource::Bundled
+    } else {
+        ToolSource::System
+    }
+}
+
+/// Determine the overall toolchain status. yt-dlp is required; ffmpeg problems degrade it.
+fn overall_status(yt_dlp: &ToolInfo, ffmpeg: &ToolInfo) -> ToolStatus {
+    if yt_dlp.status == ToolStatus::Missing {
+        ToolStatus::Missing
+    } else if yt_dlp.status == ToolStatus::Broken || ffmpeg.status == ToolStatus::Broken {
+        ToolStatus::Broken
+    } else if yt_dlp.status == ToolStatus::Outdated || ffmpeg.status == ToolStatus::Outdated {
+        ToolStatus::Outdated
+    } else {
+        ToolStatus::Ok
+    }
+}
+
+/// Build toolchain status without a `ToolManager`, from already-discovered binary paths.
+///
+/// Used when the manager failed to construct so the Tools page still shows something
+/// useful. ffprobe is looked up next to ffmpeg.
+pub async fn standalone_toolchain_status(
+    yt_dlp: Option<PathBuf>,
+    ffmpeg: Option<PathBuf>,
+) -> ToolchainStatus {
+    let timeout = ToolManagerConfig::default().version_timeout;
+    let ffprobe = ffmpeg
+        .as_ref()
+        .map(|p| p.with_file_name(Tool::Ffprobe.binary_name()));
+
+    let yt_dlp = probe_tool_info(Tool::YtDlp, yt_dlp, timeout).await;
+    let ffmpeg = probe_tool_info(Tool::Ffmpeg, ffmpeg, timeout).await;
+    let ffprobe = probe_tool_info(Tool::Ffprobe, ffprobe, timeout).await;
+
+    ToolchainStatus {
+        overall_status: overall_status(&yt_dlp, &ffmpeg),
+        yt_dlp: Some(yt_dlp),
+        ffmpeg: Some(ffmpeg),
+        ffprobe: Some(ffprobe),
+    }
+}
+
+/// Probe a single tool at `path`: `Ok` if it reports a version, `Broken` if it exists
+/// but fails, `Missing` otherwise.
+async fn probe_tool_info(tool: Tool, path: Option<PathBuf>, timeout: Duration) -> ToolInfo {
+    let (path, version, status) = match path {
+        Some(p) => match probe_version(&p, tool, timeout).await {
+            Ok(v) => (p, Some(v), ToolStatus::Ok),
+            Err(e) => {
+                log::warn!(
+                    "{} at {} failed version check: {}",
+                    tool.as_str(),
+                    p.display(),
+                    e
+                );
+                let status = if p.exists() {
+                    ToolStatus::Broken
+                } else {
+                    ToolStatus::Missing
+                };
+                (p, None, status)
+            }
+        },
+        None => (PathBuf::new(), None, ToolStatus::Missing),
+    };
+
+    ToolInfo {
+        tool,
+        path,
+        version,
+        status,
+        is_bundled: false,
+        last_checked: Some(Utc::now()),
+    }
 }
 
 /// Parse version string from tool output.
@@ -474,36 +857,348 @@ async fn fetch_manifest(url: &str) -> Result<UpdateManifest> {
     Ok(manifest)
 }
 
-/// Download a file with progress reporting.
+/// File in the app data dir holding the last manifest that was fetched successfully.
+const MANIFEST_CACHE_FILE: &str = "tool_manifest_cache.json";
+
+/// First retry delay after a failed manifest fetch; doubles per failure, capped at the TTL.
+const MANIFEST_RETRY_BASE: Duration = Duration::from_secs(60);
+
+/// A manifest as stored on disk, with when and where it was fetched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedManifest {
+    url: String,
+    fetched_at: DateTime<Utc>,
+    manifest: UpdateManifest,
+}
+
+#[derive(Debug, Default)]
+struct ManifestCacheState {
+    cached: Option<CachedManifest>,
+    consecutive_failures: u32,
+    retry_after: Option<DateTime<Utc>>,
+}
+
+/// A running tool update: its temp file and the switch that cancels it.
+struct InFlightUpdate {
+    temp_path: PathBuf,
+    cancel: watch::Sender<bool>,
+}
+
+/// Registers an update as in flight until dropped.
+struct InFlightGuard<'a> {
+    map: &'a StdMutex<HashMap<String, InFlightUpdate>>,
+    tool: String,
+    /// Flips to true when `ToolManager::cancel_update` is called for this update.
+    cancel: watch::Receiver<bool>,
+}
+
+impl<'a> InFlightGuard<'a> {
+    fn new(
+        map: &'a StdMutex<HashMap<String, InFlightUpdate>>,
+        tool: &str,
+        temp_path: PathBuf,
+    ) -> Self {
+        let (tx, cancel) = watch::channel(false);
+        if let Ok(mut map) = map.lock() {
+            map.insert(
+                tool.to_string(),
+                InFlightUpdate {
+                    temp_path,
+                    cancel: tx,
+                },
+            );
+        }
+        Self {
+            map,
+            tool: tool.to_string(),
+            cancel,
+        }
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut map) = self.map.lock() {
+            // A newer update of the same tool may have replaced our entry
+            if map
+                .get(&self.tool)
+                .is_some_and(|u| u.cancel.subscribe().same_channel(&self.cancel))
+            {
+                map.remove(&self.tool);
+            }
+        }
+    }
+}
+
+/// Last-good update manifest, reused for `ttl` and served stale while the network is failing.
+struct ManifestCache {
+    path: PathBuf,
+    ttl: Duration,
+    // Held across the fetch so concurrent checks share one request.
+    state: tokio::sync::Mutex<ManifestCacheState>,
+}
+
+impl ManifestCache {
+    /// Load the cache file at `path`. A missing or unreadable file starts an empty cache.
+    fn load(path: PathBuf, ttl: Duration) -> Self {
+        let cached = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CachedManifest>(&bytes).ok());
+        Self {
+            path,
+            ttl,
+            state: tokio::sync::Mutex::new(ManifestCacheState {
+                cached,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Return the cached manifest for `url` if it is fresh, otherwise fetch it.
+    ///
+    /// If the fetch fails and a manifest for `url` was cached before, that one is returned
+    /// instead, and further fetches back off until `retry_after`.
+    async fn get_or_fetch<F, Fut>(
+        &self,
+        url: &str,
+        now: DateTime<Utc>,
+        fetch: F,
+    ) -> Result<UpdateManifest>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<UpdateManifest>>,
+    {
+        let mut state = self.state.lock().await;
+        let cached = state.cached.as_ref().filter(|c| c.url == url).cloned();
+
+        if let Some(ref c) = cached {
+            let fresh = now.signed_duration_since(c.fetched_at) < self.ttl_chrono();
+            let backing_off = state.retry_after.is_some_and(|t| now < t);
+            if fresh || backing_off {
+                return Ok(c.manifest.clone());
+            }
+        }
+
+        match fetch().await {
+            Ok(manifest) => {
+                let entry = CachedManifest {
+                    url: url.to_string(),
+                    fetched_at: now,
+                    manifest: manifest.clone(),
+                };
+                if let Err(e) = self.save(&entry).await {
+                    log::warn!("Failed to write manifest cache: {e:#}");
+                }
+                *state = ManifestCacheState {
+                    cached: Some(entry),
+                    ..Default::default()
+                };
+                Ok(manifest)
+            }
+            Err(e) => {
+                state.consecutive_failures += 1;
+                let delay = MANIFEST_RETRY_BASE
+                    .saturating_mul(1 << (state.consecutive_failures - 1).min(16))
+                    .min(self.ttl);
+                state.retry_after = Some(
+                    now + chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::zero()),
+                );
+
+                match cached {
+                    Some(c) => {
+                        log::warn!(
+                            "Failed to fetch tool manifest, using copy from {}: {e:#}",
+                            c.fetched_at
+                        );
+                        Ok(c.manifest)
+                    }
+                    None => Err(e),
+                }
+            }
+        }
+    }
+
+    fn ttl_chrono(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX)
+    }
+
+    async fn save(&self, entry: &CachedManifest) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&self.path, serde_json::to_vec(entry)?).await?;
+        Ok(())
+    }
+}
+
+/// How to start a tool download given the size of a leftover partial file.
+#[derive(Debug, PartialEq, Eq)]
+enum ResumePlan {
+    Fresh,
+    Resume(u64),
+    /// The partial file already has every byte; go straight to checksum verification.
+    Complete,
+}
+
+fn resume_plan(existing: u64, expected_size: u64) -> ResumePlan {
+    match existing {
+        0 => ResumePlan::Fresh,
+        n if expected_size == 0 || n < expected_size => ResumePlan::Resume(n),
+        n if n == expected_size => ResumePlan::Complete,
+        // Bigger than the release: stale or corrupt
+        _ => ResumePlan::Fresh,
+    }
+}
+
+/// What to do with the partial file once the server has answered.
+#[derive(Debug, PartialEq, Eq)]
+enum PartialFile {
+    /// 206 for the requested offset: append the body.
+    Append,
+    /// Full body follows (no range requested, or the server ignored it): overwrite.
+    Truncate,
+    /// The server rejected or mangled the range: delete the partial and start over.
+    Discard,
+}
+
+fn partial_file_action(
+    offset: Option<u64>,
+    status: reqwest::StatusCode,
+    range_start: Option<u64>,
+) -> PartialFile {
+    match (offset, status) {
+        (Some(offset), reqwest::StatusCode::PARTIAL_CONTENT) if range_start == Some(offset) => {
+            PartialFile::Append
+        }
+        (Some(_), reqwest::StatusCode::PARTIAL_CONTENT)
+        | (Some(_), reqwest::StatusCode::RANGE_NOT_SATISFIABLE) => PartialFile::Discard,
+        _ => PartialFile::Truncate,
+    }
+}
+
+/// Start offset from a `Content-Range: bytes <start>-<end>/<total>` header.
+fn content_range_start(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Download a file with progress reporting, giving up when `cancel` flips to true.
+/// A canceled download's partial file is removed rather than kept for resuming.
 async fn download_file(
     url: &str,
     dest: &Path,
     expected_size: u64,
     progress_callback: impl Fn(f64) + Send + 'static,
+    mut cancel: watch::Receiver<bool>,
+) -> std::result::Result<(), ToolUpdateError> {
+    tokio::select! {
+        result = fetch_to_file(url, dest, expected_size, progress_callback) => {
+            return result.map_err(ToolUpdateError::Download);
+        }
+        Ok(_) = cancel.wait_for(|canceled| *canceled) => {}
+    }
+
+    // The download future is dropped by now, so the file is closed
+    log::info!("Download of {} canceled", dest.display());
+    let _ = fs::remove_file(dest).await;
+    Err(ToolUpdateError::Canceled)
+}
+
+/// Stream `url` into `dest`.
+///
+/// If `dest` already holds part of the file (an earlier attempt was interrupted), the
+/// rest is requested with a `Range` header and appended. Servers that don't honor the
+/// range get a full re-download.
+async fn fetch_to_file(
+    url: &str,
+    dest: &Path,
+    expected_size: u64,
+    progress_callback: impl Fn(f64) + Send + 'static,
 ) -> Result<()> {
+    let existing = fs::metadata(dest).await.map(|m| m.len()).unwrap_or(0);
+    let mut offset = match resume_plan(existing, expected_size) {
+        ResumePlan::Fresh => None,
+        ResumePlan::Resume(n) => Some(n),
+        ResumePlan::Complete => {
+            log::info!("{} already fully downloaded", dest.display());
+            progress_callback(100.0);
+            return Ok(());
+        }
+    };
+
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(600)) // 10 minute timeout for large files
         .build()?;
 
-    let response = client.get(url).send().await?.error_for_status()?;
-    let total_size = response.content_length().unwrap_or(expected_size);
-
-    // Download entire content at once (simpler than streaming for tool binaries)
-    let bytes = response.bytes().await?;
+    let (mut response, action) = loop {
+        let mut request = client.get(url);
+        if let Some(offset) = offset {
+            log::info!("Resuming {} from byte {}", dest.display(), offset);
+            request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+        }
 
-    let mut file = fs::File::create(dest).await?;
-    file.write_all(&bytes).await?;
+        let response = request.send().await?;
+        let action = partial_file_action(
+            offset,
+            response.status(),
+            content_range_start(response.headers()),
+        );
+        if action == PartialFile::Discard {
+            log::warn!(
+                "Server didn't honor range request (HTTP {}), restarting download",
+                response.status()
+            );
+            let _ = fs::remove_file(dest).await;
+            offset = None;
+            continue;
+        }
+        break (response.error_for_status()?, action);
+    };
+
+    let (mut file, mut downloaded) = match action {
+        PartialFile::Append => (
+            fs::OpenOptions::new().append(true).open(dest).await?,
+            offset.unwrap_or(0),
+        ),
+        _ => (fs::File::create(dest).await?, 0),
+    };
+    let total_size = response
+        .content_length()
+        .map(|len| downloaded + len)
+        .unwrap_or(expected_size);
+
+    // Stream to disk so an interruption leaves a partial file to resume from
+    let mut last_percent = 0u64;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+
+        if let Some(percent) = (downloaded * 100).checked_div(total_size) {
+            let percent = percent.min(100);
+            if percent > last_percent {
+                last_percent = percent;
+                progress_callback(percent as f64);
+            }
+        }
+    }
     file.flush().await?;
 
     // Report 100% completion
     progress_callback(100.0);
 
     // Log actual vs expected size
-    let actual_size = bytes.len() as u64;
-    if actual_size != total_size && total_size > 0 {
+    if downloaded != total_size && total_size > 0 {
         log::warn!(
             "Downloaded size {} differs from expected {}",
-            actual_size,
+            downloaded,
             total_size
         );
     }
@@ -511,13 +1206,12 @@ async fn download_file(
     Ok(())
 }
 
-/// Compute SHA256 hash of a file.
+/// Compute SHA256 hash of a file, reading it in chunks so large archives aren't
+/// loaded into memory.
 async fn compute_sha256(path: &Path) -> Result<String> {
-    let data = fs::read(path).await?;
-    let mut hasher = Sha256::new();
-    hasher.update(&data);
-    let result = hasher.finalize();
-    Ok(hex::encode(result))
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || checksum::hash_file(&path, ChecksumAlgorithm::Sha256))
+        .await?
 }
 
 /// Builder for ToolManagerConfig.
@@ -552,6 +1246,11 @@ impl ToolManagerConfigBuilder {
         self
     }
 
+    pub fn manifest_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.config.manifest_cache_ttl = ttl;
+        self
+    }
+
     pub fn build(self) -> ToolManagerConfig {
         self.config
     }
@@ -567,6 +1266,99 @@ impl Default for ToolManagerConfigBuilder {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tool_source_distinguishes_locations() {
+        let updated = Path::new("/home/me/.local/share/downlink/tools");
+        let bundled = Path::new("/Applications/Downlink.app/Contents/MacOS");
+
+        assert_eq!(
+            tool_source(&updated.join("yt-dlp"), updated, Some(bundled)),
+            ToolSource::Updated
+        );
+        assert_eq!(
+            tool_source(&bundled.join("ffmpeg"), updated, Some(bundled)),
+            ToolSource::Bundled
+        );
+        assert_eq!(
+            tool_source(Path::new("/usr/bin/ffprobe"), updated, Some(bundled)),
+            ToolSource::System
+        );
+        // No bundled dir configured (dev builds)
+        assert_eq!(
+            tool_source(&bundled.join("ffmpeg"), updated, None),
+            ToolSource::System
+        );
+        // Prefix match is per path component, not per character
+        assert_eq!(
+            tool_source(
+                Path::new("/home/me/.local/share/downlink/tools-old/yt-dlp"),
+                updated,
+                Some(bundled)
+            ),
+            ToolSource::System
+        );
+    }
+
+    #[test]
+    fn test_tool_path_serializes_missing_as_null() {
+        let missing = ToolPath {
+            tool: Tool::Ffprobe,
+            path: None,
+            version: None,
+            source: None,
+        };
+        let json = serde_json::to_value(&missing).unwrap();
+        assert_eq!(json["tool"], "ffprobe");
+        assert!(json["path"].is_null());
+        assert!(json["version"].is_null());
+        assert!(json["source"].is_null());
+
+        let found = ToolPath {
+            tool: Tool::YtDlp,
+            path: Some(PathBuf::from("/usr/bin/yt-dlp")),
+            version: Some("2025.06.01".to_string()),
+            source: Some(ToolSource::System),
+        };
+        assert_eq!(serde_json::to_value(&found).unwrap()["source"], "system");
+    }
+
+    #[test]
+    fn test_tool_update_error_user_messages() {
+        let download = ToolUpdateError::Download(anyhow!("connection reset"));
+        assert_eq!(
+            download.user_message("yt-dlp"),
+            "Couldn't download the yt-dlp update. Check your connection and try again."
+        );
+
+        let checksum = ToolUpdateError::ChecksumMismatch {
+            expected: "aa".to_string(),
+            actual: "bb".to_string(),
+        };
+        assert!(checksum
+            .user_message("ffmpeg")
+            .contains("didn't match its checksum"));
+        assert!(checksum.to_string().contains("expected aa, got bb"));
+
+        let install = ToolUpdateError::Install(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "permission denied",
+        ));
+        let message = install.user_message("yt-dlp");
+        assert!(message.contains("permission denied"), "{message}");
+        assert!(message.contains("writable"));
+
+        let health = ToolUpdateError::HealthCheck(anyhow!("exit status 1"));
+        assert_eq!(
+            health.user_message("ffprobe"),
+            "The new ffprobe version failed to run, so the previous version was kept."
+        );
+
+        let unknown = ToolUpdateError::UnknownTool("aria2c".to_string());
+        assert!(unknown
+            .user_message("aria2c")
+            .starts_with("aria2c can't be updated"));
+    }
+
     #[test]
     fn test_parse_version_ytdlp() {
         let output = "2024.01.01\n";
@@ -585,6 +1377,427 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_compute_sha256_streams_large_file() {
+        let path = std::env::temp_dir().join(format!("downlink-sha-{}", uuid::Uuid::new_v4()));
+        let data: Vec<u8> = (0..5 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, data).unwrap();
+
+        let digest = compute_sha256(&path).await;
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            digest.unwrap(),
+            "16b632f11cf950dda67dc4c184a3f9e0aa1ffa4c18927bb8977e7da97ca25bca"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_standalone_toolchain_status_reports_ytdlp() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("downlink-tools-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = dir.join("yt-dlp");
+        std::fs::write(&fake, "#!/bin/sh\necho 2024.01.01\n").unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let status = standalone_toolchain_status(Some(fake.clone()), None).await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let yt_dlp = status.yt_dlp.unwrap();
+        assert_eq!(yt_dlp.path, fake);
+        assert_eq!(yt_dlp.version.as_deref(), Some("2024.01.01"));
+        assert_eq!(yt_dlp.status, ToolStatus::Ok);
+        assert_eq!(status.ffmpeg.unwrap().status, ToolStatus::Missing);
+        assert_eq!(status.overall_status, ToolStatus::Ok);
+    }
+
+    /// Manager over temp dirs, with a fake yt-dlp in each dir that has a version.
+    /// A `None` version writes a binary that exits with an error.
+    #[cfg(unix)]
+    fn fake_manager(
+        updated: Option<Option<&str>>,
+        bundled: Option<Option<&str>>,
+    ) -> (ToolManager, PathBuf) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("downlink-tm-{}", uuid::Uuid::new_v4()));
+        let updated_dir = root.join("updated");
+        let bundled_dir = root.join("bundled");
+        for (dir, version) in [(&updated_dir, updated), (&bundled_dir, bundled)] {
+            std::fs::create_dir_all(dir).unwrap();
+            let Some(version) = version else { continue };
+            let script = match version {
+                Some(v) => format!("#!/bin/sh\necho {v}\n"),
+                None => "#!/bin/sh\nexit 1\n".to_string(),
+            };
+            let fake = dir.join(Tool::YtDlp.binary_name());
+            std::fs::write(&fake, script).unwrap();
+            std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let manager = ToolManager {
+            config: ToolManagerConfigBuilder::new()
+                .bundled_dir(bundled_dir)
+                .updated_dir(updated_dir.clone())
+                .build(),
+            app_dirs: AppDirs {
+                data: root.clone(),
+                logs: root.join("logs"),
+                tools: updated_dir,
+                tmp: root.join("tmp"),
+            },
+            manifest_cache: ManifestCache::load(root.join(MANIFEST_CACHE_FILE), Duration::ZERO),
+            in_flight: StdMutex::default(),
+        };
+        (manager, root)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reset_tool_falls_back_to_bundled() {
+        let (manager, root) = fake_manager(Some(Some("2025.06.01")), Some(Some("2024.01.01")));
+        let updated_binary = root.join("updated").join(Tool::YtDlp.binary_name());
+
+        let before = manager.get_tool_info(Tool::YtDlp).await;
+        assert_eq!(before.path, updated_binary);
+        assert_eq!(before.version.as_deref(), Some("2025.06.01"));
+
+        let after = manager.reset_tool(Tool::YtDlp).await.unwrap();
+        let updated_gone = !updated_binary.exists();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(updated_gone);
+        assert!(after.path.starts_with(root.join("bundled")));
+        assert!(after.is_bundled);
+        assert_eq!(after.version.as_deref(), Some("2024.01.01"));
+        assert_eq!(after.status, ToolStatus::Ok);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_update_discards_partial_download() {
+        use std::io::{Read, Write};
+
+        // Serves the start of the body, then stalls
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/yt-dlp", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 1024]);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000000\r\n\r\n");
+            let _ = stream.write_all(&[0u8; 4096]);
+            let _ = stream.flush();
+            std::thread::sleep(Duration::from_secs(30));
+        });
+
+        let (manager, root) = fake_manager(Some(Some("2024.01.01")), None);
+        std::fs::create_dir_all(root.join("tmp")).unwrap();
+        let manager = std::sync::Arc::new(manager);
+        let entry = ToolManifestEntry {
+            tool: "yt-dlp".to_string(),
+            version: "2025.06.01".to_string(),
+            download_url: url,
+            sha256: String::new(),
+            size_bytes: 1_000_000,
+        };
+        let temp_path = root.join("tmp").join("yt-dlp-2025.06.01.download");
+
+        let task = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.update_tool(&entry, |_| {}).await }
+        });
+        for _ in 0..100 {
+            if std::fs::metadata(&temp_path).is_ok_and(|m| m.len() > 0) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let had_partial = temp_path.exists();
+        let canceled = manager.cancel_update("yt-dlp");
+        let result = task.await.unwrap();
+        let partial_left = temp_path.exists();
+        let info = manager.get_tool_info(Tool::YtDlp).await;
+        let in_flight = manager.in_flight_temp_files();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(had_partial);
+        assert!(canceled);
+        assert!(matches!(result, Err(ToolUpdateError::Canceled)));
+        assert!(!partial_left);
+        assert_eq!(info.version.as_deref(), Some("2024.01.01"));
+        assert!(in_flight.is_empty());
+        assert!(!manager.cancel_update("yt-dlp"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failed_install_restores_previous_version() {
+        use std::io::{Read, Write};
+
+        // A release that downloads and verifies fine but doesn't run
+        let body = b"#!/bin/sh\nexit 1\n";
+        let sha256 = checksum::hash_reader(&body[..], ChecksumAlgorithm::Sha256).unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/yt-dlp", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 1024]);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(body);
+        });
+
+        let (manager, root) = fake_manager(Some(Some("2024.01.01")), None);
+        std::fs::create_dir_all(root.join("tmp")).unwrap();
+        let entry = ToolManifestEntry {
+            tool: "yt-dlp".to_string(),
+            version: "2025.06.01".to_string(),
+            download_url: url,
+            sha256,
+            size_bytes: body.len() as u64,
+        };
+
+        let result = manager.update_tool(&entry, |_| {}).await;
+        let info = manager.get_tool_info(Tool::YtDlp).await;
+        let temp_left = root.join("tmp").join("yt-dlp-2025.06.01.download").exists();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(matches!(result, Err(ToolUpdateError::HealthCheck(_))));
+        assert_eq!(info.version.as_deref(), Some("2024.01.01"));
+        assert!(!temp_left);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_verify_tool_reports_broken_copies() {
+        let (manager, root) = fake_manager(Some(None), Some(Some("2024.01.01")));
+        let broken_update = manager.verify_tool(Tool::YtDlp).await;
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(broken_update.status, ToolStatus::Broken);
+        assert!(broken_update.bundled_healthy);
+        assert_eq!(
+            plan_repair(&broken_update, true),
+            RepairAction::ResetToBundled
+        );
+
+        let (manager, root) = fake_manager(None, Some(None));
+        let broken_bundled = manager.verify_tool(Tool::YtDlp).await;
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(broken_bundled.status, ToolStatus::Broken);
+        assert!(!broken_bundled.bundled_healthy);
+        assert_eq!(plan_repair(&broken_bundled, true), RepairAction::Redownload);
+
+        let (manager, root) = fake_manager(Some(Some("2025.06.01")), None);
+        let healthy = manager.verify_tool(Tool::YtDlp).await;
+        let _ = std::fs::remove_dir_all(&root);
+        assert_eq!(healthy.status, ToolStatus::Ok);
+    }
+
+    #[test]
+    fn test_plan_repair_decision_tree() {
+        let check = |status, bundled_healthy| ToolCheck {
+            status,
+            path: Some(PathBuf::from("/tools/yt-dlp")),
+            bundled_healthy,
+        };
+
+        assert_eq!(
+            plan_repair(&check(ToolStatus::Ok, true), true),
+            RepairAction::None
+        );
+        assert_eq!(
+            plan_repair(&check(ToolStatus::Ok, false), false),
+            RepairAction::None
+        );
+        assert_eq!(
+            plan_repair(&check(ToolStatus::Missing, false), true),
+            RepairAction::None
+        );
+        assert_eq!(
+            plan_repair(&check(ToolStatus::Broken, true), true),
+            RepairAction::ResetToBundled
+        );
+        assert_eq!(
+            plan_repair(&check(ToolStatus::Broken, false), true),
+            RepairAction::Redownload
+        );
+        assert_eq!(
+            plan_repair(&check(ToolStatus::Broken, false), false),
+            RepairAction::Unrepairable
+        );
+    }
+
+    fn sample_manifest(version: &str) -> UpdateManifest {
+        UpdateManifest {
+            manifest_version: 1,
+            updated_at: "2025-06-01T00:00:00Z".to_string(),
+            tools: vec![ToolManifestEntry {
+                tool: "yt-dlp".to_string(),
+                version: version.to_string(),
+                download_url: "https://example.com/yt-dlp".to_string(),
+                sha256: "aa".to_string(),
+                size_bytes: 1,
+            }],
+        }
+    }
+
+    fn temp_cache_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("downlink-manifest-{}", uuid::Uuid::new_v4()))
+            .join(MANIFEST_CACHE_FILE)
+    }
+
+    #[tokio::test]
+    async fn test_manifest_cache_expires_after_ttl() {
+        const URL: &str = "https://example.com/manifest.json";
+        let path = temp_cache_path();
+        let cache = ManifestCache::load(path.clone(), Duration::from_secs(3600));
+        let t0 = Utc::now();
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let fetch = |version: &'static str| {
+            let calls = &calls;
+            move || {
+                calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move { Ok(sample_manifest(version)) }
+            }
+        };
+
+        let first = cache.get_or_fetch(URL, t0, fetch("1")).await.unwrap();
+        let within = t0 + chrono::Duration::minutes(59);
+        let cached = cache.get_or_fetch(URL, within, fetch("2")).await.unwrap();
+        assert_eq!(first.tools[0].version, "1");
+        assert_eq!(cached.tools[0].version, "1");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let expired = t0 + chrono::Duration::minutes(61);
+        let refetched = cache.get_or_fetch(URL, expired, fetch("3")).await.unwrap();
+        assert_eq!(refetched.tools[0].version, "3");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // A different manifest URL never reuses the cached one
+        let other = cache
+            .get_or_fetch("https://example.com/other.json", expired, fetch("4"))
+            .await
+            .unwrap();
+        assert_eq!(other.tools[0].version, "4");
+
+        // The last good manifest survives a restart
+        let reloaded = ManifestCache::load(path.clone(), Duration::from_secs(3600));
+        let from_disk = reloaded
+            .get_or_fetch("https://example.com/other.json", expired, fetch("5"))
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(from_disk.tools[0].version, "4");
+    }
+
+    #[tokio::test]
+    async fn test_manifest_cache_serves_stale_on_error() {
+        const URL: &str = "https://example.com/manifest.json";
+        let path = temp_cache_path();
+        let cache = ManifestCache::load(path.clone(), Duration::from_secs(3600));
+        let t0 = Utc::now();
+
+        let offline = || async { Err::<UpdateManifest, _>(anyhow!("offline")) };
+        assert!(cache.get_or_fetch(URL, t0, offline).await.is_err());
+
+        cache
+            .get_or_fetch(URL, t0, || async { Ok(sample_manifest("1")) })
+            .await
+            .unwrap();
+
+        let later = t0 + chrono::Duration::hours(2);
+        let stale = cache.get_or_fetch(URL, later, offline).await.unwrap();
+        assert_eq!(stale.tools[0].version, "1");
+
+        // Backing off: the next check within a minute doesn't hit the network
+        let soon = later + chrono::Duration::seconds(30);
+        let backed_off = cache
+            .get_or_fetch(URL, soon, || async { panic!("should be backing off") })
+            .await
+            .unwrap();
+        assert_eq!(backed_off.tools[0].version, "1");
+
+        let retry = later + chrono::Duration::seconds(61);
+        let recovered = cache
+            .get_or_fetch(URL, retry, || async { Ok(sample_manifest("2")) })
+            .await
+            .unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(recovered.tools[0].version, "2");
+    }
+
+    #[test]
+    fn test_resume_plan() {
+        assert_eq!(resume_plan(0, 1000), ResumePlan::Fresh);
+        assert_eq!(resume_plan(400, 1000), ResumePlan::Resume(400));
+        assert_eq!(resume_plan(1000, 1000), ResumePlan::Complete);
+        assert_eq!(resume_plan(1500, 1000), ResumePlan::Fresh);
+        // Manifest without a size: resume and let the checksum decide
+        assert_eq!(resume_plan(400, 0), ResumePlan::Resume(400));
+    }
+
+    #[test]
+    fn test_partial_file_action() {
+        use reqwest::StatusCode;
+
+        assert_eq!(
+            partial_file_action(Some(400), StatusCode::PARTIAL_CONTENT, Some(400)),
+            PartialFile::Append
+        );
+        // Server ignored the Range header and sent everything
+        assert_eq!(
+            partial_file_action(Some(400), StatusCode::OK, None),
+            PartialFile::Truncate
+        );
+        assert_eq!(
+            partial_file_action(Some(400), StatusCode::RANGE_NOT_SATISFIABLE, None),
+            PartialFile::Discard
+        );
+        // 206 for a different offset can't be appended
+        assert_eq!(
+            partial_file_action(Some(400), StatusCode::PARTIAL_CONTENT, Some(0)),
+            PartialFile::Discard
+        );
+        assert_eq!(
+            partial_file_action(None, StatusCode::OK, None),
+            PartialFile::Truncate
+        );
+    }
+
+    #[test]
+    fn test_content_range_start() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(content_range_start(&headers), None);
+
+        headers.insert(
+            reqwest::header::CONTENT_RANGE,
+            "bytes 400-999/1000".parse().unwrap(),
+        );
+        assert_eq!(content_range_start(&headers), Some(400));
+
+        headers.insert(
+            reqwest::header::CONTENT_RANGE,
+            "bytes */1000".parse().unwrap(),
+        );
+        assert_eq!(content_range_start(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_standalone_toolchain_status_missing_ytdlp() {
+        let missing = std::env::temp_dir().join("downlink-no-such-dir/yt-dlp");
+        let status = standalone_toolchain_status(Some(missing), None).await;
+
+        assert_eq!(status.yt_dlp.unwrap().status, ToolStatus::Missing);
+        assert_eq!(status.overall_status, ToolStatus::Missing);
+    }
+
     #[test]
     fn test_version_is_newer() {
         assert!(version_is_newer("2024.01.02", "2024.01.01"));
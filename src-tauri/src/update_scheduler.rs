@@ -0,0 +1,405 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Manager};
+
+use crate::events::{self, DownlinkEvent, ToolUpdateInfo};
+use crate::settings::{SettingsManager, UpdateSettings};
+use crate::tool_manager::{Tool, ToolManifestEntry};
+use crate::AppState;
+
+/// Wait after launch before the first check so it doesn't compete with startup.
+const STARTUP_DELAY: Duration = Duration::from_secs(30);
+
+/// How often the scheduler wakes to see if a check is due. Much shorter than any
+/// sensible interval, so a changed `check_interval_hours` applies without a restart.
+const POLL_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Poll interval while a yt-dlp update is waiting for downloads to finish.
+const DEFERRED_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What to do with a newer yt-dlp found by a scheduled check.
+#[derive(Debug, PartialEq, Eq)]
+enum AutoUpdate {
+    Apply,
+    Defer,
+    Skip,
+}
+
+/// Auto-update bookkeeping carried across polls.
+#[derive(Default)]
+struct AutoUpdateState {
+    /// A yt-dlp update waiting for active downloads to finish.
+    pending: Option<ToolManifestEntry>,
+    /// Last version that failed to install; not retried automatically.
+    failed_version: Option<String>,
+}
+
+/// Whether enough time has passed since `last_checked` (RFC 3339) to check again.
+///
+/// A missing or unparseable timestamp means we've never checked. A timestamp in the
+/// future (clock changed) is treated as due so it gets rewritten. An interval of 0
+/// disables scheduled checks.
+pub fn is_check_due(last_checked: Option<&str>, interval_hours: u32, now: DateTime<Utc>) -> bool {
+    if interval_hours == 0 {
+        return false;
+    }
+
+    let Some(last) = last_checked.and_then(|s| DateTime::parse_from_rfc3339(s).ok()) else {
+        return true;
+    };
+
+    let elapsed = now.signed_duration_since(last.with_timezone(&Utc));
+    elapsed < chrono::Duration::zero()
+        || elapsed >= chrono::Duration::hours(i64::from(interval_hours))
+}
+
+/// Decide whether to install yt-dlp `version` now.
+///
+/// Replacing the binary under a running yt-dlp process can break it (and fails
+/// outright on Windows), so wait until nothing is active. A version that already
+/// failed to install is skipped so a bad release isn't retried on every poll; the
+/// user can still update it manually.
+fn auto_update_decision(
+    version: &str,
+    active_downloads: usize,
+    failed_version: Option<&str>,
+) -> AutoUpdate {
+    if failed_version == Some(version) {
+        AutoUpdate::Skip
+    } else if active_downloads > 0 {
+        AutoUpdate::Defer
+    } else {
+        AutoUpdate::Apply
+    }
+}
+
+/// Whether scheduled checks should report updates for a manifest tool.
+fn tool_enabled(tool: Tool, updates: &UpdateSettings) -> bool {
+    match tool {
+        Tool::YtDlp => updates.auto_update_ytdlp,
+        Tool::Ffmpeg | Tool::Ffprobe => updates.auto_update_ffmpeg,
+    }
+}
+
+/// Start the background update checker. Call once from `setup`, after `AppState`
+/// is managed.
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        let mut auto = AutoUpdateState::default();
+        loop {
+            check_if_due(&app, &mut auto).await;
+            apply_pending_update(&app, &mut auto).await;
+
+            let wait = if auto.pending.is_some() {
+                DEFERRED_POLL_INTERVAL
+            } else {
+                POLL_INTERVAL
+            };
+            tokio::time::sleep(wait).await;
+        }
+    });
+}
+
+async fn check_if_due(app: &AppHandle, auto: &mut AutoUpdateState) {
+    let state = app.state::<AppState>();
+
+    let settings = state
+        .db
+        .call(|db| SettingsManager::new(db.conn()).get_user_settings())
+        .await;
+    let updates = match settings {
+        Ok(settings) => settings.updates,
+        Err(e) => {
+            log::warn!(
+                "Skipping scheduled update check, failed to load settings: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    if !(updates.auto_update_ytdlp || updates.auto_update_ffmpeg || updates.auto_update_app) {
+        return;
+    }
+    if !is_check_due(
+        updates.last_checked.as_deref(),
+        updates.check_interval_hours,
+        Utc::now(),
+    ) {
+        return;
+    }
+
+    log::info!("Running scheduled update check");
+    let mut ok = true;
+    if updates.auto_update_ytdlp || updates.auto_update_ffmpeg {
+        ok &= check_tools(app, &state, &updates, auto).await;
+    }
+    if updates.auto_update_app {
+        ok &= check_app(app).await;
+    }
+
+    // Leave last_checked alone on failure (e.g. offline) so the next poll tries again
+    if ok {
+        record_last_checked(&state).await;
+    }
+}
+
+/// Emit `ToolUpdateAvailable` for each enabled tool with a newer version, and queue
+/// a yt-dlp update for automatic install.
+async fn check_tools(
+    app: &AppHandle,
+    state: &AppState,
+    updates: &UpdateSettings,
+    auto: &mut AutoUpdateState,
+) -> bool {
+    let Some(manager) = state.tool_manager.read().await.clone() else {
+        log::warn!("Tool manager not initialized, skipping tool update check");
+        return true;
+    };
+
+    let entries = match manager.check_for_updates().await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Scheduled tool update check failed: {}", e);
+            return false;
+        }
+    };
+
+    for entry in entries {
+        let Some(tool) = Tool::from_name(&entry.tool) else {
+            continue;
+        };
+        if !tool_enabled(tool, updates) {
+            continue;
+        }
+
+        if tool == Tool::YtDlp {
+            auto.pending = Some(entry.clone());
+        }
+
+        let current = manager.get_tool_info(tool).await.version;
+        log::info!(
+            "Update available for {}: {:?} -> {}",
+            entry.tool,
+            current,
+            entry.version
+        );
+        let _ = events::emit_event(
+            app,
+            DownlinkEvent::ToolUpdateAvailable {
+                info: ToolUpdateInfo {
+                    tool: entry.tool,
+                    current,
+                    latest: Some(entry.version),
+                },
+            },
+        );
+    }
+
+    true
+}
+
+/// Emit `ToolUpdateAvailable` (tool "app") if the updater reports a new release.
+async fn check_app(app: &AppHandle) -> bool {
+    use tauri_plugin_updater::UpdaterExt;
+
+    let updater = match app.updater() {
+        Ok(updater) => updater,
+        Err(e) => {
+            log::warn!("Updater not available for scheduled check: {}", e);
+            return true;
+        }
+    };
+
+    match updater.check().await {
+        Ok(Some(update)) => {
+            log::info!("App update available: {}", update.version);
+            let _ = events::emit_event(
+                app,
+                DownlinkEvent::ToolUpdateAvailable {
+                    info: ToolUpdateInfo {
+                        tool: "app".to_string(),
+                        current: Some(env!("CARGO_PKG_VERSION").to_string()),
+                        latest: Some(update.version.clone()),
+                    },
+                },
+            );
+            true
+        }
+        Ok(None) => true,
+        Err(e) => {
+            log::warn!("Scheduled app update check failed: {}", e);
+            false
+        }
+    }
+}
+
+/// Install the pending yt-dlp update once no downloads are running. Downloads are
+/// held from starting while the binary is replaced.
+async fn apply_pending_update(app: &AppHandle, auto: &mut AutoUpdateState) {
+    let Some(entry) = auto.pending.clone() else {
+        return;
+    };
+
+    let state = app.state::<AppState>();
+    let downloads = state.download_manager.read().await.clone();
+    let held = match &downloads {
+        Some(manager) => manager.hold_starts_if_idle().await,
+        None => true,
+    };
+    let active = match &downloads {
+        Some(manager) if !held => manager.active_count().await.max(1),
+        _ => 0,
+    };
+
+    apply_update_entry(app, auto, entry, active).await;
+
+    if held {
+        if let Some(manager) = downloads {
+            manager.release_starts().await;
+        }
+    }
+}
+
+async fn apply_update_entry(
+    app: &AppHandle,
+    auto: &mut AutoUpdateState,
+    entry: ToolManifestEntry,
+    active: usize,
+) {
+    match auto_update_decision(&entry.version, active, auto.failed_version.as_deref()) {
+        AutoUpdate::Defer => {
+            log::info!(
+                "Deferring yt-dlp {} update until {} active download(s) finish",
+                entry.version,
+                active
+            );
+            return;
+        }
+        AutoUpdate::Skip => {
+            auto.pending = None;
+            return;
+        }
+        AutoUpdate::Apply => auto.pending = None,
+    }
+
+    let state = app.state::<AppState>();
+    let Some(manager) = state.tool_manager.read().await.clone() else {
+        return;
+    };
+
+    log::info!("Auto-updating yt-dlp to {}", entry.version);
+    match crate::apply_tool_update(app, &manager, &entry).await {
+        Ok(path) => log::info!("yt-dlp updated to {} at {:?}", entry.version, path),
+        Err(e) => {
+            log::error!("Auto-update of yt-dlp to {} failed: {}", entry.version, e);
+            auto.failed_version = Some(entry.version);
+        }
+    }
+}
+
+async fn record_last_checked(state: &AppState) {
+    let result = state
+        .db
+        .call(|db| {
+            let manager = SettingsManager::new(db.conn());
+            let mut settings = manager.get_user_settings()?;
+            settings.updates.last_checked = Some(Utc::now().to_rfc3339());
+            manager.save_user_settings(&settings)
+        })
+        .await;
+    if let Err(e) = result {
+        log::warn!("Failed to record update check time: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_is_check_due_when_never_checked() {
+        assert!(is_check_due(None, 24, now()));
+        assert!(is_check_due(Some("not a timestamp"), 24, now()));
+    }
+
+    #[test]
+    fn test_is_check_due_respects_interval() {
+        assert!(!is_check_due(Some("2025-06-01T00:00:00Z"), 24, now()));
+        assert!(!is_check_due(Some("2025-05-31T12:00:01Z"), 24, now()));
+        assert!(is_check_due(Some("2025-05-31T12:00:00Z"), 24, now()));
+        assert!(is_check_due(Some("2025-06-01T05:00:00Z"), 6, now()));
+    }
+
+    #[test]
+    fn test_is_check_due_handles_offsets() {
+        // 13:30+02:00 is 11:30Z, only half an hour ago
+        assert!(!is_check_due(Some("2025-06-01T13:30:00+02:00"), 1, now()));
+        assert!(is_check_due(Some("2025-06-01T12:30:00+02:00"), 1, now()));
+    }
+
+    #[test]
+    fn test_is_check_due_future_timestamp() {
+        assert!(is_check_due(Some("2030-01-01T00:00:00Z"), 24, now()));
+    }
+
+    #[test]
+    fn test_is_check_due_zero_interval_disables() {
+        assert!(!is_check_due(None, 0, now()));
+    }
+
+    #[test]
+    fn test_auto_update_applies_when_idle() {
+        assert_eq!(
+            auto_update_decision("2025.06.01", 0, None),
+            AutoUpdate::Apply
+        );
+        assert_eq!(
+            auto_update_decision("2025.06.01", 0, Some("2025.05.01")),
+            AutoUpdate::Apply
+        );
+    }
+
+    #[test]
+    fn test_auto_update_defers_while_downloading() {
+        assert_eq!(
+            auto_update_decision("2025.06.01", 1, None),
+            AutoUpdate::Defer
+        );
+        assert_eq!(
+            auto_update_decision("2025.06.01", 3, None),
+            AutoUpdate::Defer
+        );
+    }
+
+    #[test]
+    fn test_auto_update_skips_version_that_failed() {
+        assert_eq!(
+            auto_update_decision("2025.06.01", 0, Some("2025.06.01")),
+            AutoUpdate::Skip
+        );
+        assert_eq!(
+            auto_update_decision("2025.06.01", 2, Some("2025.06.01")),
+            AutoUpdate::Skip
+        );
+    }
+
+    #[test]
+    fn test_tool_enabled() {
+        let updates = UpdateSettings {
+            auto_update_ytdlp: true,
+            auto_update_ffmpeg: false,
+            ..Default::default()
+        };
+        assert!(tool_enabled(Tool::YtDlp, &updates));
+        assert!(!tool_enabled(Tool::Ffmpeg, &updates));
+        assert!(!tool_enabled(Tool::Ffprobe, &updates));
+    }
+}
@@ -1,8 +1,35 @@
 use std::collections::HashSet;
 
 use regex::Regex;
+use serde::Serialize;
 use url::Url;
 
+/// Why a single URL was rejected by [`validate_http_url`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidUrlReason {
+    Empty,
+    Malformed,
+    UnsupportedScheme,
+}
+
+/// "Invalid URL" error returned to the frontend, with a reason it can branch on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct InvalidUrl {
+    pub reason: InvalidUrlReason,
+    pub message: String,
+}
+
+impl InvalidUrl {
+    fn new(reason: InvalidUrlReason, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+        }
+    }
+}
+
 /// Extracts URLs from arbitrary text (e.g. multi-paste).
 ///
 /// Behavior:
@@ -15,7 +42,36 @@ use url::Url;
 /// - We intentionally ignore non-http(s) schemes for safety and to match yt-dlp usage.
 /// - We do not aggressively rewrite query strings.
 pub fn extract_urls(text: &str) -> Vec<String> {
-    let mut out: Vec<String> = Vec::new();
+    extract_urls_with_originals(text)
+        .into_iter()
+        .map(|u| u.url)
+        .collect()
+}
+
+/// A URL found in pasted text: the normalized form used for downloading, and the
+/// text as it appeared (fragment and port intact) for sharing it back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedUrl {
+    pub url: String,
+    pub original: String,
+}
+
+impl From<String> for ExtractedUrl {
+    /// A URL with no separate original form (already normalized).
+    fn from(url: String) -> Self {
+        Self {
+            original: url.clone(),
+            url,
+        }
+    }
+}
+
+/// Like [`extract_urls`], keeping each URL's original text as well.
+///
+/// When the same normalized URL appears more than once, the first occurrence's
+/// original text is kept.
+pub fn extract_urls_with_originals(text: &str) -> Vec<ExtractedUrl> {
+    let mut out: Vec<ExtractedUrl> = Vec::new();
     let mut seen: HashSet<String> = HashSet::new();
 
     if text.trim().is_empty() {
@@ -44,7 +100,10 @@ pub fn extract_urls(text: &str) -> Vec<String> {
 
         if let Some(normalized) = normalize_http_url(cleaned) {
             if seen.insert(normalized.clone()) {
-                out.push(normalized);
+                out.push(ExtractedUrl {
+                    url: normalized,
+                    original: cleaned.to_string(),
+                });
             }
         }
     }
@@ -60,7 +119,14 @@ pub fn contains_multiple_urls(text: &str) -> bool {
     urls.len() > 1
 }
 
-/// Normalize a presumed http(s) URL.
+/// Normalize a presumed http(s) URL, or `None` if it isn't one.
+///
+/// See [`validate_http_url`] for the rules; use that when the rejection reason matters.
+pub fn normalize_http_url(input: &str) -> Option<String> {
+    validate_http_url(input).ok()
+}
+
+/// Validate and normalize a single http(s) URL.
 ///
 /// Normalization rules:
 /// - Only accepts http/https
@@ -68,19 +134,36 @@ pub fn contains_multiple_urls(text: &str) -> bool {
 /// - Removes URL fragments (`#...`) because they are not meaningful for downloads
 /// - Removes default ports (80 for http, 443 for https)
 /// - Preserves path and query as-is (aside from Url parsing normalization)
-pub fn normalize_http_url(input: &str) -> Option<String> {
+pub fn validate_http_url(input: &str) -> Result<String, InvalidUrl> {
     let trimmed = input.trim();
     if trimmed.is_empty() {
-        return None;
+        return Err(InvalidUrl::new(InvalidUrlReason::Empty, "URL is empty"));
     }
 
-    let mut url = Url::parse(trimmed).ok()?;
+    let mut url = Url::parse(trimmed).map_err(|e| {
+        InvalidUrl::new(
+            InvalidUrlReason::Malformed,
+            format!("\"{trimmed}\" is not a valid URL: {e}"),
+        )
+    })?;
 
     match url.scheme() {
         "http" | "https" => {}
-        _ => return None,
+        other => return Err(InvalidUrl::new(
+            InvalidUrlReason::UnsupportedScheme,
+            format!(
+                "Unsupported URL scheme \"{other}\". Only http and https links can be downloaded"
+            ),
+        )),
     }
 
+    let malformed = || {
+        InvalidUrl::new(
+            InvalidUrlReason::Malformed,
+            format!("\"{trimmed}\" is not a valid URL"),
+        )
+    };
+
     // Strip fragments
     url.set_fragment(None);
 
@@ -90,7 +173,7 @@ pub fn normalize_http_url(input: &str) -> Option<String> {
     if scheme != url.scheme() {
         // There's no direct setter for scheme; re-parse with updated scheme.
         // We do a minimal rebuild.
-        let rebuilt = rebuild_with_scheme(&url, &scheme)?;
+        let rebuilt = rebuild_with_scheme(&url, &scheme).ok_or_else(malformed)?;
         url = rebuilt;
     }
 
@@ -98,7 +181,7 @@ pub fn normalize_http_url(input: &str) -> Option<String> {
         let lower = host.to_ascii_lowercase();
         if lower != host {
             // Rebuild with updated host; Url API doesn't allow setting host without mutable authority changes.
-            let rebuilt = rebuild_with_host(&url, &lower)?;
+            let rebuilt = rebuild_with_host(&url, &lower).ok_or_else(malformed)?;
             url = rebuilt;
         }
     }
@@ -114,7 +197,78 @@ pub fn normalize_http_url(input: &str) -> Option<String> {
         let _ = url.set_port(None);
     }
 
-    Some(url.to_string())
+    Ok(url.to_string())
+}
+
+/// Whether yt-dlp has an extractor for a URL's site (see [`url_support`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum UrlSupport {
+    Supported {
+        extractor: String,
+    },
+    /// No extractor is named after the site. yt-dlp's generic extractor may still
+    /// find media on the page, so the UI should warn rather than refuse.
+    Unsupported,
+    /// No host to check, or the extractor list isn't available.
+    Unknown,
+}
+
+/// Short links and renamed sites whose extractor is named after another domain.
+const EXTRACTOR_ALIASES: &[(&str, &str)] = &[
+    ("youtu.be", "youtube"),
+    ("x.com", "twitter"),
+    ("fb.watch", "facebook"),
+    ("redd.it", "reddit"),
+    ("vm.tiktok.com", "tiktok"),
+];
+
+/// Second-level labels that are part of a country suffix, as in `bbc.co.uk`.
+const COUNTRY_SECOND_LEVELS: &[&str] = &["co", "com", "net", "org", "gov", "ac", "ne", "or"];
+
+/// Check a URL's site against yt-dlp's extractor names (`yt-dlp --list-extractors`).
+///
+/// Extractors are named after the site, so `www.vimeo.com` matches `vimeo` and
+/// `vimeo:album`. An empty list means it couldn't be loaded: the verdict is Unknown.
+pub fn url_support(url: &str, extractors: &[String]) -> UrlSupport {
+    let Some(host) = Url::parse(url.trim())
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_lowercase))
+    else {
+        return UrlSupport::Unknown;
+    };
+    if extractors.is_empty() {
+        return UrlSupport::Unknown;
+    }
+
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    let site = EXTRACTOR_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == host)
+        .map(|(_, site)| *site)
+        .unwrap_or_else(|| site_name(host));
+
+    extractors
+        .iter()
+        .find(|e| {
+            e.split(':')
+                .next()
+                .unwrap_or_default()
+                .eq_ignore_ascii_case(site)
+        })
+        .map_or(UrlSupport::Unsupported, |e| UrlSupport::Supported {
+            extractor: e.clone(),
+        })
+}
+
+/// The label naming a site: `vimeo` for `player.vimeo.com`, `bbc` for `bbc.co.uk`.
+fn site_name(host: &str) -> &str {
+    let labels: Vec<&str> = host.split('.').collect();
+    match labels.len() {
+        0 | 1 => host,
+        n if n >= 3 && COUNTRY_SECOND_LEVELS.contains(&labels[n - 2]) => labels[n - 3],
+        n => labels[n - 2],
+    }
 }
 
 /// Trim common trailing punctuation which frequently appears in pasted text.
@@ -215,6 +369,20 @@ mod tests {
         assert_eq!(urls, vec!["https://example.com/watch?v=1".to_string()]);
     }
 
+    #[test]
+    fn keeps_original_text_alongside_normalized() {
+        let urls = extract_urls_with_originals(
+            "see (https://Example.com:443/watch?v=1#t=90), https://example.com/watch?v=1 again",
+        );
+        assert_eq!(
+            urls,
+            vec![ExtractedUrl {
+                url: "https://example.com/watch?v=1".to_string(),
+                original: "https://Example.com:443/watch?v=1#t=90".to_string(),
+            }]
+        );
+    }
+
     #[test]
     fn removes_default_ports() {
         let urls = extract_urls("http://example.com:80/x https://example.com:443/y");
@@ -232,4 +400,113 @@ mod tests {
         let urls = extract_urls("ftp://example.com/x https://example.com/y");
         assert_eq!(urls, vec!["https://example.com/y".to_string()]);
     }
+
+    #[test]
+    fn validate_normalizes_url() {
+        assert_eq!(
+            validate_http_url("  HTTPS://Example.COM/Watch?v=AbC#t=5  "),
+            Ok("https://example.com/Watch?v=AbC".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_strips_default_ports() {
+        assert_eq!(
+            validate_http_url("http://example.com:80/x"),
+            Ok("http://example.com/x".to_string())
+        );
+        assert_eq!(
+            validate_http_url("https://example.com:443/y"),
+            Ok("https://example.com/y".to_string())
+        );
+        assert_eq!(
+            validate_http_url("https://example.com:8443/z"),
+            Ok("https://example.com:8443/z".to_string())
+        );
+    }
+
+    fn extractors() -> Vec<String> {
+        [
+            "generic",
+            "youtube",
+            "youtube:tab",
+            "Vimeo",
+            "vimeo:album",
+            "bbc",
+            "twitter",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn url_support_matches_extractor_by_site() {
+        let list = extractors();
+        assert_eq!(
+            url_support("https://www.youtube.com/watch?v=abc", &list),
+            UrlSupport::Supported {
+                extractor: "youtube".to_string()
+            }
+        );
+        assert_eq!(
+            url_support("https://player.vimeo.com/video/1", &list),
+            UrlSupport::Supported {
+                extractor: "Vimeo".to_string()
+            }
+        );
+        assert!(matches!(
+            url_support("https://www.bbc.co.uk/iplayer/episode/x", &list),
+            UrlSupport::Supported { .. }
+        ));
+    }
+
+    #[test]
+    fn url_support_uses_aliases() {
+        let list = extractors();
+        assert!(matches!(
+            url_support("https://youtu.be/abc", &list),
+            UrlSupport::Supported { extractor } if extractor == "youtube"
+        ));
+        assert!(matches!(
+            url_support("https://x.com/user/status/1", &list),
+            UrlSupport::Supported { extractor } if extractor == "twitter"
+        ));
+    }
+
+    #[test]
+    fn url_support_unsupported_and_unknown() {
+        let list = extractors();
+        assert_eq!(
+            url_support("https://example.com/video", &list),
+            UrlSupport::Unsupported
+        );
+        // "generic" is not a site
+        assert_eq!(
+            url_support("https://generic.example.org/", &list),
+            UrlSupport::Unsupported
+        );
+        assert_eq!(url_support("not a url", &list), UrlSupport::Unknown);
+        assert_eq!(
+            url_support("https://www.youtube.com/watch?v=abc", &[]),
+            UrlSupport::Unknown
+        );
+    }
+
+    #[test]
+    fn validate_rejects_with_reason() {
+        assert_eq!(
+            validate_http_url("ftp://example.com/x").unwrap_err().reason,
+            InvalidUrlReason::UnsupportedScheme
+        );
+        assert_eq!(
+            validate_http_url("   ").unwrap_err().reason,
+            InvalidUrlReason::Empty
+        );
+        assert_eq!(
+            validate_http_url("example.com/watch").unwrap_err().reason,
+            InvalidUrlReason::Malformed
+        );
+        assert_eq!(normalize_http_url("file:///etc/passwd"), None);
+    }
 }
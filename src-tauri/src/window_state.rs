@@ -0,0 +1,257 @@
+//! Window state persistence
+//!
+//! Restores the main window's position and size on startup and saves them again
+//! as the user moves or resizes it, when `remember_window_state` is on.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalSize, WebviewWindow, WindowEvent};
+use tokio::sync::mpsc;
+
+use crate::settings::{SettingsManager, WindowState};
+use crate::AppState;
+
+/// Label of the window created from `tauri.conf.json`.
+const MAIN_WINDOW: &str = "main";
+
+/// How long moves/resizes must stop before the state is written. Dragging a
+/// window fires dozens of events a second.
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Restore the main window per the user's settings and start saving its state.
+pub fn install(app: &AppHandle) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        log::warn!("Main window not found; window state won't be remembered");
+        return;
+    };
+
+    let (remember, start_minimized, saved) = {
+        let state = app.state::<AppState>();
+        let loaded = state.db.blocking_call(|db| {
+            let settings = SettingsManager::new(db.conn());
+            let general = settings.get_user_settings().unwrap_or_default().general;
+            Ok((
+                general.remember_window_state,
+                general.start_minimized,
+                settings.get_window_state().ok(),
+            ))
+        });
+        loaded.unwrap_or((false, false, None))
+    };
+
+    if remember {
+        if let Some(saved) = saved {
+            restore(&window, &saved);
+        }
+    }
+    if start_minimized {
+        let _ = window.minimize();
+    }
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    tauri::async_runtime::spawn({
+        let app = app.clone();
+        let window = window.clone();
+        debounce(rx, SAVE_DEBOUNCE, move || {
+            let app = app.clone();
+            let window = window.clone();
+            async move { persist(&app, &window).await }
+        })
+    });
+
+    let app = app.clone();
+    window.clone().on_window_event(move |event| match event {
+        WindowEvent::Moved(_) | WindowEvent::Resized(_) => {
+            let _ = tx.send(());
+        }
+        // Don't wait out the debounce; the app may exit right after
+        WindowEvent::CloseRequested { .. } => {
+            tauri::async_runtime::block_on(persist(&app, &window));
+        }
+        _ => {}
+    });
+}
+
+/// Apply a saved state. A position that is no longer on any monitor (e.g. an
+/// unplugged display) is skipped so the window doesn't open off-screen.
+fn restore(window: &WebviewWindow, saved: &WindowState) {
+    let monitors: Vec<_> = window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|m| (*m.position(), *m.size()))
+        .collect();
+    if is_on_screen(saved, &monitors) {
+        let _ = window.set_position(PhysicalPosition::new(saved.x, saved.y));
+    }
+    let _ = window.set_size(PhysicalSize::new(saved.width, saved.height));
+    if saved.is_maximized {
+        let _ = window.maximize();
+    }
+}
+
+/// Whether the top-left corner of `saved` lies on one of `monitors`.
+fn is_on_screen(
+    saved: &WindowState,
+    monitors: &[(PhysicalPosition<i32>, PhysicalSize<u32>)],
+) -> bool {
+    monitors.iter().any(|(pos, size)| {
+        let right = i64::from(pos.x) + i64::from(size.width);
+        let bottom = i64::from(pos.y) + i64::from(size.height);
+        (i64::from(pos.x)..right).contains(&i64::from(saved.x))
+            && (i64::from(pos.y)..bottom).contains(&i64::from(saved.y))
+    })
+}
+
+/// Save the window's current state if `remember_window_state` is on.
+async fn persist(app: &AppHandle, window: &WebviewWindow) {
+    let state = app.state::<AppState>();
+    let previous = state
+        .db
+        .call(|db| {
+            let settings = SettingsManager::new(db.conn());
+            let remember = settings
+                .get_user_settings()
+                .map(|s| s.general.remember_window_state)
+                .unwrap_or(false);
+            Ok(remember.then(|| settings.get_window_state().unwrap_or_default()))
+        })
+        .await;
+    let Ok(Some(previous)) = previous else {
+        return;
+    };
+
+    let Some(current) = capture(window, previous) else {
+        return;
+    };
+    let saved = state
+        .db
+        .call(move |db| SettingsManager::new(db.conn()).save_window_state(&current))
+        .await;
+    if let Err(e) = saved {
+        log::warn!("Failed to save window state: {}", e);
+    }
+}
+
+/// Read the window's geometry. While maximized, the last normal position and size
+/// are kept so un-maximizing after a restart goes back to them. Minimized windows
+/// report meaningless coordinates and aren't captured at all.
+fn capture(window: &WebviewWindow, previous: WindowState) -> Option<WindowState> {
+    if window.is_minimized().unwrap_or(false) {
+        return None;
+    }
+    if window.is_maximized().unwrap_or(false) {
+        return Some(WindowState {
+            is_maximized: true,
+            ..previous
+        });
+    }
+    let position = window.outer_position().ok()?;
+    let size = window.inner_size().ok()?;
+    Some(WindowState {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        is_maximized: false,
+    })
+}
+
+/// Call `settled` once after each burst of `events`, when none has arrived for
+/// `quiet`. Returns when the sender is dropped, flushing a pending burst first.
+async fn debounce<T, F, Fut>(
+    mut events: mpsc::UnboundedReceiver<T>,
+    quiet: Duration,
+    mut settled: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    while events.recv().await.is_some() {
+        while let Ok(Some(_)) = tokio::time::timeout(quiet, events.recv()).await {}
+        settled().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn spawn_counter(
+        quiet: Duration,
+    ) -> (
+        mpsc::UnboundedSender<()>,
+        Arc<AtomicUsize>,
+        tokio::task::JoinHandle<()>,
+    ) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let saves = Arc::new(AtomicUsize::new(0));
+        let task = tokio::spawn({
+            let saves = saves.clone();
+            debounce(rx, quiet, move || {
+                saves.fetch_add(1, Ordering::SeqCst);
+                async {}
+            })
+        });
+        (tx, saves, task)
+    }
+
+    #[tokio::test]
+    async fn test_debounce_collapses_rapid_events() {
+        let quiet = Duration::from_millis(100);
+        let (tx, saves, task) = spawn_counter(quiet);
+
+        for _ in 0..20 {
+            tx.send(()).unwrap();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(saves.load(Ordering::SeqCst), 0);
+
+        tokio::time::sleep(quiet * 3).await;
+        assert_eq!(saves.load(Ordering::SeqCst), 1);
+
+        // A later burst is saved separately
+        tx.send(()).unwrap();
+        tx.send(()).unwrap();
+        tokio::time::sleep(quiet * 3).await;
+        assert_eq!(saves.load(Ordering::SeqCst), 2);
+
+        drop(tx);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_debounce_flushes_when_sender_dropped() {
+        let (tx, saves, task) = spawn_counter(Duration::from_secs(60));
+        tx.send(()).unwrap();
+        drop(tx);
+
+        task.await.unwrap();
+        assert_eq!(saves.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_is_on_screen() {
+        let monitors = [
+            (PhysicalPosition::new(0, 0), PhysicalSize::new(1920, 1080)),
+            (
+                PhysicalPosition::new(1920, 0),
+                PhysicalSize::new(1280, 1024),
+            ),
+        ];
+        let at = |x, y| WindowState {
+            x,
+            y,
+            ..WindowState::default()
+        };
+
+        assert!(is_on_screen(&at(100, 100), &monitors));
+        assert!(is_on_screen(&at(2000, 500), &monitors));
+        assert!(!is_on_screen(&at(3500, 100), &monitors));
+        assert!(!is_on_screen(&at(-800, 100), &monitors));
+        assert!(!is_on_screen(&at(100, 100), &[]));
+    }
+}